@@ -1,4 +1,5 @@
 import { useCallback, useEffect, useState } from 'react';
+import { orderExecutionFetch } from '../lib/orderExecution';
 
 export function useAdminWallet(orderExecutionUrl: string) {
   const [adminWallet, setAdminWallet] = useState<string | null>(null);
@@ -8,7 +9,7 @@ export function useAdminWallet(orderExecutionUrl: string) {
   const fetchAdminWallet = useCallback(async () => {
     setLoading(true);
     try {
-      const res = await fetch(`${orderExecutionUrl}/server/public-key`);
+      const res = await orderExecutionFetch(`${orderExecutionUrl}/server/public-key`);
       if (!res.ok) {
         let errorMessage = `Server returned ${res.status} ${res.statusText}`;
         try {
@@ -1,4 +1,5 @@
 import { useCallback, useEffect, useState } from 'react';
+import { orderExecutionFetch } from '../lib/orderExecution';
 import { BalancesResponse } from '../types/trading';
 
 export function useBalances(orderExecutionUrl: string, adminWallet: string | null) {
@@ -20,7 +21,7 @@ export function useBalances(orderExecutionUrl: string, adminWallet: string | nul
       }
       setLoading(true);
       try {
-        const res = await fetch(`${orderExecutionUrl}/balances?wallet=${adminWallet}`);
+        const res = await orderExecutionFetch(`${orderExecutionUrl}/balances?wallet=${adminWallet}`);
         if (!res.ok) {
           const payload = await res.json().catch(() => null);
           throw new Error(payload?.error ?? 'Failed to fetch balances');
@@ -1,4 +1,5 @@
 import { useCallback, useEffect, useState } from 'react';
+import { orderExecutionFetch } from '../lib/orderExecution';
 import { PositionRow } from '../types/trading';
 
 export function usePositions(orderExecutionUrl: string, wallet: string | null) {
@@ -20,7 +21,7 @@ export function usePositions(orderExecutionUrl: string, wallet: string | null) {
       }
       setLoading(true);
       try {
-        const res = await fetch(`${orderExecutionUrl}/positions/details?wallet=${wallet}`);
+        const res = await orderExecutionFetch(`${orderExecutionUrl}/positions/details?wallet=${wallet}`);
         if (!res.ok) {
           const payload = await res.json().catch(() => null);
           throw new Error(payload?.error ?? 'Failed to load positions');
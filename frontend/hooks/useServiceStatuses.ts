@@ -21,7 +21,7 @@ export function useServiceStatuses(orderExecutionUrl: string, indexerUrl: string
       }
     };
 
-    const exec = await check(orderExecutionUrl, '/server/public-key');
+    const exec = await check(orderExecutionUrl, '/health');
     setExecutionStatus(exec);
 
     const indexerHealth = await check(indexerUrl, '/health');
@@ -0,0 +1,7 @@
+// The order execution API is reached through this app's proxy route, which adds the API key
+// on the server so it never ships to the browser.
+export const ORDER_EXECUTION_PROXY_URL = '/api/order-execution';
+
+export function orderExecutionFetch(url: string, init: RequestInit = {}) {
+  return fetch(url, init);
+}
@@ -0,0 +1,46 @@
+import type { NextApiRequest, NextApiResponse } from 'next';
+
+// Server-side only, so the API key never reaches the browser.
+const ORDER_EXECUTION_URL = process.env.ORDER_EXECUTION_URL ?? 'http://localhost:8080';
+const ORDER_EXECUTION_API_KEY = process.env.ORDER_EXECUTION_API_KEY;
+const PREFIX = '/api/order-execution';
+
+export const config = {
+  api: { bodyParser: false },
+};
+
+function readBody(req: NextApiRequest): Promise<Buffer> {
+  return new Promise((resolve, reject) => {
+    const chunks: Buffer[] = [];
+    req.on('data', (chunk: Buffer) => chunks.push(chunk));
+    req.on('end', () => resolve(Buffer.concat(chunks)));
+    req.on('error', reject);
+  });
+}
+
+// Forwards dashboard requests to the order execution API with the dashboard's key attached.
+export default async function handler(req: NextApiRequest, res: NextApiResponse) {
+  const path = (req.url ?? '').slice(PREFIX.length);
+  const headers = new Headers();
+  const contentType = req.headers['content-type'];
+  if (contentType) {
+    headers.set('Content-Type', contentType);
+  }
+  if (ORDER_EXECUTION_API_KEY) {
+    headers.set('Authorization', `Bearer ${ORDER_EXECUTION_API_KEY}`);
+  }
+  const method = req.method ?? 'GET';
+  const body = method === 'GET' || method === 'HEAD' ? undefined : await readBody(req);
+
+  try {
+    const upstream = await fetch(`${ORDER_EXECUTION_URL}${path}`, { method, headers, body });
+    const upstreamType = upstream.headers.get('content-type');
+    if (upstreamType) {
+      res.setHeader('Content-Type', upstreamType);
+    }
+    res.status(upstream.status).send(Buffer.from(await upstream.arrayBuffer()));
+  } catch (err) {
+    console.error('Order execution API unreachable', err);
+    res.status(502).json({ error: `Cannot reach the order execution API at ${ORDER_EXECUTION_URL}` });
+  }
+}
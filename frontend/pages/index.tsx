@@ -2,6 +2,7 @@ import Head from 'next/head';
 import dynamic from 'next/dynamic';
 import { FormEvent, useCallback, useEffect, useMemo, useState } from 'react';
 import { useWallet } from '@solana/wallet-adapter-react';
+import { ORDER_EXECUTION_PROXY_URL, orderExecutionFetch } from '../lib/orderExecution';
 import { DevnetPerpMarkets } from '../lib/perpMarkets';
 
 import { BalancesCard } from '../components/BalancesCard';
@@ -15,8 +16,7 @@ import { usePositions } from '../hooks/usePositions';
 import { useServiceStatuses } from '../hooks/useServiceStatuses';
 import { OrderSide } from '../types/trading';
 
-const ORDER_EXECUTION_URL =
-  process.env.NEXT_PUBLIC_ORDER_EXECUTION_URL ?? process.env.NEXT_PUBLIC_EXECUTION_API ?? 'http://localhost:8080';
+const ORDER_EXECUTION_URL = ORDER_EXECUTION_PROXY_URL;
 const DRIFT_INDEXER_URL = process.env.NEXT_PUBLIC_DRIFT_INDEXER_URL ?? 'http://localhost:4000';
 const TRADE_HISTORY_LIMIT = 150;
 
@@ -63,7 +63,7 @@ function HomePage() {
     setTradeHistoryRefreshing(true);
     setTradeHistoryError(null);
     try {
-      const res = await fetch(`${ORDER_EXECUTION_URL}/actions/history?limit=${TRADE_HISTORY_LIMIT}`);
+      const res = await orderExecutionFetch(`${ORDER_EXECUTION_URL}/actions/history?limit=${TRADE_HISTORY_LIMIT}`);
       if (!res.ok) {
         const payload = await res
           .json()
@@ -132,7 +132,7 @@ function HomePage() {
 
     setSubmitting(true);
     try {
-      const res = await fetch(`${ORDER_EXECUTION_URL}/orders/open-isolated/execute`, {
+      const res = await orderExecutionFetch(`${ORDER_EXECUTION_URL}/orders/open-isolated/execute`, {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
         body: JSON.stringify({
@@ -168,7 +168,7 @@ function HomePage() {
     }
     setStatus('Closing position...');
     try {
-      const res = await fetch(`${ORDER_EXECUTION_URL}/orders/close/execute`, {
+      const res = await orderExecutionFetch(`${ORDER_EXECUTION_URL}/orders/close/execute`, {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
         body: JSON.stringify({ wallet: resolvedWallet, market }),
@@ -199,7 +199,7 @@ function HomePage() {
 
     setStatus('Submitting withdrawal...');
     try {
-      const res = await fetch(`${ORDER_EXECUTION_URL}/margin/transfer/execute`, {
+      const res = await orderExecutionFetch(`${ORDER_EXECUTION_URL}/margin/transfer/execute`, {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
         body: JSON.stringify({
@@ -235,7 +235,7 @@ function HomePage() {
 
     setStatus('Depositing margin...');
     try {
-      const res = await fetch(`${ORDER_EXECUTION_URL}/margin/transfer/execute`, {
+      const res = await orderExecutionFetch(`${ORDER_EXECUTION_URL}/margin/transfer/execute`, {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
         body: JSON.stringify({
@@ -270,7 +270,7 @@ function HomePage() {
     if (!Number.isFinite(amt) || amt <= 0) return;
     setStatus('Depositing to Drift account...');
     try {
-      const res = await fetch(`${ORDER_EXECUTION_URL}/margin/deposit-native/execute`, {
+      const res = await orderExecutionFetch(`${ORDER_EXECUTION_URL}/margin/deposit-native/execute`, {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
         body: JSON.stringify({ wallet: adminWallet, amount: amt, market: 'SOL' }),
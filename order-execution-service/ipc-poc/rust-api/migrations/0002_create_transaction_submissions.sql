@@ -0,0 +1,31 @@
+CREATE TABLE IF NOT EXISTS transaction_submissions (
+    id UUID PRIMARY KEY,
+    route TEXT NOT NULL,
+    wallet TEXT NOT NULL,
+    market TEXT,
+    state TEXT NOT NULL,
+    signature TEXT,
+    slot BIGINT,
+    error TEXT,
+    error_details JSONB,
+    signed_tx TEXT,
+    last_valid_block_height BIGINT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS transaction_submissions_state_idx
+    ON transaction_submissions (state);
+
+CREATE TABLE IF NOT EXISTS transaction_submission_events (
+    id BIGSERIAL PRIMARY KEY,
+    submission_id UUID NOT NULL REFERENCES transaction_submissions (id) ON DELETE CASCADE,
+    state TEXT NOT NULL,
+    signature TEXT,
+    slot BIGINT,
+    detail TEXT,
+    at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS transaction_submission_events_submission_idx
+    ON transaction_submission_events (submission_id, id);
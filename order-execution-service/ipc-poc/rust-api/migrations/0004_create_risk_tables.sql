@@ -0,0 +1,43 @@
+CREATE TABLE IF NOT EXISTS risk_daily_baselines (
+    wallet TEXT NOT NULL,
+    sub_account_id INTEGER NOT NULL,
+    day DATE NOT NULL,
+    opening_pnl DOUBLE PRECISION NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (wallet, sub_account_id, day)
+);
+
+CREATE TABLE IF NOT EXISTS risk_withdrawals (
+    id BIGSERIAL PRIMARY KEY,
+    wallet TEXT NOT NULL,
+    sub_account_id INTEGER NOT NULL,
+    market TEXT NOT NULL,
+    amount DOUBLE PRECISION NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS risk_withdrawals_wallet_created_at_idx
+    ON risk_withdrawals (wallet, sub_account_id, created_at);
+
+CREATE TABLE IF NOT EXISTS risk_liquidations (
+    wallet TEXT NOT NULL,
+    sub_account_id INTEGER NOT NULL,
+    last_liquidation_id INTEGER NOT NULL,
+    liquidated_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (wallet, sub_account_id)
+);
+
+CREATE TABLE IF NOT EXISTS risk_audit_events (
+    id BIGSERIAL PRIMARY KEY,
+    wallet TEXT NOT NULL,
+    wallet_label TEXT,
+    sub_account_id INTEGER NOT NULL,
+    route TEXT NOT NULL,
+    code TEXT NOT NULL,
+    message TEXT NOT NULL,
+    details JSONB,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS risk_audit_events_wallet_created_at_idx
+    ON risk_audit_events (wallet, created_at DESC);
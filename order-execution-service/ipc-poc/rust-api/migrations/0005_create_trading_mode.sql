@@ -0,0 +1,7 @@
+-- Single row holding the service-wide trading mode.
+CREATE TABLE IF NOT EXISTS trading_mode (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    mode TEXT NOT NULL,
+    reason TEXT,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,29 @@
+CREATE TABLE IF NOT EXISTS brackets (
+    id UUID PRIMARY KEY,
+    wallet TEXT NOT NULL,
+    wallet_label TEXT NOT NULL,
+    sub_account_id INTEGER NOT NULL,
+    market TEXT NOT NULL,
+    state TEXT NOT NULL,
+    take_profit_user_order_id SMALLINT,
+    take_profit_trigger_price DOUBLE PRECISION,
+    take_profit_limit_price DOUBLE PRECISION,
+    stop_loss_user_order_id SMALLINT,
+    stop_loss_trigger_price DOUBLE PRECISION,
+    stop_loss_limit_price DOUBLE PRECISION,
+    -- Set once the monitor has seen the position open, so an entry still waiting to fill
+    -- is not mistaken for a closed position.
+    position_seen BOOLEAN NOT NULL DEFAULT FALSE,
+    submission_id UUID,
+    signature TEXT,
+    detail TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- One open bracket per position.
+CREATE UNIQUE INDEX IF NOT EXISTS brackets_open_position_idx
+    ON brackets (wallet, sub_account_id, market)
+    WHERE state IN ('pending', 'active');
+
+CREATE INDEX IF NOT EXISTS brackets_state_idx ON brackets (state);
@@ -0,0 +1,35 @@
+CREATE TABLE IF NOT EXISTS schedules (
+    id UUID PRIMARY KEY,
+    wallet TEXT NOT NULL,
+    wallet_label TEXT NOT NULL,
+    sub_account_id INTEGER NOT NULL,
+    market TEXT NOT NULL,
+    action TEXT NOT NULL,
+    -- Signed total for opens; NULL closes the whole position.
+    size DOUBLE PRECISION,
+    leverage DOUBLE PRECISION,
+    margin DOUBLE PRECISION,
+    state TEXT NOT NULL,
+    detail TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS schedules_state_idx ON schedules (state);
+
+-- One child order per row, sent in slice_index order.
+CREATE TABLE IF NOT EXISTS schedule_slices (
+    schedule_id UUID NOT NULL REFERENCES schedules (id),
+    slice_index INTEGER NOT NULL,
+    due_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    size DOUBLE PRECISION,
+    margin DOUBLE PRECISION,
+    state TEXT NOT NULL,
+    submission_id UUID,
+    signature TEXT,
+    detail TEXT,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (schedule_id, slice_index)
+);
+
+CREATE INDEX IF NOT EXISTS schedule_slices_state_idx ON schedule_slices (state);
@@ -0,0 +1,17 @@
+-- Fills from paper-trading mode share the action log, flagged so they are never mistaken
+-- for on-chain activity.
+ALTER TABLE drift_action_logs ADD COLUMN IF NOT EXISTS paper BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS paper_positions (
+    wallet TEXT NOT NULL,
+    sub_account_id INTEGER NOT NULL,
+    market TEXT NOT NULL,
+    -- Signed base amount, negative for shorts.
+    size DOUBLE PRECISION NOT NULL DEFAULT 0,
+    entry_price DOUBLE PRECISION NOT NULL DEFAULT 0,
+    margin DOUBLE PRECISION NOT NULL DEFAULT 0,
+    realized_pnl DOUBLE PRECISION NOT NULL DEFAULT 0,
+    fees DOUBLE PRECISION NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (wallet, sub_account_id, market)
+);
@@ -0,0 +1,20 @@
+-- Settlements, funding payments and fill fees decoded from the Drift events logged by
+-- each transaction. A fill between two tracked accounts yields a row for each side.
+CREATE TABLE IF NOT EXISTS drift_pnl_events (
+    signature TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    user_account TEXT NOT NULL,
+    slot BIGINT NOT NULL,
+    ts BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    market_index SMALLINT NOT NULL,
+    -- Quote amounts in 1e6 precision.
+    pnl BIGINT NOT NULL DEFAULT 0,
+    fee BIGINT NOT NULL DEFAULT 0,
+    funding BIGINT NOT NULL DEFAULT 0,
+    inserted_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (signature, event_index, user_account)
+);
+
+CREATE INDEX IF NOT EXISTS drift_pnl_events_user_ts_idx
+    ON drift_pnl_events (user_account, ts);
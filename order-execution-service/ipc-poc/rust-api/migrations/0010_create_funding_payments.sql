@@ -0,0 +1,21 @@
+-- Written by the order-indexing-service from FundingPaymentRecord events; created here
+-- too so /funding works against a database the indexer has not touched yet.
+CREATE TABLE IF NOT EXISTS funding_payments (
+    id BIGSERIAL PRIMARY KEY,
+    wallet TEXT NOT NULL,
+    user_account TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    market_index SMALLINT NOT NULL,
+    funding_payment DOUBLE PRECISION NOT NULL,
+    base_asset_amount DOUBLE PRECISION NOT NULL,
+    user_last_cumulative_funding DOUBLE PRECISION NOT NULL,
+    amm_cumulative_funding_long DOUBLE PRECISION NOT NULL,
+    amm_cumulative_funding_short DOUBLE PRECISION NOT NULL,
+    slot BIGINT NOT NULL,
+    block_time TIMESTAMPTZ,
+    inserted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (signature, user_account, market_index)
+);
+
+CREATE INDEX IF NOT EXISTS funding_payments_wallet_market_idx
+    ON funding_payments (wallet, market_index, slot DESC);
@@ -0,0 +1,20 @@
+-- Idempotency keys belong to the API key that sent them, so two clients picking the same
+-- key neither collide nor see each other's responses. Requests made with API_AUTH off use
+-- the nil UUID.
+ALTER TABLE idempotency_keys
+    ADD COLUMN IF NOT EXISTS api_key_id UUID NOT NULL DEFAULT '00000000-0000-0000-0000-000000000000';
+
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1
+        FROM pg_index i
+        JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY (i.indkey)
+        WHERE i.indrelid = 'idempotency_keys'::regclass
+          AND i.indisprimary
+          AND a.attname = 'api_key_id'
+    ) THEN
+        ALTER TABLE idempotency_keys DROP CONSTRAINT IF EXISTS idempotency_keys_pkey;
+        ALTER TABLE idempotency_keys ADD PRIMARY KEY (api_key_id, key);
+    END IF;
+END $$;
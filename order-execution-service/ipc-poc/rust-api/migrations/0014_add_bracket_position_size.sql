@@ -0,0 +1,3 @@
+-- Position size at the monitor's last check. A leg that disappears without the position
+-- moving was cancelled some other way rather than filled.
+ALTER TABLE brackets ADD COLUMN IF NOT EXISTS position_size DOUBLE PRECISION;
@@ -0,0 +1,308 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tokio_postgres::Client;
+use tracing::{error, warn, Instrument};
+use uuid::Uuid;
+
+use crate::{db, types::ApiErrorBody};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// Every key starts with this, so a leaked one is easy to recognise.
+const KEY_PREFIX: &str = "dex_";
+/// Characters of the key kept in the clear for listings, including the prefix.
+const SHOWN_PREFIX_LEN: usize = 12;
+const MAX_NAME_LEN: usize = 64;
+
+/// What a key may do. Each route needs one scope; `admin` passes every check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// `GET` routes and `/orders/quote`.
+    Read,
+    /// Routes that return an unsigned transaction, plus `/simulate` and `/actions/decode`.
+    Build,
+    /// Routes that sign with a server wallet, and schedule control.
+    Execute,
+    /// `/admin/*`.
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Build => "build",
+            Self::Execute => "execute",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "read" => Self::Read,
+            "build" => Self::Build,
+            "execute" => Self::Execute,
+            "admin" => Self::Admin,
+            _ => return None,
+        })
+    }
+
+    /// Parses a comma-separated list, e.g. `read,execute`.
+    pub fn parse_list(value: &str) -> Result<Vec<Self>> {
+        let mut scopes = Vec::new();
+        for name in value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            let scope = Self::parse(name).ok_or_else(|| {
+                anyhow!("unknown scope '{name}', expected read, build, execute or admin")
+            })?;
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+        Ok(scopes)
+    }
+}
+
+/// A stored key, without its secret.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    /// Unix milliseconds.
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+}
+
+/// A new key; `key` is not stored and cannot be shown again.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub info: ApiKey,
+}
+
+/// The caller behind an authenticated request, added to its extensions.
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    pub key_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl ApiClient {
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope) || self.scopes.contains(&Scope::Admin)
+    }
+}
+
+/// API keys stored hashed in Postgres (`api_keys`). Checking them can be switched off with
+/// `API_AUTH=false`, which leaves every route open.
+#[derive(Clone)]
+pub struct ApiKeys {
+    db: Arc<Client>,
+    enabled: bool,
+}
+
+impl ApiKeys {
+    pub fn new(db: Arc<Client>, enabled: bool) -> Self {
+        Self { db, enabled }
+    }
+
+    pub fn from_env(db: Arc<Client>) -> Self {
+        let enabled = std::env::var("API_AUTH")
+            .map(|value| !matches!(value.trim().to_ascii_lowercase().as_str(), "0" | "false"))
+            .unwrap_or(true);
+        if !enabled {
+            warn!("API_AUTH is off: every route, including /execute, is open to anyone who can reach the API");
+        }
+        Self::new(db, enabled)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Middleware state that lets through keys holding `scope`.
+    pub fn require(&self, scope: Scope) -> ScopeGuard {
+        ScopeGuard {
+            keys: self.clone(),
+            scope,
+        }
+    }
+
+    /// The active key matching `key`, marking it used.
+    pub async fn authenticate(&self, key: &str) -> Result<Option<ApiClient>> {
+        db::touch_api_key(self.db.as_ref(), &hash_key(key)).await
+    }
+
+    pub async fn create(&self, name: &str, scopes: &[Scope]) -> Result<Option<CreatedApiKey>> {
+        create_key(self.db.as_ref(), name, scopes).await
+    }
+
+    pub async fn list(&self) -> Result<Vec<ApiKey>> {
+        db::fetch_api_keys(self.db.as_ref()).await
+    }
+
+    pub async fn revoke(&self, id: Uuid) -> Result<Option<ApiKey>> {
+        db::revoke_api_key(self.db.as_ref(), id).await
+    }
+}
+
+/// Why a key with this name and scopes cannot be created.
+pub fn check_new_key(name: &str, scopes: &[Scope]) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(format!("name must be 1-{MAX_NAME_LEN} characters"));
+    }
+    if scopes.is_empty() {
+        return Err("scopes must name at least one of read, build, execute or admin".into());
+    }
+    Ok(())
+}
+
+/// Generates and stores a key. `None` when an active key already has `name`.
+pub async fn create_key(
+    client: &Client,
+    name: &str,
+    scopes: &[Scope],
+) -> Result<Option<CreatedApiKey>> {
+    check_new_key(name, scopes).map_err(|err| anyhow!(err))?;
+    let name = name.trim();
+    let key = generate_key()?;
+    let info = db::insert_api_key(
+        client,
+        Uuid::new_v4(),
+        name,
+        &hash_key(&key),
+        &key[..SHOWN_PREFIX_LEN],
+        scopes,
+    )
+    .await?;
+    Ok(info.map(|info| CreatedApiKey { key, info }))
+}
+
+fn generate_key() -> Result<String> {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow!("failed to generate randomness"))?;
+    Ok(format!("{KEY_PREFIX}{}", bs58::encode(bytes).into_string()))
+}
+
+/// Keys are random, so a plain SHA-256 is enough to keep them out of the database.
+fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[derive(Clone)]
+pub struct ScopeGuard {
+    keys: ApiKeys,
+    scope: Scope,
+}
+
+/// Middleware: takes the key from `Authorization: Bearer <key>` or `X-Api-Key`, answers
+/// `401` when it is missing or unknown and `403` when it lacks the route's scope. The rest
+/// of the request runs in an `api_client` span, so its log lines name the caller.
+pub async fn require(
+    State(guard): State<ScopeGuard>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !guard.keys.enabled {
+        return next.run(request).await;
+    }
+    let headers = request.headers();
+    let key = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| {
+            headers
+                .get(API_KEY_HEADER)
+                .and_then(|value| value.to_str().ok())
+        })
+        .map(str::trim)
+        .filter(|key| !key.is_empty());
+    let Some(key) = key else {
+        return unauthorized("missing API key");
+    };
+    let client = match guard.keys.authenticate(key).await {
+        Ok(Some(client)) => client,
+        Ok(None) => {
+            warn!(path = %request.uri().path(), "rejected unknown or revoked API key");
+            return unauthorized("invalid API key");
+        }
+        Err(err) => {
+            error!(?err, "failed to check API key");
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "database error",
+                json!({}),
+            );
+        }
+    };
+    if !client.allows(guard.scope) {
+        warn!(
+            client = %client.name,
+            path = %request.uri().path(),
+            scope = guard.scope.as_str(),
+            "API key lacks scope"
+        );
+        return error_response(
+            StatusCode::FORBIDDEN,
+            &format!("API key lacks the '{}' scope", guard.scope.as_str()),
+            json!({ "code": "MISSING_SCOPE", "requiredScope": guard.scope }),
+        );
+    }
+
+    let span = tracing::info_span!(
+        "api_client",
+        client = %client.name,
+        key_id = %client.key_id
+    );
+    request.extensions_mut().insert(client);
+    next.run(request).instrument(span).await
+}
+
+fn unauthorized(message: &str) -> Response {
+    let mut response = error_response(
+        StatusCode::UNAUTHORIZED,
+        message,
+        json!({ "code": "UNAUTHENTICATED" }),
+    );
+    response
+        .headers_mut()
+        .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    response
+}
+
+fn error_response(status: StatusCode, message: &str, details: serde_json::Value) -> Response {
+    let body = Json(ApiErrorBody {
+        error: message,
+        details: details.as_object(),
+    });
+    (status, body).into_response()
+}
@@ -0,0 +1,64 @@
+use anyhow::{anyhow, bail, Context, Result};
+use dotenvy::dotenv;
+use rust_api::{
+    auth::{self, Scope},
+    db,
+};
+use uuid::Uuid;
+
+const USAGE: &str = "usage: apikey create <name> <scopes>   (scopes: comma-separated read,build,execute,admin)\n       apikey list\n       apikey revoke <id>";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let database_url = std::env::var("DATABASE_URL").context("DATABASE_URL not set")?;
+    let (client, _handle) = db::connect(&database_url).await?;
+    db::run_migrations(client.as_ref()).await?;
+
+    match args.first().map(String::as_str) {
+        Some("create") => {
+            let (Some(name), Some(scopes)) = (args.get(1), args.get(2)) else {
+                bail!(USAGE);
+            };
+            let scopes = Scope::parse_list(scopes)?;
+            let created = auth::create_key(client.as_ref(), name, &scopes)
+                .await?
+                .ok_or_else(|| anyhow!("an active API key is already named '{name}'"))?;
+            println!(
+                "Created API key '{}' ({})",
+                created.info.name, created.info.id
+            );
+            println!("{}", created.key);
+            println!("Store it now; it cannot be shown again.");
+        }
+        Some("list") => {
+            for key in db::fetch_api_keys(client.as_ref()).await? {
+                let scopes: Vec<&str> = key.scopes.iter().map(Scope::as_str).collect();
+                let status = if key.revoked_at.is_some() {
+                    "revoked"
+                } else {
+                    "active"
+                };
+                println!(
+                    "{}  {:<24} {}…  {:<8} {}",
+                    key.id,
+                    key.name,
+                    key.prefix,
+                    status,
+                    scopes.join(",")
+                );
+            }
+        }
+        Some("revoke") => {
+            let id = args.get(1).ok_or_else(|| anyhow!(USAGE))?;
+            let id = Uuid::parse_str(id.trim()).context("invalid API key id")?;
+            let key = db::revoke_api_key(client.as_ref(), id)
+                .await?
+                .ok_or_else(|| anyhow!("no API key with id {id}"))?;
+            println!("Revoked API key '{}' ({})", key.name, key.id);
+        }
+        _ => bail!(USAGE),
+    }
+    Ok(())
+}
@@ -18,6 +18,15 @@ async fn main() -> Result<()> {
         .ok()
         .and_then(|v| v.parse().ok())
         .unwrap_or(1000);
+    // Sub-accounts whose user accounts are scanned for PnL events, comma separated.
+    let sub_accounts: Vec<u16> = match std::env::var("BACKFILL_SUB_ACCOUNTS") {
+        Ok(value) => value
+            .split(',')
+            .map(|id| id.trim().parse())
+            .collect::<Result<_, _>>()
+            .context("invalid BACKFILL_SUB_ACCOUNTS")?,
+        Err(_) => vec![0],
+    };
 
     let wallet = Pubkey::from_str(&wallet_str).context("invalid ADMIN_WALLET")?;
     let rpc = RpcClient::new_with_commitment(rpc_url.clone(), CommitmentConfig::confirmed());
@@ -29,34 +38,77 @@ async fn main() -> Result<()> {
     let signatures = fetch_signatures(&rpc, &wallet, fetch_limit)?;
     println!("Fetched {} signatures", signatures.len());
 
+    // Settlements and fills run by keepers are not signed by the wallet, but they do
+    // touch its user accounts.
+    let user_accounts: Vec<Pubkey> = sub_accounts
+        .iter()
+        .map(|&id| decoder.user_account_pubkey(&wallet, id))
+        .collect();
+    let mut seen: HashSet<String> = signatures.iter().cloned().collect();
+    let mut user_signatures = Vec::new();
+    for user_account in &user_accounts {
+        println!("Fetching signatures for user account {user_account}");
+        for signature in fetch_signatures(&rpc, user_account, fetch_limit)? {
+            if seen.insert(signature.clone()) {
+                user_signatures.push(signature);
+            }
+        }
+    }
+    println!(
+        "Fetched {} more signatures for user accounts",
+        user_signatures.len()
+    );
+    let user_accounts: HashSet<String> = user_accounts.iter().map(ToString::to_string).collect();
+
     let mut total_rows = 0u64;
-    for signature in signatures {
-        match decoder.decode_signature(&signature) {
-            Ok((_, actions)) => {
-                if actions.is_empty() {
-                    continue;
+    let mut total_events = 0u64;
+    let wallet_signatures = signatures.into_iter().map(|signature| (signature, true));
+    let other_signatures = user_signatures
+        .into_iter()
+        .map(|signature| (signature, false));
+    for (signature, signed_by_wallet) in wallet_signatures.chain(other_signatures) {
+        let (dump, actions) = match decoder.decode_signature(&signature) {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                eprintln!("{signature}: decode failed: {err:?}");
+                continue;
+            }
+        };
+        // Only the wallet's own transactions are its actions.
+        if signed_by_wallet && !actions.is_empty() {
+            match db::insert_actions(db_client.as_ref(), &actions).await {
+                Ok(rows) => {
+                    total_rows += rows;
+                    println!("{signature}: inserted {rows} rows");
                 }
-                match db::insert_actions(db_client.as_ref(), &actions).await {
-                    Ok(rows) => {
-                        total_rows += rows;
-                        println!("{signature}: inserted {rows} rows");
-                    }
-                    Err(err) => {
-                        eprintln!("{signature}: database insert failed: {err:?}");
-                    }
+                Err(err) => {
+                    eprintln!("{signature}: database insert failed: {err:?}");
                 }
             }
+        }
+        // A keeper transaction can settle many users; keep this wallet's events.
+        let events: Vec<_> = dump
+            .pnl_events
+            .into_iter()
+            .filter(|event| user_accounts.contains(&event.user_account))
+            .collect();
+        match db::insert_pnl_events(db_client.as_ref(), &events).await {
+            Ok(0) => {}
+            Ok(rows) => {
+                total_events += rows;
+                println!("{signature}: inserted {rows} pnl events");
+            }
             Err(err) => {
-                eprintln!("{signature}: decode failed: {err:?}");
+                eprintln!("{signature}: pnl event insert failed: {err:?}");
             }
         }
     }
 
-    println!("Done. Inserted {total_rows} rows.");
+    println!("Done. Inserted {total_rows} rows and {total_events} pnl events.");
     Ok(())
 }
 
-fn fetch_signatures(client: &RpcClient, wallet: &Pubkey, max: usize) -> Result<Vec<String>> {
+fn fetch_signatures(client: &RpcClient, address: &Pubkey, max: usize) -> Result<Vec<String>> {
     let mut before: Option<Signature> = None;
     let mut signatures = Vec::new();
     let mut seen = HashSet::new();
@@ -64,15 +116,14 @@ fn fetch_signatures(client: &RpcClient, wallet: &Pubkey, max: usize) -> Result<V
     while signatures.len() < max {
         let remaining = max - signatures.len();
         let config = GetConfirmedSignaturesForAddress2Config {
-            before: before.clone(),
+            before,
             until: None,
             limit: Some(remaining.min(1000)),
             commitment: Some(CommitmentConfig::confirmed()),
-            ..Default::default()
         };
         let batch = client
-            .get_signatures_for_address_with_config(wallet, config)
-            .context("failed to fetch signatures for wallet")?;
+            .get_signatures_for_address_with_config(address, config)
+            .with_context(|| format!("failed to fetch signatures for {address}"))?;
         if batch.is_empty() {
             break;
         }
@@ -0,0 +1,108 @@
+use std::{io::Read, net::SocketAddr, sync::Arc};
+
+use anyhow::{anyhow, bail, Context, Result};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use dotenvy::dotenv;
+use rust_api::signer::{
+    encrypt_keystore, keystore_passphrase, parse_keypair, ServerSigner, SignRequest, SignResponse,
+};
+use solana_sdk::signer::Signer;
+
+const USAGE: &str =
+    "usage: signer keystore <output-path>   (secret key on stdin)\n       signer serve [addr]";
+const DEFAULT_ADDR: &str = "127.0.0.1:8090";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("keystore") => {
+            let output = args.get(1).ok_or_else(|| anyhow!(USAGE))?;
+            create_keystore(output)
+        }
+        Some("serve") => serve(args.get(1).map(String::as_str).unwrap_or(DEFAULT_ADDR)).await,
+        _ => bail!(USAGE),
+    }
+}
+
+/// Encrypts a secret key read from stdin (any `SERVER_PRIVATE_KEY` format, or the contents
+/// of a Solana CLI keypair file).
+fn create_keystore(output: &str) -> Result<()> {
+    let mut secret = String::new();
+    std::io::stdin()
+        .read_to_string(&mut secret)
+        .context("failed to read secret key from stdin")?;
+    let keypair = parse_keypair(&secret).map_err(|err| anyhow!("invalid secret key: {err}"))?;
+    let passphrase = keystore_passphrase(output)?;
+    if passphrase.is_empty() {
+        bail!("refusing to encrypt with an empty passphrase");
+    }
+    let keystore = encrypt_keystore(&keypair, &passphrase)?;
+    std::fs::write(output, keystore).with_context(|| format!("failed to write {output}"))?;
+    println!("Wrote keystore for {} to {output}", keypair.pubkey());
+    Ok(())
+}
+
+struct StandIn {
+    signer: ServerSigner,
+    token: Option<String>,
+}
+
+/// Local stand-in for the remote signing service. It signs whatever it is sent, so only
+/// use it for development.
+async fn serve(addr: &str) -> Result<()> {
+    let signer = ServerSigner::from_env()?;
+    if matches!(signer, ServerSigner::Remote(_)) {
+        bail!("the stand-in needs a local SIGNER_BACKEND (env, file or keystore)");
+    }
+    let addr: SocketAddr = addr.parse().context("invalid listen address")?;
+    println!("Signing as {} on http://{addr}/sign", signer.pubkey());
+    let state = Arc::new(StandIn {
+        signer,
+        token: std::env::var("SIGNER_REMOTE_TOKEN").ok(),
+    });
+    let app = Router::new().route("/sign", post(sign)).with_state(state);
+    axum::serve(tokio::net::TcpListener::bind(addr).await?, app).await?;
+    Ok(())
+}
+
+async fn sign(
+    State(state): State<Arc<StandIn>>,
+    headers: HeaderMap,
+    Json(request): Json<SignRequest>,
+) -> Result<Json<SignResponse>, (StatusCode, String)> {
+    if let Some(token) = &state.token {
+        let authorized = headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|provided| provided == token);
+        if !authorized {
+            return Err((StatusCode::UNAUTHORIZED, "invalid token".into()));
+        }
+    }
+    if request.pubkey != state.signer.pubkey().to_string() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("unknown key {}", request.pubkey),
+        ));
+    }
+    let message = STANDARD
+        .decode(&request.message)
+        .map_err(|err| (StatusCode::BAD_REQUEST, format!("invalid message: {err}")))?;
+    let signature = state
+        .signer
+        .sign_message(&message)
+        .await
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+    Ok(Json(SignResponse {
+        signature: signature.to_string(),
+    }))
+}
@@ -0,0 +1,435 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::time::MissedTickBehavior;
+use tokio_postgres::Client;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    db,
+    executor::{ordering_key, ExecutionRequest, ExecutorError, TxExecutor},
+    ipc::TsIpc,
+    types::{now_ms, Bracket, BracketLegKind, BracketState, SubmissionState},
+    webhooks::{WebhookEvent, Webhooks},
+};
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const WORKER_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a pending bracket without a submission is left to the request that sent it
+/// before the monitor resolves it from the chain.
+const PENDING_GRACE_MS: i64 = 2 * 60 * 1000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BracketStatus {
+    position_size: f64,
+    open_user_order_ids: Vec<u8>,
+}
+
+/// What a check makes of an active bracket.
+#[derive(Debug, PartialEq)]
+enum LegsOutcome {
+    Unchanged,
+    /// Legs filled or the position closed; whatever is still open gets cancelled.
+    Completed(&'static str),
+    /// These legs are gone but the position did not move, so they did not fill.
+    Broken(Vec<BracketLegKind>),
+}
+
+/// A leg missing from the open orders only counts as filled when the position changed
+/// since the last check, since it may also have been cancelled outside the API.
+fn classify_legs(bracket: &Bracket, status: &BracketStatus) -> LegsOutcome {
+    let missing: Vec<BracketLegKind> = bracket
+        .legs()
+        .filter(|(_, leg)| !status.open_user_order_ids.contains(&leg.user_order_id))
+        .map(|(kind, _)| kind)
+        .collect();
+    let closed = bracket.position_seen && status.position_size == 0.0;
+    let position_changed = bracket
+        .position_size
+        .is_some_and(|size| size != status.position_size);
+    if missing.is_empty() && !closed {
+        return LegsOutcome::Unchanged;
+    }
+    if !closed && !position_changed {
+        return LegsOutcome::Broken(missing);
+    }
+    LegsOutcome::Completed(match missing.as_slice() {
+        [BracketLegKind::TakeProfit] => "take profit filled",
+        [BracketLegKind::StopLoss] => "stop loss filled",
+        [] => "position closed",
+        _ => "bracket legs no longer open",
+    })
+}
+
+/// Watches open brackets and cancels the remaining leg once the other fills, or both once
+/// the position is closed some other way. A leg that vanishes without the position moving
+/// marks the bracket broken instead.
+#[derive(Clone)]
+pub struct BracketMonitor {
+    ipc: TsIpc,
+    executor: Arc<TxExecutor>,
+    db: Arc<Client>,
+    webhooks: Webhooks,
+    interval: Duration,
+}
+
+impl BracketMonitor {
+    pub fn new(
+        ipc: TsIpc,
+        executor: Arc<TxExecutor>,
+        db: Arc<Client>,
+        webhooks: Webhooks,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            ipc,
+            executor,
+            db,
+            webhooks,
+            interval,
+        }
+    }
+
+    pub fn from_env(
+        ipc: TsIpc,
+        executor: Arc<TxExecutor>,
+        db: Arc<Client>,
+        webhooks: Webhooks,
+    ) -> Self {
+        let interval = std::env::var("BRACKET_POLL_INTERVAL_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_POLL_INTERVAL);
+        Self::new(ipc, executor, db, webhooks, interval)
+    }
+
+    /// Runs the monitor for the life of the process. It keeps cancelling legs while trading
+    /// is halted, since that only ever reduces exposure.
+    pub fn spawn(self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            info!(interval = ?self.interval, "bracket monitor started");
+            loop {
+                ticker.tick().await;
+                let brackets =
+                    match db::fetch_brackets(self.db.as_ref(), None, true, i64::MAX).await {
+                        Ok(brackets) => brackets,
+                        Err(err) => {
+                            warn!(?err, "failed to load open brackets");
+                            continue;
+                        }
+                    };
+                for bracket in brackets {
+                    let id = bracket.id.clone();
+                    if let Err(err) = self.check(bracket).await {
+                        warn!(%id, error = %err, "bracket check failed");
+                    }
+                }
+            }
+        })
+    }
+
+    async fn check(&self, bracket: Bracket) -> Result<()> {
+        match bracket.state {
+            BracketState::Pending => self.settle_pending(bracket).await,
+            BracketState::Active => self.watch_legs(bracket).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Promotes or fails a bracket once its entry transaction has an outcome.
+    async fn settle_pending(&self, mut bracket: Bracket) -> Result<()> {
+        match bracket.submission_id.as_deref() {
+            Some(submission_id) => {
+                let id = Uuid::parse_str(submission_id).context("invalid submission id")?;
+                let Some(submission) = db::fetch_submission(self.db.as_ref(), id).await? else {
+                    return Ok(());
+                };
+                match submission.state {
+                    SubmissionState::Confirmed | SubmissionState::Finalized => {
+                        bracket.state = BracketState::Active;
+                        bracket.signature = submission.signature;
+                    }
+                    SubmissionState::Failed => {
+                        bracket.state = BracketState::Failed;
+                        bracket.detail = submission.error;
+                    }
+                    // Without an error the transaction is still being re-signed.
+                    SubmissionState::Expired if submission.error.is_some() => {
+                        bracket.state = BracketState::Failed;
+                        bracket.detail = submission.error;
+                    }
+                    _ => return Ok(()),
+                }
+            }
+            None => {
+                if now_ms() - bracket.updated_at < PENDING_GRACE_MS {
+                    return Ok(());
+                }
+                let status = self.status(&bracket).await?;
+                if status.open_user_order_ids.is_empty() {
+                    bracket.state = BracketState::Failed;
+                    bracket.detail = Some("no bracket legs found on-chain".into());
+                } else {
+                    bracket.state = BracketState::Active;
+                }
+            }
+        }
+        info!(
+            id = %bracket.id,
+            state = bracket.state.as_str(),
+            "bracket entry settled"
+        );
+        db::update_bracket(self.db.as_ref(), &bracket).await?;
+        Ok(())
+    }
+
+    async fn watch_legs(&self, mut bracket: Bracket) -> Result<()> {
+        let status = self.status(&bracket).await?;
+        let outcome = classify_legs(&bracket, &status);
+        let position_open = status.position_size != 0.0;
+        let seen_changed = position_open && !bracket.position_seen;
+        bracket.position_seen |= position_open;
+        // Until the position shows up, the entry size stays the baseline a fill is measured by.
+        let size_changed =
+            bracket.position_seen && bracket.position_size != Some(status.position_size);
+        if bracket.position_seen {
+            bracket.position_size = Some(status.position_size);
+        }
+
+        match outcome {
+            LegsOutcome::Unchanged => {
+                if seen_changed || size_changed {
+                    db::update_bracket(self.db.as_ref(), &bracket).await?;
+                }
+            }
+            LegsOutcome::Broken(missing) => {
+                let missing: Vec<&str> = missing.iter().map(BracketLegKind::describe).collect();
+                let detail = format!(
+                    "{} no longer open but the position did not change; remaining legs left in place",
+                    missing.join(" and ")
+                );
+                warn!(id = %bracket.id, %detail, "bracket broken");
+                bracket.state = BracketState::Broken;
+                bracket.detail = Some(detail);
+                db::update_bracket(self.db.as_ref(), &bracket).await?;
+            }
+            LegsOutcome::Completed(detail) => {
+                if !status.open_user_order_ids.is_empty() {
+                    let signature = self
+                        .cancel_legs(&bracket, &status.open_user_order_ids)
+                        .await?;
+                    bracket.signature = Some(signature);
+                }
+                bracket.state = BracketState::Completed;
+                bracket.detail = Some(detail.into());
+                info!(id = %bracket.id, detail, "bracket completed");
+                db::update_bracket(self.db.as_ref(), &bracket).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn status(&self, bracket: &Bracket) -> Result<BracketStatus> {
+        let user_order_ids: Vec<u8> = bracket.legs().map(|(_, leg)| leg.user_order_id).collect();
+        let args = json!({
+            "wallet": bracket.wallet,
+            "subAccountId": bracket.sub_account_id,
+            "market": bracket.market,
+            "userOrderIds": user_order_ids,
+        });
+        let value = self
+            .ipc
+            .call("getBracketStatus", args, WORKER_TIMEOUT)
+            .await?;
+        serde_json::from_value(value).context("invalid bracket status from worker")
+    }
+
+    async fn cancel_legs(&self, bracket: &Bracket, user_order_ids: &[u8]) -> Result<String> {
+        let args = json!({
+            "wallet": bracket.wallet,
+            "subAccountId": bracket.sub_account_id,
+            "market": bracket.market,
+            "cancelUserOrderIds": user_order_ids,
+        });
+        let value = self
+            .ipc
+            .call("buildBracketLegs", args, WORKER_TIMEOUT)
+            .await?;
+        let tx_base64 = value
+            .get("txBase64")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("worker response missing txBase64"))?;
+        let result = self
+            .executor
+            .execute(ExecutionRequest {
+                tx_base64,
+                wallet: &bracket.wallet_label,
+                route: "bracket",
+                ordering_keys: vec![ordering_key(
+                    &bracket.wallet,
+                    bracket.sub_account_id,
+                    &bracket.market,
+                )],
+                last_valid_block_height: value.get("lastValidBlockHeight").and_then(Value::as_u64),
+                progress: None,
+            })
+            .await;
+        let mut context = json!({
+            "route": "bracket",
+            "bracketId": bracket.id,
+            "wallet": bracket.wallet,
+            "walletLabel": bracket.wallet_label,
+            "subAccountId": bracket.sub_account_id,
+            "market": bracket.market,
+        });
+        let event = match &result {
+            Ok(receipt) => {
+                context["txSignature"] = json!(receipt.signature.to_string());
+                context["slot"] = json!(receipt.slot);
+                WebhookEvent::TransactionConfirmed
+            }
+            Err(err) => {
+                if let ExecutorError::Expired { signature }
+                | ExecutorError::FailedOnChain { signature, .. }
+                | ExecutorError::Unconfirmed { signature, .. } = err
+                {
+                    context["txSignature"] = json!(signature.to_string());
+                }
+                context["error"] = json!(err.to_string());
+                match err {
+                    ExecutorError::Expired { .. } => WebhookEvent::TransactionExpired,
+                    _ => WebhookEvent::TransactionFailed,
+                }
+            }
+        };
+        self.webhooks.emit(event, context).await;
+        let receipt =
+            result.map_err(|err: ExecutorError| anyhow!("failed to cancel bracket legs: {err}"))?;
+        Ok(receipt.signature.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PlacedLeg;
+
+    const TAKE_PROFIT: u8 = 11;
+    const STOP_LOSS: u8 = 12;
+
+    fn bracket(position_seen: bool, position_size: Option<f64>) -> Bracket {
+        let leg = |user_order_id| {
+            Some(PlacedLeg {
+                user_order_id,
+                trigger_price: 100.0,
+                limit_price: None,
+            })
+        };
+        Bracket {
+            id: "bracket".into(),
+            wallet: "wallet".into(),
+            wallet_label: "main".into(),
+            sub_account_id: 0,
+            market: "SOL-PERP".into(),
+            state: BracketState::Active,
+            take_profit: leg(TAKE_PROFIT),
+            stop_loss: leg(STOP_LOSS),
+            position_seen,
+            position_size,
+            submission_id: None,
+            signature: None,
+            detail: None,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    fn status(position_size: f64, open_user_order_ids: &[u8]) -> BracketStatus {
+        BracketStatus {
+            position_size,
+            open_user_order_ids: open_user_order_ids.to_vec(),
+        }
+    }
+
+    #[test]
+    fn both_legs_open_is_unchanged() {
+        let active = bracket(true, Some(2.0));
+        assert_eq!(
+            classify_legs(&active, &status(2.0, &[TAKE_PROFIT, STOP_LOSS])),
+            LegsOutcome::Unchanged
+        );
+        // Legs placed before the entry fills.
+        let unseen = bracket(false, Some(2.0));
+        assert_eq!(
+            classify_legs(&unseen, &status(0.0, &[TAKE_PROFIT, STOP_LOSS])),
+            LegsOutcome::Unchanged
+        );
+    }
+
+    #[test]
+    fn missing_leg_with_changed_position_is_filled() {
+        let bracket = bracket(true, Some(2.0));
+        assert_eq!(
+            classify_legs(&bracket, &status(1.0, &[STOP_LOSS])),
+            LegsOutcome::Completed("take profit filled")
+        );
+        assert_eq!(
+            classify_legs(&bracket, &status(1.0, &[TAKE_PROFIT])),
+            LegsOutcome::Completed("stop loss filled")
+        );
+        assert_eq!(
+            classify_legs(&bracket, &status(1.0, &[])),
+            LegsOutcome::Completed("bracket legs no longer open")
+        );
+    }
+
+    #[test]
+    fn closed_position_completes_the_bracket() {
+        let bracket = bracket(true, Some(2.0));
+        assert_eq!(
+            classify_legs(&bracket, &status(0.0, &[TAKE_PROFIT, STOP_LOSS])),
+            LegsOutcome::Completed("position closed")
+        );
+        assert_eq!(
+            classify_legs(&bracket, &status(0.0, &[TAKE_PROFIT])),
+            LegsOutcome::Completed("stop loss filled")
+        );
+    }
+
+    #[test]
+    fn missing_leg_without_position_change_is_broken() {
+        let active = bracket(true, Some(2.0));
+        assert_eq!(
+            classify_legs(&active, &status(2.0, &[STOP_LOSS])),
+            LegsOutcome::Broken(vec![BracketLegKind::TakeProfit])
+        );
+        // The entry filled but a leg was cancelled before the first check.
+        let unseen = bracket(false, Some(2.0));
+        assert_eq!(
+            classify_legs(&unseen, &status(2.0, &[TAKE_PROFIT])),
+            LegsOutcome::Broken(vec![BracketLegKind::StopLoss])
+        );
+    }
+
+    #[test]
+    fn leg_filled_before_the_position_was_seen_completes() {
+        // The entry size is the baseline until the monitor sees the position.
+        let unseen = bracket(false, Some(2.0));
+        assert_eq!(
+            classify_legs(&unseen, &status(0.0, &[STOP_LOSS])),
+            LegsOutcome::Completed("take profit filled")
+        );
+        let short = bracket(false, Some(-2.0));
+        assert_eq!(
+            classify_legs(&short, &status(0.0, &[TAKE_PROFIT])),
+            LegsOutcome::Completed("stop loss filled")
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use solana_sdk::{
+    compute_budget::{self, ComputeBudgetInstruction},
+    instruction::CompiledInstruction,
+    message::{MessageHeader, VersionedMessage},
+    pubkey::Pubkey,
+    transaction::VersionedTransaction,
+};
+
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+const DEFAULT_UNIT_MARGIN: f64 = 1.2;
+const DEFAULT_ROUTES: &str = "open=standard,close=aggressive,transfer=standard,deposit=economy";
+const DEFAULT_PROFILE: &str = "standard";
+
+// Borsh tags of the ComputeBudgetInstruction variants we override.
+const SET_COMPUTE_UNIT_LIMIT_TAG: u8 = 2;
+const SET_COMPUTE_UNIT_PRICE_TAG: u8 = 3;
+
+/// How aggressively a route bids for block space.
+#[derive(Debug, Clone)]
+pub struct FeePolicy {
+    /// Percentile of recent prioritization fees to pay, `None` to leave the price untouched.
+    pub percentile: Option<u8>,
+    pub min_micro_lamports: u64,
+    pub max_micro_lamports: u64,
+}
+
+impl FeePolicy {
+    fn builtin(profile: &str) -> Option<Self> {
+        let (percentile, max_micro_lamports) = match profile {
+            "none" => (None, 0),
+            "economy" => (Some(25), 10_000),
+            "standard" => (Some(50), 100_000),
+            "aggressive" => (Some(90), 1_000_000),
+            _ => return None,
+        };
+        Some(Self {
+            percentile,
+            min_micro_lamports: 0,
+            max_micro_lamports,
+        })
+    }
+
+    /// Unit price to bid given the fees observed on the transaction's writable accounts.
+    pub fn unit_price(&self, mut recent_fees: Vec<u64>) -> Option<u64> {
+        let percentile = self.percentile?;
+        let observed = if recent_fees.is_empty() {
+            0
+        } else {
+            recent_fees.sort_unstable();
+            let rank = f64::from(percentile.min(100)) / 100.0 * (recent_fees.len() - 1) as f64;
+            recent_fees[rank.round() as usize]
+        };
+        Some(observed.clamp(self.min_micro_lamports, self.max_micro_lamports))
+    }
+}
+
+/// Compute-budget settings applied by the executor before signing.
+#[derive(Debug, Clone)]
+pub struct ComputeBudgetConfig {
+    enabled: bool,
+    unit_margin: f64,
+    routes: HashMap<String, FeePolicy>,
+    default_policy: FeePolicy,
+}
+
+impl ComputeBudgetConfig {
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            unit_margin: DEFAULT_UNIT_MARGIN,
+            routes: HashMap::new(),
+            default_policy: FeePolicy::builtin(DEFAULT_PROFILE).expect("builtin profile"),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        let enabled = std::env::var("COMPUTE_BUDGET_ENABLED")
+            .map(|value| matches!(value.as_str(), "1" | "true" | "TRUE" | "yes" | "YES"))
+            .unwrap_or(false);
+        if !enabled {
+            return Ok(Self::disabled());
+        }
+
+        let unit_margin = match std::env::var("COMPUTE_UNIT_MARGIN") {
+            Ok(value) => value
+                .parse::<f64>()
+                .ok()
+                .filter(|margin| margin.is_finite() && *margin >= 1.0)
+                .ok_or_else(|| format!("invalid COMPUTE_UNIT_MARGIN '{value}'"))?,
+            Err(_) => DEFAULT_UNIT_MARGIN,
+        };
+        let min_micro_lamports = env_u64("PRIORITY_FEE_MIN_MICRO_LAMPORTS")?.unwrap_or(0);
+
+        let load_profile = |profile: &str| -> Result<FeePolicy, String> {
+            let mut policy = FeePolicy::builtin(profile)
+                .ok_or_else(|| format!("unknown priority fee profile '{profile}'"))?;
+            let prefix = format!("PRIORITY_FEE_{}", profile.to_uppercase());
+            if let Some(percentile) = env_u64(&format!("{prefix}_PERCENTILE"))? {
+                let percentile = u8::try_from(percentile)
+                    .ok()
+                    .filter(|p| *p <= 100)
+                    .ok_or_else(|| format!("{prefix}_PERCENTILE must be between 0 and 100"))?;
+                policy.percentile = policy.percentile.map(|_| percentile);
+            }
+            if let Some(max) = env_u64(&format!("{prefix}_MAX_MICRO_LAMPORTS"))? {
+                policy.max_micro_lamports = max;
+            }
+            policy.min_micro_lamports = min_micro_lamports.min(policy.max_micro_lamports);
+            Ok(policy)
+        };
+
+        let default_profile = std::env::var("PRIORITY_FEE_DEFAULT_PROFILE")
+            .unwrap_or_else(|_| DEFAULT_PROFILE.to_string());
+        let default_policy = load_profile(default_profile.trim())?;
+
+        let routes_spec =
+            std::env::var("PRIORITY_FEE_ROUTES").unwrap_or_else(|_| DEFAULT_ROUTES.to_string());
+        let mut routes = HashMap::new();
+        for entry in routes_spec
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+        {
+            let (route, profile) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("invalid PRIORITY_FEE_ROUTES entry '{entry}'"))?;
+            routes.insert(route.trim().to_string(), load_profile(profile.trim())?);
+        }
+
+        Ok(Self {
+            enabled,
+            unit_margin,
+            routes,
+            default_policy,
+        })
+    }
+
+    /// Policy for an execute route, `None` when compute-budget management is disabled.
+    pub fn policy_for(&self, route: &str) -> Option<&FeePolicy> {
+        if !self.enabled {
+            return None;
+        }
+        Some(self.routes.get(route).unwrap_or(&self.default_policy))
+    }
+
+    /// Compute unit limit covering `units_consumed` in simulation plus the safety margin.
+    pub fn unit_limit(&self, units_consumed: u64) -> u32 {
+        let padded = (units_consumed as f64 * self.unit_margin).ceil() as u64;
+        u32::try_from(padded)
+            .unwrap_or(MAX_COMPUTE_UNIT_LIMIT)
+            .min(MAX_COMPUTE_UNIT_LIMIT)
+    }
+}
+
+fn env_u64(name: &str) -> Result<Option<u64>, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid {name} '{value}'")),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Writable static accounts, used to scope `getRecentPrioritizationFees`.
+pub fn writable_accounts(message: &VersionedMessage) -> Vec<Pubkey> {
+    message
+        .static_account_keys()
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| message.is_maybe_writable(*index))
+        .map(|(_, key)| *key)
+        .collect()
+}
+
+/// Prepends `SetComputeUnitLimit`/`SetComputeUnitPrice`, replacing any existing
+/// instruction of the same kind. Existing signatures become invalid.
+pub fn apply(
+    tx: &mut VersionedTransaction,
+    unit_limit: Option<u32>,
+    unit_price: Option<u64>,
+) -> Result<(), String> {
+    if unit_limit.is_none() && unit_price.is_none() {
+        return Ok(());
+    }
+    match &mut tx.message {
+        VersionedMessage::Legacy(message) => rewrite(
+            &mut message.header,
+            &mut message.account_keys,
+            &mut message.instructions,
+            unit_limit,
+            unit_price,
+        ),
+        VersionedMessage::V0(message) => rewrite(
+            &mut message.header,
+            &mut message.account_keys,
+            &mut message.instructions,
+            unit_limit,
+            unit_price,
+        ),
+    }
+}
+
+fn rewrite(
+    header: &mut MessageHeader,
+    account_keys: &mut Vec<Pubkey>,
+    instructions: &mut Vec<CompiledInstruction>,
+    unit_limit: Option<u32>,
+    unit_price: Option<u64>,
+) -> Result<(), String> {
+    let program = compute_budget::id();
+    let program_index = match account_keys.iter().position(|key| *key == program) {
+        Some(index) => index,
+        None => {
+            let static_len = account_keys.len();
+            let highest_index = instructions
+                .iter()
+                .flat_map(|ix| {
+                    std::iter::once(ix.program_id_index).chain(ix.accounts.iter().copied())
+                })
+                .max()
+                .unwrap_or(0);
+            if static_len >= usize::from(u8::MAX) || highest_index == u8::MAX {
+                return Err("transaction has no room for the compute budget program".into());
+            }
+            // Read-only unsigned keys sit at the end of the static list.
+            account_keys.push(program);
+            header.num_readonly_unsigned_accounts += 1;
+            // Lookup-table indexes follow the static keys, so they shift by one.
+            let bump = |index: &mut u8| {
+                if usize::from(*index) >= static_len {
+                    *index += 1;
+                }
+            };
+            for ix in instructions.iter_mut() {
+                bump(&mut ix.program_id_index);
+                ix.accounts.iter_mut().for_each(bump);
+            }
+            static_len
+        }
+    };
+    let program_index = program_index as u8;
+
+    instructions.retain(|ix| {
+        if ix.program_id_index != program_index {
+            return true;
+        }
+        match ix.data.first() {
+            Some(&SET_COMPUTE_UNIT_LIMIT_TAG) => unit_limit.is_none(),
+            Some(&SET_COMPUTE_UNIT_PRICE_TAG) => unit_price.is_none(),
+            _ => true,
+        }
+    });
+
+    let mut prefix = Vec::new();
+    if let Some(limit) = unit_limit {
+        let ix = ComputeBudgetInstruction::set_compute_unit_limit(limit);
+        prefix.push(CompiledInstruction::new_from_raw_parts(
+            program_index,
+            ix.data,
+            Vec::new(),
+        ));
+    }
+    if let Some(price) = unit_price {
+        let ix = ComputeBudgetInstruction::set_compute_unit_price(price);
+        prefix.push(CompiledInstruction::new_from_raw_parts(
+            program_index,
+            ix.data,
+            Vec::new(),
+        ));
+    }
+    instructions.splice(0..0, prefix);
+    Ok(())
+}
@@ -0,0 +1,253 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::future::join_all;
+use solana_client::client_error::ClientError;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::{TransactionError, VersionedTransaction};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, warn};
+
+use crate::executor::{ExecutionEvent, ExecutorError, ProgressSender};
+
+const MAX_CONSECUTIVE_RPC_FAILURES: u32 = 10;
+// `getSignatureStatuses` accepts at most this many signatures per call.
+const MAX_STATUS_BATCH: usize = 256;
+
+pub enum Confirmation {
+    Confirmed { slot: u64 },
+    Failed { err: TransactionError },
+    Expired,
+}
+
+struct Pending {
+    signature: Signature,
+    tx: VersionedTransaction,
+    last_valid: u64,
+    /// Taken once the outcome is known; the entry may stay to watch for finalization.
+    reply: Option<oneshot::Sender<Result<Confirmation, ExecutorError>>>,
+    progress: Option<ProgressSender>,
+    processed: bool,
+    /// Looks past the RPC node's recent status cache, for transactions sent before a restart.
+    search_history: bool,
+}
+
+impl Pending {
+    fn emit(&self, event: ExecutionEvent) {
+        if let Some(progress) = &self.progress {
+            let _ = progress.send(event);
+        }
+    }
+
+    fn is_abandoned(&self) -> bool {
+        match &self.reply {
+            Some(reply) => reply.is_closed(),
+            None => self
+                .progress
+                .as_ref()
+                .is_none_or(|progress| progress.is_closed()),
+        }
+    }
+
+    fn resolve(&mut self, result: Result<Confirmation, ExecutorError>) {
+        if let Some(reply) = self.reply.take() {
+            let _ = reply.send(result);
+        }
+    }
+}
+
+/// Polls every in-flight transaction from one background task, batching status lookups
+/// and rebroadcasting the ones still pending each `resend_interval`.
+#[derive(Clone)]
+pub struct ConfirmationTracker {
+    requests: mpsc::UnboundedSender<Pending>,
+}
+
+impl ConfirmationTracker {
+    /// Spawns the tracking task; it stops once every tracker handle is dropped.
+    pub fn spawn(rpc: Arc<RpcClient>, resend_interval: Duration) -> Self {
+        let (requests, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run_tracker(rpc, resend_interval, receiver));
+        Self { requests }
+    }
+
+    /// Waits until the already-sent `tx` lands or the confirmed block height passes
+    /// `last_valid`. With a `progress` listener, processing and finalization are reported
+    /// too, the latter after this returns. `search_history` is for transactions that may
+    /// have landed longer ago than the node's status cache reaches.
+    pub async fn track(
+        &self,
+        tx: VersionedTransaction,
+        signature: Signature,
+        last_valid: u64,
+        progress: Option<ProgressSender>,
+        search_history: bool,
+    ) -> Result<Confirmation, ExecutorError> {
+        let (reply, receiver) = oneshot::channel();
+        let stopped = || ExecutorError::Rpc("confirmation tracker stopped".into());
+        self.requests
+            .send(Pending {
+                signature,
+                tx,
+                last_valid,
+                reply: Some(reply),
+                progress,
+                processed: false,
+                search_history,
+            })
+            .map_err(|_| stopped())?;
+        receiver.await.map_err(|_| stopped())?
+    }
+}
+
+async fn run_tracker(
+    rpc: Arc<RpcClient>,
+    resend_interval: Duration,
+    mut requests: mpsc::UnboundedReceiver<Pending>,
+) {
+    let mut pending: Vec<Pending> = Vec::new();
+    let mut failures = 0;
+    let mut ticker = tokio::time::interval(resend_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            request = requests.recv() => match request {
+                Some(request) => pending.push(request),
+                None => break,
+            },
+            _ = ticker.tick(), if !pending.is_empty() => {
+                // Callers that gave up no longer need their transaction tracked.
+                pending.retain(|entry| !entry.is_abandoned());
+                match poll(&rpc, std::mem::take(&mut pending)).await {
+                    Ok(remaining) => {
+                        failures = 0;
+                        pending = remaining;
+                    }
+                    Err((remaining, err)) => {
+                        failures += 1;
+                        warn!(error = %err, failures, "failed to poll transaction statuses");
+                        pending = remaining;
+                        if failures >= MAX_CONSECUTIVE_RPC_FAILURES {
+                            for mut entry in pending.drain(..) {
+                                let signature = entry.signature;
+                                entry.resolve(Err(ExecutorError::Rpc(format!(
+                                    "lost contact with rpc while confirming {signature}: {err}"
+                                ))));
+                            }
+                            failures = 0;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Resolves landed or expired entries and rebroadcasts the rest, which are returned.
+async fn poll(
+    rpc: &RpcClient,
+    pending: Vec<Pending>,
+) -> Result<Vec<Pending>, (Vec<Pending>, ClientError)> {
+    // Height is read before the statuses: once it is past `last_valid`, a missing status
+    // means the transaction can no longer land.
+    let height = match rpc
+        .get_block_height_with_commitment(CommitmentConfig::confirmed())
+        .await
+    {
+        Ok(height) => height,
+        Err(err) => return Err((pending, err)),
+    };
+    let mut statuses = Vec::with_capacity(pending.len());
+    for batch in pending.chunks(MAX_STATUS_BATCH) {
+        let signatures: Vec<Signature> = batch.iter().map(|entry| entry.signature).collect();
+        let response = if batch.iter().any(|entry| entry.search_history) {
+            rpc.get_signature_statuses_with_history(&signatures).await
+        } else {
+            rpc.get_signature_statuses(&signatures).await
+        };
+        match response {
+            Ok(response) => statuses.extend(response.value),
+            Err(err) => return Err((pending, err)),
+        }
+    }
+
+    let mut remaining = Vec::new();
+    for (mut entry, status) in pending.into_iter().zip(statuses) {
+        let signature = entry.signature;
+        let Some(status) = status else {
+            if entry.reply.is_some() && height <= entry.last_valid {
+                remaining.push(entry);
+            } else {
+                // Expired, or a confirmed transaction that left the status cache.
+                entry.resolve(Ok(Confirmation::Expired));
+            }
+            continue;
+        };
+
+        if entry.reply.is_some() {
+            if !status.satisfies_commitment(CommitmentConfig::confirmed()) {
+                if !entry.processed {
+                    entry.processed = true;
+                    entry.emit(ExecutionEvent::Processed {
+                        signature,
+                        slot: status.slot,
+                    });
+                }
+                remaining.push(entry);
+                continue;
+            }
+            if let Some(err) = status.err {
+                entry.resolve(Ok(Confirmation::Failed { err }));
+                continue;
+            }
+            entry.emit(ExecutionEvent::Confirmed {
+                signature,
+                slot: status.slot,
+            });
+            entry.resolve(Ok(Confirmation::Confirmed { slot: status.slot }));
+        }
+
+        if status.satisfies_commitment(CommitmentConfig::finalized()) {
+            entry.emit(ExecutionEvent::Finalized {
+                signature,
+                slot: status.slot,
+            });
+        } else if entry.progress.is_some() {
+            remaining.push(entry);
+        }
+    }
+
+    join_all(
+        remaining
+            .iter()
+            .filter(|entry| entry.reply.is_some())
+            .map(|entry| async move {
+                if let Err(err) = send_transaction(rpc, &entry.tx, true).await {
+                    debug!(signature = %entry.signature, error = %err, "rebroadcast failed");
+                }
+            }),
+    )
+    .await;
+    Ok(remaining)
+}
+
+pub(crate) async fn send_transaction(
+    rpc: &RpcClient,
+    tx: &VersionedTransaction,
+    skip_preflight: bool,
+) -> Result<(), ClientError> {
+    let config = RpcSendTransactionConfig {
+        skip_preflight,
+        preflight_commitment: Some(CommitmentConfig::confirmed().commitment),
+        // Rebroadcasting is driven by the tracker rather than the RPC node.
+        max_retries: Some(0),
+        ..RpcSendTransactionConfig::default()
+    };
+    rpc.send_transaction_with_config(tx, config)
+        .await
+        .map(|_| ())
+}
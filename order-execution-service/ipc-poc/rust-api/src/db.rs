@@ -3,9 +3,18 @@ use std::{fs, path::Path, sync::Arc};
 use anyhow::{Context, Result};
 use native_tls::TlsConnector;
 use postgres_native_tls::MakeTlsConnector;
+use serde_json::Value;
 use tokio_postgres::{types::ToSql, Client, Config};
+use uuid::Uuid;
 
-use crate::decoder::ActionRecord;
+use crate::auth::{ApiClient, ApiKey, Scope};
+use crate::decoder::{ActionRecord, PnlEvent};
+use crate::trading_mode::{TradingMode, TradingModeStatus};
+use crate::types::{
+    Bracket, BracketState, FundingPayment, PaperPosition, PlacedLeg, PnlDay, RiskEvent, Schedule,
+    ScheduleAction, ScheduleSlice, ScheduleState, SliceState, SubmissionEvent, SubmissionState,
+    TransactionSubmission, WebhookDelivery, WebhookDeliveryState,
+};
 
 pub async fn connect(database_url: &str) -> Result<(Arc<Client>, tokio::task::JoinHandle<()>)> {
     let config: Config = database_url.parse().context("invalid DATABASE_URL")?;
@@ -109,6 +118,7 @@ pub async fn insert_actions(client: &Client, actions: &[ActionRecord]) -> Result
             &action.token_account.as_deref(),
             &action.token_mint.as_deref(),
             &token_amount,
+            &action.paper,
         ];
 
         let rows = client
@@ -131,9 +141,10 @@ INSERT INTO drift_action_logs (
     amount,
     token_account,
     token_mint,
-    token_amount
+    token_amount,
+    paper
 ) VALUES (
-    $1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14,$15,$16,$17
+    $1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14,$15,$16,$17,$18
 )
 ON CONFLICT (signature, instruction_index) DO UPDATE SET
     slot = EXCLUDED.slot,
@@ -151,6 +162,7 @@ ON CONFLICT (signature, instruction_index) DO UPDATE SET
     token_account = EXCLUDED.token_account,
     token_mint = EXCLUDED.token_mint,
     token_amount = EXCLUDED.token_amount,
+    paper = EXCLUDED.paper,
     inserted_at = NOW()
 "#,
                 params,
@@ -163,6 +175,175 @@ ON CONFLICT (signature, instruction_index) DO UPDATE SET
     Ok(total)
 }
 
+/// Whether any decoded (not paper) action of `signature` is stored.
+pub async fn signature_has_actions(client: &Client, signature: &str) -> Result<bool> {
+    let row = client
+        .query_one(
+            "SELECT EXISTS (SELECT 1 FROM drift_action_logs WHERE signature = $1 AND NOT paper)",
+            &[&signature],
+        )
+        .await
+        .context("failed to query drift_action_logs")?;
+    Ok(row.get(0))
+}
+
+/// Stores decoded settlement, funding and fill fee events; replays are ignored.
+pub async fn insert_pnl_events(client: &Client, events: &[PnlEvent]) -> Result<u64> {
+    if events.is_empty() {
+        return Ok(0);
+    }
+    let mut signatures = Vec::with_capacity(events.len());
+    let mut indexes = Vec::with_capacity(events.len());
+    let mut users = Vec::with_capacity(events.len());
+    let mut slots = Vec::with_capacity(events.len());
+    let mut timestamps = Vec::with_capacity(events.len());
+    let mut kinds = Vec::with_capacity(events.len());
+    let mut markets = Vec::with_capacity(events.len());
+    let mut pnls = Vec::with_capacity(events.len());
+    let mut fees = Vec::with_capacity(events.len());
+    let mut fundings = Vec::with_capacity(events.len());
+    for event in events {
+        signatures.push(event.signature.as_str());
+        indexes.push(i32::try_from(event.event_index).context("event index exceeds i32 range")?);
+        users.push(event.user_account.as_str());
+        slots.push(i64::try_from(event.slot).context("slot exceeds i64 range")?);
+        timestamps.push(event.ts);
+        kinds.push(event.kind.as_str());
+        markets.push(event.market_index as i16);
+        pnls.push(event.pnl);
+        fees.push(event.fee);
+        fundings.push(event.funding);
+    }
+    client
+        .execute(
+            r#"
+INSERT INTO drift_pnl_events (
+    signature, event_index, user_account, slot, ts, kind, market_index, pnl, fee, funding
+)
+SELECT * FROM unnest(
+    $1::TEXT[], $2::INTEGER[], $3::TEXT[], $4::BIGINT[], $5::BIGINT[], $6::TEXT[],
+    $7::SMALLINT[], $8::BIGINT[], $9::BIGINT[], $10::BIGINT[]
+)
+ON CONFLICT (signature, event_index, user_account) DO NOTHING
+"#,
+            &[
+                &signatures,
+                &indexes,
+                &users,
+                &slots,
+                &timestamps,
+                &kinds,
+                &markets,
+                &pnls,
+                &fees,
+                &fundings,
+            ],
+        )
+        .await
+        .context("failed to insert drift_pnl_events")
+}
+
+/// Per market, per UTC day sums of a user account's events between two Unix millisecond
+/// bounds (`to` exclusive), in quote units.
+pub async fn fetch_pnl_days(
+    client: &Client,
+    user_account: &str,
+    from_ms: i64,
+    to_ms: i64,
+    market_index: Option<u16>,
+) -> Result<Vec<PnlDay>> {
+    let rows = client
+        .query(
+            r#"
+SELECT
+    market_index,
+    to_char(to_timestamp(ts) AT TIME ZONE 'UTC', 'YYYY-MM-DD') AS date,
+    (SUM(pnl) / 1e6)::DOUBLE PRECISION AS settled_pnl,
+    (SUM(fee) / 1e6)::DOUBLE PRECISION AS fees,
+    (SUM(funding) / 1e6)::DOUBLE PRECISION AS funding,
+    COUNT(*) FILTER (WHERE kind = 'fill') AS fills
+FROM drift_pnl_events
+WHERE user_account = $1
+  AND ts * 1000 >= $2
+  AND ts * 1000 < $3
+  AND ($4::SMALLINT IS NULL OR market_index = $4)
+GROUP BY market_index, date
+ORDER BY market_index, date
+"#,
+            &[
+                &user_account,
+                &from_ms,
+                &to_ms,
+                &market_index.map(|index| index as i16),
+            ],
+        )
+        .await
+        .context("failed to query drift_pnl_events")?;
+    Ok(rows
+        .iter()
+        .map(|row| PnlDay {
+            market_index: row.get::<_, i16>("market_index") as u16,
+            date: row.get("date"),
+            settled_pnl: row.get("settled_pnl"),
+            fees: row.get("fees"),
+            funding: row.get("funding"),
+            fills: row.get("fills"),
+        })
+        .collect())
+}
+
+/// A wallet's indexed funding payments in a market, newest first, with the sum over all of
+/// them.
+pub async fn fetch_funding_payments(
+    client: &Client,
+    wallet: &str,
+    market_index: u16,
+    limit: i64,
+) -> Result<(Vec<FundingPayment>, f64)> {
+    let total: f64 = client
+        .query_one(
+            "SELECT COALESCE(SUM(funding_payment), 0)::DOUBLE PRECISION AS total \
+             FROM funding_payments WHERE wallet = $1 AND market_index = $2",
+            &[&wallet, &(market_index as i16)],
+        )
+        .await
+        .context("failed to sum funding_payments")?
+        .get("total");
+    let rows = client
+        .query(
+            r#"
+SELECT
+    signature,
+    user_account,
+    market_index,
+    funding_payment,
+    base_asset_amount,
+    slot,
+    (EXTRACT(EPOCH FROM block_time) * 1000)::BIGINT AS block_time_ms
+FROM funding_payments
+WHERE wallet = $1 AND market_index = $2
+ORDER BY slot DESC, id DESC
+LIMIT $3
+"#,
+            &[&wallet, &(market_index as i16), &limit],
+        )
+        .await
+        .context("failed to query funding_payments")?;
+    let payments = rows
+        .iter()
+        .map(|row| FundingPayment {
+            signature: row.get("signature"),
+            user_account: row.get("user_account"),
+            market_index: row.get::<_, i16>("market_index") as u16,
+            funding_payment: row.get("funding_payment"),
+            base_asset_amount: row.get("base_asset_amount"),
+            slot: row.get("slot"),
+            block_time_ms: row.get("block_time_ms"),
+        })
+        .collect();
+    Ok((payments, total))
+}
+
 pub async fn fetch_actions(client: &Client, limit: i64) -> Result<Vec<ActionRecord>> {
     let rows = client
         .query(
@@ -184,7 +365,8 @@ SELECT
     amount,
     token_account,
     token_mint,
-    token_amount
+    token_amount,
+    paper
 FROM drift_action_logs
 ORDER BY slot DESC
 LIMIT $1
@@ -223,7 +405,1447 @@ LIMIT $1
                 token_account: row.get::<_, Option<String>>("token_account"),
                 token_mint: row.get::<_, Option<String>>("token_mint"),
                 token_amount: row.get::<_, Option<i64>>("token_amount").map(|v| v as u64),
+                paper: row.get("paper"),
+            })
+        })
+        .collect()
+}
+
+/// Column changes stored alongside a submission state transition.
+#[derive(Debug, Default)]
+pub struct SubmissionUpdate {
+    pub signature: Option<String>,
+    pub slot: Option<u64>,
+    pub error: Option<String>,
+    pub error_details: Option<Value>,
+    pub signed_tx: Option<String>,
+    pub last_valid_block_height: Option<u64>,
+    /// Free-form note kept on the event only.
+    pub detail: Option<String>,
+}
+
+/// Submission left unfinished by a previous run.
+#[derive(Debug)]
+pub struct ResumableSubmission {
+    pub id: Uuid,
+    pub state: SubmissionState,
+    pub signed_tx: Option<String>,
+    pub last_valid_block_height: Option<u64>,
+}
+
+pub async fn insert_submission(
+    client: &Client,
+    id: Uuid,
+    route: &str,
+    wallet: &str,
+    market: Option<&str>,
+) -> Result<()> {
+    client
+        .execute(
+            r#"
+WITH inserted AS (
+    INSERT INTO transaction_submissions (id, route, wallet, market, state)
+    VALUES ($1, $2, $3, $4, $5)
+    RETURNING id
+)
+INSERT INTO transaction_submission_events (submission_id, state)
+SELECT id, $5 FROM inserted
+"#,
+            &[
+                &id,
+                &route,
+                &wallet,
+                &market,
+                &SubmissionState::Built.as_str(),
+            ],
+        )
+        .await
+        .context("failed to insert transaction_submissions")?;
+    Ok(())
+}
+
+/// Moves a submission to `state` and appends the transition to its event history. Final
+/// outcomes are never overwritten: a confirmed submission may only become finalized, and an
+/// expired one only re-signed or have its expiry recorded again.
+pub async fn record_submission_transition(
+    client: &Client,
+    id: Uuid,
+    state: SubmissionState,
+    update: &SubmissionUpdate,
+) -> Result<()> {
+    let slot = update
+        .slot
+        .map(|v| i64::try_from(v).context("slot exceeds i64 range"))
+        .transpose()?;
+    let last_valid_block_height = update
+        .last_valid_block_height
+        .map(|v| i64::try_from(v).context("block height exceeds i64 range"))
+        .transpose()?;
+    let params: &[&(dyn ToSql + Sync)] = &[
+        &id,
+        &state.as_str(),
+        &update.signature.as_deref(),
+        &slot,
+        &update.error.as_deref(),
+        &update.error_details,
+        &update.signed_tx.as_deref(),
+        &last_valid_block_height,
+        &update.detail.as_deref(),
+    ];
+    client
+        .execute(
+            r#"
+WITH updated AS (
+    UPDATE transaction_submissions SET
+        state = $2,
+        signature = COALESCE($3, signature),
+        slot = COALESCE($4, slot),
+        error = COALESCE($5, error),
+        error_details = COALESCE($6, error_details),
+        signed_tx = COALESCE($7, signed_tx),
+        last_valid_block_height = COALESCE($8, last_valid_block_height),
+        updated_at = NOW()
+    WHERE id = $1
+      AND state NOT IN ('finalized', 'failed')
+      AND (state <> 'confirmed' OR $2 = 'finalized')
+      AND (state <> 'expired' OR $2 IN ('signed', 'expired'))
+    RETURNING id
+)
+INSERT INTO transaction_submission_events (submission_id, state, signature, slot, detail)
+SELECT id, $2, $3, $4, $9 FROM updated
+"#,
+            params,
+        )
+        .await
+        .context("failed to update transaction_submissions")?;
+    Ok(())
+}
+
+pub async fn fetch_submission(client: &Client, id: Uuid) -> Result<Option<TransactionSubmission>> {
+    let Some(row) = client
+        .query_opt(
+            r#"
+SELECT
+    id,
+    route,
+    wallet,
+    market,
+    state,
+    signature,
+    slot,
+    error,
+    error_details,
+    (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT AS created_at_ms,
+    (EXTRACT(EPOCH FROM updated_at) * 1000)::BIGINT AS updated_at_ms
+FROM transaction_submissions
+WHERE id = $1
+"#,
+            &[&id],
+        )
+        .await
+        .context("failed to query transaction_submissions")?
+    else {
+        return Ok(None);
+    };
+
+    let events = client
+        .query(
+            r#"
+SELECT
+    state,
+    signature,
+    slot,
+    detail,
+    (EXTRACT(EPOCH FROM at) * 1000)::BIGINT AS at_ms
+FROM transaction_submission_events
+WHERE submission_id = $1
+ORDER BY id
+"#,
+            &[&id],
+        )
+        .await
+        .context("failed to query transaction_submission_events")?
+        .into_iter()
+        .map(|row| {
+            Ok(SubmissionEvent {
+                state: parse_state(row.get("state"))?,
+                signature: row.get("signature"),
+                slot: row.get::<_, Option<i64>>("slot").map(|v| v as u64),
+                detail: row.get("detail"),
+                at: row.get("at_ms"),
             })
         })
+        .collect::<Result<Vec<_>>>()?;
+
+    let id: Uuid = row.get("id");
+    Ok(Some(TransactionSubmission {
+        submission_id: id.to_string(),
+        route: row.get("route"),
+        wallet: row.get("wallet"),
+        market: row.get("market"),
+        state: parse_state(row.get("state"))?,
+        signature: row.get("signature"),
+        slot: row.get::<_, Option<i64>>("slot").map(|v| v as u64),
+        error: row.get("error"),
+        error_details: row.get("error_details"),
+        created_at: row.get("created_at_ms"),
+        updated_at: row.get("updated_at_ms"),
+        events,
+    }))
+}
+
+/// Submissions that had not yet confirmed or failed, oldest first.
+pub async fn fetch_resumable_submissions(client: &Client) -> Result<Vec<ResumableSubmission>> {
+    let rows = client
+        .query(
+            r#"
+SELECT id, state, signed_tx, last_valid_block_height
+FROM transaction_submissions
+WHERE state IN ('built', 'signed', 'sent', 'processed')
+ORDER BY created_at
+"#,
+            &[],
+        )
+        .await
+        .context("failed to query unfinished transaction_submissions")?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(ResumableSubmission {
+                id: row.get("id"),
+                state: parse_state(row.get("state"))?,
+                signed_tx: row.get("signed_tx"),
+                last_valid_block_height: row
+                    .get::<_, Option<i64>>("last_valid_block_height")
+                    .map(|v| v as u64),
+            })
+        })
+        .collect()
+}
+
+fn parse_state(value: &str) -> Result<SubmissionState> {
+    SubmissionState::parse(value).with_context(|| format!("unknown submission state '{value}'"))
+}
+
+/// What an `Idempotency-Key` already maps to.
+#[derive(Debug)]
+pub enum IdempotencyClaim {
+    /// The key was free and now belongs to the caller.
+    Claimed,
+    InProgress {
+        request_hash: String,
+    },
+    Completed {
+        request_hash: String,
+        status_code: u16,
+        response: Value,
+    },
+}
+
+/// Claims `key` for a new request from the API key `api_key_id`, first dropping its record if it is older than
+/// `retention_secs`, or still in progress after `in_progress_timeout_secs`.
+pub async fn claim_idempotency_key(
+    client: &Client,
+    api_key_id: Uuid,
+    key: &str,
+    request_hash: &str,
+    retention_secs: f64,
+    in_progress_timeout_secs: f64,
+) -> Result<IdempotencyClaim> {
+    client
+        .execute(
+            r#"
+DELETE FROM idempotency_keys
+WHERE api_key_id = $1
+  AND key = $2
+  AND (
+    created_at < NOW() - make_interval(secs => $3)
+    OR (completed_at IS NULL AND created_at < NOW() - make_interval(secs => $4))
+  )
+"#,
+            &[
+                &api_key_id,
+                &key,
+                &retention_secs,
+                &in_progress_timeout_secs,
+            ],
+        )
+        .await
+        .context("failed to expire idempotency_keys")?;
+
+    let inserted = client
+        .execute(
+            r#"
+INSERT INTO idempotency_keys (api_key_id, key, request_hash)
+VALUES ($1, $2, $3)
+ON CONFLICT (api_key_id, key) DO NOTHING
+"#,
+            &[&api_key_id, &key, &request_hash],
+        )
+        .await
+        .context("failed to insert idempotency_keys")?;
+    if inserted == 1 {
+        return Ok(IdempotencyClaim::Claimed);
+    }
+
+    let row = client
+        .query_one(
+            "SELECT request_hash, status_code, response FROM idempotency_keys WHERE api_key_id = $1 AND key = $2",
+            &[&api_key_id, &key],
+        )
+        .await
+        .context("failed to query idempotency_keys")?;
+    let request_hash: String = row.get("request_hash");
+    Ok(
+        match (
+            row.get::<_, Option<i16>>("status_code"),
+            row.get::<_, Option<Value>>("response"),
+        ) {
+            (Some(status_code), Some(response)) => IdempotencyClaim::Completed {
+                request_hash,
+                status_code: status_code as u16,
+                response,
+            },
+            _ => IdempotencyClaim::InProgress { request_hash },
+        },
+    )
+}
+
+pub async fn complete_idempotency_key(
+    client: &Client,
+    api_key_id: Uuid,
+    key: &str,
+    status_code: u16,
+    response: &Value,
+) -> Result<()> {
+    client
+        .execute(
+            r#"
+UPDATE idempotency_keys
+SET status_code = $3, response = $4, completed_at = NOW()
+WHERE api_key_id = $1 AND key = $2
+"#,
+            &[&api_key_id, &key, &(status_code as i16), response],
+        )
+        .await
+        .context("failed to update idempotency_keys")?;
+    Ok(())
+}
+
+pub async fn release_idempotency_key(client: &Client, api_key_id: Uuid, key: &str) -> Result<()> {
+    client
+        .execute(
+            "DELETE FROM idempotency_keys WHERE api_key_id = $1 AND key = $2",
+            &[&api_key_id, &key],
+        )
+        .await
+        .context("failed to delete idempotency_keys")?;
+    Ok(())
+}
+
+/// Combined settled and unrealized PnL recorded at the first risk check of the current UTC
+/// day, storing `current_pnl` when there is none yet.
+pub async fn daily_opening_pnl(
+    client: &Client,
+    wallet: &str,
+    sub_account_id: u16,
+    current_pnl: f64,
+) -> Result<f64> {
+    let row = client
+        .query_one(
+            r#"
+INSERT INTO risk_daily_baselines (wallet, sub_account_id, day, opening_pnl)
+VALUES ($1, $2, (NOW() AT TIME ZONE 'UTC')::DATE, $3)
+ON CONFLICT (wallet, sub_account_id, day)
+DO UPDATE SET opening_pnl = risk_daily_baselines.opening_pnl
+RETURNING opening_pnl
+"#,
+            &[&wallet, &i32::from(sub_account_id), &current_pnl],
+        )
+        .await
+        .context("failed to upsert risk_daily_baselines")?;
+    Ok(row.get("opening_pnl"))
+}
+
+/// Today's baseline from [`daily_opening_pnl`], without storing one when there is none.
+pub async fn fetch_daily_opening_pnl(
+    client: &Client,
+    wallet: &str,
+    sub_account_id: u16,
+) -> Result<Option<f64>> {
+    let row = client
+        .query_opt(
+            r#"
+SELECT opening_pnl FROM risk_daily_baselines
+WHERE wallet = $1 AND sub_account_id = $2 AND day = (NOW() AT TIME ZONE 'UTC')::DATE
+"#,
+            &[&wallet, &i32::from(sub_account_id)],
+        )
+        .await
+        .context("failed to query risk_daily_baselines")?;
+    Ok(row.map(|row| row.get("opening_pnl")))
+}
+
+const WITHDRAWN_TODAY: &str = r#"
+SELECT COALESCE(SUM(amount), 0)::DOUBLE PRECISION AS total
+FROM risk_withdrawals
+WHERE wallet = $1
+  AND sub_account_id = $2
+  AND created_at >= date_trunc('day', NOW() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'
+"#;
+
+/// Margin withdrawn since midnight UTC.
+pub async fn withdrawn_today(client: &Client, wallet: &str, sub_account_id: u16) -> Result<f64> {
+    let row = client
+        .query_one(WITHDRAWN_TODAY, &[&wallet, &i32::from(sub_account_id)])
+        .await
+        .context("failed to query risk_withdrawals")?;
+    Ok(row.get("total"))
+}
+
+/// Records a withdrawal of `amount` unless it would take today's total past `limit`.
+/// Returns the total withdrawn before this one and whether it was recorded.
+pub async fn reserve_withdrawal(
+    client: &Client,
+    wallet: &str,
+    sub_account_id: u16,
+    market: &str,
+    amount: f64,
+    limit: f64,
+) -> Result<(f64, bool)> {
+    let sql = format!(
+        r#"
+WITH used AS ({WITHDRAWN_TODAY}),
+inserted AS (
+    INSERT INTO risk_withdrawals (wallet, sub_account_id, market, amount)
+    SELECT $1, $2, $3, $4 FROM used WHERE used.total + $4 <= $5
+    RETURNING id
+)
+SELECT used.total, EXISTS (SELECT 1 FROM inserted) AS recorded FROM used
+"#
+    );
+    let row = client
+        .query_one(
+            sql.as_str(),
+            &[
+                &wallet,
+                &i32::from(sub_account_id),
+                &market,
+                &amount,
+                &limit,
+            ],
+        )
+        .await
+        .context("failed to insert risk_withdrawals")?;
+    Ok((row.get("total"), row.get("recorded")))
+}
+
+/// Stores the latest `nextLiquidationId` and stamps `liquidated_at` when it moved or the
+/// account is being liquidated. Returns seconds since the last known liquidation.
+pub async fn observe_liquidations(
+    client: &Client,
+    wallet: &str,
+    sub_account_id: u16,
+    next_liquidation_id: u16,
+    being_liquidated: bool,
+) -> Result<Option<f64>> {
+    let row = client
+        .query_one(
+            r#"
+INSERT INTO risk_liquidations (wallet, sub_account_id, last_liquidation_id, liquidated_at)
+VALUES ($1, $2, $3, CASE WHEN $4 THEN NOW() END)
+ON CONFLICT (wallet, sub_account_id) DO UPDATE SET
+    last_liquidation_id = EXCLUDED.last_liquidation_id,
+    liquidated_at = CASE
+        WHEN $4 OR EXCLUDED.last_liquidation_id <> risk_liquidations.last_liquidation_id
+            THEN NOW()
+        ELSE risk_liquidations.liquidated_at
+    END
+RETURNING EXTRACT(EPOCH FROM NOW() - liquidated_at)::DOUBLE PRECISION AS secs_since
+"#,
+            &[
+                &wallet,
+                &i32::from(sub_account_id),
+                &i32::from(next_liquidation_id),
+                &being_liquidated,
+            ],
+        )
+        .await
+        .context("failed to upsert risk_liquidations")?;
+    Ok(row.get("secs_since"))
+}
+
+/// What [`observe_liquidations`] would return, without storing the observation.
+pub async fn peek_liquidations(
+    client: &Client,
+    wallet: &str,
+    sub_account_id: u16,
+    next_liquidation_id: u16,
+    being_liquidated: bool,
+) -> Result<Option<f64>> {
+    let row = client
+        .query_opt(
+            r#"
+SELECT CASE
+    WHEN $3 OR last_liquidation_id <> $4 THEN 0::DOUBLE PRECISION
+    ELSE EXTRACT(EPOCH FROM NOW() - liquidated_at)::DOUBLE PRECISION
+END AS secs_since
+FROM risk_liquidations
+WHERE wallet = $1 AND sub_account_id = $2
+"#,
+            &[
+                &wallet,
+                &i32::from(sub_account_id),
+                &being_liquidated,
+                &i32::from(next_liquidation_id),
+            ],
+        )
+        .await
+        .context("failed to query risk_liquidations")?;
+    Ok(match row {
+        Some(row) => row.get("secs_since"),
+        None => being_liquidated.then_some(0.0),
+    })
+}
+
+/// A risk breach to append to `risk_audit_events`.
+pub struct NewRiskEvent<'a> {
+    pub wallet: &'a str,
+    pub wallet_label: Option<&'a str>,
+    pub sub_account_id: u16,
+    pub route: &'a str,
+    pub code: &'a str,
+    pub message: &'a str,
+    pub details: &'a Value,
+}
+
+pub async fn insert_risk_event(client: &Client, event: &NewRiskEvent<'_>) -> Result<()> {
+    client
+        .execute(
+            r#"
+INSERT INTO risk_audit_events (wallet, wallet_label, sub_account_id, route, code, message, details)
+VALUES ($1, $2, $3, $4, $5, $6, $7)
+"#,
+            &[
+                &event.wallet,
+                &event.wallet_label,
+                &i32::from(event.sub_account_id),
+                &event.route,
+                &event.code,
+                &event.message,
+                event.details,
+            ],
+        )
+        .await
+        .context("failed to insert risk_audit_events")?;
+    Ok(())
+}
+
+pub async fn fetch_risk_events(
+    client: &Client,
+    wallet: Option<&str>,
+    limit: i64,
+) -> Result<Vec<RiskEvent>> {
+    let rows = client
+        .query(
+            r#"
+SELECT wallet, wallet_label, sub_account_id, route, code, message, details,
+    (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT AS created_at_ms
+FROM risk_audit_events
+WHERE $1::TEXT IS NULL OR wallet = $1
+ORDER BY created_at DESC
+LIMIT $2
+"#,
+            &[&wallet, &limit],
+        )
+        .await
+        .context("failed to query risk_audit_events")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| RiskEvent {
+            wallet: row.get("wallet"),
+            wallet_label: row.get("wallet_label"),
+            sub_account_id: row.get::<_, i32>("sub_account_id") as u16,
+            route: row.get("route"),
+            code: row.get("code"),
+            message: row.get("message"),
+            details: row.get("details"),
+            created_at: row.get("created_at_ms"),
+        })
+        .collect())
+}
+
+pub async fn fetch_trading_mode(client: &Client) -> Result<Option<TradingModeStatus>> {
+    let row = client
+        .query_opt(
+            r#"
+SELECT mode, reason, (EXTRACT(EPOCH FROM updated_at) * 1000)::BIGINT AS updated_at_ms
+FROM trading_mode
+"#,
+            &[],
+        )
+        .await
+        .context("failed to query trading_mode")?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let mode: String = row.get("mode");
+    Ok(Some(TradingModeStatus {
+        mode: TradingMode::parse(&mode)
+            .with_context(|| format!("unknown trading mode '{mode}'"))?,
+        reason: row.get("reason"),
+        updated_at: Some(row.get("updated_at_ms")),
+    }))
+}
+
+/// Returns when the mode was stored, in Unix milliseconds.
+pub async fn store_trading_mode(
+    client: &Client,
+    mode: TradingMode,
+    reason: Option<&str>,
+) -> Result<i64> {
+    let row = client
+        .query_one(
+            r#"
+INSERT INTO trading_mode (id, mode, reason, updated_at)
+VALUES (TRUE, $1, $2, NOW())
+ON CONFLICT (id) DO UPDATE SET mode = EXCLUDED.mode, reason = EXCLUDED.reason, updated_at = NOW()
+RETURNING (EXTRACT(EPOCH FROM updated_at) * 1000)::BIGINT AS updated_at_ms
+"#,
+            &[&mode.as_str(), &reason],
+        )
+        .await
+        .context("failed to update trading_mode")?;
+    Ok(row.get("updated_at_ms"))
+}
+
+const BRACKET_COLUMNS: &str = r#"
+    id, wallet, wallet_label, sub_account_id, market, state,
+    take_profit_user_order_id, take_profit_trigger_price, take_profit_limit_price,
+    stop_loss_user_order_id, stop_loss_trigger_price, stop_loss_limit_price,
+    position_seen, position_size, submission_id, signature, detail,
+    (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT AS created_at_ms,
+    (EXTRACT(EPOCH FROM updated_at) * 1000)::BIGINT AS updated_at_ms
+"#;
+
+fn bracket_from_row(row: &tokio_postgres::Row) -> Result<Bracket> {
+    let leg = |prefix: &str| -> Option<PlacedLeg> {
+        let user_order_id: Option<i16> = row.get(format!("{prefix}_user_order_id").as_str());
+        Some(PlacedLeg {
+            user_order_id: user_order_id? as u8,
+            trigger_price: row.get(format!("{prefix}_trigger_price").as_str()),
+            limit_price: row.get(format!("{prefix}_limit_price").as_str()),
+        })
+    };
+    let state: &str = row.get("state");
+    Ok(Bracket {
+        id: row.get::<_, Uuid>("id").to_string(),
+        wallet: row.get("wallet"),
+        wallet_label: row.get("wallet_label"),
+        sub_account_id: row.get::<_, i32>("sub_account_id") as u16,
+        market: row.get("market"),
+        state: BracketState::parse(state)
+            .with_context(|| format!("unknown bracket state '{state}'"))?,
+        take_profit: leg("take_profit"),
+        stop_loss: leg("stop_loss"),
+        position_seen: row.get("position_seen"),
+        position_size: row.get("position_size"),
+        submission_id: row
+            .get::<_, Option<Uuid>>("submission_id")
+            .map(|id| id.to_string()),
+        signature: row.get("signature"),
+        detail: row.get("detail"),
+        created_at: row.get("created_at_ms"),
+        updated_at: row.get("updated_at_ms"),
+    })
+}
+
+fn bracket_id(bracket: &Bracket) -> Result<Uuid> {
+    Uuid::parse_str(&bracket.id).context("invalid bracket id")
+}
+
+fn leg_columns(leg: Option<PlacedLeg>) -> (Option<i16>, Option<f64>, Option<f64>) {
+    (
+        leg.map(|leg| i16::from(leg.user_order_id)),
+        leg.map(|leg| leg.trigger_price),
+        leg.and_then(|leg| leg.limit_price),
+    )
+}
+
+/// Returns the creation time in Unix milliseconds, `None` when the position already has a
+/// pending or active bracket.
+pub async fn insert_bracket(client: &Client, bracket: &Bracket) -> Result<Option<i64>> {
+    let id = bracket_id(bracket)?;
+    let submission_id = bracket
+        .submission_id
+        .as_deref()
+        .map(Uuid::parse_str)
+        .transpose()
+        .context("invalid submission id")?;
+    let (tp_id, tp_trigger, tp_limit) = leg_columns(bracket.take_profit);
+    let (sl_id, sl_trigger, sl_limit) = leg_columns(bracket.stop_loss);
+    let params: &[&(dyn ToSql + Sync)] = &[
+        &id,
+        &bracket.wallet,
+        &bracket.wallet_label,
+        &i32::from(bracket.sub_account_id),
+        &bracket.market,
+        &bracket.state.as_str(),
+        &tp_id,
+        &tp_trigger,
+        &tp_limit,
+        &sl_id,
+        &sl_trigger,
+        &sl_limit,
+        &bracket.position_seen,
+        &bracket.position_size,
+        &submission_id,
+        &bracket.signature,
+        &bracket.detail,
+    ];
+    let row = client
+        .query_opt(
+            r#"
+INSERT INTO brackets (
+    id, wallet, wallet_label, sub_account_id, market, state,
+    take_profit_user_order_id, take_profit_trigger_price, take_profit_limit_price,
+    stop_loss_user_order_id, stop_loss_trigger_price, stop_loss_limit_price,
+    position_seen, position_size, submission_id, signature, detail
+)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+ON CONFLICT (wallet, sub_account_id, market) WHERE state IN ('pending', 'active') DO NOTHING
+RETURNING (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT AS created_at_ms
+"#,
+            params,
+        )
+        .await
+        .context("failed to insert brackets")?;
+    Ok(row.map(|row| row.get("created_at_ms")))
+}
+
+/// Stores the bracket's state, legs and notes, returning the new `updated_at` in Unix
+/// milliseconds.
+pub async fn update_bracket(client: &Client, bracket: &Bracket) -> Result<i64> {
+    let id = bracket_id(bracket)?;
+    let submission_id = bracket
+        .submission_id
+        .as_deref()
+        .map(Uuid::parse_str)
+        .transpose()
+        .context("invalid submission id")?;
+    let (tp_id, tp_trigger, tp_limit) = leg_columns(bracket.take_profit);
+    let (sl_id, sl_trigger, sl_limit) = leg_columns(bracket.stop_loss);
+    let params: &[&(dyn ToSql + Sync)] = &[
+        &id,
+        &bracket.state.as_str(),
+        &tp_id,
+        &tp_trigger,
+        &tp_limit,
+        &sl_id,
+        &sl_trigger,
+        &sl_limit,
+        &bracket.position_seen,
+        &bracket.position_size,
+        &submission_id,
+        &bracket.signature,
+        &bracket.detail,
+    ];
+    let row = client
+        .query_one(
+            r#"
+UPDATE brackets SET
+    state = $2,
+    take_profit_user_order_id = $3,
+    take_profit_trigger_price = $4,
+    take_profit_limit_price = $5,
+    stop_loss_user_order_id = $6,
+    stop_loss_trigger_price = $7,
+    stop_loss_limit_price = $8,
+    position_seen = $9,
+    position_size = $10,
+    submission_id = $11,
+    signature = $12,
+    detail = $13,
+    updated_at = NOW()
+WHERE id = $1
+RETURNING (EXTRACT(EPOCH FROM updated_at) * 1000)::BIGINT AS updated_at_ms
+"#,
+            params,
+        )
+        .await
+        .context("failed to update brackets")?;
+    Ok(row.get("updated_at_ms"))
+}
+
+/// The pending or active bracket on a position.
+pub async fn fetch_open_bracket(
+    client: &Client,
+    wallet: &str,
+    sub_account_id: u16,
+    market: &str,
+) -> Result<Option<Bracket>> {
+    let sql = format!(
+        "SELECT {BRACKET_COLUMNS} FROM brackets \
+         WHERE wallet = $1 AND sub_account_id = $2 AND market = $3 \
+         AND state IN ('pending', 'active')"
+    );
+    client
+        .query_opt(
+            sql.as_str(),
+            &[&wallet, &i32::from(sub_account_id), &market],
+        )
+        .await
+        .context("failed to query brackets")?
+        .map(|row| bracket_from_row(&row))
+        .transpose()
+}
+
+/// Newest first; `open_only` keeps pending and active brackets.
+pub async fn fetch_brackets(
+    client: &Client,
+    wallet: Option<&str>,
+    open_only: bool,
+    limit: i64,
+) -> Result<Vec<Bracket>> {
+    let sql = format!(
+        "SELECT {BRACKET_COLUMNS} FROM brackets \
+         WHERE ($1::TEXT IS NULL OR wallet = $1) \
+         AND (NOT $2 OR state IN ('pending', 'active')) \
+         ORDER BY created_at DESC LIMIT $3"
+    );
+    client
+        .query(sql.as_str(), &[&wallet, &open_only, &limit])
+        .await
+        .context("failed to query brackets")?
+        .iter()
+        .map(bracket_from_row)
+        .collect()
+}
+
+const SCHEDULE_COLUMNS: &str = r#"
+    s.id, s.wallet, s.wallet_label, s.sub_account_id, s.market, s.action, s.size,
+    s.leverage, s.margin, s.state, s.detail,
+    (SELECT COUNT(*) FROM schedule_slices c WHERE c.schedule_id = s.id) AS slice_count,
+    (SELECT COUNT(*) FROM schedule_slices c
+        WHERE c.schedule_id = s.id AND c.state = 'confirmed') AS confirmed_slices,
+    (EXTRACT(EPOCH FROM s.created_at) * 1000)::BIGINT AS created_at_ms,
+    (EXTRACT(EPOCH FROM s.updated_at) * 1000)::BIGINT AS updated_at_ms
+"#;
+
+fn schedule_from_row(row: &tokio_postgres::Row) -> Result<Schedule> {
+    let action: &str = row.get("action");
+    let state: &str = row.get("state");
+    Ok(Schedule {
+        id: row.get::<_, Uuid>("id").to_string(),
+        wallet: row.get("wallet"),
+        wallet_label: row.get("wallet_label"),
+        sub_account_id: row.get::<_, i32>("sub_account_id") as u16,
+        market: row.get("market"),
+        action: ScheduleAction::parse(action)
+            .with_context(|| format!("unknown schedule action '{action}'"))?,
+        size: row.get("size"),
+        leverage: row.get("leverage"),
+        margin: row.get("margin"),
+        state: ScheduleState::parse(state)
+            .with_context(|| format!("unknown schedule state '{state}'"))?,
+        detail: row.get("detail"),
+        slice_count: row.get::<_, i64>("slice_count") as u32,
+        confirmed_slices: row.get::<_, i64>("confirmed_slices") as u32,
+        created_at: row.get("created_at_ms"),
+        updated_at: row.get("updated_at_ms"),
+        slices: None,
+    })
+}
+
+fn slice_from_row(row: &tokio_postgres::Row) -> Result<ScheduleSlice> {
+    let state: &str = row.get("state");
+    Ok(ScheduleSlice {
+        index: row.get::<_, i32>("slice_index") as u32,
+        due_at: row.get("due_at_ms"),
+        size: row.get("size"),
+        margin: row.get("margin"),
+        state: SliceState::parse(state)
+            .with_context(|| format!("unknown slice state '{state}'"))?,
+        submission_id: row
+            .get::<_, Option<Uuid>>("submission_id")
+            .map(|id| id.to_string()),
+        signature: row.get("signature"),
+        detail: row.get("detail"),
+        updated_at: row.get("updated_at_ms"),
+    })
+}
+
+/// Stores a new schedule together with its slices, returning the creation time in Unix
+/// milliseconds.
+pub async fn insert_schedule(
+    client: &Client,
+    schedule: &Schedule,
+    slices: &[ScheduleSlice],
+) -> Result<i64> {
+    let id = Uuid::parse_str(&schedule.id).context("invalid schedule id")?;
+    let indexes: Vec<i32> = slices.iter().map(|slice| slice.index as i32).collect();
+    let due_at: Vec<i64> = slices.iter().map(|slice| slice.due_at).collect();
+    let sizes: Vec<Option<f64>> = slices.iter().map(|slice| slice.size).collect();
+    let margins: Vec<Option<f64>> = slices.iter().map(|slice| slice.margin).collect();
+    let params: &[&(dyn ToSql + Sync)] = &[
+        &id,
+        &schedule.wallet,
+        &schedule.wallet_label,
+        &i32::from(schedule.sub_account_id),
+        &schedule.market,
+        &schedule.action.as_str(),
+        &schedule.size,
+        &schedule.leverage,
+        &schedule.margin,
+        &schedule.state.as_str(),
+        &indexes,
+        &due_at,
+        &sizes,
+        &margins,
+        &SliceState::Pending.as_str(),
+    ];
+    let row = client
+        .query_one(
+            r#"
+WITH inserted AS (
+    INSERT INTO schedules (
+        id, wallet, wallet_label, sub_account_id, market, action, size, leverage, margin, state
+    )
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+    RETURNING id, created_at
+), slices AS (
+    INSERT INTO schedule_slices (schedule_id, slice_index, due_at, size, margin, state)
+    SELECT
+        inserted.id,
+        slice.slice_index,
+        to_timestamp(slice.due_at_ms / 1000.0::DOUBLE PRECISION),
+        slice.size,
+        slice.margin,
+        $15
+    FROM inserted,
+        unnest($11::INTEGER[], $12::BIGINT[], $13::DOUBLE PRECISION[], $14::DOUBLE PRECISION[])
+            AS slice (slice_index, due_at_ms, size, margin)
+)
+SELECT (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT AS created_at_ms FROM inserted
+"#,
+            params,
+        )
+        .await
+        .context("failed to insert schedules")?;
+    Ok(row.get("created_at_ms"))
+}
+
+pub async fn fetch_schedule(client: &Client, id: Uuid) -> Result<Option<Schedule>> {
+    let sql = format!("SELECT {SCHEDULE_COLUMNS} FROM schedules s WHERE s.id = $1");
+    client
+        .query_opt(sql.as_str(), &[&id])
+        .await
+        .context("failed to query schedules")?
+        .map(|row| schedule_from_row(&row))
+        .transpose()
+}
+
+/// Newest first.
+pub async fn fetch_schedules(
+    client: &Client,
+    wallet: Option<&str>,
+    limit: i64,
+) -> Result<Vec<Schedule>> {
+    let sql = format!(
+        "SELECT {SCHEDULE_COLUMNS} FROM schedules s \
+         WHERE ($1::TEXT IS NULL OR s.wallet = $1) \
+         ORDER BY s.created_at DESC LIMIT $2"
+    );
+    client
+        .query(sql.as_str(), &[&wallet, &limit])
+        .await
+        .context("failed to query schedules")?
+        .iter()
+        .map(schedule_from_row)
+        .collect()
+}
+
+/// Active schedules, plus any other schedule that still has a slice in flight, oldest first.
+pub async fn fetch_runnable_schedules(client: &Client) -> Result<Vec<Schedule>> {
+    let sql = format!(
+        "SELECT {SCHEDULE_COLUMNS} FROM schedules s \
+         WHERE s.state = 'active' OR EXISTS ( \
+             SELECT 1 FROM schedule_slices c \
+             WHERE c.schedule_id = s.id AND c.state = 'submitted') \
+         ORDER BY s.created_at"
+    );
+    client
+        .query(sql.as_str(), &[])
+        .await
+        .context("failed to query schedules")?
+        .iter()
+        .map(schedule_from_row)
+        .collect()
+}
+
+/// In slice order.
+pub async fn fetch_schedule_slices(client: &Client, id: Uuid) -> Result<Vec<ScheduleSlice>> {
+    client
+        .query(
+            r#"
+SELECT
+    slice_index,
+    (EXTRACT(EPOCH FROM due_at) * 1000)::BIGINT AS due_at_ms,
+    size,
+    margin,
+    state,
+    submission_id,
+    signature,
+    detail,
+    (EXTRACT(EPOCH FROM updated_at) * 1000)::BIGINT AS updated_at_ms
+FROM schedule_slices
+WHERE schedule_id = $1
+ORDER BY slice_index
+"#,
+            &[&id],
+        )
+        .await
+        .context("failed to query schedule_slices")?
+        .iter()
+        .map(slice_from_row)
         .collect()
 }
+
+/// Stores a slice's state, submission and notes.
+pub async fn update_schedule_slice(
+    client: &Client,
+    schedule_id: Uuid,
+    slice: &ScheduleSlice,
+) -> Result<()> {
+    let submission_id = slice
+        .submission_id
+        .as_deref()
+        .map(Uuid::parse_str)
+        .transpose()
+        .context("invalid submission id")?;
+    client
+        .execute(
+            r#"
+UPDATE schedule_slices SET
+    state = $3,
+    submission_id = $4,
+    signature = $5,
+    detail = $6,
+    updated_at = NOW()
+WHERE schedule_id = $1 AND slice_index = $2
+"#,
+            &[
+                &schedule_id,
+                &(slice.index as i32),
+                &slice.state.as_str(),
+                &submission_id,
+                &slice.signature,
+                &slice.detail,
+            ],
+        )
+        .await
+        .context("failed to update schedule_slices")?;
+    Ok(())
+}
+
+/// Moves a schedule to `to` if it is in one of the `from` states, returning whether it
+/// moved. Ending a schedule cancels its pending slices. Resuming one delays the pending
+/// slices so the next is due now, keeping their spacing.
+pub async fn transition_schedule(
+    client: &Client,
+    id: Uuid,
+    from: &[ScheduleState],
+    to: ScheduleState,
+    detail: Option<&str>,
+) -> Result<bool> {
+    let from: Vec<&str> = from.iter().map(ScheduleState::as_str).collect();
+    let ends = matches!(
+        to,
+        ScheduleState::Completed | ScheduleState::Cancelled | ScheduleState::Failed
+    );
+    let resumes = to == ScheduleState::Active;
+    let row = client
+        .query_one(
+            r#"
+WITH updated AS (
+    UPDATE schedules SET state = $3, detail = COALESCE($4, detail), updated_at = NOW()
+    WHERE id = $1 AND state = ANY($2)
+    RETURNING id
+), cancelled AS (
+    UPDATE schedule_slices SET state = 'cancelled', updated_at = NOW()
+    WHERE $5 AND state = 'pending' AND schedule_id IN (SELECT id FROM updated)
+), shifted AS (
+    UPDATE schedule_slices SET due_at = due_at + GREATEST(
+        NOW() - (
+            SELECT MIN(due_at) FROM schedule_slices
+            WHERE schedule_id = $1 AND state = 'pending'
+        ),
+        INTERVAL '0'
+    )
+    WHERE $6 AND state = 'pending' AND schedule_id IN (SELECT id FROM updated)
+)
+SELECT COUNT(*) AS moved FROM updated
+"#,
+            &[&id, &from, &to.as_str(), &detail, &ends, &resumes],
+        )
+        .await
+        .context("failed to update schedules")?;
+    Ok(row.get::<_, i64>("moved") > 0)
+}
+
+const PAPER_POSITION_COLUMNS: &str = r#"
+    wallet, sub_account_id, market, size, entry_price, margin, realized_pnl, fees,
+    (EXTRACT(EPOCH FROM updated_at) * 1000)::BIGINT AS updated_at_ms
+"#;
+
+fn paper_position_from_row(row: &tokio_postgres::Row) -> PaperPosition {
+    PaperPosition {
+        wallet: row.get("wallet"),
+        sub_account_id: row.get::<_, i32>("sub_account_id") as u16,
+        market: row.get("market"),
+        size: row.get("size"),
+        entry_price: row.get("entry_price"),
+        margin: row.get("margin"),
+        realized_pnl: row.get("realized_pnl"),
+        fees: row.get("fees"),
+        unrealized_pnl: None,
+        updated_at: row.get("updated_at_ms"),
+    }
+}
+
+pub async fn fetch_paper_position(
+    client: &Client,
+    wallet: &str,
+    sub_account_id: u16,
+    market: &str,
+) -> Result<Option<PaperPosition>> {
+    let sql = format!(
+        "SELECT {PAPER_POSITION_COLUMNS} FROM paper_positions \
+         WHERE wallet = $1 AND sub_account_id = $2 AND market = $3"
+    );
+    let row = client
+        .query_opt(
+            sql.as_str(),
+            &[&wallet, &i32::from(sub_account_id), &market],
+        )
+        .await
+        .context("failed to query paper_positions")?;
+    Ok(row.map(|row| paper_position_from_row(&row)))
+}
+
+/// Every paper position of a wallet, optionally for one sub-account, by market.
+pub async fn fetch_paper_positions(
+    client: &Client,
+    wallet: &str,
+    sub_account_id: Option<u16>,
+) -> Result<Vec<PaperPosition>> {
+    let sql = format!(
+        "SELECT {PAPER_POSITION_COLUMNS} FROM paper_positions \
+         WHERE wallet = $1 AND ($2::INTEGER IS NULL OR sub_account_id = $2) \
+         ORDER BY sub_account_id, market"
+    );
+    let rows = client
+        .query(sql.as_str(), &[&wallet, &sub_account_id.map(i32::from)])
+        .await
+        .context("failed to query paper_positions")?;
+    Ok(rows.iter().map(paper_position_from_row).collect())
+}
+
+/// Stores a paper position, returning the new `updated_at` in Unix milliseconds.
+pub async fn upsert_paper_position(client: &Client, position: &PaperPosition) -> Result<i64> {
+    let params: &[&(dyn ToSql + Sync)] = &[
+        &position.wallet,
+        &i32::from(position.sub_account_id),
+        &position.market,
+        &position.size,
+        &position.entry_price,
+        &position.margin,
+        &position.realized_pnl,
+        &position.fees,
+    ];
+    let row = client
+        .query_one(
+            r#"
+INSERT INTO paper_positions (
+    wallet, sub_account_id, market, size, entry_price, margin, realized_pnl, fees
+)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+ON CONFLICT (wallet, sub_account_id, market) DO UPDATE SET
+    size = EXCLUDED.size,
+    entry_price = EXCLUDED.entry_price,
+    margin = EXCLUDED.margin,
+    realized_pnl = EXCLUDED.realized_pnl,
+    fees = EXCLUDED.fees,
+    updated_at = NOW()
+RETURNING (EXTRACT(EPOCH FROM updated_at) * 1000)::BIGINT AS updated_at_ms
+"#,
+            params,
+        )
+        .await
+        .context("failed to upsert paper_positions")?;
+    Ok(row.get("updated_at_ms"))
+}
+
+const WEBHOOK_DELIVERY_COLUMNS: &str = "\
+    d.id, d.event_id, d.event_type, d.url, d.payload, d.state, d.attempts, d.last_status, \
+    d.last_error, \
+    (EXTRACT(EPOCH FROM d.next_attempt_at) * 1000)::BIGINT AS next_attempt_at_ms, \
+    (EXTRACT(EPOCH FROM d.delivered_at) * 1000)::BIGINT AS delivered_at_ms, \
+    (EXTRACT(EPOCH FROM d.created_at) * 1000)::BIGINT AS created_at_ms";
+
+fn webhook_delivery_from_row(row: &tokio_postgres::Row) -> Result<WebhookDelivery> {
+    let state: &str = row.get("state");
+    let event_id: Uuid = row.get("event_id");
+    Ok(WebhookDelivery {
+        id: row.get("id"),
+        event_id: event_id.to_string(),
+        event_type: row.get("event_type"),
+        url: row.get("url"),
+        state: WebhookDeliveryState::parse(state)
+            .with_context(|| format!("unknown webhook delivery state '{state}'"))?,
+        attempts: row.get::<_, i32>("attempts") as u32,
+        last_status: row.get::<_, Option<i32>>("last_status").map(|v| v as u16),
+        last_error: row.get("last_error"),
+        next_attempt_at: row.get("next_attempt_at_ms"),
+        delivered_at: row.get("delivered_at_ms"),
+        created_at: row.get("created_at_ms"),
+        payload: row.get("payload"),
+    })
+}
+
+/// Queues `payload` for each of `urls`, due immediately.
+pub async fn insert_webhook_deliveries(
+    client: &Client,
+    event_id: Uuid,
+    event_type: &str,
+    urls: &[String],
+    payload: &Value,
+) -> Result<u64> {
+    client
+        .execute(
+            r#"
+INSERT INTO webhook_deliveries (event_id, event_type, url, payload, state)
+SELECT $1, $2, url, $4, $5 FROM unnest($3::TEXT[]) AS url
+"#,
+            &[
+                &event_id,
+                &event_type,
+                &urls,
+                payload,
+                &WebhookDeliveryState::Pending.as_str(),
+            ],
+        )
+        .await
+        .context("failed to insert webhook_deliveries")
+}
+
+/// Claims up to `limit` due deliveries, oldest first, by pushing their next attempt
+/// `lease_secs` out; recording the attempt replaces that.
+pub async fn claim_webhook_deliveries(
+    client: &Client,
+    limit: i64,
+    lease_secs: f64,
+) -> Result<Vec<WebhookDelivery>> {
+    let sql = format!(
+        "WITH due AS ( \
+             SELECT id FROM webhook_deliveries \
+             WHERE state = 'pending' AND next_attempt_at <= NOW() \
+             ORDER BY next_attempt_at, id LIMIT $1 \
+             FOR UPDATE SKIP LOCKED) \
+         UPDATE webhook_deliveries d SET \
+             next_attempt_at = NOW() + make_interval(secs => $2), \
+             updated_at = NOW() \
+         FROM due WHERE d.id = due.id \
+         RETURNING {WEBHOOK_DELIVERY_COLUMNS}"
+    );
+    client
+        .query(sql.as_str(), &[&limit, &lease_secs])
+        .await
+        .context("failed to claim webhook_deliveries")?
+        .iter()
+        .map(webhook_delivery_from_row)
+        .collect()
+}
+
+/// Records one attempt. A `pending` delivery is tried again after `retry_secs`.
+pub async fn record_webhook_attempt(
+    client: &Client,
+    id: i64,
+    state: WebhookDeliveryState,
+    status: Option<u16>,
+    error: Option<&str>,
+    retry_secs: f64,
+) -> Result<()> {
+    client
+        .execute(
+            r#"
+UPDATE webhook_deliveries SET
+    state = $2,
+    attempts = attempts + 1,
+    last_status = $3,
+    last_error = $4,
+    next_attempt_at = NOW() + make_interval(secs => $5),
+    delivered_at = CASE WHEN $2 = 'delivered' THEN NOW() ELSE delivered_at END,
+    updated_at = NOW()
+WHERE id = $1
+"#,
+            &[
+                &id,
+                &state.as_str(),
+                &status.map(i32::from),
+                &error,
+                &retry_secs,
+            ],
+        )
+        .await
+        .context("failed to update webhook_deliveries")?;
+    Ok(())
+}
+
+/// Newest first.
+pub async fn fetch_webhook_deliveries(
+    client: &Client,
+    state: Option<WebhookDeliveryState>,
+    event_type: Option<&str>,
+    limit: i64,
+) -> Result<Vec<WebhookDelivery>> {
+    let sql = format!(
+        "SELECT {WEBHOOK_DELIVERY_COLUMNS} FROM webhook_deliveries d \
+         WHERE ($1::TEXT IS NULL OR d.state = $1) \
+         AND ($2::TEXT IS NULL OR d.event_type = $2) \
+         ORDER BY d.created_at DESC, d.id DESC LIMIT $3"
+    );
+    client
+        .query(
+            sql.as_str(),
+            &[&state.map(|state| state.as_str()), &event_type, &limit],
+        )
+        .await
+        .context("failed to query webhook_deliveries")?
+        .iter()
+        .map(webhook_delivery_from_row)
+        .collect()
+}
+
+/// Puts a delivery back in the queue, due now and with a fresh attempt budget, whatever
+/// its state. `None` when there is no such delivery.
+pub async fn replay_webhook_delivery(client: &Client, id: i64) -> Result<Option<WebhookDelivery>> {
+    let sql = format!(
+        "UPDATE webhook_deliveries d SET \
+             state = 'pending', attempts = 0, next_attempt_at = NOW(), delivered_at = NULL, \
+             updated_at = NOW() \
+         WHERE d.id = $1 \
+         RETURNING {WEBHOOK_DELIVERY_COLUMNS}"
+    );
+    client
+        .query_opt(sql.as_str(), &[&id])
+        .await
+        .context("failed to replay webhook_deliveries")?
+        .map(|row| webhook_delivery_from_row(&row))
+        .transpose()
+}
+
+const API_KEY_COLUMNS: &str = "\
+    k.id, k.name, k.prefix, k.scopes, \
+    (EXTRACT(EPOCH FROM k.created_at) * 1000)::BIGINT AS created_at_ms, \
+    (EXTRACT(EPOCH FROM k.last_used_at) * 1000)::BIGINT AS last_used_at_ms, \
+    (EXTRACT(EPOCH FROM k.revoked_at) * 1000)::BIGINT AS revoked_at_ms";
+
+fn parse_scopes(values: Vec<String>) -> Result<Vec<Scope>> {
+    values
+        .iter()
+        .map(|value| {
+            Scope::parse(value).with_context(|| format!("unknown API key scope '{value}'"))
+        })
+        .collect()
+}
+
+fn api_key_from_row(row: &tokio_postgres::Row) -> Result<ApiKey> {
+    let id: Uuid = row.get("id");
+    Ok(ApiKey {
+        id: id.to_string(),
+        name: row.get("name"),
+        prefix: row.get("prefix"),
+        scopes: parse_scopes(row.get("scopes"))?,
+        created_at: row.get("created_at_ms"),
+        last_used_at: row.get("last_used_at_ms"),
+        revoked_at: row.get("revoked_at_ms"),
+    })
+}
+
+/// `None` when an active key already has `name`.
+pub async fn insert_api_key(
+    client: &Client,
+    id: Uuid,
+    name: &str,
+    key_hash: &str,
+    prefix: &str,
+    scopes: &[Scope],
+) -> Result<Option<ApiKey>> {
+    let scopes: Vec<&str> = scopes.iter().map(Scope::as_str).collect();
+    let sql = format!(
+        "INSERT INTO api_keys AS k (id, name, key_hash, prefix, scopes) \
+         VALUES ($1, $2, $3, $4, $5) \
+         ON CONFLICT (name) WHERE revoked_at IS NULL DO NOTHING \
+         RETURNING {API_KEY_COLUMNS}"
+    );
+    client
+        .query_opt(sql.as_str(), &[&id, &name, &key_hash, &prefix, &scopes])
+        .await
+        .context("failed to insert api_keys")?
+        .map(|row| api_key_from_row(&row))
+        .transpose()
+}
+
+/// Looks up an active key by hash and records that it was used.
+pub async fn touch_api_key(client: &Client, key_hash: &str) -> Result<Option<ApiClient>> {
+    let Some(row) = client
+        .query_opt(
+            r#"
+UPDATE api_keys SET last_used_at = NOW()
+WHERE key_hash = $1 AND revoked_at IS NULL
+RETURNING id, name, scopes
+"#,
+            &[&key_hash],
+        )
+        .await
+        .context("failed to update api_keys")?
+    else {
+        return Ok(None);
+    };
+    Ok(Some(ApiClient {
+        key_id: row.get("id"),
+        name: row.get("name"),
+        scopes: parse_scopes(row.get("scopes"))?,
+    }))
+}
+
+/// Active keys first, then by creation.
+pub async fn fetch_api_keys(client: &Client) -> Result<Vec<ApiKey>> {
+    let sql = format!(
+        "SELECT {API_KEY_COLUMNS} FROM api_keys k \
+         ORDER BY k.revoked_at IS NOT NULL, k.created_at"
+    );
+    client
+        .query(sql.as_str(), &[])
+        .await
+        .context("failed to query api_keys")?
+        .iter()
+        .map(api_key_from_row)
+        .collect()
+}
+
+/// `None` when there is no such key; revoking a revoked key keeps its first `revoked_at`.
+pub async fn revoke_api_key(client: &Client, id: Uuid) -> Result<Option<ApiKey>> {
+    let sql = format!(
+        "UPDATE api_keys k SET revoked_at = COALESCE(k.revoked_at, NOW()) \
+         WHERE k.id = $1 \
+         RETURNING {API_KEY_COLUMNS}"
+    );
+    client
+        .query_opt(sql.as_str(), &[&id])
+        .await
+        .context("failed to revoke api_keys")?
+        .map(|row| api_key_from_row(&row))
+        .transpose()
+}
@@ -11,15 +11,19 @@ use sha2::{Digest, Sha256};
 use solana_client::{rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
 use solana_rpc_client::{http_sender::HttpSender, rpc_client::RpcClientConfig};
 use solana_sdk::{
-    commitment_config::CommitmentConfig, instruction::CompiledInstruction,
-    message::VersionedMessage, pubkey::Pubkey, signature::Signature,
+    commitment_config::CommitmentConfig,
+    instruction::{CompiledInstruction, InstructionError},
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::TransactionError,
 };
 use solana_transaction_status::{
     option_serializer::OptionSerializer, UiLoadedAddresses, UiTransactionEncoding,
     UiTransactionStatusMeta, UiTransactionTokenBalance,
 };
 
-const DEFAULT_DRIFT_PROGRAM: &str = "dRiftyHA39MWEi3m9aunc5MzRF1JYuBsbn6VPcn33UH";
+pub(crate) const DEFAULT_DRIFT_PROGRAM: &str = "dRiftyHA39MWEi3m9aunc5MzRF1JYuBsbn6VPcn33UH";
 
 static DEPOSIT_DISC: Lazy<[u8; 8]> =
     Lazy::new(|| anchor_discriminator("deposit_into_isolated_perp_position"));
@@ -28,6 +32,47 @@ static WITHDRAW_DISC: Lazy<[u8; 8]> =
 static PLACE_PERP_ORDER_DISC: Lazy<[u8; 8]> =
     Lazy::new(|| anchor_discriminator("place_perp_order"));
 
+static ORDER_ACTION_EVENT: Lazy<[u8; 8]> = Lazy::new(|| event_discriminator("OrderActionRecord"));
+static SETTLE_PNL_EVENT: Lazy<[u8; 8]> = Lazy::new(|| event_discriminator("SettlePnlRecord"));
+static FUNDING_PAYMENT_EVENT: Lazy<[u8; 8]> =
+    Lazy::new(|| event_discriminator("FundingPaymentRecord"));
+
+/// `OrderAction::Fill` in the Drift IDL.
+const ORDER_ACTION_FILL: u8 = 2;
+
+static DRIFT_ERRORS: Lazy<HashMap<u32, DriftProgramError>> = Lazy::new(|| {
+    #[derive(Deserialize)]
+    struct Idl {
+        errors: Vec<DriftProgramError>,
+    }
+    let idl: Idl = serde_json::from_str(include_str!("../idl/drift.json"))
+        .expect("bundled drift IDL is valid JSON");
+    idl.errors
+        .into_iter()
+        .map(|error| (error.code, error))
+        .collect()
+});
+
+/// Custom program error as declared in the Drift IDL.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DriftProgramError {
+    pub code: u32,
+    pub name: String,
+    #[serde(default)]
+    pub msg: String,
+}
+
+/// Resolves a failed instruction to its Drift error. Only meaningful when the failing
+/// instruction targets the Drift program; other programs reuse the same code space.
+pub fn drift_error_from_tx_error(err: &TransactionError) -> Option<(u8, DriftProgramError)> {
+    match err {
+        TransactionError::InstructionError(index, InstructionError::Custom(code)) => {
+            DRIFT_ERRORS.get(code).cloned().map(|error| (*index, error))
+        }
+        _ => None,
+    }
+}
+
 #[derive(Clone)]
 pub struct DriftDecoder {
     client: Arc<RpcClient>,
@@ -52,6 +97,19 @@ impl DriftDecoder {
         })
     }
 
+    pub fn program_id(&self) -> Pubkey {
+        self.drift_program
+    }
+
+    /// Drift `User` account PDA for `authority` and `sub_account_id`.
+    pub fn user_account_pubkey(&self, authority: &Pubkey, sub_account_id: u16) -> Pubkey {
+        Pubkey::find_program_address(
+            &[b"user", authority.as_ref(), &sub_account_id.to_le_bytes()],
+            &self.drift_program,
+        )
+        .0
+    }
+
     pub fn decode_signature(&self, sig_str: &str) -> Result<(SignatureDump, Vec<ActionRecord>)> {
         let signature = Signature::from_str(sig_str).context("invalid signature")?;
         let config = RpcTransactionConfig {
@@ -137,12 +195,18 @@ impl DriftDecoder {
             tracing::warn!(signature = %sig_str, "no drift instructions");
         }
 
+        let pnl_events = match &meta.log_messages {
+            OptionSerializer::Some(logs) => decode_pnl_events(sig_str, tx.slot, logs),
+            _ => Vec::new(),
+        };
+
         Ok((
             SignatureDump {
                 signature: sig_str.to_string(),
                 slot: tx.slot,
                 block_time: tx.block_time,
                 instructions: instruction_dumps,
+                pnl_events,
             },
             action_records,
         ))
@@ -331,6 +395,7 @@ pub struct SignatureDump {
     pub slot: u64,
     pub block_time: Option<i64>,
     pub instructions: Vec<InstructionDump>,
+    pub pnl_events: Vec<PnlEvent>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -375,6 +440,78 @@ pub struct ActionRecord {
     pub token_account: Option<String>,
     pub token_mint: Option<String>,
     pub token_amount: Option<u64>,
+    /// Recorded by paper-trading mode rather than decoded from a transaction.
+    #[serde(default)]
+    pub paper: bool,
+}
+
+/// A settlement, funding payment or fill fee read from the Drift events a transaction
+/// logged, attributed to one user account. Amounts are in quote precision (1e6).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PnlEvent {
+    pub signature: String,
+    /// Position of the event among the transaction's logged events.
+    pub event_index: usize,
+    pub slot: u64,
+    /// Unix seconds, as stamped by the program.
+    pub ts: i64,
+    /// `fill`, `settle_pnl` or `funding`.
+    pub kind: String,
+    /// Drift `User` account the amounts belong to.
+    pub user_account: String,
+    pub market_index: u16,
+    /// PnL moved into the account's balance by a settlement.
+    pub pnl: i64,
+    /// Fee paid on a fill; negative for a maker rebate.
+    pub fee: i64,
+    /// Funding paid (negative) or received (positive).
+    pub funding: i64,
+}
+
+/// Leading fields of `OrderActionRecord`; the rest are not needed.
+#[derive(BorshDeserialize)]
+struct OrderActionEvent {
+    ts: i64,
+    action: u8,
+    _action_explanation: u8,
+    market_index: u16,
+    market_type: MarketType,
+    _filler: Option<[u8; 32]>,
+    _filler_reward: Option<u64>,
+    _fill_record_id: Option<u64>,
+    _base_asset_amount_filled: Option<u64>,
+    _quote_asset_amount_filled: Option<u64>,
+    taker_fee: Option<u64>,
+    maker_fee: Option<i64>,
+    _referrer_reward: Option<u32>,
+    _quote_asset_amount_surplus: Option<i64>,
+    _spot_fulfillment_method_fee: Option<u64>,
+    taker: Option<[u8; 32]>,
+    _taker_order_id: Option<u32>,
+    _taker_order_direction: Option<PositionDirection>,
+    _taker_order_base_asset_amount: Option<u64>,
+    _taker_order_cumulative_base_asset_amount_filled: Option<u64>,
+    _taker_order_cumulative_quote_asset_amount_filled: Option<u64>,
+    maker: Option<[u8; 32]>,
+}
+
+/// Leading fields of `SettlePnlRecord`.
+#[derive(BorshDeserialize)]
+struct SettlePnlEvent {
+    ts: i64,
+    user: [u8; 32],
+    market_index: u16,
+    pnl: i128,
+}
+
+/// Leading fields of `FundingPaymentRecord`.
+#[derive(BorshDeserialize)]
+struct FundingPaymentEvent {
+    ts: i64,
+    _user_authority: [u8; 32],
+    user: [u8; 32],
+    market_index: u16,
+    funding_payment: i64,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -442,14 +579,14 @@ struct IsolatedPerpMovementArgs {
 }
 
 #[derive(Debug, BorshDeserialize, Clone)]
-struct OrderParams {
+pub(crate) struct OrderParams {
     order_type: OrderType,
-    market_type: MarketType,
+    pub(crate) market_type: MarketType,
     direction: PositionDirection,
     user_order_id: u8,
-    base_asset_amount: u64,
+    pub(crate) base_asset_amount: u64,
     price: u64,
-    market_index: u16,
+    pub(crate) market_index: u16,
     reduce_only: bool,
     post_only: PostOnlyParam,
     bit_flags: u8,
@@ -483,8 +620,8 @@ impl OrderType {
     }
 }
 
-#[derive(Debug, Clone, Copy, BorshDeserialize)]
-enum MarketType {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshDeserialize)]
+pub(crate) enum MarketType {
     Spot,
     Perp,
 }
@@ -551,7 +688,7 @@ impl OrderTriggerCondition {
     }
 }
 
-fn anchor_discriminator(name: &str) -> [u8; 8] {
+pub(crate) fn anchor_discriminator(name: &str) -> [u8; 8] {
     let mut hasher = Sha256::new();
     hasher.update(format!("global:{name}"));
     let hash = hasher.finalize();
@@ -560,6 +697,81 @@ fn anchor_discriminator(name: &str) -> [u8; 8] {
     disc
 }
 
+pub(crate) fn event_discriminator(name: &str) -> [u8; 8] {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("event:{name}"));
+    let hash = hasher.finalize();
+    let mut disc = [0u8; 8];
+    disc.copy_from_slice(&hash[..8]);
+    disc
+}
+
+/// Reads perp fills, PnL settlements and funding payments from the `Program data:` lines
+/// of a transaction's logs. Events that fail to decode are skipped.
+fn decode_pnl_events(signature: &str, slot: u64, logs: &[String]) -> Vec<PnlEvent> {
+    let mut events = Vec::new();
+    let payloads = logs
+        .iter()
+        .filter_map(|line| line.strip_prefix("Program data: "))
+        .filter_map(|data| BASE64_STANDARD.decode(data).ok())
+        .filter(|data| data.len() > 8);
+    for (event_index, data) in payloads.enumerate() {
+        let (disc, mut body) = data.split_at(8);
+        let event = |kind: &str, user: [u8; 32], ts: i64, market_index: u16| PnlEvent {
+            signature: signature.to_string(),
+            event_index,
+            slot,
+            ts,
+            kind: kind.to_string(),
+            user_account: Pubkey::new_from_array(user).to_string(),
+            market_index,
+            pnl: 0,
+            fee: 0,
+            funding: 0,
+        };
+        if disc == ORDER_ACTION_EVENT.as_slice() {
+            let Ok(record) = OrderActionEvent::deserialize(&mut body) else {
+                tracing::warn!(%signature, event_index, "undecodable OrderActionRecord");
+                continue;
+            };
+            if record.action != ORDER_ACTION_FILL || record.market_type != MarketType::Perp {
+                continue;
+            }
+            if let Some(taker) = record.taker {
+                events.push(PnlEvent {
+                    fee: record.taker_fee.unwrap_or_default() as i64,
+                    ..event("fill", taker, record.ts, record.market_index)
+                });
+            }
+            if let Some(maker) = record.maker {
+                events.push(PnlEvent {
+                    fee: record.maker_fee.unwrap_or_default(),
+                    ..event("fill", maker, record.ts, record.market_index)
+                });
+            }
+        } else if disc == SETTLE_PNL_EVENT.as_slice() {
+            let Ok(record) = SettlePnlEvent::deserialize(&mut body) else {
+                tracing::warn!(%signature, event_index, "undecodable SettlePnlRecord");
+                continue;
+            };
+            events.push(PnlEvent {
+                pnl: record.pnl.clamp(i64::MIN.into(), i64::MAX.into()) as i64,
+                ..event("settle_pnl", record.user, record.ts, record.market_index)
+            });
+        } else if disc == FUNDING_PAYMENT_EVENT.as_slice() {
+            let Ok(record) = FundingPaymentEvent::deserialize(&mut body) else {
+                tracing::warn!(%signature, event_index, "undecodable FundingPaymentRecord");
+                continue;
+            };
+            events.push(PnlEvent {
+                funding: record.funding_payment,
+                ..event("funding", record.user, record.ts, record.market_index)
+            });
+        }
+    }
+    events
+}
+
 fn build_token_mint_lookup(meta: &UiTransactionStatusMeta) -> HashMap<usize, String> {
     let mut map = HashMap::new();
     let mut ingest = |balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>| {
@@ -613,6 +825,7 @@ fn build_action_record(
             token_account,
             token_mint,
             token_amount: amount,
+            paper: false,
         }
     };
 
@@ -1,104 +1,683 @@
-use std::sync::Arc;
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
 
 use base64::{engine::general_purpose::STANDARD, Engine as _};
-use bs58;
+use dashmap::DashMap;
+use serde::Serialize;
+use solana_account_decoder::UiAccountEncoding;
 use solana_client::client_error::ClientError;
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_client::rpc_config::RpcSendTransactionConfig;
-use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+use solana_client::rpc_config::{
+    RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig,
+};
+use solana_sdk::clock::MAX_PROCESSING_AGE;
 use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
-use solana_sdk::signer::keypair::Keypair;
-use solana_sdk::signer::Signer;
-use solana_sdk::transaction::{Transaction, VersionedTransaction};
+use solana_sdk::transaction::{TransactionError, VersionedTransaction};
+use solana_transaction_status::UiTransactionEncoding;
 use thiserror::Error;
-use tokio::sync::Mutex;
-use tracing::{error, info};
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tracing::{error, info, warn};
+
+use crate::compute_budget::{self, ComputeBudgetConfig, FeePolicy};
+use crate::confirmation::{self, Confirmation, ConfirmationTracker};
+use crate::policy::{self, TxPolicy};
+use crate::signer::{ServerSigner, SignerError};
+use crate::wallets::{market_key, WalletRegistry};
 
 #[derive(Debug, Error)]
 pub enum ExecutorError {
-    #[error("missing SERVER_PRIVATE_KEY env var")]
-    MissingKey,
-    #[error("invalid private key: {0}")]
-    InvalidKey(String),
+    #[error(transparent)]
+    Signer(#[from] SignerError),
+    #[error("unknown server wallet '{0}'")]
+    UnknownWallet(String),
     #[error("decode error: {0}")]
     Decode(String),
     #[error("rpc error: {0}")]
     Rpc(String),
+    #[error("invalid executor configuration: {0}")]
+    Config(String),
+    #[error("transaction rejected by policy: {0}")]
+    PolicyViolation(String),
+    #[error("failed to record signed transaction: {0}")]
+    Record(String),
+    #[error("transaction {signature} expired before confirmation")]
+    Expired { signature: Signature },
+    #[error("transaction {signature} failed on-chain: {err}")]
+    FailedOnChain {
+        signature: Signature,
+        err: TransactionError,
+        /// Program invoked by the failing top-level instruction, if any.
+        failed_program_id: Option<String>,
+    },
+    /// Sending or tracking failed after the transaction was signed and sent, so it may
+    /// still land.
+    #[error("transaction {signature} was sent but its outcome is unknown: {reason}")]
+    Unconfirmed {
+        signature: Signature,
+        reason: String,
+    },
+}
+
+impl ExecutorError {
+    /// Whether a transaction was sent before the failure, so repeating the request could
+    /// execute twice.
+    pub fn sent(&self) -> bool {
+        matches!(
+            self,
+            Self::Expired { .. } | Self::FailedOnChain { .. } | Self::Unconfirmed { .. }
+        )
+    }
+
+    /// Keeps outcomes and turns any other error into [`ExecutorError::Unconfirmed`].
+    fn after_send(self, signature: Signature) -> Self {
+        if self.sent() {
+            return self;
+        }
+        Self::Unconfirmed {
+            signature,
+            reason: self.to_string(),
+        }
+    }
+}
+
+const DEFAULT_RESEND_INTERVAL: Duration = Duration::from_secs(2);
+const DEFAULT_MAX_BLOCKHASH_REFRESHES: u32 = 2;
+
+/// How the executor rebroadcasts a transaction while waiting for confirmation.
+#[derive(Debug, Clone)]
+pub struct SendConfig {
+    pub resend_interval: Duration,
+    /// Times an expired transaction may be re-signed with a fresh blockhash.
+    pub max_blockhash_refreshes: u32,
+}
+
+impl Default for SendConfig {
+    fn default() -> Self {
+        Self {
+            resend_interval: DEFAULT_RESEND_INTERVAL,
+            max_blockhash_refreshes: DEFAULT_MAX_BLOCKHASH_REFRESHES,
+        }
+    }
+}
+
+impl SendConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            resend_interval: std::env::var("TX_RESEND_INTERVAL_MS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|millis| *millis > 0)
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.resend_interval),
+            max_blockhash_refreshes: std::env::var("TX_MAX_BLOCKHASH_REFRESHES")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(defaults.max_blockhash_refreshes),
+        }
+    }
+}
+
+/// A transaction that reached `confirmed` commitment without error.
+#[derive(Debug, Clone)]
+pub struct ExecutionReceipt {
+    pub signature: Signature,
+    pub slot: u64,
+    /// Earlier signatures of the same transaction whose blockhash expired unconfirmed.
+    pub expired_signatures: Vec<Signature>,
+}
+
+/// Progress of one execution. Failure and expiry are reported through the `execute` result.
+#[derive(Debug)]
+pub enum ExecutionEvent {
+    /// `tx_base64` is the exact signed transaction, enough to resume tracking after a restart.
+    /// It is not sent until the listener answers on `recorded`, so a restart cannot lose a
+    /// transaction that may land; an error stops the execution. A listener that drops
+    /// `recorded` unanswered does not hold up the send.
+    Signed {
+        signature: Signature,
+        tx_base64: String,
+        last_valid_block_height: u64,
+        recorded: oneshot::Sender<Result<(), String>>,
+    },
+    Sent {
+        signature: Signature,
+    },
+    Processed {
+        signature: Signature,
+        slot: u64,
+    },
+    Confirmed {
+        signature: Signature,
+        slot: u64,
+    },
+    /// Only reported while a progress listener is still attached.
+    Finalized {
+        signature: Signature,
+        slot: u64,
+    },
+    /// The blockhash expired unconfirmed and the transaction is being re-signed.
+    Resigning {
+        signature: Signature,
+    },
+}
+
+pub type ProgressSender = mpsc::UnboundedSender<ExecutionEvent>;
+
+/// Executions for the same sub-account and market are serialised; everything else runs
+/// concurrently. Markets compare by [`market_key`], so `SOL` and `SOL-PERP` share a lane.
+pub fn ordering_key(wallet: &str, sub_account_id: u16, market: &str) -> String {
+    format!("{}:{sub_account_id}:{}", wallet.trim(), market_key(market))
+}
+
+/// A worker-built transaction to sign and send.
+pub struct ExecutionRequest<'a> {
+    pub tx_base64: &'a str,
+    /// Label of the registry wallet that signs, and must pay for, the transaction.
+    pub wallet: &'a str,
+    /// Selects the priority fee policy.
+    pub route: &'a str,
+    /// Executions sharing any key run one at a time, in arrival order; others run
+    /// concurrently. A transaction touching several markets holds each market's lane.
+    pub ordering_keys: Vec<String>,
+    /// Expiry of the worker's blockhash, when known.
+    pub last_valid_block_height: Option<u64>,
+    pub progress: Option<ProgressSender>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationReport {
+    pub success: bool,
+    pub err: Option<TransactionError>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+    /// Program invoked by the failing top-level instruction, if any.
+    pub failed_program_id: Option<String>,
+    /// Raw post-simulation data for the requested accounts, in request order.
+    #[serde(skip)]
+    pub accounts: Vec<Option<Vec<u8>>>,
 }
 
 pub struct TxExecutor {
-    rpc: RpcClient,
-    keypair: Arc<Keypair>,
-    lock: Mutex<()>,
+    rpc: Arc<RpcClient>,
+    wallets: WalletRegistry,
+    ordering: DashMap<String, Arc<Mutex<()>>>,
+    tracker: OnceLock<ConfirmationTracker>,
+    budget: ComputeBudgetConfig,
+    send: SendConfig,
+    policy: TxPolicy,
 }
 
 impl TxExecutor {
-    pub fn new(rpc_url: String, keypair: Keypair) -> Self {
+    pub fn new(rpc_url: String, wallets: WalletRegistry) -> Self {
         Self {
-            rpc: RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed()),
-            keypair: Arc::new(keypair),
-            lock: Mutex::new(()),
+            rpc: Arc::new(RpcClient::new_with_commitment(
+                rpc_url,
+                CommitmentConfig::confirmed(),
+            )),
+            wallets,
+            ordering: DashMap::new(),
+            tracker: OnceLock::new(),
+            budget: ComputeBudgetConfig::disabled(),
+            send: SendConfig::default(),
+            policy: TxPolicy::default(),
         }
     }
 
+    pub fn with_compute_budget(mut self, budget: ComputeBudgetConfig) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    pub fn with_policy(mut self, policy: TxPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_send_config(mut self, send: SendConfig) -> Self {
+        self.send = send;
+        self
+    }
+
     pub fn from_env() -> Result<Self, ExecutorError> {
         let rpc_url = std::env::var("RPC_URL")
             .unwrap_or_else(|_| "https://api.devnet.solana.com".to_string());
-        let key_str = std::env::var("SERVER_PRIVATE_KEY").map_err(|_| ExecutorError::MissingKey)?;
-        let keypair = load_keypair(&key_str).map_err(|err| ExecutorError::InvalidKey(err))?;
-        Ok(Self::new(rpc_url, keypair))
+        let wallets = WalletRegistry::from_env()?;
+        let budget = ComputeBudgetConfig::from_env().map_err(ExecutorError::Config)?;
+        let policy = TxPolicy::from_env().map_err(ExecutorError::Config)?;
+        Ok(Self::new(rpc_url, wallets)
+            .with_compute_budget(budget)
+            .with_policy(policy)
+            .with_send_config(SendConfig::from_env()))
     }
 
     pub fn public_key_base58(&self) -> String {
-        self.keypair.pubkey().to_string()
-    }
-
-    pub async fn execute(&self, tx_base64: &str) -> Result<Signature, ExecutorError> {
-        let _guard = self.lock.lock().await;
-        let bytes = STANDARD
-            .decode(tx_base64)
-            .map_err(|err| ExecutorError::Decode(err.to_string()))?;
-        let mut tx: VersionedTransaction =
-            bincode::deserialize(&bytes).map_err(|err| ExecutorError::Decode(err.to_string()))?;
-        // Sign the transaction using the Signer trait
-        let message = tx.message.serialize();
-        let signature = self
-            .keypair
-            .as_ref()
-            .try_sign_message(&message)
-            .map_err(|err| ExecutorError::Decode(format!("signing error: {err}")))?;
-        // Update the transaction signatures
-        if let Some(first_sig) = tx.signatures.first_mut() {
-            *first_sig = signature;
-        }
-        let signature = tx.signatures[0];
-
-        match self
-            .rpc
-            .send_and_confirm_transaction_with_spinner_and_config(
-                &tx,
-                CommitmentConfig::confirmed(),
-                RpcSendTransactionConfig {
-                    skip_preflight: false,
-                    preflight_commitment: Some(CommitmentConfig::confirmed().commitment),
-                    ..RpcSendTransactionConfig::default()
+        self.wallets.primary().pubkey().to_string()
+    }
+
+    pub fn wallets(&self) -> &WalletRegistry {
+        &self.wallets
+    }
+
+    /// Signs and sends a worker-built transaction, rebroadcasting until it confirms or its
+    /// blockhash expires.
+    pub async fn execute(
+        &self,
+        request: ExecutionRequest<'_>,
+    ) -> Result<ExecutionReceipt, ExecutorError> {
+        // Lanes are always taken in sorted order, so two multi-lane executions cannot each
+        // hold a lane the other is waiting for.
+        let mut keys = request.ordering_keys.clone();
+        keys.sort();
+        keys.dedup();
+        let locks: Vec<Arc<Mutex<()>>> = keys
+            .iter()
+            .map(|key| Arc::clone(self.ordering.entry(key.clone()).or_default().value()))
+            .collect();
+        let mut guards = Vec::with_capacity(locks.len());
+        for lock in &locks {
+            guards.push(lock.lock().await);
+        }
+        let result = self.execute_ordered(&request).await;
+        drop(guards);
+        drop(locks);
+        for key in &keys {
+            self.ordering
+                .remove_if(key, |_, lock| Arc::strong_count(lock) == 1);
+        }
+        result
+    }
+
+    async fn execute_ordered(
+        &self,
+        request: &ExecutionRequest<'_>,
+    ) -> Result<ExecutionReceipt, ExecutorError> {
+        let route = request.route;
+        let signer = self
+            .wallets
+            .get(request.wallet)
+            .map(|wallet| Arc::clone(&wallet.signer))
+            .ok_or_else(|| ExecutorError::UnknownWallet(request.wallet.to_string()))?;
+        let mut tx = decode_transaction(request.tx_base64)?;
+        if let Some(policy) = self.budget.policy_for(route) {
+            self.apply_compute_budget(&mut tx, route, policy).await;
+        }
+        self.enforce_policy(&tx, &signer).await.inspect_err(|err| {
+            warn!(route, error = %err, "refusing to sign transaction");
+        })?;
+        // Only a transaction we sign alone can be re-signed over a new blockhash.
+        let resignable = tx.message.header().num_required_signatures == 1;
+        let mut last_valid = match request.last_valid_block_height {
+            Some(height) => height,
+            // A blockhash is never valid for longer than this past the current height.
+            None => self.block_height().await? + MAX_PROCESSING_AGE as u64,
+        };
+        let mut expired_signatures = Vec::new();
+
+        loop {
+            let signed = async {
+                let signature = sign(&signer, &mut tx).await?;
+                let tx_base64 = encode_transaction(&tx)?;
+                record_signed(&request.progress, signature, tx_base64, last_valid).await?;
+                Ok::<_, ExecutorError>(signature)
+            };
+            let signature = match signed.await {
+                Ok(signature) => signature,
+                // A failed re-sign leaves the earlier transaction's expiry as the outcome.
+                Err(err) => match expired_signatures.pop() {
+                    Some(signature) => {
+                        warn!(%signature, route, error = %err, "re-signing expired transaction failed");
+                        return Err(ExecutorError::Expired { signature });
+                    }
+                    None => return Err(err),
                 },
+            };
+            confirmation::send_transaction(&self.rpc, &tx, false)
+                .await
+                .map_err(|err| {
+                    log_rpc_error(&err);
+                    ExecutorError::Rpc(err.to_string()).after_send(signature)
+                })?;
+            emit(&request.progress, ExecutionEvent::Sent { signature });
+
+            let confirmation = self
+                .tracker()
+                .track(
+                    tx.clone(),
+                    signature,
+                    last_valid,
+                    request.progress.clone(),
+                    false,
+                )
+                .await
+                .map_err(|err| err.after_send(signature))?;
+            match settle(&tx, signature, confirmation, route) {
+                Ok(slot) => {
+                    return Ok(ExecutionReceipt {
+                        signature,
+                        slot,
+                        expired_signatures,
+                    });
+                }
+                Err(ExecutorError::Expired { signature }) => {
+                    let refreshes = expired_signatures.len() as u32;
+                    if !resignable || refreshes >= self.send.max_blockhash_refreshes {
+                        return Err(ExecutorError::Expired { signature });
+                    }
+                    emit(&request.progress, ExecutionEvent::Resigning { signature });
+                    // The old blockhash is past its last valid height, so the earlier
+                    // signature can no longer land and re-signing cannot double-execute.
+                    let (blockhash, height) = self
+                        .rpc
+                        .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
+                        .await
+                        .map_err(|err| {
+                            log_rpc_error(&err);
+                            warn!(%signature, route, error = %err, "failed to refresh blockhash");
+                            ExecutorError::Expired { signature }
+                        })?;
+                    tx.message.set_recent_blockhash(blockhash);
+                    last_valid = height;
+                    expired_signatures.push(signature);
+                    warn!(%signature, route, attempt = refreshes + 1, "blockhash expired, re-signing");
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Picks up tracking of a transaction signed before a restart. It is rebroadcast but
+    /// never re-signed, since the original may still land, and its status is searched in
+    /// the full transaction history, since it may have landed during the downtime.
+    pub async fn resume(
+        &self,
+        signed_tx_base64: &str,
+        last_valid_block_height: u64,
+        progress: Option<ProgressSender>,
+    ) -> Result<ExecutionReceipt, ExecutorError> {
+        let tx = decode_transaction(signed_tx_base64)?;
+        let signature = *tx
+            .signatures
+            .first()
+            .ok_or_else(|| ExecutorError::Decode("transaction has no signature slots".into()))?;
+        if let Err(err) = confirmation::send_transaction(&self.rpc, &tx, true).await {
+            warn!(%signature, error = %err, "rebroadcast of resumed transaction failed");
+        }
+        let confirmation = self
+            .tracker()
+            .track(
+                tx.clone(),
+                signature,
+                last_valid_block_height,
+                progress,
+                true,
             )
+            .await?;
+        settle(&tx, signature, confirmation, "resume").map(|slot| ExecutionReceipt {
+            signature,
+            slot,
+            expired_signatures: Vec::new(),
+        })
+    }
+
+    /// Runs the pre-sign policy, including the on-chain ownership check of withdraw
+    /// destinations. Re-signing over a fresh blockhash keeps the checked message intact.
+    async fn enforce_policy(
+        &self,
+        tx: &VersionedTransaction,
+        signer: &ServerSigner,
+    ) -> Result<(), ExecutorError> {
+        let signer = signer.pubkey();
+        let destinations = self
+            .policy
+            .check(&tx.message, &signer)
+            .map_err(ExecutorError::PolicyViolation)?;
+        if destinations.is_empty() {
+            return Ok(());
+        }
+
+        let accounts = self
+            .rpc
+            .get_multiple_accounts(&destinations)
+            .await
+            .map_err(|err| ExecutorError::Rpc(err.to_string()))?;
+        for (key, account) in destinations.iter().zip(accounts) {
+            let account = account.ok_or_else(|| {
+                ExecutorError::PolicyViolation(format!("withdraw destination {key} does not exist"))
+            })?;
+            policy::verify_token_owner(key, &account.owner, &account.data, &signer)
+                .map_err(ExecutorError::PolicyViolation)?;
+        }
+        Ok(())
+    }
+
+    fn tracker(&self) -> &ConfirmationTracker {
+        self.tracker.get_or_init(|| {
+            ConfirmationTracker::spawn(Arc::clone(&self.rpc), self.send.resend_interval)
+        })
+    }
+
+    async fn block_height(&self) -> Result<u64, ExecutorError> {
+        self.rpc
+            .get_block_height_with_commitment(CommitmentConfig::confirmed())
+            .await
+            .map_err(|err| ExecutorError::Rpc(err.to_string()))
+    }
+
+    /// Dry-runs the transaction against current chain state without signing or sending it.
+    /// `accounts` are returned post-simulation so callers can inspect the resulting state.
+    pub async fn simulate(
+        &self,
+        tx_base64: &str,
+        accounts: &[Pubkey],
+    ) -> Result<SimulationReport, ExecutorError> {
+        let tx = decode_transaction(tx_base64)?;
+        self.simulate_transaction(&tx, accounts).await
+    }
+
+    async fn simulate_transaction(
+        &self,
+        tx: &VersionedTransaction,
+        accounts: &[Pubkey],
+    ) -> Result<SimulationReport, ExecutorError> {
+        let accounts_config =
+            (!accounts.is_empty()).then(|| RpcSimulateTransactionAccountsConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                addresses: accounts.iter().map(ToString::to_string).collect(),
+            });
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            commitment: Some(CommitmentConfig::confirmed()),
+            encoding: Some(UiTransactionEncoding::Base64),
+            accounts: accounts_config,
+            ..RpcSimulateTransactionConfig::default()
+        };
+
+        let result = self
+            .rpc
+            .simulate_transaction_with_config(tx, config)
             .await
-        {
-            Ok(_) => {
-                info!(%signature, "transaction executed");
-                Ok(signature)
+            .map_err(|err| {
+                log_rpc_error(&err);
+                ExecutorError::Rpc(err.to_string())
+            })?
+            .value;
+
+        Ok(SimulationReport {
+            success: result.err.is_none(),
+            failed_program_id: result
+                .err
+                .as_ref()
+                .and_then(|err| failed_program_id(tx, err)),
+            err: result.err,
+            logs: result.logs.unwrap_or_default(),
+            units_consumed: result.units_consumed,
+            accounts: result
+                .accounts
+                .unwrap_or_default()
+                .into_iter()
+                .map(|account| account.and_then(|account| account.data.decode()))
+                .collect(),
+        })
+    }
+
+    /// Best effort: on any failure the transaction is sent with whatever budget the worker set.
+    async fn apply_compute_budget(
+        &self,
+        tx: &mut VersionedTransaction,
+        route: &str,
+        policy: &FeePolicy,
+    ) {
+        // Rewriting the message would invalidate signatures we cannot reproduce.
+        if tx.message.header().num_required_signatures != 1 {
+            warn!(
+                route,
+                "skipping compute budget for multi-signer transaction"
+            );
+            return;
+        }
+
+        let unit_limit = match self.simulate_transaction(tx, &[]).await {
+            Ok(report) if report.success => report
+                .units_consumed
+                .map(|units| self.budget.unit_limit(units)),
+            Ok(report) => {
+                warn!(route, err = ?report.err, "simulation failed, keeping compute unit limit");
+                None
             }
             Err(err) => {
-                log_rpc_error(&err);
-                Err(ExecutorError::Rpc(err.to_string()))
+                warn!(route, error = %err, "simulation unavailable, keeping compute unit limit");
+                None
+            }
+        };
+
+        let unit_price = if policy.percentile.is_some() {
+            let writable = compute_budget::writable_accounts(&tx.message);
+            match self.rpc.get_recent_prioritization_fees(&writable).await {
+                Ok(fees) => {
+                    policy.unit_price(fees.into_iter().map(|fee| fee.prioritization_fee).collect())
+                }
+                Err(err) => {
+                    warn!(route, error = %err, "failed to fetch recent prioritization fees");
+                    None
+                }
             }
+        } else {
+            None
+        };
+
+        match compute_budget::apply(tx, unit_limit, unit_price) {
+            Ok(()) => info!(route, ?unit_limit, ?unit_price, "applied compute budget"),
+            Err(err) => warn!(route, error = %err, "failed to apply compute budget"),
+        }
+    }
+}
+
+async fn sign(
+    signer: &ServerSigner,
+    tx: &mut VersionedTransaction,
+) -> Result<Signature, ExecutorError> {
+    let message = tx.message.serialize();
+    let signature = signer.sign_message(&message).await?;
+    // The signing wallet is always the fee payer, i.e. the first signer.
+    let slot = tx
+        .signatures
+        .first_mut()
+        .ok_or_else(|| ExecutorError::Decode("transaction has no signature slots".into()))?;
+    *slot = signature;
+    Ok(signature)
+}
+
+/// Maps a tracked outcome to the confirmed slot or the matching error.
+fn settle(
+    tx: &VersionedTransaction,
+    signature: Signature,
+    confirmation: Confirmation,
+    route: &str,
+) -> Result<u64, ExecutorError> {
+    match confirmation {
+        Confirmation::Confirmed { slot } => {
+            info!(%signature, slot, route, "transaction executed");
+            Ok(slot)
         }
+        Confirmation::Failed { err } => {
+            error!(%signature, route, error = %err, "transaction failed on-chain");
+            Err(ExecutorError::FailedOnChain {
+                signature,
+                failed_program_id: failed_program_id(tx, &err),
+                err,
+            })
+        }
+        Confirmation::Expired => {
+            warn!(%signature, route, "transaction expired before confirmation");
+            Err(ExecutorError::Expired { signature })
+        }
+    }
+}
+
+/// Reports a signed transaction and waits for the listener to record it.
+async fn record_signed(
+    progress: &Option<ProgressSender>,
+    signature: Signature,
+    tx_base64: String,
+    last_valid_block_height: u64,
+) -> Result<(), ExecutorError> {
+    let Some(progress) = progress else {
+        return Ok(());
+    };
+    let (recorded, answer) = oneshot::channel();
+    let event = ExecutionEvent::Signed {
+        signature,
+        tx_base64,
+        last_valid_block_height,
+        recorded,
+    };
+    if progress.send(event).is_err() {
+        return Ok(());
+    }
+    match answer.await {
+        Ok(Err(err)) => Err(ExecutorError::Record(err)),
+        Ok(Ok(())) | Err(_) => Ok(()),
+    }
+}
+
+fn emit(progress: &Option<ProgressSender>, event: ExecutionEvent) {
+    if let Some(progress) = progress {
+        // A listener that went away only loses progress updates, not the result.
+        let _ = progress.send(event);
     }
 }
 
+fn failed_program_id(tx: &VersionedTransaction, err: &TransactionError) -> Option<String> {
+    let TransactionError::InstructionError(index, _) = err else {
+        return None;
+    };
+    let ix = tx.message.instructions().get(usize::from(*index))?;
+    tx.message
+        .static_account_keys()
+        .get(usize::from(ix.program_id_index))
+        .map(ToString::to_string)
+}
+
+fn decode_transaction(tx_base64: &str) -> Result<VersionedTransaction, ExecutorError> {
+    let bytes = STANDARD
+        .decode(tx_base64)
+        .map_err(|err| ExecutorError::Decode(err.to_string()))?;
+    bincode::deserialize(&bytes).map_err(|err| ExecutorError::Decode(err.to_string()))
+}
+
+fn encode_transaction(tx: &VersionedTransaction) -> Result<String, ExecutorError> {
+    bincode::serialize(tx)
+        .map(|bytes| STANDARD.encode(bytes))
+        .map_err(|err| ExecutorError::Decode(err.to_string()))
+}
+
 fn log_rpc_error(err: &ClientError) {
     let err_str = err.to_string();
     if err_str.contains("SendTransactionPreflightFailure") {
@@ -107,30 +686,3 @@ fn log_rpc_error(err: &ClientError) {
         error!(error = ?err, "rpc error");
     }
 }
-
-fn load_keypair(key_str: &str) -> Result<Keypair, String> {
-    let trimmed = key_str.trim();
-    if trimmed.is_empty() {
-        return Err("empty private key".into());
-    }
-    if trimmed.starts_with('[') {
-        let bytes: Vec<u8> =
-            serde_json::from_str(trimmed).map_err(|err| format!("invalid json array: {err}"))?;
-        return Keypair::from_bytes(&bytes).map_err(|err| err.to_string());
-    }
-    if trimmed.contains(',') {
-        let mut bytes = Vec::new();
-        for part in trimmed.split(',') {
-            let value: u8 = part
-                .trim()
-                .parse()
-                .map_err(|err| format!("invalid byte '{part}': {err}"))?;
-            bytes.push(value);
-        }
-        return Keypair::from_bytes(&bytes).map_err(|err| err.to_string());
-    }
-    let decoded = bs58::decode(trimmed)
-        .into_vec()
-        .map_err(|err| format!("invalid base58: {err}"))?;
-    Keypair::from_bytes(&decoded).map_err(|err| err.to_string())
-}
@@ -0,0 +1,289 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio_postgres::Client;
+use tracing::{error, info, warn, Instrument};
+use uuid::Uuid;
+
+use crate::{auth::ApiClient, db, types::ApiErrorBody};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+const REPLAYED_HEADER: &str = "idempotent-replayed";
+const DEFAULT_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long a key may stay in progress before it is assumed abandoned, e.g. by a crash
+/// between claiming it and storing the response.
+const DEFAULT_IN_PROGRESS_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const MAX_KEY_LEN: usize = 255;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Response extension for a failure that came after a transaction was sent. The response
+/// is stored like any other, so a retry cannot send the trade again.
+#[derive(Debug, Clone, Copy)]
+pub struct TransactionSent;
+
+/// Postgres-backed record of `Idempotency-Key` outcomes for the `/execute` routes.
+#[derive(Clone)]
+pub struct IdempotencyStore {
+    db: Arc<Client>,
+    retention: Duration,
+    in_progress_timeout: Duration,
+}
+
+impl IdempotencyStore {
+    pub fn new(db: Arc<Client>, retention: Duration, in_progress_timeout: Duration) -> Self {
+        Self {
+            db,
+            retention,
+            in_progress_timeout,
+        }
+    }
+
+    pub fn from_env(db: Arc<Client>) -> Self {
+        let retention = std::env::var("IDEMPOTENCY_RETENTION_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_RETENTION);
+        let in_progress_timeout = std::env::var("IDEMPOTENCY_IN_PROGRESS_TIMEOUT_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_IN_PROGRESS_TIMEOUT);
+        Self::new(db, retention, in_progress_timeout)
+    }
+}
+
+/// Middleware: the first request with a key runs and its response is stored; duplicates
+/// with the same payload get that response back (or an in-progress status), duplicates with
+/// a different payload get `409`. A `5xx` response from before anything was sent is not
+/// stored, so the key is freed for a retry; one marked [`TransactionSent`] is. Keys are scoped to the calling API key, or shared when auth is off. Requests
+/// without the header pass straight through.
+pub async fn enforce(
+    State(store): State<IdempotencyStore>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return next.run(request).await;
+    };
+    let key = match key.to_str().map(str::trim) {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LEN => key.to_string(),
+        _ => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "Idempotency-Key must be 1-255 visible ASCII characters",
+            )
+        }
+    };
+
+    let api_key_id = request
+        .extensions()
+        .get::<ApiClient>()
+        .map(|client| client.key_id)
+        .unwrap_or(Uuid::nil());
+
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => return error_response(StatusCode::PAYLOAD_TOO_LARGE, "request body too large"),
+    };
+    let request_hash = request_hash(parts.uri.path(), &bytes);
+
+    let claim = match db::claim_idempotency_key(
+        store.db.as_ref(),
+        api_key_id,
+        &key,
+        &request_hash,
+        store.retention.as_secs_f64(),
+        store.in_progress_timeout.as_secs_f64(),
+    )
+    .await
+    {
+        Ok(claim) => claim,
+        Err(err) => {
+            error!(?err, %key, "failed to claim idempotency key");
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "database error");
+        }
+    };
+
+    match claim {
+        db::IdempotencyClaim::Claimed => {}
+        db::IdempotencyClaim::InProgress {
+            request_hash: stored,
+        } if stored == request_hash => {
+            info!(%key, "idempotent request still in progress");
+            let body = json!({ "idempotencyKey": key, "status": "in_progress" });
+            return replayed(StatusCode::ACCEPTED, body);
+        }
+        db::IdempotencyClaim::Completed {
+            request_hash: stored,
+            status_code,
+            response,
+        } if stored == request_hash => {
+            info!(%key, status_code, "replaying idempotent response");
+            let status = StatusCode::from_u16(status_code).unwrap_or(StatusCode::OK);
+            return replayed(status, response);
+        }
+        _ => {
+            warn!(%key, "idempotency key reused with a different payload");
+            return error_response(
+                StatusCode::CONFLICT,
+                "Idempotency-Key was already used for a different request",
+            );
+        }
+    }
+
+    // Runs detached so a client disconnect cannot cancel a trade half-way and leave the key
+    // stuck in progress. The span keeps the caller on the handler's log lines.
+    let request = Request::from_parts(parts, Body::from(bytes));
+    let response = match tokio::spawn(next.run(request).in_current_span()).await {
+        Ok(response) => response,
+        Err(err) => {
+            error!(?err, %key, "idempotent request handler panicked");
+            release(&store, api_key_id, &key).await;
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "internal error");
+        }
+    };
+
+    let (parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            error!(?err, %key, "failed to buffer idempotent response");
+            release(&store, api_key_id, &key).await;
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "internal error");
+        }
+    };
+    if !stores_response(
+        parts.status,
+        parts.extensions.get::<TransactionSent>().is_some(),
+    ) {
+        release(&store, api_key_id, &key).await;
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+    let stored = serde_json::from_slice(&bytes)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+    if let Err(err) = db::complete_idempotency_key(
+        store.db.as_ref(),
+        api_key_id,
+        &key,
+        parts.status.as_u16(),
+        &stored,
+    )
+    .await
+    {
+        error!(?err, %key, "failed to store idempotent response");
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Server errors from before a transaction was sent are usually transient, so they are not
+/// replayed to a retry. Once one was sent, a retry could execute it twice.
+fn stores_response(status: StatusCode, sent: bool) -> bool {
+    sent || !status.is_server_error()
+}
+
+/// Frees `key` so the next request with it runs again.
+async fn release(store: &IdempotencyStore, api_key_id: Uuid, key: &str) {
+    if let Err(err) = db::release_idempotency_key(store.db.as_ref(), api_key_id, key).await {
+        error!(?err, %key, "failed to release idempotency key");
+    }
+}
+
+/// Hash of the route and the body; JSON bodies are normalised so key order does not matter.
+fn request_hash(path: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(path.as_bytes());
+    hasher.update([0]);
+    match serde_json::from_slice::<Value>(body) {
+        Ok(value) => hasher.update(value.to_string().as_bytes()),
+        Err(_) => hasher.update(body),
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn replayed(status: StatusCode, body: Value) -> Response {
+    let mut response = (status, Json(body)).into_response();
+    response.headers_mut().insert(
+        HeaderName::from_static(REPLAYED_HEADER),
+        HeaderValue::from_static("true"),
+    );
+    response
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    let body = Json(ApiErrorBody {
+        error: message,
+        details: None,
+    });
+    (status, body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_ignores_json_key_order_and_whitespace() {
+        let a = request_hash("/execute/orders", br#"{"market":"SOL-PERP","size":1}"#);
+        let b = request_hash(
+            "/execute/orders",
+            b"{ \"size\": 1,\n \"market\": \"SOL-PERP\" }",
+        );
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 64);
+    }
+
+    #[test]
+    fn hash_depends_on_path_and_body() {
+        let body = br#"{"market":"SOL-PERP","size":1}"#;
+        let hash = request_hash("/execute/orders", body);
+        assert_ne!(hash, request_hash("/execute/orders/close", body));
+        assert_ne!(
+            hash,
+            request_hash("/execute/orders", br#"{"market":"SOL-PERP","size":2}"#)
+        );
+        // Bodies that are not JSON are hashed as they are.
+        assert_ne!(
+            request_hash("/execute/orders", b"size=1"),
+            request_hash("/execute/orders", b"size=2")
+        );
+    }
+
+    #[test]
+    fn server_errors_are_stored_only_after_a_send() {
+        assert!(stores_response(StatusCode::OK, false));
+        assert!(stores_response(StatusCode::UNPROCESSABLE_ENTITY, false));
+        assert!(!stores_response(StatusCode::INTERNAL_SERVER_ERROR, false));
+        assert!(!stores_response(StatusCode::BAD_GATEWAY, false));
+        assert!(stores_response(StatusCode::BAD_GATEWAY, true));
+        assert!(stores_response(StatusCode::GATEWAY_TIMEOUT, true));
+    }
+
+    #[tokio::test]
+    async fn replay_returns_stored_response_with_header() {
+        let response = replayed(StatusCode::CREATED, json!({ "signature": "abc" }));
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[REPLAYED_HEADER], "true");
+        let body = to_bytes(response.into_body(), MAX_BODY_BYTES)
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            json!({ "signature": "abc" })
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use dashmap::DashMap;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::{
     env,
@@ -11,7 +11,7 @@ use thiserror::Error;
 use tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
     process::{Child, ChildStdin, Command},
-    sync::{oneshot, Mutex},
+    sync::{broadcast, oneshot, Mutex},
     task::JoinHandle,
 };
 use tracing::{error, info, warn};
@@ -33,6 +33,54 @@ pub enum IpcError {
     Write(String),
 }
 
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+const WATCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Unsolicited message pushed by the worker outside of any request/response pair.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", content = "data", rename_all = "camelCase")]
+pub enum WorkerEvent {
+    #[serde(rename_all = "camelCase")]
+    PositionChanged {
+        wallet: String,
+        market: String,
+        size: f64,
+        isolated_margin: Option<f64>,
+    },
+    #[serde(rename_all = "camelCase")]
+    OrderFilled {
+        wallet: String,
+        market: String,
+        order_id: u32,
+        direction: String,
+        filled_size: f64,
+        fill_price: Option<f64>,
+    },
+    #[serde(rename_all = "camelCase")]
+    OraclePriceUpdate {
+        market: String,
+        price: f64,
+        slot: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    SubscriptionLost {
+        wallet: Option<String>,
+        reason: String,
+    },
+}
+
+impl WorkerEvent {
+    /// Wallet the event relates to, `None` for market-wide or worker-wide events.
+    pub fn wallet(&self) -> Option<&str> {
+        match self {
+            WorkerEvent::PositionChanged { wallet, .. }
+            | WorkerEvent::OrderFilled { wallet, .. } => Some(wallet),
+            WorkerEvent::OraclePriceUpdate { .. } => None,
+            WorkerEvent::SubscriptionLost { wallet, .. } => wallet.as_deref(),
+        }
+    }
+}
+
 struct Worker {
     child: Child,
     stdin: Arc<Mutex<ChildStdin>>,
@@ -44,6 +92,7 @@ struct Inner {
     worker_path: PathBuf,
     pending: DashMap<String, oneshot::Sender<Result<Value, IpcError>>>,
     worker: Mutex<Option<Worker>>,
+    events: broadcast::Sender<WorkerEvent>,
 }
 
 #[derive(Deserialize)]
@@ -61,13 +110,22 @@ struct WorkerResponse {
     error: Option<WorkerErrorPayload>,
 }
 
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WorkerMessage {
+    Response(WorkerResponse),
+    Event(WorkerEvent),
+}
+
 impl Inner {
     fn new(node_path: PathBuf, worker_path: PathBuf) -> Arc<Self> {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
         Arc::new(Self {
             node_path,
             worker_path,
             pending: DashMap::new(),
             worker: Mutex::new(None),
+            events,
         })
     }
 
@@ -127,10 +185,11 @@ impl Inner {
         let mut lines = BufReader::new(stdout).lines();
 
         while let Some(line) = lines.next_line().await? {
-            match serde_json::from_str::<WorkerResponse>(&line) {
-                Ok(response) => self.dispatch_response(response),
+            match serde_json::from_str::<WorkerMessage>(&line) {
+                Ok(WorkerMessage::Response(response)) => self.dispatch_response(response),
+                Ok(WorkerMessage::Event(event)) => self.dispatch_event(event),
                 Err(err) => {
-                    warn!(line, error = %err, "failed to parse worker response");
+                    warn!(line, error = %err, "failed to parse worker message");
                 }
             }
         }
@@ -161,12 +220,22 @@ impl Inner {
         }
     }
 
+    fn dispatch_event(&self, event: WorkerEvent) {
+        // A send error only means nobody is subscribed right now.
+        let _ = self.events.send(event);
+    }
+
     async fn handle_worker_failure(self: &Arc<Self>) {
         let mut guard = self.worker.lock().await;
         if let Some(mut worker) = guard.take() {
             warn!("tearing down crashed worker");
             let _ = worker.child.kill().await;
             worker.reader.abort();
+            // Account subscriptions live in the worker process, so a restart drops all of them.
+            self.dispatch_event(WorkerEvent::SubscriptionLost {
+                wallet: None,
+                reason: "worker restarted".into(),
+            });
         }
         self.fail_all_pending(IpcError::WorkerCrashed);
     }
@@ -205,7 +274,7 @@ impl Inner {
         self.pending.insert(id.clone(), sender);
 
         let guard = self.worker.lock().await;
-        let worker = guard.as_ref().ok_or_else(|| IpcError::WorkerCrashed)?;
+        let worker = guard.as_ref().ok_or(IpcError::WorkerCrashed)?;
 
         {
             let mut stdin = worker.stdin.lock().await;
@@ -239,6 +308,14 @@ impl Inner {
 #[derive(Clone)]
 pub struct TsIpc {
     inner: Arc<Inner>,
+    _shutdown: Arc<ShutdownGuard>,
+}
+
+/// Kills the worker once the last `TsIpc` handle is dropped. Handles are cloned into
+/// every request and stream task, so tearing down on each clone's drop would restart
+/// the worker constantly and discard its account subscriptions.
+struct ShutdownGuard {
+    inner: Arc<Inner>,
 }
 
 impl TsIpc {
@@ -259,7 +336,12 @@ impl TsIpc {
         let inner = Inner::new(node_path, worker_path);
         inner.ensure_worker().await?;
 
-        Ok(Self { inner })
+        Ok(Self {
+            _shutdown: Arc::new(ShutdownGuard {
+                inner: Arc::clone(&inner),
+            }),
+            inner,
+        })
     }
 
     pub async fn call(
@@ -288,9 +370,28 @@ impl TsIpc {
             Err(err) => Err(err),
         }
     }
+
+    /// Subscribe to events pushed by the worker. Lagging receivers drop the oldest events.
+    pub fn subscribe(&self) -> broadcast::Receiver<WorkerEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// Ask the worker to keep a live account subscription for `wallet` and push its updates.
+    pub async fn watch_wallet(&self, wallet: &str) -> Result<(), IpcError> {
+        self.call("watchWallet", json!({ "wallet": wallet }), WATCH_TIMEOUT)
+            .await
+            .map(|_| ())
+    }
+
+    /// Release a subscription previously requested with [`TsIpc::watch_wallet`].
+    pub async fn unwatch_wallet(&self, wallet: &str) -> Result<(), IpcError> {
+        self.call("unwatchWallet", json!({ "wallet": wallet }), WATCH_TIMEOUT)
+            .await
+            .map(|_| ())
+    }
 }
 
-impl Drop for TsIpc {
+impl Drop for ShutdownGuard {
     fn drop(&mut self) {
         let inner = Arc::clone(&self.inner);
         tokio::spawn(async move {
@@ -1,6 +1,21 @@
+pub mod auth;
+pub mod brackets;
+pub mod compute_budget;
+pub mod confirmation;
 pub mod db;
 pub mod decoder;
 pub mod executor;
+pub mod idempotency;
 pub mod ipc;
+pub mod liquidation;
+pub mod paper;
+pub mod policy;
+pub mod risk;
 pub mod routes;
+pub mod scheduler;
+pub mod signer;
+pub mod stream;
+pub mod trading_mode;
 pub mod types;
+pub mod wallets;
+pub mod webhooks;
@@ -0,0 +1,586 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use reqwest::Client as ReqwestClient;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::{io::AsyncWriteExt, time::MissedTickBehavior};
+use tracing::{error, info, warn};
+
+use crate::{
+    ipc::IpcError,
+    routes::{self, AppState},
+    types::now_ms,
+};
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(30);
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(10);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_WARNING_BUFFER: f64 = 2.0;
+const DEFAULT_CRITICAL_BUFFER: f64 = 1.25;
+
+#[derive(Debug, Error)]
+pub enum LiquidationError {
+    #[error("invalid liquidation monitor configuration: {0}")]
+    Config(String),
+    #[error(transparent)]
+    Worker(#[from] IpcError),
+    #[error("invalid risk snapshot: {0}")]
+    Snapshot(String),
+    #[error("alert sink {sink} failed: {message}")]
+    Sink { sink: String, message: String },
+}
+
+/// How close a position is to liquidation, by its margin ratio as a multiple of the
+/// market's maintenance ratio (its buffer).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertLevel {
+    Ok,
+    Warning,
+    Critical,
+}
+
+/// Buffers below which a position is a warning or critical. A buffer of 1 is the
+/// maintenance requirement itself.
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    pub warning: f64,
+    pub critical: f64,
+}
+
+impl Thresholds {
+    fn level(&self, buffer: f64) -> AlertLevel {
+        if buffer < self.critical {
+            AlertLevel::Critical
+        } else if buffer < self.warning {
+            AlertLevel::Warning
+        } else {
+            AlertLevel::Ok
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertEvent {
+    /// The position moved to another level.
+    Threshold,
+    /// The monitor tried to top up a critical position.
+    AutoDeposit,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoDepositOutcome {
+    pub amount: f64,
+    pub tx_signature: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiquidationAlert {
+    pub event: AlertEvent,
+    pub wallet: String,
+    pub wallet_label: String,
+    pub sub_account_id: u16,
+    pub market: String,
+    pub level: AlertLevel,
+    pub previous_level: AlertLevel,
+    /// Signed base amount, negative for shorts.
+    pub size: f64,
+    pub notional: f64,
+    /// Isolated margin plus unrealized PnL.
+    pub collateral: f64,
+    pub margin_ratio: f64,
+    pub maintenance_margin_ratio: f64,
+    pub buffer: f64,
+    pub at_ms: i64,
+    pub auto_deposit: Option<AutoDepositOutcome>,
+}
+
+/// Where alerts go. Sinks are listed in `LIQUIDATION_ALERT_SINKS` as `log`,
+/// `file:<path>` and `webhook:<url>`, separated by commas.
+#[derive(Clone)]
+pub enum AlertSink {
+    /// A `warn!` line per alert.
+    Log,
+    /// One JSON object per line, appended to the file.
+    File(PathBuf),
+    /// The alert as a JSON `POST` body.
+    Webhook { client: ReqwestClient, url: String },
+}
+
+impl AlertSink {
+    pub fn parse(spec: &str) -> Result<Self, LiquidationError> {
+        let spec = spec.trim();
+        if spec == "log" {
+            return Ok(Self::Log);
+        }
+        match spec.split_once(':') {
+            Some(("file", path)) if !path.trim().is_empty() => {
+                Ok(Self::File(PathBuf::from(path.trim())))
+            }
+            Some(("webhook", url)) if !url.trim().is_empty() => {
+                let client = ReqwestClient::builder()
+                    .timeout(WEBHOOK_TIMEOUT)
+                    .build()
+                    .map_err(|err| LiquidationError::Config(err.to_string()))?;
+                Ok(Self::Webhook {
+                    client,
+                    url: url.trim().to_string(),
+                })
+            }
+            _ => Err(LiquidationError::Config(format!(
+                "unknown alert sink '{spec}', expected log, file:<path> or webhook:<url>"
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Self::Log => "log".into(),
+            Self::File(path) => format!("file:{}", path.display()),
+            Self::Webhook { url, .. } => format!("webhook:{url}"),
+        }
+    }
+
+    pub async fn send(&self, alert: &LiquidationAlert) -> Result<(), LiquidationError> {
+        let failed = |message: String| LiquidationError::Sink {
+            sink: self.name(),
+            message,
+        };
+        match self {
+            Self::Log => {
+                warn!(
+                    event = ?alert.event,
+                    wallet = %alert.wallet_label,
+                    sub_account = alert.sub_account_id,
+                    market = %alert.market,
+                    level = ?alert.level,
+                    previous = ?alert.previous_level,
+                    buffer = alert.buffer,
+                    margin_ratio = alert.margin_ratio,
+                    auto_deposit = ?alert.auto_deposit,
+                    "liquidation alert"
+                );
+                Ok(())
+            }
+            Self::File(path) => {
+                let mut line = serde_json::to_vec(alert).map_err(|err| failed(err.to_string()))?;
+                line.push(b'\n');
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .map_err(|err| failed(err.to_string()))?;
+                file.write_all(&line)
+                    .await
+                    .map_err(|err| failed(err.to_string()))
+            }
+            Self::Webhook { client, url } => {
+                let response = client
+                    .post(url)
+                    .json(alert)
+                    .send()
+                    .await
+                    .map_err(|err| failed(err.to_string()))?;
+                if !response.status().is_success() {
+                    return Err(failed(format!("responded {}", response.status())));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MarginSnapshot {
+    positions: Vec<MarginPosition>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MarginPosition {
+    market: String,
+    size: f64,
+    notional: f64,
+    /// USDC deposited into the position, the token amount rather than Drift's scaled
+    /// balance.
+    isolated_margin: f64,
+    unrealized_pnl: f64,
+    maintenance_margin_ratio: f64,
+}
+
+impl MarginPosition {
+    /// Isolated margin plus unrealized PnL.
+    fn collateral(&self) -> f64 {
+        self.isolated_margin + self.unrealized_pnl
+    }
+
+    fn margin_ratio(&self) -> f64 {
+        self.collateral() / self.notional
+    }
+
+    fn buffer(&self) -> f64 {
+        self.margin_ratio() / self.maintenance_margin_ratio
+    }
+}
+
+/// A registry wallet's position in one market.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PositionKey {
+    label: String,
+    sub_account_id: u16,
+    market: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Tracked {
+    level: AlertLevel,
+    /// Set when an auto-deposit failed, so it is not retried until the level changes.
+    deposit_failed: bool,
+}
+
+/// Periodically reads the isolated positions of every registry wallet and sub-account,
+/// alerts when a position's buffer crosses a threshold, and can top up critical
+/// positions from the sub-account's cross collateral.
+pub struct LiquidationMonitor {
+    state: AppState,
+    interval: Duration,
+    thresholds: Thresholds,
+    sinks: Vec<AlertSink>,
+    /// Largest single auto-deposit in USDC; `None` leaves positions alone.
+    auto_deposit_max: Option<f64>,
+    tracked: HashMap<PositionKey, Tracked>,
+}
+
+impl LiquidationMonitor {
+    pub fn new(
+        state: AppState,
+        interval: Duration,
+        thresholds: Thresholds,
+        sinks: Vec<AlertSink>,
+        auto_deposit_max: Option<f64>,
+    ) -> Self {
+        Self {
+            state,
+            interval,
+            thresholds,
+            sinks,
+            auto_deposit_max,
+            tracked: HashMap::new(),
+        }
+    }
+
+    /// `None` unless `LIQUIDATION_MONITOR` is `true` or `1`. Reads the poll interval,
+    /// thresholds, sinks and auto-deposit cap from the `LIQUIDATION_*` variables.
+    pub fn from_env(state: AppState) -> Result<Option<Self>, LiquidationError> {
+        let enabled = std::env::var("LIQUIDATION_MONITOR")
+            .map(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true"))
+            .unwrap_or(false);
+        if !enabled {
+            return Ok(None);
+        }
+        let interval = std::env::var("LIQUIDATION_POLL_INTERVAL_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_POLL_INTERVAL);
+        let thresholds = Thresholds {
+            warning: number_from_env("LIQUIDATION_WARNING_BUFFER")?
+                .unwrap_or(DEFAULT_WARNING_BUFFER),
+            critical: number_from_env("LIQUIDATION_CRITICAL_BUFFER")?
+                .unwrap_or(DEFAULT_CRITICAL_BUFFER),
+        };
+        if thresholds.critical < 1.0 || thresholds.warning <= thresholds.critical {
+            return Err(LiquidationError::Config(
+                "buffers must satisfy 1 <= LIQUIDATION_CRITICAL_BUFFER < LIQUIDATION_WARNING_BUFFER"
+                    .into(),
+            ));
+        }
+        let sinks = std::env::var("LIQUIDATION_ALERT_SINKS")
+            .unwrap_or_else(|_| "log".into())
+            .split(',')
+            .filter(|spec| !spec.trim().is_empty())
+            .map(AlertSink::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if sinks.is_empty() {
+            return Err(LiquidationError::Config(
+                "LIQUIDATION_ALERT_SINKS names no sinks".into(),
+            ));
+        }
+        let auto_deposit_max = number_from_env("LIQUIDATION_AUTO_DEPOSIT_MAX")?;
+        info!(
+            interval = ?interval,
+            warning = thresholds.warning,
+            critical = thresholds.critical,
+            sinks = ?sinks.iter().map(AlertSink::name).collect::<Vec<_>>(),
+            auto_deposit_max,
+            "liquidation monitor enabled"
+        );
+        Ok(Some(Self::new(
+            state,
+            interval,
+            thresholds,
+            sinks,
+            auto_deposit_max,
+        )))
+    }
+
+    /// Runs the monitor for the life of the process.
+    pub fn spawn(mut self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            info!(interval = ?self.interval, "liquidation monitor started");
+            loop {
+                ticker.tick().await;
+                self.check_all().await;
+            }
+        })
+    }
+
+    /// Checks every registry wallet and sub-account once.
+    pub async fn check_all(&mut self) {
+        let accounts: Vec<(String, String, u16)> = self
+            .state
+            .executor
+            .wallets()
+            .iter()
+            .flat_map(|entry| {
+                let pubkey = entry.pubkey().to_string();
+                entry
+                    .sub_accounts
+                    .iter()
+                    .map(move |sub| (entry.label.clone(), pubkey.clone(), *sub))
+            })
+            .collect();
+        for (label, wallet, sub_account_id) in accounts {
+            if let Err(err) = self.check_account(&label, &wallet, sub_account_id).await {
+                warn!(%label, sub_account_id, error = %err, "liquidation check failed");
+            }
+        }
+    }
+
+    async fn check_account(
+        &mut self,
+        label: &str,
+        wallet: &str,
+        sub_account_id: u16,
+    ) -> Result<(), LiquidationError> {
+        let snapshot: MarginSnapshot = serde_json::from_value(
+            self.state
+                .ipc
+                .call(
+                    "getRiskSnapshot",
+                    json!({ "wallet": wallet, "subAccountId": sub_account_id }),
+                    SNAPSHOT_TIMEOUT,
+                )
+                .await?,
+        )
+        .map_err(|err| LiquidationError::Snapshot(err.to_string()))?;
+
+        // Positions that closed since the last check are forgotten.
+        self.tracked.retain(|key, _| {
+            key.label != label
+                || key.sub_account_id != sub_account_id
+                || snapshot
+                    .positions
+                    .iter()
+                    .any(|position| position.market == key.market)
+        });
+
+        for position in snapshot
+            .positions
+            .iter()
+            .filter(|position| position.notional > 0.0 && position.maintenance_margin_ratio > 0.0)
+        {
+            let key = PositionKey {
+                label: label.to_string(),
+                sub_account_id,
+                market: position.market.clone(),
+            };
+            let mut alert = self.alert(&key, wallet, position);
+            let tracked = self.tracked.get(&key).copied().unwrap_or(Tracked {
+                level: AlertLevel::Ok,
+                deposit_failed: false,
+            });
+            alert.previous_level = tracked.level;
+            let mut next = Tracked {
+                level: alert.level,
+                deposit_failed: tracked.deposit_failed && tracked.level == alert.level,
+            };
+
+            if alert.level != tracked.level {
+                self.notify(&alert).await;
+            }
+            if alert.level == AlertLevel::Critical && !next.deposit_failed {
+                if let Some(outcome) = self.auto_deposit(&key, position).await {
+                    next.deposit_failed = outcome.error.is_some();
+                    alert.event = AlertEvent::AutoDeposit;
+                    alert.auto_deposit = Some(outcome);
+                    self.notify(&alert).await;
+                }
+            }
+            self.tracked.insert(key, next);
+        }
+        Ok(())
+    }
+
+    fn alert(
+        &self,
+        key: &PositionKey,
+        wallet: &str,
+        position: &MarginPosition,
+    ) -> LiquidationAlert {
+        let buffer = position.buffer();
+        LiquidationAlert {
+            event: AlertEvent::Threshold,
+            wallet: wallet.to_string(),
+            wallet_label: key.label.clone(),
+            sub_account_id: key.sub_account_id,
+            market: key.market.clone(),
+            level: self.thresholds.level(buffer),
+            previous_level: AlertLevel::Ok,
+            size: position.size,
+            notional: position.notional,
+            collateral: position.collateral(),
+            margin_ratio: position.margin_ratio(),
+            maintenance_margin_ratio: position.maintenance_margin_ratio,
+            buffer,
+            at_ms: now_ms(),
+            auto_deposit: None,
+        }
+    }
+
+    /// Deposits enough to lift the position back to the warning buffer, up to the cap.
+    /// `None` when auto-deposits are off or cannot run.
+    async fn auto_deposit(
+        &self,
+        key: &PositionKey,
+        position: &MarginPosition,
+    ) -> Option<AutoDepositOutcome> {
+        let max = self.auto_deposit_max.filter(|max| *max > 0.0)?;
+        // Paper trading never sends transactions.
+        if self.state.paper.is_some() {
+            return None;
+        }
+        let target =
+            self.thresholds.warning * position.maintenance_margin_ratio * position.notional;
+        let amount = (target - position.collateral()).min(max);
+        if amount <= 0.0 {
+            return None;
+        }
+        let amount = (amount * 1e6).ceil() / 1e6;
+        let result = routes::deposit_isolated_margin(
+            &self.state,
+            &key.label,
+            key.sub_account_id,
+            &key.market,
+            amount,
+        )
+        .await;
+        Some(match result {
+            Ok(signature) => {
+                info!(
+                    label = %key.label,
+                    sub_account = key.sub_account_id,
+                    market = %key.market,
+                    amount,
+                    %signature,
+                    "auto-deposited isolated margin"
+                );
+                AutoDepositOutcome {
+                    amount,
+                    tx_signature: Some(signature),
+                    error: None,
+                }
+            }
+            Err(message) => {
+                error!(
+                    label = %key.label,
+                    sub_account = key.sub_account_id,
+                    market = %key.market,
+                    amount,
+                    %message,
+                    "auto-deposit failed"
+                );
+                AutoDepositOutcome {
+                    amount,
+                    tx_signature: None,
+                    error: Some(message),
+                }
+            }
+        })
+    }
+
+    async fn notify(&self, alert: &LiquidationAlert) {
+        for sink in &self.sinks {
+            if let Err(err) = sink.send(alert).await {
+                warn!(error = %err, "failed to deliver liquidation alert");
+            }
+        }
+    }
+}
+
+fn number_from_env(name: &str) -> Result<Option<f64>, LiquidationError> {
+    let Ok(value) = std::env::var(name) else {
+        return Ok(None);
+    };
+    match value.trim().parse::<f64>() {
+        Ok(number) if number.is_finite() && number >= 0.0 => Ok(Some(number)),
+        _ => Err(LiquidationError::Config(format!(
+            "{name} must be a non-negative number"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLDS: Thresholds = Thresholds {
+        warning: DEFAULT_WARNING_BUFFER,
+        critical: DEFAULT_CRITICAL_BUFFER,
+    };
+
+    fn position(isolated_margin: f64, unrealized_pnl: f64) -> MarginPosition {
+        serde_json::from_value(json!({
+            "market": "SOL-PERP",
+            "size": 10.0,
+            "notional": 1_000.0,
+            "isolatedMargin": isolated_margin,
+            "unrealizedPnl": unrealized_pnl,
+            "maintenanceMarginRatio": 0.05,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn buffer_is_margin_ratio_over_maintenance() {
+        let position = position(120.0, -20.0);
+        assert_eq!(position.collateral(), 100.0);
+        assert!((position.margin_ratio() - 0.1).abs() < 1e-12);
+        assert!((position.buffer() - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn thin_positions_alert() {
+        assert_eq!(
+            THRESHOLDS.level(position(150.0, 0.0).buffer()),
+            AlertLevel::Ok
+        );
+        assert_eq!(
+            THRESHOLDS.level(position(80.0, 0.0).buffer()),
+            AlertLevel::Warning
+        );
+        assert_eq!(
+            THRESHOLDS.level(position(80.0, -20.0).buffer()),
+            AlertLevel::Critical
+        );
+    }
+}
@@ -1,15 +1,29 @@
 use std::{net::SocketAddr, sync::Arc};
 
 use anyhow::Context;
-use axum::Router;
+use axum::{http::HeaderValue, Router};
 use rust_api::{
+    auth::ApiKeys,
+    brackets::BracketMonitor,
     db,
     decoder::DriftDecoder,
-    executor, ipc,
+    executor,
+    idempotency::IdempotencyStore,
+    ipc,
+    liquidation::LiquidationMonitor,
+    paper::PaperBook,
+    risk::RiskEngine,
     routes::{self, AppState},
+    scheduler::Scheduler,
+    stream::PositionStreams,
+    trading_mode::TradingSwitch,
+    webhooks::Webhooks,
 };
 use tower::ServiceBuilder;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use tower_http::{
+    cors::{AllowOrigin, Any, CorsLayer},
+    trace::TraceLayer,
+};
 use tracing::info;
 use tracing_subscriber::EnvFilter;
 
@@ -35,17 +49,47 @@ async fn main() -> anyhow::Result<()> {
 
     let decoder = Arc::new(DriftDecoder::from_env()?);
 
+    let streams = PositionStreams::from_env(ipc.clone());
+    let risk = RiskEngine::from_env(ipc.clone(), db_client.clone())?;
+    let trading = TradingSwitch::load(db_client.clone()).await?;
+    let paper = PaperBook::from_env(ipc.clone(), db_client.clone())?;
+    let webhooks = Webhooks::from_env(db_client.clone())?;
+    webhooks.clone().spawn();
+    let executor = Arc::new(executor);
+    BracketMonitor::from_env(
+        ipc.clone(),
+        executor.clone(),
+        db_client.clone(),
+        webhooks.clone(),
+    )
+    .spawn();
     let state = AppState {
         ipc,
-        executor: Arc::new(executor),
+        executor,
         db: db_client.clone(),
         decoder,
+        streams,
+        idempotency: IdempotencyStore::from_env(db_client.clone()),
+        risk,
+        trading,
+        paper,
+        webhooks,
+        api_keys: ApiKeys::from_env(db_client.clone()),
     };
 
+    let resumed = routes::resume_submissions(&state).await?;
+    if resumed > 0 {
+        info!(resumed, "resumed unfinished transaction submissions");
+    }
+    Scheduler::from_env(state.clone()).spawn();
+    if let Some(monitor) = LiquidationMonitor::from_env(state.clone())? {
+        monitor.spawn();
+    }
+
     let app: Router = routes::router(state).layer(
         ServiceBuilder::new()
             .layer(TraceLayer::new_for_http())
-            .layer(CorsLayer::permissive()),
+            .layer(cors_layer()?),
     );
 
     let addr: SocketAddr = ([127, 0, 0, 1], 8080).into();
@@ -60,6 +104,27 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Any origin unless `CORS_ALLOWED_ORIGINS` lists them. API keys travel in headers, not
+/// cookies, so an open policy does not hand them to other sites.
+fn cors_layer() -> anyhow::Result<CorsLayer> {
+    let Ok(origins) = std::env::var("CORS_ALLOWED_ORIGINS") else {
+        return Ok(CorsLayer::permissive());
+    };
+    let origins = origins
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            HeaderValue::from_str(origin)
+                .with_context(|| format!("invalid origin '{origin}' in CORS_ALLOWED_ORIGINS"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods(Any)
+        .allow_headers(Any))
+}
+
 fn load_env() {
     let cwd = std::env::current_dir().unwrap_or_default();
     let candidates = [
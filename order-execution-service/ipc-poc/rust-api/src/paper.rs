@@ -0,0 +1,503 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tokio_postgres::Client;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    db,
+    decoder::ActionRecord,
+    ipc::{IpcError, TsIpc},
+    types::PaperPosition,
+};
+
+const MARKET_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_SLIPPAGE_BPS: f64 = 5.0;
+const DEFAULT_FEE_BPS: f64 = 5.0;
+/// Margin a position has to keep, as a share of its notional: the 100x leverage cap.
+const MIN_MARGIN_RATIO: f64 = 0.01;
+/// Drift's base, price and quote precisions, used for the action log.
+const BASE_PRECISION: f64 = 1e9;
+const PRICE_PRECISION: f64 = 1e6;
+const QUOTE_PRECISION: f64 = 1e6;
+/// Quote spot market the isolated margin is held in.
+const QUOTE_SPOT_MARKET_INDEX: u16 = 0;
+
+#[derive(Debug, Error)]
+pub enum PaperError {
+    #[error("invalid paper trading configuration: {0}")]
+    Config(String),
+    #[error(transparent)]
+    Worker(#[from] IpcError),
+    #[error("invalid market data: {0}")]
+    Market(String),
+    #[error("paper trading database error: {0}")]
+    Database(anyhow::Error),
+    /// The order cannot be filled against the virtual position.
+    #[error("{0}")]
+    Rejected(String),
+}
+
+/// The virtual position an order applies to.
+pub struct PaperAccount<'a> {
+    pub wallet: &'a str,
+    pub sub_account_id: u16,
+    pub market: &'a str,
+}
+
+pub enum PaperOrder {
+    /// Adds `margin` to the position, then fills `size` (signed) at market.
+    Open {
+        size: f64,
+        margin: f64,
+        /// Fills only when the slipped price is no worse than this.
+        limit_price: Option<f64>,
+    },
+    /// Reduces the position by `size`, or closes all of it.
+    Close { size: Option<f64> },
+    /// Moves margin into (positive) or out of the position.
+    Transfer { delta: f64 },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaperFill {
+    /// Signed base amount, negative for sells.
+    pub size: f64,
+    pub price: f64,
+    pub oracle_price: f64,
+    pub fee: f64,
+    pub realized_pnl: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaperExecution {
+    /// `paper:<uuid>`, the key of its rows in `drift_action_logs`.
+    pub signature: String,
+    pub fill: Option<PaperFill>,
+    pub position: PaperPosition,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MarketPrice {
+    symbol: String,
+    market_index: u16,
+    slot: u64,
+    price: f64,
+}
+
+/// Fills orders against virtual isolated positions at the oracle price, for
+/// `PAPER_TRADING` mode. Positions are kept in Postgres (`paper_positions`) and fills are
+/// logged to `drift_action_logs` with `paper` set.
+#[derive(Clone)]
+pub struct PaperBook {
+    ipc: TsIpc,
+    db: Arc<Client>,
+    costs: FillCosts,
+    /// Serialises fills so each one reads the position the previous one wrote.
+    lock: Arc<Mutex<()>>,
+}
+
+impl PaperBook {
+    pub fn new(ipc: TsIpc, db: Arc<Client>, slippage_bps: f64, fee_bps: f64) -> Self {
+        Self {
+            ipc,
+            db,
+            costs: FillCosts {
+                slippage_bps,
+                fee_bps,
+            },
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// `None` unless `PAPER_TRADING` is `true` or `1`. Slippage and fees come from
+    /// `PAPER_SLIPPAGE_BPS` and `PAPER_FEE_BPS`.
+    pub fn from_env(ipc: TsIpc, db: Arc<Client>) -> Result<Option<Self>, PaperError> {
+        let enabled = std::env::var("PAPER_TRADING")
+            .map(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true"))
+            .unwrap_or(false);
+        if !enabled {
+            return Ok(None);
+        }
+        let slippage_bps = bps_from_env("PAPER_SLIPPAGE_BPS", DEFAULT_SLIPPAGE_BPS)?;
+        let fee_bps = bps_from_env("PAPER_FEE_BPS", DEFAULT_FEE_BPS)?;
+        info!(slippage_bps, fee_bps, "paper trading enabled");
+        Ok(Some(Self::new(ipc, db, slippage_bps, fee_bps)))
+    }
+
+    /// Applies `order` to the account's virtual position. With `dry_run` the result is
+    /// computed but nothing is stored.
+    pub async fn execute(
+        &self,
+        account: &PaperAccount<'_>,
+        order: PaperOrder,
+        dry_run: bool,
+    ) -> Result<PaperExecution, PaperError> {
+        let _guard = self.lock.lock().await;
+        let market = self.market(account.market).await?;
+        let mut position = db::fetch_paper_position(
+            self.db.as_ref(),
+            account.wallet,
+            account.sub_account_id,
+            &market.symbol,
+        )
+        .await
+        .map_err(PaperError::Database)?
+        .unwrap_or_else(|| PaperPosition {
+            wallet: account.wallet.to_string(),
+            sub_account_id: account.sub_account_id,
+            market: market.symbol.clone(),
+            ..Default::default()
+        });
+
+        let signature = format!("paper:{}", Uuid::new_v4());
+        let mut actions = Vec::new();
+        let fill = match order {
+            PaperOrder::Open {
+                size,
+                margin,
+                limit_price,
+            } => {
+                let price = self.costs.fill_price(market.price, size);
+                if let Some(limit) = limit_price {
+                    let marketable = if size > 0.0 {
+                        limit >= price
+                    } else {
+                        limit <= price
+                    };
+                    if !marketable {
+                        return Err(PaperError::Rejected(format!(
+                            "limit price {limit} would not fill at {price:.6}"
+                        )));
+                    }
+                }
+                position.margin += margin;
+                actions.push(movement_record(&signature, &market, margin));
+                let fill = self.costs.fill(&mut position, size, price, market.price);
+                let required = position.size.abs() * market.price * MIN_MARGIN_RATIO;
+                if equity(&position, market.price) < required {
+                    return Err(PaperError::Rejected(format!(
+                        "insufficient paper margin: {required:.6} required"
+                    )));
+                }
+                actions.push(order_record(&signature, &market, &fill, false));
+                Some(fill)
+            }
+            PaperOrder::Close { size } => {
+                if position.size == 0.0 {
+                    return Err(PaperError::Rejected(format!(
+                        "no open {} paper position to close",
+                        market.symbol
+                    )));
+                }
+                let amount = size.map_or(position.size.abs(), |size| size.min(position.size.abs()));
+                let size = -amount * position.size.signum();
+                let price = self.costs.fill_price(market.price, size);
+                let fill = self.costs.fill(&mut position, size, price, market.price);
+                actions.push(order_record(&signature, &market, &fill, true));
+                Some(fill)
+            }
+            PaperOrder::Transfer { delta } => {
+                if delta < 0.0 {
+                    let free = equity(&position, market.price).min(position.margin)
+                        - position.size.abs() * market.price * MIN_MARGIN_RATIO;
+                    if -delta > free {
+                        return Err(PaperError::Rejected(format!(
+                            "only {:.6} of paper margin can be withdrawn",
+                            free.max(0.0)
+                        )));
+                    }
+                }
+                position.margin += delta;
+                actions.push(movement_record(&signature, &market, delta));
+                None
+            }
+        };
+        position.unrealized_pnl = Some(unrealized_pnl(&position, market.price));
+
+        if !dry_run {
+            position.updated_at = db::upsert_paper_position(self.db.as_ref(), &position)
+                .await
+                .map_err(PaperError::Database)?;
+            for (index, action) in actions.iter_mut().enumerate() {
+                action.instruction_index = index;
+            }
+            db::insert_actions(self.db.as_ref(), &actions)
+                .await
+                .map_err(PaperError::Database)?;
+            info!(
+                %signature,
+                wallet = account.wallet,
+                market = %market.symbol,
+                size = position.size,
+                margin = position.margin,
+                "paper order filled"
+            );
+        }
+        Ok(PaperExecution {
+            signature,
+            fill,
+            position,
+        })
+    }
+
+    /// The wallet's paper positions, valued at the current oracle prices.
+    pub async fn positions(
+        &self,
+        wallet: &str,
+        sub_account_id: Option<u16>,
+    ) -> Result<Vec<PaperPosition>, PaperError> {
+        let mut positions = db::fetch_paper_positions(self.db.as_ref(), wallet, sub_account_id)
+            .await
+            .map_err(PaperError::Database)?;
+        for position in &mut positions {
+            if position.size == 0.0 {
+                position.unrealized_pnl = Some(0.0);
+                continue;
+            }
+            // A market the worker cannot price is still listed, without its PnL.
+            if let Ok(market) = self.market(&position.market).await {
+                position.unrealized_pnl = Some(unrealized_pnl(position, market.price));
+            }
+        }
+        Ok(positions)
+    }
+
+    async fn market(&self, symbol: &str) -> Result<MarketPrice, PaperError> {
+        let value = self
+            .ipc
+            .call("getMarket", json!({ "symbol": symbol }), MARKET_TIMEOUT)
+            .await?;
+        let market: MarketPrice =
+            serde_json::from_value(value).map_err(|err| PaperError::Market(err.to_string()))?;
+        if !market.price.is_finite() || market.price <= 0.0 {
+            return Err(PaperError::Market(format!(
+                "no oracle price for {}",
+                market.symbol
+            )));
+        }
+        Ok(market)
+    }
+}
+
+/// Slippage and fees charged on paper fills.
+#[derive(Debug, Clone, Copy)]
+struct FillCosts {
+    slippage_bps: f64,
+    fee_bps: f64,
+}
+
+impl FillCosts {
+    /// The oracle price moved against the taker by the configured slippage.
+    fn fill_price(&self, oracle_price: f64, size: f64) -> f64 {
+        oracle_price * (1.0 + size.signum() * self.slippage_bps / 10_000.0)
+    }
+
+    /// Fills `size` against the position, realizing PnL on the part that reduces it and
+    /// averaging the entry price on the part that adds to it. Fees and realized PnL settle
+    /// into the margin.
+    fn fill(&self, position: &mut PaperPosition, size: f64, price: f64, oracle: f64) -> PaperFill {
+        let fee = size.abs() * price * self.fee_bps / 10_000.0;
+        let mut realized_pnl = 0.0;
+        let current = position.size;
+        if current == 0.0 || current.signum() == size.signum() {
+            let total = current + size;
+            position.entry_price = (current * position.entry_price + size * price) / total;
+        } else {
+            let reduced = size.abs().min(current.abs());
+            realized_pnl = reduced * (price - position.entry_price) * current.signum();
+            if size.abs() > current.abs() {
+                position.entry_price = price;
+            }
+        }
+        position.size = current + size;
+        if position.size.abs() < f64::EPSILON {
+            position.size = 0.0;
+            position.entry_price = 0.0;
+        }
+        position.margin += realized_pnl - fee;
+        position.realized_pnl += realized_pnl;
+        position.fees += fee;
+        PaperFill {
+            size,
+            price,
+            oracle_price: oracle,
+            fee,
+            realized_pnl,
+        }
+    }
+}
+
+fn unrealized_pnl(position: &PaperPosition, price: f64) -> f64 {
+    position.size * (price - position.entry_price)
+}
+
+fn equity(position: &PaperPosition, price: f64) -> f64 {
+    position.margin + unrealized_pnl(position, price)
+}
+
+fn bps_from_env(name: &str, default: f64) -> Result<f64, PaperError> {
+    let Ok(value) = std::env::var(name) else {
+        return Ok(default);
+    };
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|bps| bps.is_finite() && *bps >= 0.0)
+        .ok_or_else(|| PaperError::Config(format!("{name} must be a non-negative number")))
+}
+
+fn paper_record(signature: &str, market: &MarketPrice, action_type: &str) -> ActionRecord {
+    let block_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .ok();
+    ActionRecord {
+        signature: signature.to_string(),
+        slot: market.slot,
+        block_time,
+        instruction_index: 0,
+        action_type: action_type.to_string(),
+        market_index: Some(market.market_index),
+        perp_market_index: Some(market.market_index),
+        spot_market_index: None,
+        direction: None,
+        base_asset_amount: None,
+        price: None,
+        reduce_only: None,
+        leverage: None,
+        amount: None,
+        token_account: None,
+        token_mint: None,
+        token_amount: None,
+        paper: true,
+    }
+}
+
+fn movement_record(signature: &str, market: &MarketPrice, delta: f64) -> ActionRecord {
+    let action_type = if delta >= 0.0 {
+        "depositIntoIsolatedPerpPosition"
+    } else {
+        "withdrawFromIsolatedPerpPosition"
+    };
+    let amount = (delta.abs() * QUOTE_PRECISION).round() as u64;
+    ActionRecord {
+        spot_market_index: Some(QUOTE_SPOT_MARKET_INDEX),
+        amount: Some(amount),
+        token_amount: Some(amount),
+        ..paper_record(signature, market, action_type)
+    }
+}
+
+fn order_record(
+    signature: &str,
+    market: &MarketPrice,
+    fill: &PaperFill,
+    reduce_only: bool,
+) -> ActionRecord {
+    let direction = if fill.size > 0.0 { "Long" } else { "Short" };
+    ActionRecord {
+        direction: Some(direction.to_string()),
+        base_asset_amount: Some((fill.size.abs() * BASE_PRECISION).round() as u64),
+        price: Some((fill.price * PRICE_PRECISION).round() as u64),
+        reduce_only: Some(reduce_only),
+        ..paper_record(signature, market, "placePerpOrder")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COSTS: FillCosts = FillCosts {
+        slippage_bps: 10.0,
+        fee_bps: 10.0,
+    };
+
+    fn position(size: f64, entry_price: f64, margin: f64) -> PaperPosition {
+        PaperPosition {
+            wallet: "wallet".into(),
+            sub_account_id: 0,
+            market: "SOL-PERP".into(),
+            size,
+            entry_price,
+            margin,
+            realized_pnl: 0.0,
+            fees: 0.0,
+            unrealized_pnl: None,
+            updated_at: 0,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn slippage_moves_price_against_the_taker() {
+        assert_close(COSTS.fill_price(100.0, 2.0), 100.1);
+        assert_close(COSTS.fill_price(100.0, -2.0), 99.9);
+    }
+
+    #[test]
+    fn adding_averages_the_entry_price() {
+        let mut position = position(1.0, 100.0, 50.0);
+        let fill = COSTS.fill(&mut position, 3.0, 120.0, 120.0);
+        assert_close(position.size, 4.0);
+        assert_close(position.entry_price, 115.0);
+        assert_close(fill.fee, 0.36);
+        assert_close(fill.realized_pnl, 0.0);
+        assert_close(position.margin, 50.0 - 0.36);
+        assert_close(position.fees, 0.36);
+    }
+
+    #[test]
+    fn reducing_realizes_pnl_and_keeps_the_entry() {
+        let mut position = position(-4.0, 100.0, 100.0);
+        let fill = COSTS.fill(&mut position, 1.0, 90.0, 90.0);
+        assert_close(position.size, -3.0);
+        assert_close(position.entry_price, 100.0);
+        assert_close(fill.realized_pnl, 10.0);
+        assert_close(position.margin, 100.0 + 10.0 - 0.09);
+        assert_close(position.realized_pnl, 10.0);
+    }
+
+    #[test]
+    fn closing_resets_the_position() {
+        let mut position = position(2.0, 100.0, 100.0);
+        let fill = COSTS.fill(&mut position, -2.0, 95.0, 95.0);
+        assert_eq!(position.size, 0.0);
+        assert_eq!(position.entry_price, 0.0);
+        assert_close(fill.realized_pnl, -10.0);
+    }
+
+    #[test]
+    fn flipping_enters_the_remainder_at_the_fill_price() {
+        let mut position = position(2.0, 100.0, 100.0);
+        let fill = COSTS.fill(&mut position, -5.0, 110.0, 110.0);
+        assert_close(position.size, -3.0);
+        assert_close(position.entry_price, 110.0);
+        assert_close(fill.realized_pnl, 20.0);
+    }
+
+    #[test]
+    fn equity_includes_unrealized_pnl() {
+        let position = position(-2.0, 100.0, 50.0);
+        assert_close(unrealized_pnl(&position, 90.0), 20.0);
+        assert_close(equity(&position, 90.0), 70.0);
+    }
+}
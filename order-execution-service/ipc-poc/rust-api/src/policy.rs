@@ -0,0 +1,710 @@
+use std::{collections::HashMap, str::FromStr};
+
+use borsh::BorshDeserialize;
+use once_cell::sync::Lazy;
+use solana_sdk::{
+    compute_budget, instruction::CompiledInstruction, message::VersionedMessage, pubkey,
+    pubkey::Pubkey, rent::Rent, system_instruction::SystemInstruction, system_program,
+};
+
+use crate::decoder::{anchor_discriminator, MarketType, OrderParams, DEFAULT_DRIFT_PROGRAM};
+
+const TOKEN_PROGRAM: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGqPFXCWuBvf9Ss623VQ5DA");
+const TOKEN_2022_PROGRAM: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+const ASSOCIATED_TOKEN_PROGRAM: Pubkey = pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+const DEFAULT_MAX_PRIORITY_FEE_MICRO_LAMPORTS: u64 = 10_000_000;
+
+const SET_COMPUTE_UNIT_PRICE_TAG: u8 = 3;
+
+// SPL token instruction tags the worker is allowed to emit.
+const TOKEN_INITIALIZE_ACCOUNT: u8 = 1;
+const TOKEN_CLOSE_ACCOUNT: u8 = 9;
+const TOKEN_INITIALIZE_ACCOUNT_2: u8 = 16;
+const TOKEN_SYNC_NATIVE: u8 = 17;
+const TOKEN_INITIALIZE_ACCOUNT_3: u8 = 18;
+
+// Position of `userTokenAccount` in Drift's withdraw and deposit instructions.
+const DRIFT_WITHDRAW_TOKEN_ACCOUNT: usize = 6;
+const DRIFT_DEPOSIT_TOKEN_ACCOUNT: usize = 5;
+/// A Token-2022 account with the immutable-owner extension; plain SPL accounts are 165.
+const MAX_TOKEN_ACCOUNT_SPACE: u64 = 170;
+/// The Drift SDK funds wrapped SOL accounts with a flat 0.01 SOL for rent, which closing
+/// the account in the same transaction refunds.
+const WRAPPED_SOL_RENT_RESERVE: u64 = 10_000_000;
+
+#[derive(Debug, Clone, Copy)]
+enum DriftIx {
+    InitializeUser,
+    InitializeUserStats,
+    Deposit,
+    Withdraw,
+    TransferDeposit,
+    DepositIntoIsolatedPerpPosition,
+    WithdrawFromIsolatedPerpPosition,
+    TransferIsolatedPerpPositionDeposit,
+    PlacePerpOrder,
+    PlaceAndTakePerpOrder,
+    PlaceOrders,
+    ModifyOrder,
+    ModifyOrderByUserId,
+    CancelOrder,
+    CancelOrderByUserId,
+    CancelOrders,
+    CancelOrdersByIds,
+    SettlePnl,
+}
+
+static DRIFT_ALLOWLIST: Lazy<HashMap<[u8; 8], DriftIx>> = Lazy::new(|| {
+    [
+        ("initialize_user", DriftIx::InitializeUser),
+        ("initialize_user_stats", DriftIx::InitializeUserStats),
+        ("deposit", DriftIx::Deposit),
+        ("withdraw", DriftIx::Withdraw),
+        ("transfer_deposit", DriftIx::TransferDeposit),
+        (
+            "deposit_into_isolated_perp_position",
+            DriftIx::DepositIntoIsolatedPerpPosition,
+        ),
+        (
+            "withdraw_from_isolated_perp_position",
+            DriftIx::WithdrawFromIsolatedPerpPosition,
+        ),
+        (
+            "transfer_isolated_perp_position_deposit",
+            DriftIx::TransferIsolatedPerpPositionDeposit,
+        ),
+        ("place_perp_order", DriftIx::PlacePerpOrder),
+        ("place_and_take_perp_order", DriftIx::PlaceAndTakePerpOrder),
+        ("place_orders", DriftIx::PlaceOrders),
+        ("modify_order", DriftIx::ModifyOrder),
+        ("modify_order_by_user_id", DriftIx::ModifyOrderByUserId),
+        ("cancel_order", DriftIx::CancelOrder),
+        ("cancel_order_by_user_id", DriftIx::CancelOrderByUserId),
+        ("cancel_orders", DriftIx::CancelOrders),
+        ("cancel_orders_by_ids", DriftIx::CancelOrdersByIds),
+        ("settle_pnl", DriftIx::SettlePnl),
+    ]
+    .into_iter()
+    .map(|(name, kind)| (anchor_discriminator(name), kind))
+    .collect()
+});
+
+/// Tokens a Drift instruction moves between the wallet and Drift, by account position.
+enum TokenFlow {
+    None,
+    Withdraw { to: usize },
+    Deposit { from: usize, amount: u64 },
+}
+
+/// A token-program-owned account funded from the wallet by the system program.
+struct FundedAccount {
+    index: usize,
+    key: Pubkey,
+    lamports: u64,
+    space: u64,
+}
+
+#[derive(BorshDeserialize)]
+struct SpotAmountArgs {
+    market_index: u16,
+    amount: u64,
+}
+
+#[derive(BorshDeserialize)]
+struct IsolatedAmountArgs {
+    spot_market_index: u16,
+    _perp_market_index: u16,
+    amount: u64,
+}
+
+/// `modify_order` arguments up to the new base size; the remaining fields are not policed.
+/// Enum fields are read by their one-byte variant index.
+#[derive(BorshDeserialize)]
+struct ModifyOrderArgs {
+    _order_id: Option<u32>,
+    _direction: Option<u8>,
+    base_asset_amount: Option<u64>,
+}
+
+#[derive(BorshDeserialize)]
+struct ModifyOrderByUserIdArgs {
+    _user_order_id: u8,
+    _direction: Option<u8>,
+    base_asset_amount: Option<u64>,
+}
+
+/// Per-market ceilings, in the market's native precision. Unset means unlimited.
+#[derive(Debug, Clone, Default)]
+pub struct MarketLimits {
+    default: Option<u64>,
+    by_market: HashMap<u16, u64>,
+}
+
+impl MarketLimits {
+    /// Parses `"<marketIndex>=<amount>,..."`, where `*` sets the limit for unlisted markets.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut limits = Self::default();
+        for entry in spec.split(',').filter(|entry| !entry.trim().is_empty()) {
+            let (market, amount) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("invalid limit entry '{entry}'"))?;
+            let amount: u64 = amount
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid limit amount in '{entry}'"))?;
+            match market.trim() {
+                "*" => limits.default = Some(amount),
+                index => {
+                    let index = index
+                        .parse()
+                        .map_err(|_| format!("invalid market index in '{entry}'"))?;
+                    limits.by_market.insert(index, amount);
+                }
+            }
+        }
+        Ok(limits)
+    }
+
+    fn check(&self, market: u16, amount: u64, what: &str) -> Result<(), String> {
+        match self.by_market.get(&market).copied().or(self.default) {
+            Some(max) if amount > max => Err(format!(
+                "{what} of {amount} on market {market} exceeds limit {max}"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// For amounts whose market is not known: the lowest configured limit applies.
+    fn check_any(&self, amount: u64, what: &str) -> Result<(), String> {
+        let lowest = self.by_market.values().copied().chain(self.default).min();
+        match lowest {
+            Some(max) if amount > max => Err(format!("{what} of {amount} exceeds limit {max}")),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Rules a worker-built transaction must satisfy before the server key signs it.
+#[derive(Debug, Clone)]
+pub struct TxPolicy {
+    drift_program: Pubkey,
+    max_priority_fee_micro_lamports: u64,
+    max_deposit: MarketLimits,
+    max_withdraw: MarketLimits,
+    max_base_size: MarketLimits,
+}
+
+impl Default for TxPolicy {
+    fn default() -> Self {
+        Self::new(Pubkey::from_str(DEFAULT_DRIFT_PROGRAM).expect("valid drift program id"))
+    }
+}
+
+impl TxPolicy {
+    pub fn new(drift_program: Pubkey) -> Self {
+        Self {
+            drift_program,
+            max_priority_fee_micro_lamports: DEFAULT_MAX_PRIORITY_FEE_MICRO_LAMPORTS,
+            max_deposit: MarketLimits::default(),
+            max_withdraw: MarketLimits::default(),
+            max_base_size: MarketLimits::default(),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        let drift_program =
+            std::env::var("DRIFT_PROGRAM_ID").unwrap_or_else(|_| DEFAULT_DRIFT_PROGRAM.to_string());
+        let drift_program = Pubkey::from_str(&drift_program)
+            .map_err(|_| format!("invalid DRIFT_PROGRAM_ID '{drift_program}'"))?;
+        let limits = |name: &str| -> Result<MarketLimits, String> {
+            match std::env::var(name) {
+                Ok(spec) => MarketLimits::parse(&spec).map_err(|err| format!("{name}: {err}")),
+                Err(_) => Ok(MarketLimits::default()),
+            }
+        };
+
+        let mut policy = Self::new(drift_program);
+        if let Ok(value) = std::env::var("TX_POLICY_MAX_PRIORITY_FEE_MICRO_LAMPORTS") {
+            policy.max_priority_fee_micro_lamports = value.trim().parse().map_err(|_| {
+                format!("invalid TX_POLICY_MAX_PRIORITY_FEE_MICRO_LAMPORTS '{value}'")
+            })?;
+        }
+        policy.max_deposit = limits("TX_POLICY_MAX_DEPOSIT")?;
+        policy.max_withdraw = limits("TX_POLICY_MAX_WITHDRAW")?;
+        policy.max_base_size = limits("TX_POLICY_MAX_BASE_SIZE")?;
+        Ok(policy)
+    }
+
+    /// Validates the message for signing by `signer` alone.
+    ///
+    /// Returns the withdraw destinations that are not created in this transaction; the
+    /// caller must confirm on-chain that `signer` owns them. Accounts funded through the
+    /// system program must be initialised to `signer` in the same message and hold no more
+    /// than their rent plus what a Drift deposit takes from them (wrapped SOL).
+    pub fn check(
+        &self,
+        message: &VersionedMessage,
+        signer: &Pubkey,
+    ) -> Result<Vec<Pubkey>, String> {
+        let keys = message.static_account_keys();
+        if keys.first() != Some(signer) {
+            return Err("fee payer is not the server wallet".into());
+        }
+        let signers = message.header().num_required_signatures;
+        if signers != 1 {
+            return Err(format!(
+                "transaction requires {signers} signers, only the server wallet may sign"
+            ));
+        }
+
+        let account = |ix: &CompiledInstruction, position: usize| -> Result<Pubkey, String> {
+            let index = *ix
+                .accounts
+                .get(position)
+                .ok_or("instruction is missing accounts")?;
+            keys.get(usize::from(index))
+                .copied()
+                .ok_or_else(|| "account from an address lookup table cannot be verified".into())
+        };
+
+        let mut created_token_accounts = Vec::new();
+        let mut withdraw_destinations = Vec::new();
+        let mut funded_accounts = Vec::new();
+        let mut deposits: Vec<(Pubkey, u64)> = Vec::new();
+        for (index, ix) in message.instructions().iter().enumerate() {
+            let program = keys
+                .get(usize::from(ix.program_id_index))
+                .copied()
+                .ok_or_else(|| format!("instruction {index} has an unresolvable program"))?;
+            let result = if program == self.drift_program {
+                self.check_drift(ix).and_then(|flow| {
+                    match flow {
+                        TokenFlow::Withdraw { to } => withdraw_destinations.push(account(ix, to)?),
+                        TokenFlow::Deposit { from, amount } => {
+                            deposits.push((account(ix, from)?, amount))
+                        }
+                        TokenFlow::None => {}
+                    }
+                    Ok(())
+                })
+            } else if program == compute_budget::id() {
+                self.check_compute_budget(ix)
+            } else if program == system_program::id() {
+                check_system(ix).and_then(|(lamports, space)| {
+                    funded_accounts.push(FundedAccount {
+                        index,
+                        key: account(ix, 1)?,
+                        lamports,
+                        space,
+                    });
+                    Ok(())
+                })
+            } else if program == TOKEN_PROGRAM || program == TOKEN_2022_PROGRAM {
+                check_token(ix, signer, &account).map(|created| {
+                    created_token_accounts.extend(created);
+                })
+            } else if program == ASSOCIATED_TOKEN_PROGRAM {
+                check_associated_token(ix, signer, &account).map(|created| {
+                    created_token_accounts.push(created);
+                })
+            } else {
+                Err(format!("program {program} is not allowlisted"))
+            };
+            result.map_err(|err| format!("instruction {index}: {err}"))?;
+        }
+
+        for funded in &funded_accounts {
+            check_funded(funded, &created_token_accounts, &deposits)
+                .map_err(|err| format!("instruction {}: {err}", funded.index))?;
+        }
+
+        withdraw_destinations.retain(|key| !created_token_accounts.contains(key));
+        withdraw_destinations.dedup();
+        Ok(withdraw_destinations)
+    }
+
+    /// Returns the tokens the instruction moves in or out of the wallet, if any.
+    fn check_drift(&self, ix: &CompiledInstruction) -> Result<TokenFlow, String> {
+        let Some((disc, mut rest)) = ix.data.split_first_chunk::<8>() else {
+            return Err("drift instruction shorter than its discriminator".into());
+        };
+        let kind = DRIFT_ALLOWLIST
+            .get(disc)
+            .copied()
+            .ok_or("drift instruction is not allowlisted")?;
+        let malformed = |err: std::io::Error| format!("malformed {kind:?} arguments: {err}");
+
+        match kind {
+            DriftIx::Deposit => {
+                let args = SpotAmountArgs::deserialize(&mut rest).map_err(malformed)?;
+                self.max_deposit
+                    .check(args.market_index, args.amount, "deposit")?;
+                return Ok(TokenFlow::Deposit {
+                    from: DRIFT_DEPOSIT_TOKEN_ACCOUNT,
+                    amount: args.amount,
+                });
+            }
+            DriftIx::DepositIntoIsolatedPerpPosition => {
+                let args = IsolatedAmountArgs::deserialize(&mut rest).map_err(malformed)?;
+                self.max_deposit
+                    .check(args.spot_market_index, args.amount, "deposit")?;
+            }
+            DriftIx::Withdraw => {
+                let args = SpotAmountArgs::deserialize(&mut rest).map_err(malformed)?;
+                self.max_withdraw
+                    .check(args.market_index, args.amount, "withdrawal")?;
+                return Ok(TokenFlow::Withdraw {
+                    to: DRIFT_WITHDRAW_TOKEN_ACCOUNT,
+                });
+            }
+            DriftIx::WithdrawFromIsolatedPerpPosition => {
+                let args = IsolatedAmountArgs::deserialize(&mut rest).map_err(malformed)?;
+                self.max_withdraw
+                    .check(args.spot_market_index, args.amount, "withdrawal")?;
+                return Ok(TokenFlow::Withdraw {
+                    to: DRIFT_WITHDRAW_TOKEN_ACCOUNT,
+                });
+            }
+            DriftIx::PlacePerpOrder | DriftIx::PlaceAndTakePerpOrder => {
+                let params = OrderParams::deserialize(&mut rest).map_err(malformed)?;
+                self.check_order(&params)?;
+            }
+            DriftIx::PlaceOrders => {
+                let orders = Vec::<OrderParams>::deserialize(&mut rest).map_err(malformed)?;
+                orders
+                    .iter()
+                    .try_for_each(|params| self.check_order(params))?;
+            }
+            // The order's market is only known on-chain, so a new size is held to every
+            // market's limit.
+            DriftIx::ModifyOrder => {
+                let args = ModifyOrderArgs::deserialize(&mut rest).map_err(malformed)?;
+                if let Some(size) = args.base_asset_amount {
+                    self.max_base_size.check_any(size, "order size")?;
+                }
+            }
+            DriftIx::ModifyOrderByUserId => {
+                let args = ModifyOrderByUserIdArgs::deserialize(&mut rest).map_err(malformed)?;
+                if let Some(size) = args.base_asset_amount {
+                    self.max_base_size.check_any(size, "order size")?;
+                }
+            }
+            // Account setup, transfers between the wallet's own Drift accounts, cancels and
+            // settlement cannot move funds out of the wallet.
+            DriftIx::InitializeUser
+            | DriftIx::InitializeUserStats
+            | DriftIx::TransferDeposit
+            | DriftIx::TransferIsolatedPerpPositionDeposit
+            | DriftIx::CancelOrder
+            | DriftIx::CancelOrderByUserId
+            | DriftIx::CancelOrders
+            | DriftIx::CancelOrdersByIds
+            | DriftIx::SettlePnl => {}
+        }
+        Ok(TokenFlow::None)
+    }
+
+    fn check_order(&self, params: &OrderParams) -> Result<(), String> {
+        if params.market_type != MarketType::Perp {
+            return Err("only perp orders may be placed".into());
+        }
+        self.max_base_size
+            .check(params.market_index, params.base_asset_amount, "order size")
+    }
+
+    fn check_compute_budget(&self, ix: &CompiledInstruction) -> Result<(), String> {
+        if let Some((&SET_COMPUTE_UNIT_PRICE_TAG, price)) = ix.data.split_first() {
+            let price = price
+                .try_into()
+                .map(u64::from_le_bytes)
+                .map_err(|_| "malformed compute unit price")?;
+            if price > self.max_priority_fee_micro_lamports {
+                return Err(format!(
+                    "priority fee of {price} micro-lamports exceeds limit {}",
+                    self.max_priority_fee_micro_lamports
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Only account creation for token accounts (wrapped SOL) is allowed, never plain transfers.
+/// Returns the new account's lamports and space.
+fn check_system(ix: &CompiledInstruction) -> Result<(u64, u64), String> {
+    let instruction: SystemInstruction = bincode::deserialize(&ix.data)
+        .map_err(|err| format!("malformed system instruction: {err}"))?;
+    match instruction {
+        SystemInstruction::CreateAccount {
+            owner,
+            lamports,
+            space,
+        }
+        | SystemInstruction::CreateAccountWithSeed {
+            owner,
+            lamports,
+            space,
+            ..
+        } if owner == TOKEN_PROGRAM || owner == TOKEN_2022_PROGRAM => {
+            if space > MAX_TOKEN_ACCOUNT_SPACE {
+                return Err(format!(
+                    "token account space of {space} exceeds {MAX_TOKEN_ACCOUNT_SPACE}"
+                ));
+            }
+            Ok((lamports, space))
+        }
+        other => Err(format!("system instruction {other:?} is not allowed")),
+    }
+}
+
+/// A funded account must become a token account of the signer, holding its rent plus at
+/// most the amount deposited from it into Drift.
+fn check_funded(
+    funded: &FundedAccount,
+    created_token_accounts: &[Pubkey],
+    deposits: &[(Pubkey, u64)],
+) -> Result<(), String> {
+    if !created_token_accounts.contains(&funded.key) {
+        return Err(format!(
+            "account {} is not initialised as a token account of the server wallet",
+            funded.key
+        ));
+    }
+    let deposited = deposits
+        .iter()
+        .filter(|(key, _)| *key == funded.key)
+        .fold(0u64, |total, (_, amount)| total.saturating_add(*amount));
+    let limit = rent_allowance(funded.space).saturating_add(deposited);
+    if funded.lamports > limit {
+        return Err(format!(
+            "funding of {} lamports for {} exceeds its rent plus deposits of {limit}",
+            funded.lamports, funded.key
+        ));
+    }
+    Ok(())
+}
+
+fn rent_allowance(space: u64) -> u64 {
+    Rent::default()
+        .minimum_balance(space as usize)
+        .max(WRAPPED_SOL_RENT_RESERVE)
+}
+
+/// Returns the token account initialised by the instruction, which must belong to `signer`.
+fn check_token(
+    ix: &CompiledInstruction,
+    signer: &Pubkey,
+    account: &impl Fn(&CompiledInstruction, usize) -> Result<Pubkey, String>,
+) -> Result<Option<Pubkey>, String> {
+    let (tag, rest) = ix.data.split_first().ok_or("empty token instruction")?;
+    match *tag {
+        TOKEN_INITIALIZE_ACCOUNT => {
+            if account(ix, 2)? != *signer {
+                return Err("token account must be owned by the server wallet".into());
+            }
+            Ok(Some(account(ix, 0)?))
+        }
+        TOKEN_INITIALIZE_ACCOUNT_2 | TOKEN_INITIALIZE_ACCOUNT_3 => {
+            let owner = rest
+                .get(..32)
+                .and_then(|bytes| Pubkey::try_from(bytes).ok())
+                .ok_or("malformed token account owner")?;
+            if owner != *signer {
+                return Err("token account must be owned by the server wallet".into());
+            }
+            Ok(Some(account(ix, 0)?))
+        }
+        TOKEN_CLOSE_ACCOUNT => {
+            if account(ix, 1)? != *signer {
+                return Err("closed token account must refund the server wallet".into());
+            }
+            Ok(None)
+        }
+        TOKEN_SYNC_NATIVE => Ok(None),
+        other => Err(format!("token instruction {other} is not allowed")),
+    }
+}
+
+/// Returns the associated token account, whose address the ATA program derives from `signer`.
+fn check_associated_token(
+    ix: &CompiledInstruction,
+    signer: &Pubkey,
+    account: &impl Fn(&CompiledInstruction, usize) -> Result<Pubkey, String>,
+) -> Result<Pubkey, String> {
+    // 0 = Create, 1 = CreateIdempotent; an empty payload is the legacy Create.
+    if !matches!(ix.data.first(), None | Some(0) | Some(1)) {
+        return Err("associated token instruction is not allowed".into());
+    }
+    if account(ix, 2)? != *signer {
+        return Err("associated token account must belong to the server wallet".into());
+    }
+    account(ix, 1)
+}
+
+/// Checks that raw account data is an SPL token account owned by `signer`.
+pub fn verify_token_owner(
+    key: &Pubkey,
+    program: &Pubkey,
+    data: &[u8],
+    signer: &Pubkey,
+) -> Result<(), String> {
+    if *program != TOKEN_PROGRAM && *program != TOKEN_2022_PROGRAM {
+        return Err(format!("withdraw destination {key} is not a token account"));
+    }
+    let owner = data
+        .get(32..64)
+        .and_then(|bytes| Pubkey::try_from(bytes).ok())
+        .ok_or_else(|| format!("withdraw destination {key} is not a token account"))?;
+    if owner != *signer {
+        return Err(format!(
+            "withdraw destination {key} is not owned by the server wallet"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{
+        instruction::{AccountMeta, Instruction},
+        message::Message,
+        system_instruction,
+    };
+
+    use super::*;
+
+    const DEPOSIT: u64 = 5_000_000_000;
+
+    /// Wraps `lamports` of SOL into a seeded account and deposits `DEPOSIT` of it into Drift.
+    fn wrap_and_deposit(lamports: u64, initialise: bool) -> (VersionedMessage, Pubkey) {
+        let signer = Pubkey::new_unique();
+        let drift = Pubkey::from_str(DEFAULT_DRIFT_PROGRAM).unwrap();
+        let wsol = Pubkey::create_with_seed(&signer, "wsol", &TOKEN_PROGRAM).unwrap();
+        let mut instructions = vec![system_instruction::create_account_with_seed(
+            &signer,
+            &wsol,
+            &signer,
+            "wsol",
+            lamports,
+            165,
+            &TOKEN_PROGRAM,
+        )];
+        if initialise {
+            let mut data = vec![TOKEN_INITIALIZE_ACCOUNT_3];
+            data.extend_from_slice(signer.as_ref());
+            instructions.push(Instruction {
+                program_id: TOKEN_PROGRAM,
+                accounts: vec![
+                    AccountMeta::new(wsol, false),
+                    AccountMeta::new_readonly(Pubkey::new_unique(), false),
+                ],
+                data,
+            });
+        }
+        let mut data = anchor_discriminator("deposit").to_vec();
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&DEPOSIT.to_le_bytes());
+        data.push(0);
+        let mut accounts: Vec<AccountMeta> = (0..DRIFT_DEPOSIT_TOKEN_ACCOUNT)
+            .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+            .collect();
+        accounts[3] = AccountMeta::new_readonly(signer, true);
+        accounts.push(AccountMeta::new(wsol, false));
+        accounts.push(AccountMeta::new_readonly(TOKEN_PROGRAM, false));
+        instructions.push(Instruction {
+            program_id: drift,
+            accounts,
+            data,
+        });
+        let message = Message::new(&instructions, Some(&signer));
+        (VersionedMessage::Legacy(message), signer)
+    }
+
+    #[test]
+    fn parses_market_and_default_limits() {
+        let limits = MarketLimits::parse(" 0=100, 1 = 50 ,*=10,").unwrap();
+        assert_eq!(limits.default, Some(10));
+        assert_eq!(limits.by_market.get(&0), Some(&100));
+        assert_eq!(limits.by_market.get(&1), Some(&50));
+
+        assert!(MarketLimits::parse("0").is_err());
+        assert!(MarketLimits::parse("0=ten").is_err());
+        assert!(MarketLimits::parse("x=10").is_err());
+        assert!(MarketLimits::parse("").unwrap().default.is_none());
+    }
+
+    #[test]
+    fn market_limit_overrides_default() {
+        let limits = MarketLimits::parse("0=100,*=10").unwrap();
+        assert!(limits.check(0, 100, "deposit").is_ok());
+        assert!(limits.check(0, 101, "deposit").is_err());
+        assert!(limits.check(7, 10, "deposit").is_ok());
+        assert_eq!(
+            limits.check(7, 11, "deposit").unwrap_err(),
+            "deposit of 11 on market 7 exceeds limit 10"
+        );
+    }
+
+    #[test]
+    fn unset_limits_allow_anything() {
+        let limits = MarketLimits::default();
+        assert!(limits.check(3, u64::MAX, "withdrawal").is_ok());
+        assert!(limits.check_any(u64::MAX, "order size").is_ok());
+    }
+
+    #[test]
+    fn unknown_market_is_held_to_lowest_limit() {
+        let limits = MarketLimits::parse("0=100,1=40,*=70").unwrap();
+        assert!(limits.check_any(40, "order size").is_ok());
+        assert!(limits.check_any(41, "order size").is_err());
+
+        let limits = MarketLimits::parse("0=100,1=40,*=20").unwrap();
+        assert!(limits.check_any(21, "order size").is_err());
+    }
+
+    #[test]
+    fn priority_fee_is_capped() {
+        let policy = TxPolicy::default();
+        let ix = |price: u64| CompiledInstruction {
+            program_id_index: 0,
+            accounts: vec![],
+            data: [&[SET_COMPUTE_UNIT_PRICE_TAG][..], &price.to_le_bytes()].concat(),
+        };
+        assert!(policy
+            .check_compute_budget(&ix(DEFAULT_MAX_PRIORITY_FEE_MICRO_LAMPORTS))
+            .is_ok());
+        assert!(policy
+            .check_compute_budget(&ix(DEFAULT_MAX_PRIORITY_FEE_MICRO_LAMPORTS + 1))
+            .is_err());
+        // Compute unit limits are not policed.
+        let limit = CompiledInstruction {
+            program_id_index: 0,
+            accounts: vec![],
+            data: vec![2, 0, 0, 0, 0],
+        };
+        assert!(policy.check_compute_budget(&limit).is_ok());
+    }
+
+    #[test]
+    fn wrapped_sol_may_hold_rent_plus_deposit() {
+        let rent = rent_allowance(165);
+        let (message, signer) = wrap_and_deposit(rent + DEPOSIT, true);
+        assert_eq!(TxPolicy::default().check(&message, &signer), Ok(vec![]));
+    }
+
+    #[test]
+    fn rejects_funding_beyond_rent_plus_deposit() {
+        let rent = rent_allowance(165);
+        let (message, signer) = wrap_and_deposit(rent + DEPOSIT + 1, true);
+        let err = TxPolicy::default().check(&message, &signer).unwrap_err();
+        assert!(err.contains("exceeds its rent plus deposits"), "{err}");
+    }
+
+    #[test]
+    fn rejects_funded_account_not_initialised_to_signer() {
+        let rent = rent_allowance(165);
+        let (message, signer) = wrap_and_deposit(rent, false);
+        let err = TxPolicy::default().check(&message, &signer).unwrap_err();
+        assert!(err.contains("not initialised as a token account"), "{err}");
+    }
+}
@@ -0,0 +1,795 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio_postgres::Client;
+use tracing::{info, warn};
+
+use crate::{
+    db,
+    ipc::{IpcError, TsIpc},
+    wallets::market_key,
+};
+
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum RiskError {
+    #[error("invalid risk configuration: {0}")]
+    Config(String),
+    #[error(transparent)]
+    Worker(#[from] IpcError),
+    #[error("invalid risk snapshot: {0}")]
+    Snapshot(String),
+    #[error("risk database error: {0}")]
+    Database(anyhow::Error),
+    #[error("{}", .0.message)]
+    Breach(RiskBreach),
+}
+
+/// A limit a request would exceed.
+#[derive(Debug)]
+pub struct RiskBreach {
+    pub code: &'static str,
+    pub message: String,
+    /// `limit` and the value that exceeded it, plus any market.
+    pub details: Value,
+}
+
+/// Limits for one wallet. Unset fields are unlimited.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RiskLimits {
+    /// Total notional across all perp positions after an order.
+    pub max_notional: Option<f64>,
+    pub max_leverage: Option<f64>,
+    pub max_open_positions: Option<usize>,
+    /// Loss since the first check of the UTC day, settled plus unrealized.
+    pub daily_loss_limit: Option<f64>,
+    pub max_withdrawal_per_day: Option<f64>,
+    pub liquidation_cooldown_secs: Option<u64>,
+    /// Overrides keyed by perp market.
+    #[serde(default)]
+    pub markets: HashMap<String, MarketLimits>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct MarketLimits {
+    /// Notional of the position in this market after an order.
+    pub max_notional: Option<f64>,
+    pub max_leverage: Option<f64>,
+}
+
+impl RiskLimits {
+    /// `self` with unset fields and missing markets taken from `fallback`.
+    fn or(mut self, fallback: &RiskLimits) -> RiskLimits {
+        self.max_notional = self.max_notional.or(fallback.max_notional);
+        self.max_leverage = self.max_leverage.or(fallback.max_leverage);
+        self.max_open_positions = self.max_open_positions.or(fallback.max_open_positions);
+        self.daily_loss_limit = self.daily_loss_limit.or(fallback.daily_loss_limit);
+        self.max_withdrawal_per_day = self
+            .max_withdrawal_per_day
+            .or(fallback.max_withdrawal_per_day);
+        self.liquidation_cooldown_secs = self
+            .liquidation_cooldown_secs
+            .or(fallback.liquidation_cooldown_secs);
+        for (market, limits) in &fallback.markets {
+            let entry = self.markets.entry(market.clone()).or_default();
+            entry.max_notional = entry.max_notional.or(limits.max_notional);
+            entry.max_leverage = entry.max_leverage.or(limits.max_leverage);
+        }
+        self
+    }
+
+    fn normalise(mut self, name: &str) -> Result<Self, RiskError> {
+        let positive = |field: &str, value: Option<f64>| match value {
+            Some(value) if !value.is_finite() || value <= 0.0 => Err(RiskError::Config(format!(
+                "{name}.{field} must be a positive number"
+            ))),
+            _ => Ok(()),
+        };
+        positive("maxNotional", self.max_notional)?;
+        positive("maxLeverage", self.max_leverage)?;
+        positive("dailyLossLimit", self.daily_loss_limit)?;
+        positive("maxWithdrawalPerDay", self.max_withdrawal_per_day)?;
+        for (market, limits) in &self.markets {
+            positive(
+                &format!("markets.{market}.maxNotional"),
+                limits.max_notional,
+            )?;
+            positive(
+                &format!("markets.{market}.maxLeverage"),
+                limits.max_leverage,
+            )?;
+        }
+        self.markets = self
+            .markets
+            .into_iter()
+            .map(|(market, limits)| (market_key(&market), limits))
+            .collect();
+        Ok(self)
+    }
+
+    fn market_max_notional(&self, market: &str) -> Option<f64> {
+        self.markets
+            .get(market)
+            .and_then(|limits| limits.max_notional)
+    }
+
+    fn market_max_leverage(&self, market: &str) -> Option<f64> {
+        self.markets
+            .get(market)
+            .and_then(|limits| limits.max_leverage)
+            .or(self.max_leverage)
+    }
+
+    fn limits_opens(&self) -> bool {
+        self.max_notional.is_some()
+            || self.max_leverage.is_some()
+            || self.max_open_positions.is_some()
+            || self.daily_loss_limit.is_some()
+            || self.liquidation_cooldown_secs.is_some()
+            || !self.markets.is_empty()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RiskConfigFile {
+    #[serde(default)]
+    default: RiskLimits,
+    /// Keyed by wallet label or public key.
+    #[serde(default)]
+    wallets: HashMap<String, RiskLimits>,
+}
+
+/// Who a checked request acts for.
+pub struct RiskContext<'a> {
+    pub wallet: &'a str,
+    pub wallet_label: Option<&'a str>,
+    pub sub_account_id: u16,
+    pub route: &'a str,
+    /// Simulations are checked, but withdrawals they would make are not counted.
+    pub dry_run: bool,
+}
+
+/// The part of a request the limits apply to. Closes and deposits only reduce risk and are
+/// never checked.
+pub enum RiskOrder<'a> {
+    Open(OpenOrder<'a>),
+    /// Opens sent together, checked as if they had all filled.
+    Opens(Vec<OpenOrder<'a>>),
+    Withdraw {
+        market: &'a str,
+        amount: f64,
+    },
+}
+
+pub struct OpenOrder<'a> {
+    pub market: &'a str,
+    /// Signed base amount, negative for shorts.
+    pub size: f64,
+    pub leverage: f64,
+    pub margin: f64,
+    /// Valued at the higher of this and the oracle price.
+    pub limit_price: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RiskSnapshot {
+    positions: Vec<SnapshotPosition>,
+    settled_perp_pnl: f64,
+    next_liquidation_id: u16,
+    being_liquidated: bool,
+    /// Oracle prices of the requested markets, keyed as requested.
+    #[serde(default)]
+    market_prices: HashMap<String, f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotPosition {
+    market: String,
+    size: f64,
+    notional: f64,
+    isolated_margin: f64,
+    unrealized_pnl: f64,
+}
+
+/// Per-wallet limits on opens and margin withdrawals, loaded from `RISK_CONFIG`.
+#[derive(Clone)]
+pub struct RiskEngine {
+    ipc: TsIpc,
+    db: Arc<Client>,
+    default: Arc<RiskLimits>,
+    wallets: Arc<HashMap<String, RiskLimits>>,
+}
+
+impl RiskEngine {
+    pub fn new(
+        ipc: TsIpc,
+        db: Arc<Client>,
+        default: RiskLimits,
+        wallets: HashMap<String, RiskLimits>,
+    ) -> Result<Self, RiskError> {
+        let default = default.normalise("default")?;
+        let wallets = wallets
+            .into_iter()
+            .map(|(wallet, limits)| {
+                let limits = limits.normalise(&wallet)?.or(&default);
+                Ok((wallet.trim().to_string(), limits))
+            })
+            .collect::<Result<_, RiskError>>()?;
+        Ok(Self {
+            ipc,
+            db,
+            default: Arc::new(default),
+            wallets: Arc::new(wallets),
+        })
+    }
+
+    /// Reads the JSON file named by `RISK_CONFIG`; without it every limit is off.
+    pub fn from_env(ipc: TsIpc, db: Arc<Client>) -> Result<Self, RiskError> {
+        let Ok(path) = std::env::var("RISK_CONFIG") else {
+            return Self::new(ipc, db, RiskLimits::default(), HashMap::new());
+        };
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|err| RiskError::Config(format!("failed to read {path}: {err}")))?;
+        let file: RiskConfigFile = serde_json::from_str(&contents)
+            .map_err(|err| RiskError::Config(format!("invalid {path}: {err}")))?;
+        let engine = Self::new(ipc, db, file.default, file.wallets)?;
+        info!(
+            path = %path,
+            wallets = engine.wallets.len(),
+            "loaded risk limits"
+        );
+        Ok(engine)
+    }
+
+    /// Limits for a wallet, by label first and then public key.
+    pub fn limits_for(&self, wallet: &str, label: Option<&str>) -> &RiskLimits {
+        label
+            .and_then(|label| self.wallets.get(label))
+            .or_else(|| self.wallets.get(wallet.trim()))
+            .unwrap_or(&self.default)
+    }
+
+    /// Rejects `order` with [`RiskError::Breach`] when it would exceed a limit, after
+    /// logging the breach and recording it in `risk_audit_events`.
+    pub async fn check(
+        &self,
+        ctx: &RiskContext<'_>,
+        order: &RiskOrder<'_>,
+    ) -> Result<(), RiskError> {
+        match self.breach(ctx, order, true).await? {
+            None => Ok(()),
+            Some(breach) => {
+                self.record_breach(ctx, &breach).await;
+                Err(RiskError::Breach(breach))
+            }
+        }
+    }
+
+    /// The limit `order` would exceed, if any, leaving the database untouched: no audit
+    /// event, daily PnL baseline, liquidation observation or withdrawal is stored.
+    pub async fn evaluate(
+        &self,
+        ctx: &RiskContext<'_>,
+        order: &RiskOrder<'_>,
+    ) -> Result<Option<RiskBreach>, RiskError> {
+        self.breach(ctx, order, false).await
+    }
+
+    async fn breach(
+        &self,
+        ctx: &RiskContext<'_>,
+        order: &RiskOrder<'_>,
+        record: bool,
+    ) -> Result<Option<RiskBreach>, RiskError> {
+        let limits = self.limits_for(ctx.wallet, ctx.wallet_label);
+        let outcome = match order {
+            RiskOrder::Open(open) => {
+                if !limits.limits_opens() {
+                    return Ok(None);
+                }
+                self.check_opens(ctx, limits, std::slice::from_ref(open), record)
+                    .await?
+            }
+            RiskOrder::Opens(opens) => {
+                if !limits.limits_opens() || opens.is_empty() {
+                    return Ok(None);
+                }
+                self.check_opens(ctx, limits, opens, record).await?
+            }
+            RiskOrder::Withdraw { market, amount } => {
+                let Some(limit) = limits.max_withdrawal_per_day else {
+                    return Ok(None);
+                };
+                self.check_withdrawal(ctx, limit, market, *amount, record)
+                    .await?
+            }
+        };
+        Ok(outcome.err())
+    }
+
+    async fn check_opens(
+        &self,
+        ctx: &RiskContext<'_>,
+        limits: &RiskLimits,
+        orders: &[OpenOrder<'_>],
+        record: bool,
+    ) -> Result<Result<(), RiskBreach>, RiskError> {
+        let mut markets: Vec<&str> = orders.iter().map(|order| order.market).collect();
+        markets.sort_unstable();
+        markets.dedup();
+        let args = json!({
+            "wallet": ctx.wallet,
+            "subAccountId": ctx.sub_account_id,
+            "markets": markets,
+        });
+        let snapshot: RiskSnapshot = serde_json::from_value(
+            self.ipc
+                .call("getRiskSnapshot", args, SNAPSHOT_TIMEOUT)
+                .await?,
+        )
+        .map_err(|err| RiskError::Snapshot(err.to_string()))?;
+
+        if let Some(cooldown) = limits.liquidation_cooldown_secs {
+            let (client, wallet, sub) = (self.db.as_ref(), ctx.wallet, ctx.sub_account_id);
+            let (next_id, being) = (snapshot.next_liquidation_id, snapshot.being_liquidated);
+            let since = if record {
+                db::observe_liquidations(client, wallet, sub, next_id, being).await
+            } else {
+                db::peek_liquidations(client, wallet, sub, next_id, being).await
+            }
+            .map_err(RiskError::Database)?;
+            if let Some(since) = since.filter(|since| *since < cooldown as f64) {
+                let remaining = (cooldown as f64 - since).ceil() as u64;
+                return Ok(Err(RiskBreach {
+                    code: "RISK_LIQUIDATION_COOLDOWN",
+                    message: format!("wallet was liquidated; opens resume in {remaining}s"),
+                    details: json!({ "limit": cooldown, "retryAfterSecs": remaining }),
+                }));
+            }
+        }
+
+        if let Some(limit) = limits.daily_loss_limit {
+            let current = snapshot.current_pnl();
+            let opening = if record {
+                db::daily_opening_pnl(self.db.as_ref(), ctx.wallet, ctx.sub_account_id, current)
+                    .await
+            } else {
+                db::fetch_daily_opening_pnl(self.db.as_ref(), ctx.wallet, ctx.sub_account_id)
+                    .await
+                    .map(|opening| opening.unwrap_or(current))
+            }
+            .map_err(RiskError::Database)?;
+            if let Err(breach) = check_daily_loss(limit, opening, current) {
+                return Ok(Err(breach));
+            }
+        }
+
+        check_positions(limits, &snapshot, orders)
+    }
+
+    /// Counts the withdrawal towards today's total as soon as it passes, so concurrent
+    /// requests cannot both slip under the limit. Withdrawals that later fail still count.
+    async fn check_withdrawal(
+        &self,
+        ctx: &RiskContext<'_>,
+        limit: f64,
+        market: &str,
+        amount: f64,
+        record: bool,
+    ) -> Result<Result<(), RiskBreach>, RiskError> {
+        let (withdrawn, accepted) = if ctx.dry_run || !record {
+            let withdrawn = db::withdrawn_today(self.db.as_ref(), ctx.wallet, ctx.sub_account_id)
+                .await
+                .map_err(RiskError::Database)?;
+            (withdrawn, withdrawn + amount <= limit)
+        } else {
+            db::reserve_withdrawal(
+                self.db.as_ref(),
+                ctx.wallet,
+                ctx.sub_account_id,
+                &market_key(market),
+                amount,
+                limit,
+            )
+            .await
+            .map_err(RiskError::Database)?
+        };
+        if accepted {
+            return Ok(Ok(()));
+        }
+        Ok(Err(RiskBreach {
+            code: "RISK_DAILY_WITHDRAWAL_LIMIT",
+            message: format!(
+                "withdrawing {amount} would take today's withdrawals past the limit of {limit}"
+            ),
+            details: json!({
+                "limit": limit,
+                "withdrawnToday": withdrawn,
+                "amount": amount,
+            }),
+        }))
+    }
+
+    async fn record_breach(&self, ctx: &RiskContext<'_>, breach: &RiskBreach) {
+        warn!(
+            wallet = ctx.wallet,
+            wallet_label = ctx.wallet_label,
+            sub_account_id = ctx.sub_account_id,
+            route = ctx.route,
+            code = breach.code,
+            details = %breach.details,
+            "risk limit breached: {}",
+            breach.message
+        );
+        let event = db::NewRiskEvent {
+            wallet: ctx.wallet,
+            wallet_label: ctx.wallet_label,
+            sub_account_id: ctx.sub_account_id,
+            route: ctx.route,
+            code: breach.code,
+            message: &breach.message,
+            details: &breach.details,
+        };
+        if let Err(err) = db::insert_risk_event(self.db.as_ref(), &event).await {
+            warn!(
+                ?err,
+                code = breach.code,
+                "failed to record risk audit event"
+            );
+        }
+    }
+}
+
+impl RiskSnapshot {
+    /// Settled plus unrealized PnL.
+    fn current_pnl(&self) -> f64 {
+        self.settled_perp_pnl
+            + self
+                .positions
+                .iter()
+                .map(|position| position.unrealized_pnl)
+                .sum::<f64>()
+    }
+}
+
+fn check_daily_loss(limit: f64, opening: f64, current: f64) -> Result<(), RiskBreach> {
+    let loss = opening - current;
+    if loss >= limit {
+        return Err(RiskBreach {
+            code: "RISK_DAILY_LOSS_LIMIT",
+            message: format!("daily loss {loss:.2} has reached the limit of {limit}"),
+            details: json!({ "limit": limit, "loss": loss }),
+        });
+    }
+    Ok(())
+}
+
+/// One market's share of the orders being checked.
+struct MarketOpen {
+    key: String,
+    size: f64,
+    margin: f64,
+    leverage: f64,
+    price: f64,
+}
+
+/// The position count, leverage and notional limits for `orders` placed together, as if
+/// every one of them filled. Each market is valued at the higher of its oracle price and
+/// the highest limit price among its orders.
+fn check_positions(
+    limits: &RiskLimits,
+    snapshot: &RiskSnapshot,
+    orders: &[OpenOrder<'_>],
+) -> Result<Result<(), RiskBreach>, RiskError> {
+    let mut opens: Vec<MarketOpen> = Vec::new();
+    for order in orders {
+        let oracle = snapshot
+            .market_prices
+            .get(order.market)
+            .copied()
+            .filter(|price| price.is_finite() && *price > 0.0)
+            .ok_or_else(|| RiskError::Snapshot(format!("no oracle price for {}", order.market)))?;
+        let price = order.limit_price.map_or(oracle, |limit| limit.max(oracle));
+        let key = market_key(order.market);
+        match opens.iter_mut().find(|open| open.key == key) {
+            Some(open) => {
+                open.size += order.size;
+                open.margin += order.margin;
+                open.leverage = open.leverage.max(order.leverage);
+                open.price = open.price.max(price);
+            }
+            None => opens.push(MarketOpen {
+                key,
+                size: order.size,
+                margin: order.margin,
+                leverage: order.leverage,
+                price,
+            }),
+        }
+    }
+    let existing = |key: &str| {
+        snapshot
+            .positions
+            .iter()
+            .find(|position| market_key(&position.market) == key)
+    };
+
+    if let Some(limit) = limits.max_open_positions {
+        let open_positions = snapshot.positions.len();
+        let new_markets: Vec<&str> = opens
+            .iter()
+            .filter(|open| existing(&open.key).is_none())
+            .map(|open| open.key.as_str())
+            .collect();
+        if !new_markets.is_empty() && open_positions + new_markets.len() > limit {
+            // The first market that does not fit.
+            let market = new_markets[limit.saturating_sub(open_positions)];
+            return Ok(Err(RiskBreach {
+                code: "RISK_MAX_OPEN_POSITIONS",
+                message: format!("wallet already has the maximum of {limit} open positions"),
+                details: json!({
+                    "limit": limit,
+                    "openPositions": open_positions,
+                    "market": market,
+                }),
+            }));
+        }
+    }
+
+    let mut opened_notional = 0.0;
+    for open in &opens {
+        let key = &open.key;
+        let position = existing(key);
+        let position_notional =
+            (position.map_or(0.0, |position| position.size) + open.size).abs() * open.price;
+        opened_notional += position_notional;
+        if let Some(limit) = limits.market_max_leverage(key) {
+            let position_margin =
+                position.map_or(0.0, |position| position.isolated_margin) + open.margin;
+            let effective = open.leverage.max(position_notional / position_margin);
+            if effective > limit {
+                return Ok(Err(RiskBreach {
+                    code: "RISK_MAX_LEVERAGE",
+                    message: format!("leverage {effective:.2} exceeds the {key} limit of {limit}"),
+                    details: json!({ "limit": limit, "leverage": effective, "market": key }),
+                }));
+            }
+        }
+        if let Some(limit) = limits.market_max_notional(key) {
+            if position_notional > limit {
+                return Ok(Err(RiskBreach {
+                    code: "RISK_MAX_NOTIONAL",
+                    message: format!(
+                        "{key} notional {position_notional:.2} exceeds the limit of {limit}"
+                    ),
+                    details: json!({
+                        "limit": limit,
+                        "notional": position_notional,
+                        "market": key,
+                    }),
+                }));
+            }
+        }
+    }
+
+    if let Some(limit) = limits.max_notional {
+        let others: f64 = snapshot
+            .positions
+            .iter()
+            .filter(|position| {
+                let key = market_key(&position.market);
+                !opens.iter().any(|open| open.key == key)
+            })
+            .map(|position| position.notional)
+            .sum();
+        let total = others + opened_notional;
+        if total > limit {
+            return Ok(Err(RiskBreach {
+                code: "RISK_MAX_NOTIONAL",
+                message: format!("total notional {total:.2} exceeds the limit of {limit}"),
+                details: json!({ "limit": limit, "notional": total }),
+            }));
+        }
+    }
+    Ok(Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(value: Value) -> RiskLimits {
+        serde_json::from_value::<RiskLimits>(value)
+            .unwrap()
+            .normalise("test")
+            .unwrap()
+    }
+
+    fn snapshot(positions: Value, prices: Value) -> RiskSnapshot {
+        serde_json::from_value(json!({
+            "positions": positions,
+            "settledPerpPnl": 0.0,
+            "nextLiquidationId": 1,
+            "beingLiquidated": false,
+            "marketPrices": prices,
+        }))
+        .unwrap()
+    }
+
+    /// An isolated open at `leverage`, every market priced at 100.
+    fn open(market: &str, size: f64, leverage: f64) -> OpenOrder<'_> {
+        OpenOrder {
+            market,
+            size,
+            leverage,
+            margin: size.abs() * 100.0 / leverage,
+            limit_price: None,
+        }
+    }
+
+    fn breach_code(
+        limits: &RiskLimits,
+        snapshot: &RiskSnapshot,
+        orders: &[OpenOrder<'_>],
+    ) -> Option<&'static str> {
+        check_positions(limits, snapshot, orders)
+            .unwrap()
+            .err()
+            .map(|breach| breach.code)
+    }
+
+    #[test]
+    fn daily_loss_breaches_at_limit() {
+        assert!(check_daily_loss(100.0, 1_000.0, 901.0).is_ok());
+        assert!(check_daily_loss(100.0, 1_000.0, 1_200.0).is_ok());
+        let breach = check_daily_loss(100.0, 1_000.0, 900.0).unwrap_err();
+        assert_eq!(breach.code, "RISK_DAILY_LOSS_LIMIT");
+        assert_eq!(breach.details["loss"], 100.0);
+    }
+
+    #[test]
+    fn max_notional_counts_other_positions() {
+        let limits = limits(json!({ "maxNotional": 10_000.0 }));
+        let snapshot = snapshot(
+            json!([{
+                "market": "ETH-PERP",
+                "size": 2.0,
+                "notional": 6_000.0,
+                "isolatedMargin": 0.0,
+                "unrealizedPnl": 0.0,
+            }]),
+            json!({ "SOL-PERP": 100.0 }),
+        );
+        assert_eq!(
+            breach_code(&limits, &snapshot, &[open("SOL-PERP", 40.0, 1.0)]),
+            None
+        );
+        assert_eq!(
+            breach_code(&limits, &snapshot, &[open("SOL-PERP", 41.0, 1.0)]),
+            Some("RISK_MAX_NOTIONAL")
+        );
+    }
+
+    #[test]
+    fn orders_in_one_market_are_checked_together() {
+        let limits = limits(json!({ "markets": { "SOL-PERP": { "maxNotional": 5_000.0 } } }));
+        let snapshot = snapshot(json!([]), json!({ "SOL-PERP": 100.0, "sol": 100.0 }));
+        assert_eq!(
+            breach_code(&limits, &snapshot, &[open("SOL-PERP", 30.0, 1.0)]),
+            None
+        );
+        assert_eq!(
+            breach_code(
+                &limits,
+                &snapshot,
+                &[open("SOL-PERP", 30.0, 1.0), open("sol", 30.0, 1.0)]
+            ),
+            Some("RISK_MAX_NOTIONAL")
+        );
+    }
+
+    #[test]
+    fn reducing_an_existing_position_lowers_its_notional() {
+        let limits = limits(json!({ "markets": { "SOL": { "maxNotional": 5_000.0 } } }));
+        let snapshot = snapshot(
+            json!([{
+                "market": "SOL-PERP",
+                "size": 60.0,
+                "notional": 6_000.0,
+                "isolatedMargin": 0.0,
+                "unrealizedPnl": 0.0,
+            }]),
+            json!({ "SOL-PERP": 100.0 }),
+        );
+        assert_eq!(
+            breach_code(&limits, &snapshot, &[open("SOL-PERP", -20.0, 1.0)]),
+            None
+        );
+        assert_eq!(
+            breach_code(&limits, &snapshot, &[open("SOL-PERP", 1.0, 1.0)]),
+            Some("RISK_MAX_NOTIONAL")
+        );
+    }
+
+    #[test]
+    fn limit_price_above_oracle_values_the_order() {
+        let limits = limits(json!({ "maxNotional": 1_000.0 }));
+        let snapshot = snapshot(json!([]), json!({ "SOL-PERP": 100.0 }));
+        let mut order = open("SOL-PERP", 10.0, 1.0);
+        assert_eq!(
+            breach_code(&limits, &snapshot, std::slice::from_ref(&order)),
+            None
+        );
+        order.limit_price = Some(101.0);
+        assert_eq!(
+            breach_code(&limits, &snapshot, &[order]),
+            Some("RISK_MAX_NOTIONAL")
+        );
+    }
+
+    #[test]
+    fn market_leverage_overrides_default() {
+        let limits = limits(json!({
+            "maxLeverage": 5.0,
+            "markets": { "BTC-PERP": { "maxLeverage": 2.0 } },
+        }));
+        let snapshot = snapshot(json!([]), json!({ "SOL-PERP": 100.0, "BTC-PERP": 100.0 }));
+        assert_eq!(
+            breach_code(&limits, &snapshot, &[open("SOL-PERP", 1.0, 5.0)]),
+            None
+        );
+        assert_eq!(
+            breach_code(&limits, &snapshot, &[open("SOL-PERP", 1.0, 6.0)]),
+            Some("RISK_MAX_LEVERAGE")
+        );
+        assert_eq!(
+            breach_code(&limits, &snapshot, &[open("BTC-PERP", 1.0, 3.0)]),
+            Some("RISK_MAX_LEVERAGE")
+        );
+    }
+
+    #[test]
+    fn only_new_markets_count_towards_open_positions() {
+        let limits = limits(json!({ "maxOpenPositions": 2 }));
+        let snapshot = snapshot(
+            json!([{
+                "market": "SOL-PERP",
+                "size": 1.0,
+                "notional": 100.0,
+                "isolatedMargin": 0.0,
+                "unrealizedPnl": 0.0,
+            }]),
+            json!({ "SOL-PERP": 100.0, "ETH-PERP": 100.0, "BTC-PERP": 100.0 }),
+        );
+        let orders = [open("SOL-PERP", 1.0, 1.0), open("ETH-PERP", 1.0, 1.0)];
+        assert_eq!(breach_code(&limits, &snapshot, &orders), None);
+
+        let orders = [open("ETH-PERP", 1.0, 1.0), open("BTC-PERP", 1.0, 1.0)];
+        let breach = check_positions(&limits, &snapshot, &orders)
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(breach.code, "RISK_MAX_OPEN_POSITIONS");
+        assert_eq!(breach.details["market"], "BTC");
+    }
+
+    #[test]
+    fn missing_oracle_price_is_an_error() {
+        let snapshot = snapshot(json!([]), json!({}));
+        assert!(matches!(
+            check_positions(
+                &RiskLimits::default(),
+                &snapshot,
+                &[open("SOL-PERP", 1.0, 1.0)]
+            ),
+            Err(RiskError::Snapshot(_))
+        ));
+    }
+}
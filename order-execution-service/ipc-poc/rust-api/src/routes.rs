@@ -1,60 +1,193 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
 use axum::{
     extract::{OriginalUri, Path, Query, State},
     http::{StatusCode, Uri},
-    response::{IntoResponse, Response},
+    middleware,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     routing::{get, post},
     Json, Router,
 };
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use futures::stream::{self, Stream};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::mpsc;
 use tokio_postgres::Client;
 use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 
 use crate::{
+    auth::{self, ApiKey, ApiKeys, CreatedApiKey, Scope},
     db,
-    decoder::{ActionRecord, DriftDecoder},
-    executor::ExecutorError,
+    decoder::{drift_error_from_tx_error, ActionRecord, DriftDecoder},
+    executor::{ordering_key, ExecutionEvent, ExecutionReceipt, ExecutionRequest, ExecutorError},
+    idempotency::{self, IdempotencyStore, TransactionSent},
     ipc::{IpcError, TsIpc},
+    paper::{PaperAccount, PaperBook, PaperError, PaperOrder},
+    risk::{OpenOrder, RiskContext, RiskEngine, RiskError, RiskOrder},
+    scheduler::{self, DispatchError},
+    signer::SignerError,
+    stream::{PositionStreams, SnapshotReceiver, WalletSnapshot},
+    trading_mode::{TradeAction, TradingMode, TradingModeStatus, TradingSwitch},
     types::{
-        ApiErrorBody, ClosePositionRequest, DepositNativeRequest, DepositTokenRequest,
-        IsolatedBalanceQuery, OpenIsolatedRequest, TransferMarginRequest, WalletQuery,
+        now_ms, AmendBracketRequest, ApiErrorBody, BatchLeg, BatchRequest, Bracket, BracketLeg,
+        BracketLegKind, BracketQuery, BracketRequest, BracketState, CancelOrdersRequest,
+        ClosePositionRequest, CreateApiKeyRequest, DepositNativeRequest, DepositTokenRequest,
+        FundingQuery, FundingRate, FundingReport, IsolatedBalanceQuery, MarketPnl,
+        ModifyOrderRequest, OpenIsolatedRequest, OpenOrdersQuery, OrderChanges, OrderKind,
+        OrderOptions, PaperPosition, PaperPositionsQuery, PlacedLeg, PnlQuery, PnlReport,
+        PnlTotals, PositionSide, PostOnly, Quote, QuoteRequest, QuoteRisk, RemoveBracketRequest,
+        RiskEvent, RiskEventsQuery, Schedule, ScheduleAction, ScheduleQuery, ScheduleRequest,
+        ScheduleSlice, ScheduleState, SimulateRequest, SliceState, SubmissionState,
+        TradingModeRequest, TransactionSubmission, TransferMarginRequest, WalletQuery,
+        WebhookDeliveriesQuery, WebhookDelivery,
     },
+    wallets::{market_key, WalletSummary},
+    webhooks::{WebhookEvent, Webhooks},
 };
 
+/// Ordering market for deposits and cancels that do not name a market.
+const COLLATERAL_ORDERING_MARKET: &str = "COLLATERAL";
+/// Most legs accepted in one batch request.
+const MAX_BATCH_LEGS: usize = 10;
+
 #[derive(Clone)]
 pub struct AppState {
     pub ipc: TsIpc,
     pub executor: Arc<crate::executor::TxExecutor>,
     pub db: Arc<Client>,
     pub decoder: Arc<DriftDecoder>,
+    pub streams: PositionStreams,
+    pub idempotency: IdempotencyStore,
+    pub risk: RiskEngine,
+    pub trading: TradingSwitch,
+    /// Set in paper-trading mode, where fills are simulated instead of sent.
+    pub paper: Option<PaperBook>,
+    pub webhooks: Webhooks,
+    pub api_keys: ApiKeys,
 }
 
 pub fn router(state: AppState) -> Router {
-    Router::new()
+    let keys = &state.api_keys;
+    let read_routes = Router::new()
         .route("/positions", get(get_positions))
         .route("/balances", get(get_balances))
         .route("/positions/details", get(get_position_details))
         .route("/trade-history", get(get_trades))
+        .route("/pnl", get(get_pnl))
+        .route("/funding", get(get_funding))
         .route("/markets/:symbol", get(get_market))
         .route("/positions/isolated-balance", get(get_isolated_balance))
         .route("/server/public-key", get(get_server_public_key))
+        .route("/wallets", get(list_wallets))
+        .route("/stream/positions", get(stream_positions))
+        .route("/orders/open", get(get_open_orders))
+        .route("/orders/quote", post(quote_order))
+        .route("/orders/brackets", get(get_brackets))
+        .route("/orders/schedules", get(get_schedules))
+        .route("/orders/schedules/:id", get(get_schedule))
+        .route("/actions/history", get(get_admin_history))
+        .route("/transactions/:id", get(get_transaction))
+        .route("/risk/events", get(get_risk_events))
+        .route("/paper/positions", get(get_paper_positions))
+        .route_layer(middleware::from_fn_with_state(
+            keys.require(Scope::Read),
+            auth::require,
+        ));
+
+    let build_routes = Router::new()
         .route("/orders/open-isolated", post(open_isolated))
-        .route("/orders/open-isolated/execute", post(open_isolated_execute))
         .route("/orders/close", post(close_position))
-        .route("/orders/close/execute", post(close_position_execute))
+        .route("/orders/cancel", post(cancel_orders))
+        .route("/orders/modify", post(modify_order))
+        .route("/orders/batch", post(batch_orders))
+        .route("/orders/bracket", post(open_bracket))
+        .route("/orders/bracket/amend", post(amend_bracket))
+        .route("/orders/bracket/remove", post(remove_bracket))
         .route("/margin/transfer", post(transfer_margin))
-        .route("/margin/transfer/execute", post(transfer_margin_execute))
         .route("/margin/deposit-native", post(deposit_native))
+        .route("/margin/deposit-token", post(deposit_token))
+        .route("/simulate", post(simulate_route))
+        .route("/actions/decode", post(decode_signature_route))
+        .route_layer(middleware::from_fn_with_state(
+            keys.require(Scope::Build),
+            auth::require,
+        ));
+
+    // Only the routes that sign and send honour `Idempotency-Key`. The key is checked
+    // first, so an unauthenticated request cannot claim one.
+    let execute_routes = Router::new()
+        .route("/orders/open-isolated/execute", post(open_isolated_execute))
+        .route("/orders/close/execute", post(close_position_execute))
+        .route("/margin/transfer/execute", post(transfer_margin_execute))
         .route(
             "/margin/deposit-native/execute",
             post(deposit_native_execute),
         )
-        .route("/margin/deposit-token", post(deposit_token))
         .route("/margin/deposit-token/execute", post(deposit_token_execute))
-        .route("/actions/decode", post(decode_signature_route))
-        .route("/actions/history", get(get_admin_history))
+        .route("/orders/cancel/execute", post(cancel_orders_execute))
+        .route("/orders/modify/execute", post(modify_order_execute))
+        .route("/orders/batch/execute", post(batch_orders_execute))
+        .route("/orders/schedule", post(create_schedule))
+        .route("/orders/bracket/execute", post(open_bracket_execute))
+        .route("/orders/bracket/amend/execute", post(amend_bracket_execute))
+        .route(
+            "/orders/bracket/remove/execute",
+            post(remove_bracket_execute),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.idempotency.clone(),
+            idempotency::enforce,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            keys.require(Scope::Execute),
+            auth::require,
+        ));
+
+    let schedule_routes = Router::new()
+        .route("/orders/schedules/:id/pause", post(pause_schedule))
+        .route("/orders/schedules/:id/resume", post(resume_schedule))
+        .route("/orders/schedules/:id/cancel", post(cancel_schedule))
+        .route_layer(middleware::from_fn_with_state(
+            keys.require(Scope::Execute),
+            auth::require,
+        ));
+
+    let admin_routes = Router::new()
+        .route(
+            "/admin/trading-mode",
+            get(get_trading_mode).post(set_trading_mode),
+        )
+        .route("/admin/webhooks/deliveries", get(get_webhook_deliveries))
+        .route(
+            "/admin/webhooks/deliveries/:id/replay",
+            post(replay_webhook_delivery),
+        )
+        .route("/admin/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/admin/api-keys/:id/revoke", post(revoke_api_key))
+        .route_layer(middleware::from_fn_with_state(
+            keys.require(Scope::Admin),
+            auth::require,
+        ));
+
+    Router::new()
+        .route("/health", get(health))
+        .merge(read_routes)
+        .merge(build_routes)
+        .merge(execute_routes)
+        .merge(schedule_routes)
+        .merge(admin_routes)
         .with_state(state)
 }
 
@@ -62,6 +195,9 @@ pub fn router(state: AppState) -> Router {
 struct ApiError {
     status: StatusCode,
     message: String,
+    details: Option<serde_json::Map<String, Value>>,
+    /// A transaction went out before the failure; see [`TransactionSent`].
+    sent: bool,
 }
 
 impl ApiError {
@@ -69,7 +205,23 @@ impl ApiError {
         Self {
             status,
             message: message.into(),
+            details: None,
+            sent: false,
+        }
+    }
+
+    /// Marks an error returned after a transaction was sent.
+    fn after_send(mut self) -> Self {
+        self.sent = true;
+        self
+    }
+
+    /// Extra top-level fields returned next to `error`.
+    fn with_details(mut self, details: Value) -> Self {
+        if let Value::Object(map) = details {
+            self.details = Some(map);
         }
+        self
     }
 }
 
@@ -77,8 +229,13 @@ impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let body = Json(ApiErrorBody {
             error: &self.message,
+            details: self.details.as_ref(),
         });
-        (self.status, body).into_response()
+        let mut response = (self.status, body).into_response();
+        if self.sent {
+            response.extensions_mut().insert(TransactionSent);
+        }
+        response
     }
 }
 
@@ -114,6 +271,7 @@ fn ensure_positive(name: &str, value: f64) -> Result<(), ApiError> {
 }
 
 const WORKER_TIMEOUT: Duration = Duration::from_secs(10);
+const STREAM_HEARTBEAT: Duration = Duration::from_secs(15);
 
 #[derive(Deserialize)]
 struct DecodeSignatureRequest {
@@ -134,6 +292,17 @@ struct HistoryQuery {
 
 const MAX_HISTORY_LIMIT: i64 = 166;
 const MAX_HISTORY_DB_ROWS: i64 = 500;
+const MAX_RISK_EVENTS_LIMIT: i64 = 500;
+const MAX_BRACKETS_LIMIT: i64 = 500;
+const MAX_SCHEDULES_LIMIT: i64 = 500;
+const MAX_FUNDING_LIMIT: i64 = 500;
+const MAX_WEBHOOK_DELIVERIES_LIMIT: i64 = 500;
+const DEFAULT_PNL_WINDOW_MS: i64 = 30 * 24 * 60 * 60 * 1000;
+const MAX_SCHEDULE_SLICES: u32 = 500;
+const MAX_SCHEDULE_DURATION_SECS: u64 = 7 * 24 * 60 * 60;
+/// How far in the past `startAt` may be, to allow for clock skew.
+const SCHEDULE_START_TOLERANCE_MS: i64 = 60 * 1000;
+const MAX_SCHEDULE_DELAY_MS: i64 = 30 * 24 * 60 * 60 * 1000;
 
 #[derive(Serialize)]
 struct HistoryEntry {
@@ -151,12 +320,13 @@ struct HistoryEntry {
     token_mint: Option<String>,
     token_amount: Option<u64>,
     leverage: Option<f64>,
+    paper: bool,
 }
 
 async fn open_isolated(
     State(state): State<AppState>,
     OriginalUri(uri): OriginalUri,
-    Json(body): Json<OpenIsolatedRequest>,
+    Json(mut body): Json<OpenIsolatedRequest>,
 ) -> Result<Json<Value>, ApiError> {
     log_request("/orders/open-isolated", &uri, serialize_payload(&body));
     log_request(
@@ -164,14 +334,22 @@ async fn open_isolated(
         &uri,
         serialize_payload(&body),
     );
-    let value = open_isolated_build(&state, &body).await?;
+    ensure_trading_allowed(&state, TradeAction::Open)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        Some(&body.market),
+    )?;
+    let value = open_isolated_build(&state, &body, acting.sub_account_id).await?;
     Ok(Json(value))
 }
 
 async fn open_isolated_execute(
     State(state): State<AppState>,
     OriginalUri(uri): OriginalUri,
-    Json(body): Json<OpenIsolatedRequest>,
+    Json(mut body): Json<OpenIsolatedRequest>,
 ) -> Result<Json<Value>, ApiError> {
     log_request("/orders/open-isolated", &uri, serialize_payload(&body));
     log_request(
@@ -179,8 +357,89 @@ async fn open_isolated_execute(
         &uri,
         serialize_payload(&body),
     );
-    let value = open_isolated_build(&state, &body).await?;
-    let executed = execute_transaction(&state, value).await?;
+    ensure_trading_allowed(&state, TradeAction::Open)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        Some(&body.market),
+    )?;
+    if let Some(paper) = &state.paper {
+        open_isolated_args(&body, acting.sub_account_id)?;
+        let limit_price = match body.order.order_type {
+            OrderKind::Market => None,
+            OrderKind::Limit => body.order.price,
+            kind => {
+                return Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "paper trading fills market and limit orders, not {}",
+                        kind.as_str()
+                    ),
+                ))
+            }
+        };
+        let order = PaperOrder::Open {
+            size: body.size,
+            margin: body.margin,
+            limit_price,
+        };
+        return execute_paper(
+            paper,
+            &body.wallet,
+            &acting,
+            &body.market,
+            order,
+            body.simulate,
+        )
+        .await;
+    }
+    let value = open_isolated_build(&state, &body, acting.sub_account_id).await?;
+    enforce_risk(
+        &state,
+        &body.wallet,
+        &acting,
+        "open",
+        body.simulate,
+        RiskOrder::Open(OpenOrder {
+            market: &body.market,
+            size: body.size,
+            leverage: body.leverage,
+            margin: body.margin,
+            limit_price: body.order.price,
+        }),
+    )
+    .await?;
+    if body.simulate {
+        let simulated = attach_simulation(&state, value, &body.wallet, Some(&body.market)).await?;
+        return Ok(Json(simulated));
+    }
+
+    if body.asynchronous {
+        let submitted = submit_transaction(
+            &state,
+            value,
+            "open",
+            &acting,
+            &body.wallet,
+            Some(&body.market),
+        )
+        .await?;
+        return Ok(Json(submitted));
+    }
+    let executed = execute_transaction(
+        &state,
+        value,
+        "open",
+        &acting,
+        vec![ordering_key(
+            &body.wallet,
+            acting.sub_account_id,
+            &body.market,
+        )],
+    )
+    .await?;
 
     if let Some(signature) = executed
         .get("txSignature")
@@ -210,18 +469,26 @@ async fn open_isolated_execute(
 async fn close_position(
     State(state): State<AppState>,
     OriginalUri(uri): OriginalUri,
-    Json(body): Json<ClosePositionRequest>,
+    Json(mut body): Json<ClosePositionRequest>,
 ) -> Result<Json<Value>, ApiError> {
     log_request("/orders/close", &uri, serialize_payload(&body));
     log_request("/orders/close/execute", &uri, serialize_payload(&body));
-    let value = close_position_build(&state, &body).await?;
+    ensure_trading_allowed(&state, TradeAction::Close)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        Some(&body.market),
+    )?;
+    let value = close_position_build(&state, &body, acting.sub_account_id).await?;
     Ok(Json(value))
 }
 
 async fn close_position_execute(
     State(state): State<AppState>,
     OriginalUri(uri): OriginalUri,
-    Json(body): Json<ClosePositionRequest>,
+    Json(mut body): Json<ClosePositionRequest>,
 ) -> Result<Json<Value>, ApiError> {
     info!("[CLOSE_POSITION_EXECUTE] Starting close position request");
     log_request("/orders/close/execute", &uri, serialize_payload(&body));
@@ -229,7 +496,30 @@ async fn close_position_execute(
     info!("[CLOSE_POSITION_EXECUTE] Building close position transaction for wallet: {}, market: {}, size: {:?}", 
 		body.wallet, body.market, body.size);
 
-    let value = match close_position_build(&state, &body).await {
+    ensure_trading_allowed(&state, TradeAction::Close)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        Some(&body.market),
+    )?;
+    if let Some(paper) = &state.paper {
+        if let Some(size) = body.size {
+            ensure_positive("size", size)?;
+        }
+        let order = PaperOrder::Close { size: body.size };
+        return execute_paper(
+            paper,
+            &body.wallet,
+            &acting,
+            &body.market,
+            order,
+            body.simulate,
+        )
+        .await;
+    }
+    let value = match close_position_build(&state, &body, acting.sub_account_id).await {
         Ok(v) => {
             let tx_preview = v
                 .get("txBase64")
@@ -258,8 +548,39 @@ async fn close_position_execute(
         }
     };
 
+    if body.simulate {
+        info!("[CLOSE_POSITION_EXECUTE] Simulating transaction instead of executing");
+        let simulated = attach_simulation(&state, value, &body.wallet, Some(&body.market)).await?;
+        return Ok(Json(simulated));
+    }
+
+    if body.asynchronous {
+        let submitted = submit_transaction(
+            &state,
+            value,
+            "close",
+            &acting,
+            &body.wallet,
+            Some(&body.market),
+        )
+        .await?;
+        return Ok(Json(submitted));
+    }
+
     info!("[CLOSE_POSITION_EXECUTE] Executing transaction");
-    let executed = match execute_transaction(&state, value).await {
+    let executed = match execute_transaction(
+        &state,
+        value,
+        "close",
+        &acting,
+        vec![ordering_key(
+            &body.wallet,
+            acting.sub_account_id,
+            &body.market,
+        )],
+    )
+    .await
+    {
         Ok(result) => {
             // Extract and log the transaction signature
             let tx_signature = result
@@ -285,6 +606,14 @@ async fn close_position_execute(
         }
     };
 
+    if let Some(signature) = executed.get("txSignature").and_then(Value::as_str) {
+        if let Err(err) = decode_and_store_signature(&state, signature).await {
+            warn!(
+                "[CLOSE_POSITION_EXECUTE] failed to persist decoded actions signature={signature} error={err}"
+            );
+        }
+    }
+
     // Ensure txSignature is in the response
     let response = if executed.get("txSignature").is_some() {
         executed
@@ -309,17 +638,23 @@ async fn decode_signature_route(
         ));
     }
 
-    let (_, actions) = state.decoder.decode_signature(signature).map_err(|err| {
+    let (dump, actions) = state.decoder.decode_signature(signature).map_err(|err| {
         error!(?err, signature = signature, "failed to decode signature");
         ApiError::new(StatusCode::BAD_GATEWAY, "failed to decode signature")
     })?;
 
-    let rows_written = db::insert_actions(state.db.as_ref(), &actions)
+    let rows_written = store_actions(&state, signature, &actions)
         .await
         .map_err(|err| {
             error!(?err, "database error while persisting actions");
             ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
         })?;
+    db::insert_pnl_events(state.db.as_ref(), &dump.pnl_events)
+        .await
+        .map_err(|err| {
+            error!(?err, "database error while persisting pnl events");
+            ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        })?;
 
     Ok(Json(DecodeSignatureResponse {
         signature: signature.to_string(),
@@ -412,24 +747,33 @@ fn build_history_entry(signature: String, group: &[ActionRecord]) -> HistoryEntr
         token_mint,
         token_amount,
         leverage: primary.leverage,
+        paper: primary.paper,
     }
 }
 
 async fn transfer_margin(
     State(state): State<AppState>,
     OriginalUri(uri): OriginalUri,
-    Json(body): Json<TransferMarginRequest>,
+    Json(mut body): Json<TransferMarginRequest>,
 ) -> Result<Json<Value>, ApiError> {
     log_request("/margin/transfer", &uri, serialize_payload(&body));
     log_request("/margin/transfer/execute", &uri, serialize_payload(&body));
-    let value = transfer_margin_build(&state, &body).await?;
+    ensure_trading_allowed(&state, transfer_action(body.delta))?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        Some(&body.market),
+    )?;
+    let value = transfer_margin_build(&state, &body, acting.sub_account_id).await?;
     Ok(Json(value))
 }
 
 async fn transfer_margin_execute(
     State(state): State<AppState>,
     OriginalUri(uri): OriginalUri,
-    Json(body): Json<TransferMarginRequest>,
+    Json(mut body): Json<TransferMarginRequest>,
 ) -> Result<Json<Value>, ApiError> {
     info!("[TRANSFER_MARGIN_EXECUTE] Starting transfer margin request");
     log_request("/margin/transfer/execute", &uri, serialize_payload(&body));
@@ -437,7 +781,33 @@ async fn transfer_margin_execute(
     info!("[TRANSFER_MARGIN_EXECUTE] Building transfer margin transaction for wallet: {}, market: {}, delta: {}", 
 		body.wallet, body.market, body.delta);
 
-    let value = match transfer_margin_build(&state, &body).await {
+    ensure_trading_allowed(&state, transfer_action(body.delta))?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        Some(&body.market),
+    )?;
+    if let Some(paper) = &state.paper {
+        if !body.delta.is_finite() || body.delta == 0.0 {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "delta must be a non-zero number",
+            ));
+        }
+        let order = PaperOrder::Transfer { delta: body.delta };
+        return execute_paper(
+            paper,
+            &body.wallet,
+            &acting,
+            &body.market,
+            order,
+            body.simulate,
+        )
+        .await;
+    }
+    let value = match transfer_margin_build(&state, &body, acting.sub_account_id).await {
         Ok(v) => {
             let tx_preview = v
                 .get("txBase64")
@@ -466,8 +836,54 @@ async fn transfer_margin_execute(
         }
     };
 
+    if body.delta < 0.0 {
+        enforce_risk(
+            &state,
+            &body.wallet,
+            &acting,
+            "transfer",
+            body.simulate,
+            RiskOrder::Withdraw {
+                market: &body.market,
+                amount: -body.delta,
+            },
+        )
+        .await?;
+    }
+
+    if body.simulate {
+        info!("[TRANSFER_MARGIN_EXECUTE] Simulating transaction instead of executing");
+        let simulated = attach_simulation(&state, value, &body.wallet, Some(&body.market)).await?;
+        return Ok(Json(simulated));
+    }
+
+    if body.asynchronous {
+        let submitted = submit_transaction(
+            &state,
+            value,
+            "transfer",
+            &acting,
+            &body.wallet,
+            Some(&body.market),
+        )
+        .await?;
+        return Ok(Json(submitted));
+    }
+
     info!("[TRANSFER_MARGIN_EXECUTE] Executing transaction");
-    let executed = match execute_transaction(&state, value).await {
+    let executed = match execute_transaction(
+        &state,
+        value,
+        "transfer",
+        &acting,
+        vec![ordering_key(
+            &body.wallet,
+            acting.sub_account_id,
+            &body.market,
+        )],
+    )
+    .await
+    {
         Ok(result) => {
             // Extract and log the transaction signature
             let tx_signature = result
@@ -500,7 +916,7 @@ async fn transfer_margin_execute(
 async fn deposit_native(
     State(state): State<AppState>,
     OriginalUri(uri): OriginalUri,
-    Json(body): Json<DepositNativeRequest>,
+    Json(mut body): Json<DepositNativeRequest>,
 ) -> Result<Json<Value>, ApiError> {
     log_request("/margin/deposit-native", &uri, serialize_payload(&body));
     log_request(
@@ -508,14 +924,22 @@ async fn deposit_native(
         &uri,
         serialize_payload(&body),
     );
-    let value = deposit_native_build(&state, &body).await?;
+    ensure_trading_allowed(&state, TradeAction::Deposit)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        None,
+    )?;
+    let value = deposit_native_build(&state, &body, acting.sub_account_id).await?;
     Ok(Json(value))
 }
 
 async fn deposit_native_execute(
     State(state): State<AppState>,
     OriginalUri(uri): OriginalUri,
-    Json(body): Json<DepositNativeRequest>,
+    Json(mut body): Json<DepositNativeRequest>,
 ) -> Result<Json<Value>, ApiError> {
     info!("[DEPOSIT_NATIVE_EXECUTE] Starting deposit native SOL request");
     log_request(
@@ -523,11 +947,20 @@ async fn deposit_native_execute(
         &uri,
         serialize_payload(&body),
     );
+    ensure_live(&state)?;
 
     info!("[DEPOSIT_NATIVE_EXECUTE] Building deposit native transaction for wallet: {}, amount: {}, market: {:?}", 
 		body.wallet, body.amount, body.market);
 
-    let value = match deposit_native_build(&state, &body).await {
+    ensure_trading_allowed(&state, TradeAction::Deposit)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        None,
+    )?;
+    let value = match deposit_native_build(&state, &body, acting.sub_account_id).await {
         Ok(v) => {
             let tx_preview = v
                 .get("txBase64")
@@ -556,8 +989,39 @@ async fn deposit_native_execute(
         }
     };
 
+    if body.simulate {
+        info!("[DEPOSIT_NATIVE_EXECUTE] Simulating transaction instead of executing");
+        let simulated = attach_simulation(&state, value, &body.wallet, None).await?;
+        return Ok(Json(simulated));
+    }
+
+    if body.asynchronous {
+        let submitted = submit_transaction(
+            &state,
+            value,
+            "deposit",
+            &acting,
+            &body.wallet,
+            body.market.as_deref(),
+        )
+        .await?;
+        return Ok(Json(submitted));
+    }
+
     info!("[DEPOSIT_NATIVE_EXECUTE] Executing transaction");
-    let executed = match execute_transaction(&state, value).await {
+    let executed = match execute_transaction(
+        &state,
+        value,
+        "deposit",
+        &acting,
+        vec![ordering_key(
+            &body.wallet,
+            acting.sub_account_id,
+            body.market.as_deref().unwrap_or(COLLATERAL_ORDERING_MARKET),
+        )],
+    )
+    .await
+    {
         Ok(result) => {
             let tx_signature = result
                 .get("txSignature")
@@ -589,7 +1053,7 @@ async fn deposit_native_execute(
 async fn deposit_token(
     State(state): State<AppState>,
     OriginalUri(uri): OriginalUri,
-    Json(body): Json<DepositTokenRequest>,
+    Json(mut body): Json<DepositTokenRequest>,
 ) -> Result<Json<Value>, ApiError> {
     log_request("/margin/deposit-token", &uri, serialize_payload(&body));
     log_request(
@@ -597,14 +1061,22 @@ async fn deposit_token(
         &uri,
         serialize_payload(&body),
     );
-    let value = deposit_token_build(&state, &body).await?;
+    ensure_trading_allowed(&state, TradeAction::Deposit)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        None,
+    )?;
+    let value = deposit_token_build(&state, &body, acting.sub_account_id).await?;
     Ok(Json(value))
 }
 
 async fn deposit_token_execute(
     State(state): State<AppState>,
     OriginalUri(uri): OriginalUri,
-    Json(body): Json<DepositTokenRequest>,
+    Json(mut body): Json<DepositTokenRequest>,
 ) -> Result<Json<Value>, ApiError> {
     info!("[DEPOSIT_TOKEN_EXECUTE] Starting deposit token request");
     log_request(
@@ -612,11 +1084,20 @@ async fn deposit_token_execute(
         &uri,
         serialize_payload(&body),
     );
+    ensure_live(&state)?;
 
     info!("[DEPOSIT_TOKEN_EXECUTE] Building deposit token transaction for wallet: {}, amount: {}, market: {:?}", 
 		body.wallet, body.amount, body.market);
 
-    let value = match deposit_token_build(&state, &body).await {
+    ensure_trading_allowed(&state, TradeAction::Deposit)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        None,
+    )?;
+    let value = match deposit_token_build(&state, &body, acting.sub_account_id).await {
         Ok(v) => {
             let tx_preview = v
                 .get("txBase64")
@@ -645,8 +1126,39 @@ async fn deposit_token_execute(
         }
     };
 
+    if body.simulate {
+        info!("[DEPOSIT_TOKEN_EXECUTE] Simulating transaction instead of executing");
+        let simulated = attach_simulation(&state, value, &body.wallet, None).await?;
+        return Ok(Json(simulated));
+    }
+
+    if body.asynchronous {
+        let submitted = submit_transaction(
+            &state,
+            value,
+            "deposit",
+            &acting,
+            &body.wallet,
+            body.market.as_deref(),
+        )
+        .await?;
+        return Ok(Json(submitted));
+    }
+
     info!("[DEPOSIT_TOKEN_EXECUTE] Executing transaction");
-    let executed = match execute_transaction(&state, value).await {
+    let executed = match execute_transaction(
+        &state,
+        value,
+        "deposit",
+        &acting,
+        vec![ordering_key(
+            &body.wallet,
+            acting.sub_account_id,
+            body.market.as_deref().unwrap_or(COLLATERAL_ORDERING_MARKET),
+        )],
+    )
+    .await
+    {
         Ok(result) => {
             let tx_signature = result
                 .get("txSignature")
@@ -675,360 +1187,3291 @@ async fn deposit_token_execute(
     Ok(Json(executed))
 }
 
-async fn open_isolated_build(
-    state: &AppState,
-    body: &OpenIsolatedRequest,
-) -> Result<Value, ApiError> {
-    validate_wallet(&body.wallet)?;
-    ensure_positive("margin", body.margin)?;
-    if !body.size.is_finite() || body.size == 0.0 {
-        return Err(ApiError::new(
-            StatusCode::BAD_REQUEST,
-            "size must be a non-zero number",
-        ));
+async fn batch_orders(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<BatchRequest>,
+) -> Result<Json<Value>, ApiError> {
+    log_request("/orders/batch", &uri, serialize_payload(&body));
+    let acting = resolve_batch(&state, &mut body)?;
+    let value = batch_build(&state, &body, acting.sub_account_id).await?;
+    Ok(Json(value))
+}
+
+async fn batch_orders_execute(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<BatchRequest>,
+) -> Result<Json<Value>, ApiError> {
+    log_request("/orders/batch/execute", &uri, serialize_payload(&body));
+    ensure_live(&state)?;
+    let acting = resolve_batch(&state, &mut body)?;
+    acting.signing_label()?;
+    let mut value = batch_build(&state, &body, acting.sub_account_id).await?;
+    // The opens are checked together, so legs cannot each pass against the same snapshot
+    // and add up to more than the limits allow.
+    let mut opens = Vec::new();
+    for leg in &body.legs {
+        match leg {
+            BatchLeg::Open(open) => opens.push(OpenOrder {
+                market: &open.market,
+                size: open.size,
+                leverage: open.leverage,
+                margin: open.margin,
+                limit_price: open.order.price,
+            }),
+            BatchLeg::Transfer(transfer) if transfer.delta < 0.0 => {
+                let order = RiskOrder::Withdraw {
+                    market: &transfer.market,
+                    amount: -transfer.delta,
+                };
+                enforce_risk(&state, &body.wallet, &acting, "batch", body.simulate, order).await?;
+            }
+            _ => {}
+        }
+    }
+    enforce_risk(
+        &state,
+        &body.wallet,
+        &acting,
+        "batch",
+        body.simulate,
+        RiskOrder::Opens(opens),
+    )
+    .await?;
+
+    let transactions: Vec<Value> = value
+        .get_mut("transactions")
+        .and_then(Value::as_array_mut)
+        .map(std::mem::take)
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "worker response missing transactions",
+            )
+        })?;
+    if body.simulate {
+        let mut simulated = Vec::with_capacity(transactions.len());
+        for tx in transactions {
+            simulated.push(attach_simulation(&state, tx, &body.wallet, None).await?);
+        }
+        value["transactions"] = json!(simulated);
+        return Ok(Json(value));
+    }
+
+    // Later transactions are only sent once the earlier ones confirm. Each one holds the
+    // lane of every market in the batch, so it is ordered with single-market requests.
+    let keys: Vec<String> = body
+        .legs
+        .iter()
+        .map(|leg| ordering_key(&body.wallet, acting.sub_account_id, leg.market()))
+        .collect();
+    let mut outcomes: Vec<Value> = Vec::with_capacity(transactions.len());
+    let mut failure: Option<(usize, ApiError)> = None;
+    for (index, tx) in transactions.into_iter().enumerate() {
+        if failure.is_some() {
+            outcomes.push(json!({ "outcome": "skipped" }));
+            continue;
+        }
+        match execute_transaction(&state, tx, "batch", &acting, keys.clone()).await {
+            Ok(executed) => {
+                if let Some(signature) = executed.get("txSignature").and_then(Value::as_str) {
+                    if let Err(err) = decode_and_store_signature(&state, signature).await {
+                        warn!(%signature, error = %err, "failed to persist decoded batch actions");
+                    }
+                }
+                outcomes.push(executed);
+            }
+            Err(err) => {
+                let mut outcome = json!({
+                    "outcome": "failed",
+                    "error": err.message,
+                });
+                if let (Some(obj), Some(details)) = (outcome.as_object_mut(), &err.details) {
+                    obj.extend(details.clone());
+                }
+                outcomes.push(outcome);
+                failure = Some((index, err));
+            }
+        }
+    }
+
+    let legs = batch_leg_results(&value, &outcomes);
+    value["transactions"] = json!(outcomes);
+    value["legs"] = json!(legs);
+    match failure {
+        // Nothing landed, which is also the only way an atomic batch can fail.
+        Some((0, err)) => Err(err),
+        Some((_, err)) => Err(err.with_details(json!({
+            "partial": true,
+            "legs": value["legs"],
+            "transactions": value["transactions"],
+        }))),
+        None => Ok(Json(value)),
     }
-    if !body.leverage.is_finite() || body.leverage <= 0.0 || body.leverage > 100.0 {
+}
+
+/// Applies the trading mode to every leg and resolves the wallet against each leg's market.
+fn resolve_batch(state: &AppState, body: &mut BatchRequest) -> Result<ActingWallet, ApiError> {
+    if body.legs.is_empty() || body.legs.len() > MAX_BATCH_LEGS {
         return Err(ApiError::new(
             StatusCode::BAD_REQUEST,
-            "leverage must be between 0 and 100",
+            format!("legs must hold between 1 and {MAX_BATCH_LEGS} entries"),
         ));
     }
-
-    let args = json!({
-        "wallet": body.wallet,
-        "market": body.market,
-        "size": body.size,
-        "leverage": body.leverage,
-        "margin": body.margin,
-    });
-    info!("open isolated request -> {}", body.market);
-    call_worker(state, "openIsolated", args, WORKER_TIMEOUT).await
+    for leg in &body.legs {
+        let action = match leg {
+            BatchLeg::Open(_) => TradeAction::Open,
+            BatchLeg::Close(_) => TradeAction::Close,
+            BatchLeg::Transfer(transfer) => transfer_action(transfer.delta),
+        };
+        ensure_trading_allowed(state, action)?;
+    }
+    let mut acting = None;
+    for leg in &body.legs {
+        acting = Some(resolve_wallet(
+            state,
+            &mut body.wallet,
+            body.wallet_label.as_deref(),
+            body.sub_account_id,
+            Some(leg.market()),
+        )?);
+    }
+    acting.ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, "legs must not be empty"))
 }
 
-async fn close_position_build(
+async fn batch_build(
     state: &AppState,
-    body: &ClosePositionRequest,
+    body: &BatchRequest,
+    sub_account_id: u16,
 ) -> Result<Value, ApiError> {
-    debug!(
-        "[CLOSE_POSITION_BUILD] Starting build for wallet: {}, market: {}, size: {:?}",
-        body.wallet, body.market, body.size
-    );
-
     validate_wallet(&body.wallet)?;
-
-    if let Some(size) = body.size {
-        if !size.is_finite() || size <= 0.0 {
-            warn!("[CLOSE_POSITION_BUILD] Invalid size provided: {}", size);
-            return Err(ApiError::new(
-                StatusCode::BAD_REQUEST,
-                "size must be positive when provided",
-            ));
-        }
+    for (index, leg) in body.legs.iter().enumerate() {
+        let result = match leg {
+            BatchLeg::Open(open) => {
+                validate_open(open.size, open.leverage, open.margin, &open.order)
+            }
+            BatchLeg::Close(close) => match close.size {
+                Some(size) => ensure_positive("size", size),
+                None => Ok(()),
+            },
+            BatchLeg::Transfer(transfer)
+                if !transfer.delta.is_finite() || transfer.delta == 0.0 =>
+            {
+                Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "delta must be a non-zero number",
+                ))
+            }
+            BatchLeg::Transfer(_) => Ok(()),
+        };
+        result.map_err(|err| ApiError::new(err.status, format!("leg {index}: {}", err.message)))?;
     }
 
-    // Build args conditionally - only include size if it's Some(value)
-    // TypeScript schema expects size to be optional (undefined) or number, not null
-    let args = if let Some(size) = body.size {
-        json!({
-            "wallet": body.wallet,
-            "market": body.market,
-            "size": size,
-        })
-    } else {
-        json!({
-            "wallet": body.wallet,
-            "market": body.market,
-        })
-    };
-
-    debug!(
-        "[CLOSE_POSITION_BUILD] Calling worker with args: {:?}",
-        args
-    );
-
-    match call_worker(state, "closePosition", args, WORKER_TIMEOUT).await {
-        Ok(result) => {
-            debug!("[CLOSE_POSITION_BUILD] Worker returned successfully");
-            Ok(result)
-        }
-        Err(e) => {
-            error!("[CLOSE_POSITION_BUILD] Worker call failed: {:?}", e);
-            Err(e)
-        }
-    }
+    let mut args = json!({
+        "wallet": body.wallet,
+        "subAccountId": sub_account_id,
+    });
+    extend_args(&mut args, &json!({ "legs": body.legs }))?;
+    info!("batch request -> {} legs", body.legs.len());
+    call_worker(state, "buildBatch", args, WORKER_TIMEOUT).await
 }
 
-async fn transfer_margin_build(
-    state: &AppState,
-    body: &TransferMarginRequest,
-) -> Result<Value, ApiError> {
-    debug!(
-        "[TRANSFER_MARGIN_BUILD] Starting build for wallet: {}, market: {}, delta: {}",
-        body.wallet, body.market, body.delta
-    );
+/// One result per leg, carrying the outcome and signature of the transaction it went out in.
+fn batch_leg_results(value: &Value, outcomes: &[Value]) -> Vec<Value> {
+    let legs = value
+        .get("legs")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    legs.into_iter()
+        .map(|mut leg| {
+            let outcome = leg
+                .get("transaction")
+                .and_then(Value::as_u64)
+                .and_then(|index| outcomes.get(index as usize));
+            if let (Some(obj), Some(outcome)) = (leg.as_object_mut(), outcome) {
+                obj.insert("outcome".into(), outcome["outcome"].clone());
+                if let Some(signature) = outcome.get("txSignature") {
+                    obj.insert("txSignature".into(), signature.clone());
+                }
+            }
+            leg
+        })
+        .collect()
+}
 
+async fn create_schedule(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<ScheduleRequest>,
+) -> Result<Json<Schedule>, ApiError> {
+    log_request("/orders/schedule", &uri, serialize_payload(&body));
+    ensure_live(&state)?;
+    ensure_trading_allowed(&state, scheduler::trade_action(body.action))?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        Some(&body.market),
+    )?;
+    let wallet_label = acting.signing_label()?.to_string();
     validate_wallet(&body.wallet)?;
-
-    if !body.delta.is_finite() || body.delta == 0.0 {
-        warn!(
-            "[TRANSFER_MARGIN_BUILD] Invalid delta provided: {}",
-            body.delta
-        );
-        return Err(ApiError::new(
-            StatusCode::BAD_REQUEST,
-            "delta must be a non-zero number",
-        ));
+    let slices = schedule_slices(&body)?;
+    if body.action == ScheduleAction::Open {
+        // The whole parent is checked up front; each slice is checked again when it is sent.
+        enforce_risk(
+            &state,
+            &body.wallet,
+            &acting,
+            "schedule",
+            true,
+            RiskOrder::Open(OpenOrder {
+                market: &body.market,
+                size: body.size.unwrap_or_default(),
+                leverage: body.leverage.unwrap_or_default(),
+                margin: body.margin.unwrap_or_default(),
+                limit_price: None,
+            }),
+        )
+        .await?;
     }
 
-    let operation = if body.delta > 0.0 {
-        "deposit"
-    } else {
-        "withdraw"
+    let mut schedule = Schedule {
+        id: Uuid::new_v4().to_string(),
+        wallet: body.wallet.clone(),
+        wallet_label,
+        sub_account_id: acting.sub_account_id,
+        market: body.market.clone(),
+        action: body.action,
+        size: body.size,
+        leverage: body.leverage,
+        margin: body.margin,
+        state: ScheduleState::Active,
+        detail: None,
+        slice_count: slices.len() as u32,
+        confirmed_slices: 0,
+        created_at: 0,
+        updated_at: 0,
+        slices: None,
     };
+    schedule.created_at = db::insert_schedule(state.db.as_ref(), &schedule, &slices)
+        .await
+        .map_err(schedule_db_error)?;
+    schedule.updated_at = schedule.created_at;
     info!(
-        "[TRANSFER_MARGIN_BUILD] Transfer operation: {} (delta: {})",
-        operation, body.delta
+        id = %schedule.id,
+        market = %schedule.market,
+        slices = schedule.slice_count,
+        "schedule created"
     );
+    schedule.slices = Some(slices);
+    Ok(Json(schedule))
+}
 
-    let args = json!({
-        "wallet": body.wallet,
-        "market": body.market,
-        "delta": body.delta,
-    });
-
-    debug!(
-        "[TRANSFER_MARGIN_BUILD] Calling worker with args: {:?}",
-        args
-    );
+/// Validates a schedule request and splits it into evenly sized slices, the first due at
+/// `startAt` and the rest spread over `durationSecs`.
+fn schedule_slices(body: &ScheduleRequest) -> Result<Vec<ScheduleSlice>, ApiError> {
+    let invalid = |message: String| Err(ApiError::new(StatusCode::BAD_REQUEST, message));
+    let count = body.slices.unwrap_or(1);
+    if count == 0 || count > MAX_SCHEDULE_SLICES {
+        return invalid(format!(
+            "slices must be between 1 and {MAX_SCHEDULE_SLICES}"
+        ));
+    }
+    let duration_ms = match body.duration_secs {
+        _ if count == 1 => 0,
+        Some(secs) if (1..=MAX_SCHEDULE_DURATION_SECS).contains(&secs) => secs as i64 * 1000,
+        _ => {
+            return invalid(format!(
+                "durationSecs between 1 and {MAX_SCHEDULE_DURATION_SECS} is required with more than one slice"
+            ))
+        }
+    };
+    let now = now_ms();
+    let start = body.start_at.unwrap_or(now);
+    if start < now - SCHEDULE_START_TOLERANCE_MS {
+        return invalid(format!("startAt {start} is in the past"));
+    }
+    if start > now + MAX_SCHEDULE_DELAY_MS {
+        return invalid("startAt is more than 30 days away".into());
+    }
 
-    match call_worker(state, "transferMargin", args, WORKER_TIMEOUT).await {
-        Ok(result) => {
-            debug!("[TRANSFER_MARGIN_BUILD] Worker returned successfully");
-            Ok(result)
+    let (size, margin) = match body.action {
+        ScheduleAction::Open => {
+            let (Some(size), Some(leverage), Some(margin)) =
+                (body.size, body.leverage, body.margin)
+            else {
+                return invalid("open schedules need size, leverage and margin".into());
+            };
+            validate_open(size, leverage, margin, &OrderOptions::default())?;
+            (Some(size), Some(margin))
         }
-        Err(e) => {
-            error!("[TRANSFER_MARGIN_BUILD] Worker call failed: {:?}", e);
-            Err(e)
+        ScheduleAction::Close => {
+            if body.leverage.is_some() || body.margin.is_some() {
+                return invalid("leverage and margin are only used by open schedules".into());
+            }
+            match body.size {
+                Some(size) => ensure_positive("size", size)?,
+                None if count > 1 => {
+                    return invalid("size is required to close over more than one slice".into())
+                }
+                None => {}
+            }
+            (body.size, None)
         }
-    }
+    };
+
+    // The last slice takes the rounding remainder so the slices add up to the total.
+    let share = |total: f64, index: u32| {
+        let each = total / f64::from(count);
+        if index + 1 == count {
+            total - each * f64::from(count - 1)
+        } else {
+            each
+        }
+    };
+    let interval = duration_ms / i64::from(count);
+    Ok((0..count)
+        .map(|index| ScheduleSlice {
+            index,
+            due_at: start + interval * i64::from(index),
+            size: size.map(|total| share(total, index)),
+            margin: margin.map(|total| share(total, index)),
+            state: SliceState::Pending,
+            submission_id: None,
+            signature: None,
+            detail: None,
+            updated_at: now,
+        })
+        .collect())
 }
 
-async fn deposit_native_build(
+async fn get_schedules(
+    State(state): State<AppState>,
+    Query(query): Query<ScheduleQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<Vec<Schedule>>, ApiError> {
+    log_request("/orders/schedules", &uri, serialize_payload(&query));
+    if let Some(wallet) = &query.wallet {
+        validate_wallet(wallet)?;
+    }
+    let limit = query.limit.unwrap_or(100).clamp(1, MAX_SCHEDULES_LIMIT);
+    db::fetch_schedules(state.db.as_ref(), query.wallet.as_deref(), limit)
+        .await
+        .map(Json)
+        .map_err(schedule_db_error)
+}
+
+async fn get_schedule(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Path(id): Path<String>,
+) -> Result<Json<Schedule>, ApiError> {
+    log_request("/orders/schedules", &uri, None);
+    let id = parse_schedule_id(&id)?;
+    load_schedule(&state, id).await.map(Json)
+}
+
+async fn pause_schedule(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Path(id): Path<String>,
+) -> Result<Json<Schedule>, ApiError> {
+    log_request("/orders/schedules/pause", &uri, None);
+    let id = parse_schedule_id(&id)?;
+    change_schedule(&state, id, &[ScheduleState::Active], ScheduleState::Paused).await
+}
+
+async fn resume_schedule(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Path(id): Path<String>,
+) -> Result<Json<Schedule>, ApiError> {
+    log_request("/orders/schedules/resume", &uri, None);
+    let id = parse_schedule_id(&id)?;
+    change_schedule(&state, id, &[ScheduleState::Paused], ScheduleState::Active).await
+}
+
+async fn cancel_schedule(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Path(id): Path<String>,
+) -> Result<Json<Schedule>, ApiError> {
+    log_request("/orders/schedules/cancel", &uri, None);
+    let id = parse_schedule_id(&id)?;
+    change_schedule(
+        &state,
+        id,
+        &[ScheduleState::Active, ScheduleState::Paused],
+        ScheduleState::Cancelled,
+    )
+    .await
+}
+
+/// Moves the schedule between states, returning `409` with its current state when it is
+/// in none of `from`.
+async fn change_schedule(
     state: &AppState,
-    body: &DepositNativeRequest,
+    id: Uuid,
+    from: &[ScheduleState],
+    to: ScheduleState,
+) -> Result<Json<Schedule>, ApiError> {
+    let moved = db::transition_schedule(state.db.as_ref(), id, from, to, None)
+        .await
+        .map_err(schedule_db_error)?;
+    let schedule = load_schedule(state, id).await?;
+    if !moved {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            format!("schedule is {}", schedule.state.as_str()),
+        )
+        .with_details(json!({ "state": schedule.state })));
+    }
+    info!(%id, state = to.as_str(), "schedule updated");
+    Ok(Json(schedule))
+}
+
+/// The schedule with its slices.
+async fn load_schedule(state: &AppState, id: Uuid) -> Result<Schedule, ApiError> {
+    let mut schedule = db::fetch_schedule(state.db.as_ref(), id)
+        .await
+        .map_err(schedule_db_error)?
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "schedule not found"))?;
+    let slices = db::fetch_schedule_slices(state.db.as_ref(), id)
+        .await
+        .map_err(schedule_db_error)?;
+    schedule.slices = Some(slices);
+    Ok(schedule)
+}
+
+fn parse_schedule_id(id: &str) -> Result<Uuid, ApiError> {
+    Uuid::parse_str(id.trim())
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "invalid schedule id"))
+}
+
+fn schedule_db_error(err: anyhow::Error) -> ApiError {
+    error!(?err, "schedule database error");
+    ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+}
+
+/// Builds a schedule slice the way its execute route would and submits it under
+/// `submission_id`.
+pub(crate) async fn submit_schedule_slice(
+    state: &AppState,
+    schedule: &Schedule,
+    slice: &ScheduleSlice,
+    submission_id: Uuid,
+) -> Result<(), DispatchError> {
+    let dispatch_error = |err: ApiError| {
+        if err.status.is_server_error() {
+            DispatchError::Unavailable(err.message)
+        } else {
+            DispatchError::Rejected(err.message)
+        }
+    };
+    let mut wallet = schedule.wallet.clone();
+    let acting = resolve_wallet(
+        state,
+        &mut wallet,
+        Some(&schedule.wallet_label),
+        Some(schedule.sub_account_id),
+        Some(&schedule.market),
+    )
+    .map_err(dispatch_error)?;
+    let value = build_schedule_slice(state, schedule, slice, &acting, &wallet)
+        .await
+        .map_err(dispatch_error)?;
+    submit_transaction_as(
+        state,
+        submission_id,
+        value,
+        "schedule",
+        &acting,
+        &wallet,
+        Some(&schedule.market),
+    )
+    .await
+    .map_err(dispatch_error)?;
+    Ok(())
+}
+
+/// Moves `amount` of a registry wallet's cross collateral into an isolated position, the
+/// way `/margin/transfer/execute` would, and returns the transaction signature.
+pub(crate) async fn deposit_isolated_margin(
+    state: &AppState,
+    label: &str,
+    sub_account_id: u16,
+    market: &str,
+    amount: f64,
+) -> Result<String, String> {
+    ensure_trading_allowed(state, TradeAction::Deposit).map_err(|err| err.message)?;
+    let mut wallet = String::new();
+    let acting = resolve_wallet(
+        state,
+        &mut wallet,
+        Some(label),
+        Some(sub_account_id),
+        Some(market),
+    )
+    .map_err(|err| err.message)?;
+    let body = TransferMarginRequest {
+        wallet: wallet.clone(),
+        wallet_label: acting.label.clone(),
+        sub_account_id: Some(acting.sub_account_id),
+        market: market.to_string(),
+        delta: amount,
+        simulate: false,
+        asynchronous: false,
+    };
+    let value = transfer_margin_build(state, &body, acting.sub_account_id)
+        .await
+        .map_err(|err| err.message)?;
+    let executed = execute_transaction(
+        state,
+        value,
+        "liquidation",
+        &acting,
+        vec![ordering_key(&wallet, acting.sub_account_id, market)],
+    )
+    .await
+    .map_err(|err| err.message)?;
+    executed
+        .get("txSignature")
+        .and_then(Value::as_str)
+        .map(str::to_owned)
+        .ok_or_else(|| "txSignature missing from executor response".to_string())
+}
+
+async fn build_schedule_slice(
+    state: &AppState,
+    schedule: &Schedule,
+    slice: &ScheduleSlice,
+    acting: &ActingWallet,
+    wallet: &str,
 ) -> Result<Value, ApiError> {
-    debug!(
-        "[DEPOSIT_NATIVE_BUILD] Starting build for wallet: {}, amount: {}, market: {:?}",
-        body.wallet, body.amount, body.market
+    match schedule.action {
+        ScheduleAction::Open => {
+            let body = OpenIsolatedRequest {
+                wallet: wallet.to_string(),
+                wallet_label: acting.label.clone(),
+                sub_account_id: Some(acting.sub_account_id),
+                market: schedule.market.clone(),
+                size: slice.size.unwrap_or_default(),
+                leverage: schedule.leverage.unwrap_or_default(),
+                margin: slice.margin.unwrap_or_default(),
+                order: OrderOptions::default(),
+                simulate: false,
+                asynchronous: true,
+            };
+            let value = open_isolated_build(state, &body, acting.sub_account_id).await?;
+            enforce_risk(
+                state,
+                wallet,
+                acting,
+                "schedule",
+                false,
+                RiskOrder::Open(OpenOrder {
+                    market: &body.market,
+                    size: body.size,
+                    leverage: body.leverage,
+                    margin: body.margin,
+                    limit_price: None,
+                }),
+            )
+            .await?;
+            Ok(value)
+        }
+        ScheduleAction::Close => {
+            let body = ClosePositionRequest {
+                wallet: wallet.to_string(),
+                wallet_label: acting.label.clone(),
+                sub_account_id: Some(acting.sub_account_id),
+                market: schedule.market.clone(),
+                size: slice.size,
+                simulate: false,
+                asynchronous: true,
+            };
+            close_position_build(state, &body, acting.sub_account_id).await
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuoteMarket {
+    symbol: String,
+    price: f64,
+    taker_fee: f64,
+    maker_fee: f64,
+    initial_margin_ratio: f64,
+    maintenance_margin_ratio: f64,
+    min_order_size: f64,
+}
+
+async fn quote_order(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<QuoteRequest>,
+) -> Result<Json<Quote>, ApiError> {
+    log_request("/orders/quote", &uri, serialize_payload(&body));
+    let wanted = [body.size, body.leverage, body.margin];
+    if wanted.iter().filter(|value| value.is_some()).count() != 2 {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "exactly two of size, leverage and margin are required",
+        ));
+    }
+    validate_order_options(&body.order)?;
+    let value = call_worker(
+        &state,
+        "getMarket",
+        json!({ "symbol": body.market }),
+        WORKER_TIMEOUT,
+    )
+    .await?;
+    let market: QuoteMarket = worker_value(value, "market data")?;
+    if !market.price.is_finite() || market.price <= 0.0 {
+        return Err(ApiError::new(
+            StatusCode::BAD_GATEWAY,
+            format!("no oracle price for {}", market.symbol),
+        ));
+    }
+
+    let entry_price = estimate_entry_price(&body.order, market.price);
+    if !entry_price.is_finite() || entry_price <= 0.0 {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("estimated entry price {entry_price} is not positive"),
+        ));
+    }
+    let direction = match (body.size, body.direction) {
+        (Some(size), Some(direction)) if (size > 0.0) != (direction == PositionSide::Long) => {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "direction does not match the sign of size",
+            ))
+        }
+        (Some(size), _) if size < 0.0 => PositionSide::Short,
+        (Some(_), _) => PositionSide::Long,
+        (None, Some(direction)) => direction,
+        (None, None) => {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "direction is required when size is left out",
+            ))
+        }
+    };
+    let (size, leverage, margin) = match (body.size, body.leverage, body.margin) {
+        (Some(size), Some(leverage), None) => {
+            ensure_positive("leverage", leverage)?;
+            (size, leverage, size.abs() * entry_price / leverage)
+        }
+        (Some(size), None, Some(margin)) => {
+            ensure_positive("margin", margin)?;
+            (size, size.abs() * entry_price / margin, margin)
+        }
+        (None, Some(leverage), Some(margin)) => {
+            let base = margin * leverage / entry_price;
+            let size = match direction {
+                PositionSide::Long => base,
+                PositionSide::Short => -base,
+            };
+            (size, leverage, margin)
+        }
+        _ => unreachable!("two of size, leverage and margin are set"),
+    };
+    validate_open(size, leverage, margin, &body.order)?;
+
+    let notional = size.abs() * entry_price;
+    let fee_rate = if body.order.post_only == PostOnly::None {
+        market.taker_fee
+    } else {
+        market.maker_fee
+    };
+    let fee = notional * fee_rate;
+    let mmr = market.maintenance_margin_ratio;
+    let liquidation_price = liquidation_price(size, entry_price, margin - fee, mmr);
+
+    let risk = if body.wallet.trim().is_empty() && body.wallet_label.is_none() {
+        None
+    } else {
+        let acting = resolve_wallet(
+            &state,
+            &mut body.wallet,
+            body.wallet_label.as_deref(),
+            body.sub_account_id,
+            Some(&body.market),
+        )?;
+        validate_wallet(&body.wallet)?;
+        let order = RiskOrder::Open(OpenOrder {
+            market: &body.market,
+            size,
+            leverage,
+            margin,
+            limit_price: body.order.price,
+        });
+        let ctx = RiskContext {
+            wallet: &body.wallet,
+            wallet_label: acting.label.as_deref(),
+            sub_account_id: acting.sub_account_id,
+            route: "quote",
+            dry_run: true,
+        };
+        // A quote only reports what the limits say; it records nothing.
+        Some(
+            match state
+                .risk
+                .evaluate(&ctx, &order)
+                .await
+                .map_err(map_risk_error)?
+            {
+                None => QuoteRisk {
+                    passed: true,
+                    code: None,
+                    message: None,
+                },
+                Some(breach) => QuoteRisk {
+                    passed: false,
+                    code: Some(breach.code.to_string()),
+                    message: Some(breach.message),
+                },
+            },
+        )
+    };
+
+    Ok(Json(Quote {
+        market: market.symbol,
+        direction,
+        size,
+        leverage,
+        margin,
+        notional,
+        oracle_price: market.price,
+        entry_price,
+        fee_rate,
+        fee,
+        margin_ratio: (margin - fee) / notional,
+        initial_margin_ratio: market.initial_margin_ratio,
+        maintenance_margin_ratio: mmr,
+        max_leverage: 1.0 / market.initial_margin_ratio,
+        liquidation_price,
+        min_order_size: market.min_order_size,
+        risk,
+    }))
+}
+
+/// Where an order is expected to fill: its limit price, the middle of its auction, or
+/// else the trigger or oracle price.
+fn estimate_entry_price(order: &OrderOptions, oracle_price: f64) -> f64 {
+    let auction_mid = match (order.auction_start_price, order.auction_end_price) {
+        (Some(start), Some(end)) => Some((start + end) / 2.0),
+        _ => None,
+    };
+    match order.order_type {
+        OrderKind::Limit | OrderKind::TriggerLimit => order.price.unwrap_or(oracle_price),
+        OrderKind::Oracle => {
+            oracle_price + auction_mid.or(order.oracle_price_offset).unwrap_or(0.0)
+        }
+        OrderKind::TriggerMarket => auction_mid.or(order.trigger_price).unwrap_or(oracle_price),
+        OrderKind::Market => auction_mid.unwrap_or(oracle_price),
+    }
+}
+
+/// Price at which an isolated position's equity falls to its maintenance margin.
+fn liquidation_price(
+    size: f64,
+    entry_price: f64,
+    margin: f64,
+    maintenance_ratio: f64,
+) -> Option<f64> {
+    let base = size.abs();
+    let price = if size > 0.0 {
+        (entry_price - margin / base) / (1.0 - maintenance_ratio)
+    } else {
+        (entry_price + margin / base) / (1.0 + maintenance_ratio)
+    };
+    (price.is_finite() && price > 0.0).then_some(price)
+}
+
+async fn get_open_orders(
+    State(state): State<AppState>,
+    Query(query): Query<OpenOrdersQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<Value>, ApiError> {
+    validate_wallet(&query.wallet)?;
+    log_request("/orders/open", &uri, serialize_payload(&query));
+    let mut args = json!({
+        "wallet": query.wallet,
+        "subAccountId": query.sub_account_id.unwrap_or(0),
+    });
+    if let Some(market) = &query.market {
+        extend_args(&mut args, &json!({ "market": market }))?;
+    }
+    call_worker(&state, "getOpenOrders", args, WORKER_TIMEOUT)
+        .await
+        .map(Json)
+}
+
+async fn cancel_orders(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<CancelOrdersRequest>,
+) -> Result<Json<Value>, ApiError> {
+    log_request("/orders/cancel", &uri, serialize_payload(&body));
+    ensure_trading_allowed(&state, TradeAction::Close)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        None,
+    )?;
+    let value = cancel_orders_build(&state, &body, acting.sub_account_id).await?;
+    Ok(Json(value))
+}
+
+async fn cancel_orders_execute(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<CancelOrdersRequest>,
+) -> Result<Json<Value>, ApiError> {
+    log_request("/orders/cancel/execute", &uri, serialize_payload(&body));
+    ensure_live(&state)?;
+    ensure_trading_allowed(&state, TradeAction::Close)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        None,
+    )?;
+    let value = cancel_orders_build(&state, &body, acting.sub_account_id).await?;
+    let market = body.market.as_deref();
+    if body.simulate {
+        let simulated = attach_simulation(&state, value, &body.wallet, market).await?;
+        return Ok(Json(simulated));
+    }
+
+    if body.asynchronous {
+        let submitted =
+            submit_transaction(&state, value, "cancel", &acting, &body.wallet, market).await?;
+        return Ok(Json(submitted));
+    }
+    let executed = execute_transaction(
+        &state,
+        value,
+        "cancel",
+        &acting,
+        vec![ordering_key(
+            &body.wallet,
+            acting.sub_account_id,
+            market.unwrap_or(COLLATERAL_ORDERING_MARKET),
+        )],
+    )
+    .await?;
+    Ok(Json(executed))
+}
+
+async fn cancel_orders_build(
+    state: &AppState,
+    body: &CancelOrdersRequest,
+    sub_account_id: u16,
+) -> Result<Value, ApiError> {
+    validate_wallet(&body.wallet)?;
+    let selectors = [
+        body.order_ids.is_some(),
+        body.user_order_ids.is_some(),
+        body.market.is_some(),
+        body.all,
+    ];
+    if selectors.iter().filter(|set| **set).count() != 1 {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "exactly one of orderIds, userOrderIds, market or all is required",
+        ));
+    }
+    let empty = |ids: Option<usize>| ids == Some(0);
+    if empty(body.order_ids.as_ref().map(Vec::len))
+        || empty(body.user_order_ids.as_ref().map(Vec::len))
+    {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "order id lists must not be empty",
+        ));
+    }
+    if body
+        .user_order_ids
+        .as_ref()
+        .is_some_and(|ids| ids.contains(&0))
+    {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "userOrderIds must be between 1 and 255",
+        ));
+    }
+
+    let mut args = json!({
+        "wallet": body.wallet,
+        "subAccountId": sub_account_id,
+        "all": body.all,
+    });
+    extend_args(
+        &mut args,
+        &json!({
+            "orderIds": body.order_ids,
+            "userOrderIds": body.user_order_ids,
+            "market": body.market,
+        }),
+    )?;
+    info!("cancel orders request -> {}", body.wallet);
+    call_worker(state, "cancelOrders", args, WORKER_TIMEOUT).await
+}
+
+async fn modify_order(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<ModifyOrderRequest>,
+) -> Result<Json<Value>, ApiError> {
+    log_request("/orders/modify", &uri, serialize_payload(&body));
+    ensure_trading_allowed(&state, modify_action(&body.changes))?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        None,
+    )?;
+    let (_, value) = modify_order_build(&state, &mut body, &acting).await?;
+    Ok(Json(value))
+}
+
+async fn modify_order_execute(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<ModifyOrderRequest>,
+) -> Result<Json<Value>, ApiError> {
+    log_request("/orders/modify/execute", &uri, serialize_payload(&body));
+    ensure_live(&state)?;
+    ensure_trading_allowed(&state, modify_action(&body.changes))?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        None,
+    )?;
+    let (market, value) = modify_order_build(&state, &mut body, &acting).await?;
+    if let Some(order) = modified_open_order(&body.changes, &market, &value)? {
+        enforce_risk(
+            &state,
+            &body.wallet,
+            &acting,
+            "modify",
+            body.simulate,
+            RiskOrder::Open(order),
+        )
+        .await?;
+    }
+    if body.simulate {
+        let simulated = attach_simulation(&state, value, &body.wallet, Some(&market)).await?;
+        return Ok(Json(simulated));
+    }
+
+    if body.asynchronous {
+        let submitted = submit_transaction(
+            &state,
+            value,
+            "modify",
+            &acting,
+            &body.wallet,
+            Some(&market),
+        )
+        .await?;
+        return Ok(Json(submitted));
+    }
+    let executed = execute_transaction(
+        &state,
+        value,
+        "modify",
+        &acting,
+        vec![ordering_key(&body.wallet, acting.sub_account_id, &market)],
+    )
+    .await?;
+    Ok(Json(executed))
+}
+
+/// Builds the modification and returns it with the order's market, which only the worker
+/// knows and which registry wallets are then held to.
+async fn modify_order_build(
+    state: &AppState,
+    body: &mut ModifyOrderRequest,
+    acting: &ActingWallet,
+) -> Result<(String, Value), ApiError> {
+    validate_wallet(&body.wallet)?;
+    match (body.order_id, body.user_order_id) {
+        (Some(_), None) | (None, Some(1..)) => {}
+        _ => {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "exactly one of orderId or userOrderId (1-255) is required",
+            ))
+        }
+    }
+    let changes = &body.changes;
+    for (name, value) in [
+        ("size", changes.size),
+        ("price", changes.price),
+        ("triggerPrice", changes.trigger_price),
+    ] {
+        if let Some(value) = value {
+            ensure_positive(name, value)?;
+        }
+    }
+    if changes
+        .oracle_price_offset
+        .is_some_and(|offset| !offset.is_finite())
+    {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "oraclePriceOffset must be a number",
+        ));
+    }
+    if changes.max_ts.is_some_and(|max_ts| max_ts <= 0) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "maxTs must be a Unix time in seconds",
+        ));
+    }
+    let mut args = json!({
+        "wallet": body.wallet,
+        "subAccountId": acting.sub_account_id,
+    });
+    extend_args(
+        &mut args,
+        &json!({ "orderId": body.order_id, "userOrderId": body.user_order_id }),
+    )?;
+    extend_args(&mut args, changes)?;
+    if args.as_object().map_or(0, |obj| obj.len()) <= 3 {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "nothing to modify"));
+    }
+
+    let value = call_worker(state, "modifyOrder", args, WORKER_TIMEOUT).await?;
+    let market = value
+        .pointer("/order/market")
+        .and_then(Value::as_str)
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "worker response missing order market",
+            )
+        })?;
+    // Re-resolving with the market applies the registry's market list.
+    resolve_wallet(
+        state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        Some(acting.sub_account_id),
+        Some(&market),
+    )?;
+    Ok((market, value))
+}
+
+/// The order as the worker says it stands once modified.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModifiedOrder {
+    direction: PositionSide,
+    unfilled_size: f64,
+    price: Option<f64>,
+    reduce_only: bool,
+}
+
+/// What a modification is checked against the risk limits as: an open of the order's
+/// unfilled size at its new price, with no margin added. `None` when it changes neither size
+/// nor price, or leaves the order reduce-only.
+fn modified_open_order<'a>(
+    changes: &OrderChanges,
+    market: &'a str,
+    value: &Value,
+) -> Result<Option<OpenOrder<'a>>, ApiError> {
+    if modify_action(changes) == TradeAction::Close
+        || (changes.size.is_none() && changes.price.is_none())
+    {
+        return Ok(None);
+    }
+    let order: ModifiedOrder = value
+        .get("order")
+        .cloned()
+        .and_then(|order| serde_json::from_value(order).ok())
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "worker response missing modified order",
+            )
+        })?;
+    if order.reduce_only {
+        return Ok(None);
+    }
+    let size = match order.direction {
+        PositionSide::Long => order.unfilled_size,
+        PositionSide::Short => -order.unfilled_size,
+    };
+    Ok(Some(OpenOrder {
+        market,
+        size,
+        leverage: 0.0,
+        margin: 0.0,
+        limit_price: order.price,
+    }))
+}
+
+/// Modifying an order counts as opening unless it makes the order reduce-only.
+fn modify_action(changes: &OrderChanges) -> TradeAction {
+    if changes.reduce_only == Some(true) {
+        TradeAction::Close
+    } else {
+        TradeAction::Open
+    }
+}
+
+async fn open_bracket(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<BracketRequest>,
+) -> Result<Json<Value>, ApiError> {
+    log_request("/orders/bracket", &uri, serialize_payload(&body));
+    ensure_trading_allowed(&state, TradeAction::Open)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.open.wallet,
+        body.open.wallet_label.as_deref(),
+        body.open.sub_account_id,
+        Some(&body.open.market),
+    )?;
+    let value = open_bracket_build(&state, &body, acting.sub_account_id).await?;
+    Ok(Json(value))
+}
+
+async fn open_bracket_execute(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<BracketRequest>,
+) -> Result<Json<Value>, ApiError> {
+    log_request("/orders/bracket/execute", &uri, serialize_payload(&body));
+    ensure_live(&state)?;
+    ensure_trading_allowed(&state, TradeAction::Open)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.open.wallet,
+        body.open.wallet_label.as_deref(),
+        body.open.sub_account_id,
+        Some(&body.open.market),
+    )?;
+    let open = &body.open;
+    let wallet_label = acting.signing_label()?.to_string();
+    if !open.simulate {
+        ensure_no_open_bracket(&state, &open.wallet, acting.sub_account_id, &open.market).await?;
+    }
+    let value = open_bracket_build(&state, &body, acting.sub_account_id).await?;
+    enforce_risk(
+        &state,
+        &open.wallet,
+        &acting,
+        "bracket",
+        open.simulate,
+        RiskOrder::Open(OpenOrder {
+            market: &open.market,
+            size: open.size,
+            leverage: open.leverage,
+            margin: open.margin,
+            limit_price: open.order.price,
+        }),
+    )
+    .await?;
+    if open.simulate {
+        let simulated = attach_simulation(&state, value, &open.wallet, Some(&open.market)).await?;
+        return Ok(Json(simulated));
+    }
+
+    let legs = placed_legs(&value)?;
+    let bracket = new_bracket(
+        &open.wallet,
+        wallet_label,
+        &acting,
+        &open.market,
+        legs,
+        Some(open.size),
     );
+    if !open.asynchronous {
+        let executed = execute_bracket_change(&state, value, &acting, None, bracket).await?;
+        if let Some(signature) = executed.get("txSignature").and_then(Value::as_str) {
+            if let Err(err) = decode_and_store_signature(&state, signature).await {
+                warn!(%signature, error = %err, "failed to persist decoded bracket actions");
+            }
+        }
+        return Ok(Json(executed));
+    }
+
+    // The monitor promotes the bracket once the submission confirms.
+    let mut bracket = Bracket {
+        state: BracketState::Pending,
+        ..bracket
+    };
+    bracket.created_at = insert_pending_bracket(&state, &bracket).await?;
+    let submitted = match submit_transaction(
+        &state,
+        value,
+        "bracket",
+        &acting,
+        &open.wallet,
+        Some(&open.market),
+    )
+    .await
+    {
+        Ok(submitted) => submitted,
+        Err(err) => {
+            bracket.state = BracketState::Failed;
+            bracket.detail = Some(err.message.clone());
+            store_bracket(&state, &mut bracket).await?;
+            return Err(err);
+        }
+    };
+    bracket.submission_id = submitted
+        .get("submissionId")
+        .and_then(Value::as_str)
+        .map(str::to_owned);
+    store_bracket(&state, &mut bracket).await?;
+    Ok(Json(with_bracket(submitted, &bracket)))
+}
+
+async fn open_bracket_build(
+    state: &AppState,
+    body: &BracketRequest,
+    sub_account_id: u16,
+) -> Result<Value, ApiError> {
+    let mut args = open_isolated_args(&body.open, sub_account_id)?;
+    validate_bracket_legs(body.take_profit, body.stop_loss, Some(body.open.size))?;
+    extend_args(
+        &mut args,
+        &json!({ "takeProfit": body.take_profit, "stopLoss": body.stop_loss }),
+    )?;
+    info!("open bracket request -> {}", body.open.market);
+    call_worker(state, "openBracket", args, WORKER_TIMEOUT).await
+}
+
+async fn amend_bracket(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<AmendBracketRequest>,
+) -> Result<Json<Value>, ApiError> {
+    log_request("/orders/bracket/amend", &uri, serialize_payload(&body));
+    ensure_trading_allowed(&state, TradeAction::Close)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        Some(&body.market),
+    )?;
+    let (_, value) = amend_bracket_build(&state, &body, &acting).await?;
+    Ok(Json(value))
+}
+
+async fn amend_bracket_execute(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<AmendBracketRequest>,
+) -> Result<Json<Value>, ApiError> {
+    log_request(
+        "/orders/bracket/amend/execute",
+        &uri,
+        serialize_payload(&body),
+    );
+    ensure_live(&state)?;
+    ensure_trading_allowed(&state, TradeAction::Close)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        Some(&body.market),
+    )?;
+    let wallet_label = acting.signing_label()?.to_string();
+    let (existing, value) = amend_bracket_build(&state, &body, &acting).await?;
+    if body.simulate {
+        let simulated = attach_simulation(&state, value, &body.wallet, Some(&body.market)).await?;
+        return Ok(Json(simulated));
+    }
+
+    let placed = placed_legs(&value)?;
+    let updated = match &existing {
+        Some(bracket) => {
+            let mut updated = bracket.clone();
+            if placed.take_profit.is_some() {
+                updated.take_profit = placed.take_profit;
+            }
+            if placed.stop_loss.is_some() {
+                updated.stop_loss = placed.stop_loss;
+            }
+            updated.detail = None;
+            updated
+        }
+        None => {
+            let mut bracket = new_bracket(
+                &body.wallet,
+                wallet_label,
+                &acting,
+                &body.market,
+                placed,
+                None,
+            );
+            // Legs can only be attached to a position that is already open.
+            bracket.position_seen = true;
+            bracket
+        }
+    };
+    let executed =
+        execute_bracket_change(&state, value, &acting, existing.as_ref(), updated).await?;
+    Ok(Json(executed))
+}
+
+/// Builds the leg change for an amend, returning the bracket it replaces legs of, if any.
+async fn amend_bracket_build(
+    state: &AppState,
+    body: &AmendBracketRequest,
+    acting: &ActingWallet,
+) -> Result<(Option<Bracket>, Value), ApiError> {
+    validate_wallet(&body.wallet)?;
+    validate_bracket_legs(body.take_profit, body.stop_loss, None)?;
+    let existing = find_open_bracket(state, &body.wallet, acting.sub_account_id, &body.market)
+        .await?
+        .map(ensure_bracket_settled)
+        .transpose()?;
+    let cancel: Vec<u8> = existing
+        .iter()
+        .flat_map(Bracket::legs)
+        .filter(|(kind, _)| match kind {
+            BracketLegKind::TakeProfit => body.take_profit.is_some(),
+            BracketLegKind::StopLoss => body.stop_loss.is_some(),
+        })
+        .map(|(_, leg)| leg.user_order_id)
+        .collect();
+    let mut args = json!({
+        "wallet": body.wallet,
+        "subAccountId": acting.sub_account_id,
+        "market": body.market,
+        "cancelUserOrderIds": cancel,
+    });
+    extend_args(
+        &mut args,
+        &json!({ "takeProfit": body.take_profit, "stopLoss": body.stop_loss }),
+    )?;
+    info!("amend bracket request -> {}", body.market);
+    let value = call_worker(state, "buildBracketLegs", args, WORKER_TIMEOUT).await?;
+    Ok((existing, value))
+}
+
+async fn remove_bracket(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<RemoveBracketRequest>,
+) -> Result<Json<Value>, ApiError> {
+    log_request("/orders/bracket/remove", &uri, serialize_payload(&body));
+    ensure_trading_allowed(&state, TradeAction::Close)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        Some(&body.market),
+    )?;
+    let (_, value) = remove_bracket_build(&state, &body, &acting).await?;
+    Ok(Json(value))
+}
+
+async fn remove_bracket_execute(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(mut body): Json<RemoveBracketRequest>,
+) -> Result<Json<Value>, ApiError> {
+    log_request(
+        "/orders/bracket/remove/execute",
+        &uri,
+        serialize_payload(&body),
+    );
+    ensure_live(&state)?;
+    ensure_trading_allowed(&state, TradeAction::Close)?;
+    let acting = resolve_wallet(
+        &state,
+        &mut body.wallet,
+        body.wallet_label.as_deref(),
+        body.sub_account_id,
+        Some(&body.market),
+    )?;
+    acting.signing_label()?;
+    let (existing, value) = remove_bracket_build(&state, &body, &acting).await?;
+    if body.simulate {
+        let simulated = attach_simulation(&state, value, &body.wallet, Some(&body.market)).await?;
+        return Ok(Json(simulated));
+    }
+
+    let mut updated = existing.clone();
+    if body.leg != Some(BracketLegKind::StopLoss) {
+        updated.take_profit = None;
+    }
+    if body.leg != Some(BracketLegKind::TakeProfit) {
+        updated.stop_loss = None;
+    }
+    if updated.legs().next().is_none() {
+        updated.state = BracketState::Cancelled;
+        updated.detail = Some("removed".into());
+    }
+    let executed = execute_bracket_change(&state, value, &acting, Some(&existing), updated).await?;
+    Ok(Json(executed))
+}
+
+async fn remove_bracket_build(
+    state: &AppState,
+    body: &RemoveBracketRequest,
+    acting: &ActingWallet,
+) -> Result<(Bracket, Value), ApiError> {
+    validate_wallet(&body.wallet)?;
+    let existing = find_open_bracket(state, &body.wallet, acting.sub_account_id, &body.market)
+        .await?
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "no open bracket for this position"))
+        .and_then(ensure_bracket_settled)?;
+    let cancel: Vec<u8> = existing
+        .legs()
+        .filter(|(kind, _)| body.leg.is_none_or(|leg| leg == *kind))
+        .map(|(_, leg)| leg.user_order_id)
+        .collect();
+    if cancel.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "the bracket has no such leg",
+        ));
+    }
+    let args = json!({
+        "wallet": body.wallet,
+        "subAccountId": acting.sub_account_id,
+        "market": body.market,
+        "cancelUserOrderIds": cancel,
+    });
+    info!("remove bracket request -> {}", body.market);
+    let value = call_worker(state, "buildBracketLegs", args, WORKER_TIMEOUT).await?;
+    Ok((existing, value))
+}
+
+async fn get_brackets(
+    State(state): State<AppState>,
+    Query(query): Query<BracketQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<Vec<Bracket>>, ApiError> {
+    log_request("/orders/brackets", &uri, serialize_payload(&query));
+    if let Some(wallet) = &query.wallet {
+        validate_wallet(wallet)?;
+    }
+    let limit = query.limit.unwrap_or(100).clamp(1, MAX_BRACKETS_LIMIT);
+    db::fetch_brackets(state.db.as_ref(), query.wallet.as_deref(), false, limit)
+        .await
+        .map(Json)
+        .map_err(bracket_db_error)
+}
+
+/// Positive trigger and limit prices, and for a known entry direction a take-profit on the
+/// profitable side of the stop-loss.
+fn validate_bracket_legs(
+    take_profit: Option<BracketLeg>,
+    stop_loss: Option<BracketLeg>,
+    size: Option<f64>,
+) -> Result<(), ApiError> {
+    if take_profit.is_none() && stop_loss.is_none() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "takeProfit or stopLoss is required",
+        ));
+    }
+    for (name, leg) in [("takeProfit", take_profit), ("stopLoss", stop_loss)] {
+        let Some(leg) = leg else { continue };
+        ensure_positive(&format!("{name}.triggerPrice"), leg.trigger_price)?;
+        if let Some(limit_price) = leg.limit_price {
+            ensure_positive(&format!("{name}.limitPrice"), limit_price)?;
+        }
+    }
+    if let (Some(take_profit), Some(stop_loss), Some(size)) = (take_profit, stop_loss, size) {
+        let ordered = if size > 0.0 {
+            take_profit.trigger_price > stop_loss.trigger_price
+        } else {
+            take_profit.trigger_price < stop_loss.trigger_price
+        };
+        if !ordered {
+            let side = if size > 0.0 { "above" } else { "below" };
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                format!("takeProfit.triggerPrice must be {side} stopLoss.triggerPrice"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct PlacedLegs {
+    take_profit: Option<PlacedLeg>,
+    stop_loss: Option<PlacedLeg>,
+}
+
+fn placed_legs(value: &Value) -> Result<PlacedLegs, ApiError> {
+    value
+        .get("legs")
+        .map(|legs| serde_json::from_value(legs.clone()))
+        .transpose()
+        .map(Option::unwrap_or_default)
+        .map_err(|err| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("invalid bracket legs from worker: {err}"),
+            )
+        })
+}
+
+/// `entry_size` is the signed size the entry opens, kept until the monitor sees the
+/// position so a leg that fills first still counts as a fill.
+fn new_bracket(
+    wallet: &str,
+    wallet_label: String,
+    acting: &ActingWallet,
+    market: &str,
+    legs: PlacedLegs,
+    entry_size: Option<f64>,
+) -> Bracket {
+    Bracket {
+        id: Uuid::new_v4().to_string(),
+        wallet: wallet.to_string(),
+        wallet_label,
+        sub_account_id: acting.sub_account_id,
+        market: market_key(market),
+        state: BracketState::Active,
+        take_profit: legs.take_profit,
+        stop_loss: legs.stop_loss,
+        position_seen: false,
+        position_size: entry_size,
+        submission_id: None,
+        signature: None,
+        detail: None,
+        created_at: 0,
+        updated_at: 0,
+    }
+}
+
+async fn find_open_bracket(
+    state: &AppState,
+    wallet: &str,
+    sub_account_id: u16,
+    market: &str,
+) -> Result<Option<Bracket>, ApiError> {
+    db::fetch_open_bracket(
+        state.db.as_ref(),
+        wallet,
+        sub_account_id,
+        &market_key(market),
+    )
+    .await
+    .map_err(bracket_db_error)
+}
+
+async fn ensure_no_open_bracket(
+    state: &AppState,
+    wallet: &str,
+    sub_account_id: u16,
+    market: &str,
+) -> Result<(), ApiError> {
+    match find_open_bracket(state, wallet, sub_account_id, market).await? {
+        Some(bracket) => Err(bracket_conflict(&bracket)),
+        None => Ok(()),
+    }
+}
+
+/// Leg changes wait until the bracket's last transaction has settled.
+fn ensure_bracket_settled(bracket: Bracket) -> Result<Bracket, ApiError> {
+    if bracket.state == BracketState::Pending {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "the bracket has a transaction in flight",
+        )
+        .with_details(json!({ "bracketId": bracket.id })));
+    }
+    Ok(bracket)
+}
+
+fn bracket_conflict(bracket: &Bracket) -> ApiError {
+    ApiError::new(
+        StatusCode::CONFLICT,
+        "the position already has an open bracket",
+    )
+    .with_details(json!({ "bracketId": bracket.id }))
+}
+
+async fn insert_pending_bracket(state: &AppState, bracket: &Bracket) -> Result<i64, ApiError> {
+    let pending = Bracket {
+        state: BracketState::Pending,
+        ..bracket.clone()
+    };
+    db::insert_bracket(state.db.as_ref(), &pending)
+        .await
+        .map_err(bracket_db_error)?
+        .ok_or_else(|| bracket_conflict(bracket))
+}
+
+async fn store_bracket(state: &AppState, bracket: &mut Bracket) -> Result<(), ApiError> {
+    bracket.updated_at = db::update_bracket(state.db.as_ref(), bracket)
+        .await
+        .map_err(bracket_db_error)?;
+    Ok(())
+}
+
+/// Sends a bracket's transaction with the bracket held `pending`, so the monitor leaves it
+/// alone, then stores `after`. When the transaction does not land `before` is restored, or
+/// a new bracket is marked failed.
+async fn execute_bracket_change(
+    state: &AppState,
+    value: Value,
+    acting: &ActingWallet,
+    before: Option<&Bracket>,
+    mut after: Bracket,
+) -> Result<Value, ApiError> {
+    match before {
+        Some(before) => {
+            let mut pending = Bracket {
+                state: BracketState::Pending,
+                ..before.clone()
+            };
+            store_bracket(state, &mut pending).await?;
+        }
+        None => {
+            after.created_at = insert_pending_bracket(state, &after).await?;
+        }
+    }
+
+    let key = ordering_key(&after.wallet, after.sub_account_id, &after.market);
+    match execute_transaction(state, value, "bracket", acting, vec![key]).await {
+        Ok(executed) => {
+            after.signature = executed
+                .get("txSignature")
+                .and_then(Value::as_str)
+                .map(str::to_owned);
+            store_bracket(state, &mut after)
+                .await
+                .map_err(ApiError::after_send)?;
+            Ok(with_bracket(executed, &after))
+        }
+        Err(err) => {
+            let mut restored = match before {
+                Some(before) => before.clone(),
+                None => Bracket {
+                    state: BracketState::Failed,
+                    detail: Some(err.message.clone()),
+                    ..after
+                },
+            };
+            store_bracket(state, &mut restored)
+                .await
+                .map_err(|store_err| ApiError {
+                    sent: err.sent,
+                    ..store_err
+                })?;
+            Err(err)
+        }
+    }
+}
+
+fn with_bracket(mut value: Value, bracket: &Bracket) -> Value {
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("bracket".into(), json!(bracket));
+    }
+    value
+}
+
+fn bracket_db_error(err: anyhow::Error) -> ApiError {
+    error!(?err, "bracket database error");
+    ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+}
+
+async fn open_isolated_build(
+    state: &AppState,
+    body: &OpenIsolatedRequest,
+    sub_account_id: u16,
+) -> Result<Value, ApiError> {
+    let args = open_isolated_args(body, sub_account_id)?;
+    info!(
+        "open isolated request -> {} ({})",
+        body.market,
+        body.order.order_type.as_str()
+    );
+    call_worker(state, "openIsolated", args, WORKER_TIMEOUT).await
+}
+
+/// Validates an open-isolated request and turns it into worker arguments.
+fn open_isolated_args(body: &OpenIsolatedRequest, sub_account_id: u16) -> Result<Value, ApiError> {
+    validate_wallet(&body.wallet)?;
+    validate_open(body.size, body.leverage, body.margin, &body.order)?;
+
+    let mut args = json!({
+        "wallet": body.wallet,
+        "subAccountId": sub_account_id,
+        "market": body.market,
+        "size": body.size,
+        "leverage": body.leverage,
+        "margin": body.margin,
+    });
+    extend_args(&mut args, &body.order)?;
+    Ok(args)
+}
+
+fn validate_open(
+    size: f64,
+    leverage: f64,
+    margin: f64,
+    order: &OrderOptions,
+) -> Result<(), ApiError> {
+    ensure_positive("margin", margin)?;
+    if !size.is_finite() || size == 0.0 {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "size must be a non-zero number",
+        ));
+    }
+    if !leverage.is_finite() || leverage <= 0.0 || leverage > 100.0 {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "leverage must be between 0 and 100",
+        ));
+    }
+    validate_order_options(order)
+}
+
+/// Checks that the fields set in `order` belong to its order type and are in range, so
+/// the worker only sees combinations Drift accepts.
+fn validate_order_options(order: &OrderOptions) -> Result<(), ApiError> {
+    let invalid = |message: String| Err(ApiError::new(StatusCode::BAD_REQUEST, message));
+    let kind = order.order_type;
+    let name = kind.as_str();
+
+    let needs_price = matches!(kind, OrderKind::Limit | OrderKind::TriggerLimit);
+    match order.price {
+        Some(price) if !needs_price => {
+            return invalid(format!("price is not used by {name} orders (got {price})"))
+        }
+        Some(price) => ensure_positive("price", price)?,
+        None if needs_price => return invalid(format!("{name} orders need a price")),
+        None => {}
+    }
+
+    if kind.is_trigger() {
+        match order.trigger_price {
+            Some(price) => ensure_positive("triggerPrice", price)?,
+            None => return invalid(format!("{name} orders need a triggerPrice")),
+        }
+        if order.trigger_condition.is_none() {
+            return invalid(format!(
+                "{name} orders need a triggerCondition (above or below)"
+            ));
+        }
+    } else if order.trigger_price.is_some() || order.trigger_condition.is_some() {
+        return invalid(format!(
+            "triggerPrice and triggerCondition are only used by trigger orders, not {name}"
+        ));
+    }
+
+    match order.oracle_price_offset {
+        Some(offset) if kind != OrderKind::Oracle => {
+            return invalid(format!(
+                "oraclePriceOffset is only used by oracle orders (got {offset})"
+            ))
+        }
+        Some(offset) if !offset.is_finite() => {
+            return invalid("oraclePriceOffset must be a finite number".into())
+        }
+        None if kind == OrderKind::Oracle => {
+            return invalid("oracle orders need an oraclePriceOffset".into())
+        }
+        _ => {}
+    }
+
+    if order.post_only != PostOnly::None && kind != OrderKind::Limit {
+        return invalid(format!("postOnly is only used by limit orders, not {name}"));
+    }
+    if order.immediate_or_cancel {
+        if !matches!(kind, OrderKind::Limit | OrderKind::Oracle) {
+            return invalid(format!(
+                "immediateOrCancel is only used by limit and oracle orders, not {name}"
+            ));
+        }
+        if order.post_only != PostOnly::None {
+            return invalid("an order cannot be both postOnly and immediateOrCancel".into());
+        }
+    }
+
+    let has_auction_prices =
+        order.auction_start_price.is_some() || order.auction_end_price.is_some();
+    if order.auction_duration.is_some() || has_auction_prices {
+        if kind.is_trigger() {
+            return invalid(
+                "trigger orders start their auction when triggered; auction fields are not used"
+                    .into(),
+            );
+        }
+        if order.auction_duration.is_none() {
+            return invalid("auctionStartPrice and auctionEndPrice need an auctionDuration".into());
+        }
+        for (field, value) in [
+            ("auctionStartPrice", order.auction_start_price),
+            ("auctionEndPrice", order.auction_end_price),
+        ] {
+            let Some(value) = value else { continue };
+            if kind == OrderKind::Oracle {
+                if !value.is_finite() {
+                    return invalid(format!("{field} must be a finite number"));
+                }
+            } else {
+                ensure_positive(field, value)?;
+            }
+        }
+    }
+
+    if let Some(max_ts) = order.max_ts {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or_default();
+        if max_ts <= now {
+            return invalid(format!("maxTs {max_ts} is not in the future"));
+        }
+    }
+    Ok(())
+}
+
+/// Adds the fields of `extra` to the worker `args` object. Null fields are left out, since
+/// the worker's schemas accept a missing optional field but not `null`.
+fn extend_args<T: Serialize>(args: &mut Value, extra: &T) -> Result<(), ApiError> {
+    let extra = serde_json::to_value(extra).map_err(|err| {
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to serialize worker arguments: {err}"),
+        )
+    })?;
+    if let (Some(args), Value::Object(extra)) = (args.as_object_mut(), extra) {
+        args.extend(extra.into_iter().filter(|(_, value)| !value.is_null()));
+    }
+    Ok(())
+}
+
+async fn close_position_build(
+    state: &AppState,
+    body: &ClosePositionRequest,
+    sub_account_id: u16,
+) -> Result<Value, ApiError> {
+    debug!(
+        "[CLOSE_POSITION_BUILD] Starting build for wallet: {}, market: {}, size: {:?}",
+        body.wallet, body.market, body.size
+    );
+
+    validate_wallet(&body.wallet)?;
+
+    if let Some(size) = body.size {
+        if !size.is_finite() || size <= 0.0 {
+            warn!("[CLOSE_POSITION_BUILD] Invalid size provided: {}", size);
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "size must be positive when provided",
+            ));
+        }
+    }
+
+    // Build args conditionally - only include size if it's Some(value)
+    // TypeScript schema expects size to be optional (undefined) or number, not null
+    let args = if let Some(size) = body.size {
+        json!({
+            "wallet": body.wallet,
+            "subAccountId": sub_account_id,
+            "market": body.market,
+            "size": size,
+        })
+    } else {
+        json!({
+            "wallet": body.wallet,
+            "subAccountId": sub_account_id,
+            "market": body.market,
+        })
+    };
+
+    debug!(
+        "[CLOSE_POSITION_BUILD] Calling worker with args: {:?}",
+        args
+    );
+
+    match call_worker(state, "closePosition", args, WORKER_TIMEOUT).await {
+        Ok(result) => {
+            debug!("[CLOSE_POSITION_BUILD] Worker returned successfully");
+            Ok(result)
+        }
+        Err(e) => {
+            error!("[CLOSE_POSITION_BUILD] Worker call failed: {:?}", e);
+            Err(e)
+        }
+    }
+}
+
+async fn transfer_margin_build(
+    state: &AppState,
+    body: &TransferMarginRequest,
+    sub_account_id: u16,
+) -> Result<Value, ApiError> {
+    debug!(
+        "[TRANSFER_MARGIN_BUILD] Starting build for wallet: {}, market: {}, delta: {}",
+        body.wallet, body.market, body.delta
+    );
+
+    validate_wallet(&body.wallet)?;
+
+    if !body.delta.is_finite() || body.delta == 0.0 {
+        warn!(
+            "[TRANSFER_MARGIN_BUILD] Invalid delta provided: {}",
+            body.delta
+        );
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "delta must be a non-zero number",
+        ));
+    }
+
+    let operation = if body.delta > 0.0 {
+        "deposit"
+    } else {
+        "withdraw"
+    };
+    info!(
+        "[TRANSFER_MARGIN_BUILD] Transfer operation: {} (delta: {})",
+        operation, body.delta
+    );
+
+    let args = json!({
+        "wallet": body.wallet,
+        "subAccountId": sub_account_id,
+        "market": body.market,
+        "delta": body.delta,
+    });
+
+    debug!(
+        "[TRANSFER_MARGIN_BUILD] Calling worker with args: {:?}",
+        args
+    );
+
+    match call_worker(state, "transferMargin", args, WORKER_TIMEOUT).await {
+        Ok(result) => {
+            debug!("[TRANSFER_MARGIN_BUILD] Worker returned successfully");
+            Ok(result)
+        }
+        Err(e) => {
+            error!("[TRANSFER_MARGIN_BUILD] Worker call failed: {:?}", e);
+            Err(e)
+        }
+    }
+}
+
+async fn deposit_native_build(
+    state: &AppState,
+    body: &DepositNativeRequest,
+    sub_account_id: u16,
+) -> Result<Value, ApiError> {
+    debug!(
+        "[DEPOSIT_NATIVE_BUILD] Starting build for wallet: {}, amount: {}, market: {:?}",
+        body.wallet, body.amount, body.market
+    );
+
+    validate_wallet(&body.wallet)?;
+
+    if !body.amount.is_finite() || body.amount <= 0.0 {
+        warn!(
+            "[DEPOSIT_NATIVE_BUILD] Invalid amount provided: {}",
+            body.amount
+        );
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "amount must be positive",
+        ));
+    }
+
+    let args = json!({
+        "wallet": body.wallet,
+        "subAccountId": sub_account_id,
+        "amount": body.amount,
+        "market": body.market,
+    });
+
+    debug!(
+        "[DEPOSIT_NATIVE_BUILD] Calling worker with args: {:?}",
+        args
+    );
+
+    match call_worker(state, "depositNativeSol", args, WORKER_TIMEOUT).await {
+        Ok(result) => {
+            debug!("[DEPOSIT_NATIVE_BUILD] Worker returned successfully");
+            Ok(result)
+        }
+        Err(e) => {
+            error!("[DEPOSIT_NATIVE_BUILD] Worker call failed: {:?}", e);
+            Err(e)
+        }
+    }
+}
+
+async fn deposit_token_build(
+    state: &AppState,
+    body: &DepositTokenRequest,
+    sub_account_id: u16,
+) -> Result<Value, ApiError> {
+    debug!(
+        "[DEPOSIT_TOKEN_BUILD] Starting build for wallet: {}, amount: {}, market: {:?}",
+        body.wallet, body.amount, body.market
+    );
+
+    validate_wallet(&body.wallet)?;
+
+    if !body.amount.is_finite() || body.amount <= 0.0 {
+        warn!(
+            "[DEPOSIT_TOKEN_BUILD] Invalid amount provided: {}",
+            body.amount
+        );
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "amount must be positive",
+        ));
+    }
+
+    let args = json!({
+        "wallet": body.wallet,
+        "subAccountId": sub_account_id,
+        "amount": body.amount,
+        "market": body.market,
+    });
+
+    debug!("[DEPOSIT_TOKEN_BUILD] Calling worker with args: {:?}", args);
+
+    match call_worker(state, "depositToken", args, WORKER_TIMEOUT).await {
+        Ok(result) => {
+            debug!("[DEPOSIT_TOKEN_BUILD] Worker returned successfully");
+            Ok(result)
+        }
+        Err(e) => {
+            error!("[DEPOSIT_TOKEN_BUILD] Worker call failed: {:?}", e);
+            Err(e)
+        }
+    }
+}
+
+async fn get_positions(
+    State(state): State<AppState>,
+    Query(query): Query<WalletQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<Value>, ApiError> {
+    validate_wallet(&query.wallet)?;
+    log_request("/positions", &uri, serialize_payload(&query));
+    let args = json!({ "wallet": query.wallet });
+    state
+        .ipc
+        .call("getPositions", args, Duration::from_secs(5))
+        .await
+        .map(Json)
+        .map_err(map_ipc_error)
+}
+
+async fn get_position_details(
+    State(state): State<AppState>,
+    Query(query): Query<WalletQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<Value>, ApiError> {
+    validate_wallet(&query.wallet)?;
+    log_request("/positions/details", &uri, serialize_payload(&query));
+    let args = json!({ "wallet": query.wallet });
+    state
+        .ipc
+        .call("getPositionDetails", args, Duration::from_secs(5))
+        .await
+        .map(Json)
+        .map_err(map_ipc_error)
+}
+
+async fn get_balances(
+    State(state): State<AppState>,
+    Query(query): Query<WalletQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<Value>, ApiError> {
+    validate_wallet(&query.wallet)?;
+    log_request("/balances", &uri, serialize_payload(&query));
+    let args = json!({ "wallet": query.wallet });
+    state
+        .ipc
+        .call("getBalances", args, Duration::from_secs(5))
+        .await
+        .map(Json)
+        .map_err(map_ipc_error)
+}
+
+async fn get_trades(
+    State(state): State<AppState>,
+    Query(query): Query<WalletQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<Value>, ApiError> {
+    validate_wallet(&query.wallet)?;
+    log_request("/trade-history", &uri, serialize_payload(&query));
+    let args = json!({ "wallet": query.wallet });
+    state
+        .ipc
+        .call("getTrades", args, Duration::from_secs(5))
+        .await
+        .map(Json)
+        .map_err(map_ipc_error)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MarketRef {
+    symbol: String,
+    market_index: u16,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PositionDetail {
+    market_index: u16,
+    position_size: f64,
+    unrealized_pnl: f64,
+}
+
+async fn get_pnl(
+    State(state): State<AppState>,
+    Query(query): Query<PnlQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<PnlReport>, ApiError> {
+    validate_wallet(&query.wallet)?;
+    log_request("/pnl", &uri, serialize_payload(&query));
+    let authority = Pubkey::from_str(&query.wallet)
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "wallet must be a valid public key"))?;
+    let now = now_ms();
+    let to = query.to.unwrap_or(now);
+    let from = query.from.unwrap_or(to - DEFAULT_PNL_WINDOW_MS);
+    if from >= to {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "from must be before to",
+        ));
+    }
+
+    let markets: Vec<MarketRef> = worker_value(
+        call_worker(&state, "listMarkets", json!({}), WORKER_TIMEOUT).await?,
+        "market list",
+    )?;
+    let market_index = match query.market.as_deref() {
+        Some(symbol) => {
+            let market: MarketRef = worker_value(
+                call_worker(
+                    &state,
+                    "getMarket",
+                    json!({ "symbol": symbol }),
+                    WORKER_TIMEOUT,
+                )
+                .await?,
+                "market data",
+            )?;
+            Some(market.market_index)
+        }
+        None => None,
+    };
+
+    let sub_account_id = query.sub_account_id.unwrap_or(0);
+    let user_account = state
+        .decoder
+        .user_account_pubkey(&authority, sub_account_id)
+        .to_string();
+    let days = db::fetch_pnl_days(state.db.as_ref(), &user_account, from, to, market_index)
+        .await
+        .map_err(|err| {
+            error!(?err, wallet = %query.wallet, "failed to fetch pnl events");
+            ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        })?;
+
+    // Unrealized PnL is as of now, so it only belongs to a window that has not ended.
+    let includes_unrealized = to >= now;
+    let positions: Vec<PositionDetail> = if includes_unrealized {
+        worker_value(
+            call_worker(
+                &state,
+                "getPositionDetails",
+                json!({ "wallet": query.wallet, "subAccountId": sub_account_id }),
+                WORKER_TIMEOUT,
+            )
+            .await?,
+            "position details",
+        )?
+    } else {
+        Vec::new()
+    };
+
+    let symbols: HashMap<u16, String> = markets
+        .into_iter()
+        .map(|market| (market.market_index, market.symbol))
+        .collect();
+    let market_pnl = |index: u16| MarketPnl {
+        market: symbols
+            .get(&index)
+            .cloned()
+            .unwrap_or_else(|| format!("MARKET_{index}")),
+        market_index: index,
+        totals: PnlTotals::default(),
+        days: Vec::new(),
+    };
+    let mut by_market: BTreeMap<u16, MarketPnl> = BTreeMap::new();
+    for day in days {
+        let market = by_market
+            .entry(day.market_index)
+            .or_insert_with(|| market_pnl(day.market_index));
+        market.totals.add_day(&day);
+        market.days.push(day);
+    }
+    for position in positions
+        .into_iter()
+        .filter(|position| market_index.is_none_or(|index| index == position.market_index))
+    {
+        by_market
+            .entry(position.market_index)
+            .or_insert_with(|| market_pnl(position.market_index))
+            .totals
+            .add_unrealized(position.unrealized_pnl);
+    }
+
+    let mut totals = PnlTotals::default();
+    for market in by_market.values() {
+        totals.add(&market.totals);
+    }
+
+    Ok(Json(PnlReport {
+        wallet: query.wallet,
+        sub_account_id,
+        from,
+        to,
+        includes_unrealized,
+        totals,
+        markets: by_market.into_values().collect(),
+    }))
+}
+
+async fn get_funding(
+    State(state): State<AppState>,
+    Query(query): Query<FundingQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<FundingReport>, ApiError> {
+    validate_wallet(&query.wallet)?;
+    log_request("/funding", &uri, serialize_payload(&query));
+    let limit = query.limit.unwrap_or(100).clamp(1, MAX_FUNDING_LIMIT);
+
+    let rate: FundingRate = worker_value(
+        call_worker(
+            &state,
+            "getFundingRate",
+            json!({ "symbol": query.market }),
+            WORKER_TIMEOUT,
+        )
+        .await?,
+        "funding rate",
+    )?;
+    let positions: Vec<PositionDetail> = worker_value(
+        call_worker(
+            &state,
+            "getPositionDetails",
+            json!({ "wallet": query.wallet }),
+            WORKER_TIMEOUT,
+        )
+        .await?,
+        "position details",
+    )?;
+    let position_size = positions
+        .iter()
+        .find(|position| position.market_index == rate.market_index)
+        .map(|position| position.position_size)
+        .filter(|size| *size != 0.0);
+
+    let (payments, total_payments) =
+        db::fetch_funding_payments(state.db.as_ref(), &query.wallet, rate.market_index, limit)
+            .await
+            .map_err(|err| {
+                error!(?err, wallet = %query.wallet, "failed to fetch funding payments");
+                ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+            })?;
+
+    Ok(Json(FundingReport {
+        wallet: query.wallet,
+        market: rate.symbol.clone(),
+        projected_payment: position_size.map(|size| -size * rate.projected_rate),
+        position_size,
+        rate,
+        total_payments,
+        payments,
+    }))
+}
+
+fn worker_value<T: serde::de::DeserializeOwned>(value: Value, what: &str) -> Result<T, ApiError> {
+    serde_json::from_value(value).map_err(|err| {
+        ApiError::new(
+            StatusCode::BAD_GATEWAY,
+            format!("invalid {what} from worker: {err}"),
+        )
+    })
+}
+
+async fn get_market(
+    State(state): State<AppState>,
+    Path(symbol): Path<String>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<Value>, ApiError> {
+    log_request("/markets", &uri, None);
+    let args = json!({ "symbol": symbol });
+    state
+        .ipc
+        .call("getMarket", args, Duration::from_secs(5))
+        .await
+        .map(Json)
+        .map_err(map_ipc_error)
+}
+
+async fn get_isolated_balance(
+    State(state): State<AppState>,
+    Query(query): Query<IsolatedBalanceQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<Value>, ApiError> {
+    validate_wallet(&query.wallet)?;
+    log_request(
+        "/positions/isolated-balance",
+        &uri,
+        serialize_payload(&query),
+    );
+    let args = json!({
+        "wallet": query.wallet,
+        "market": query.market,
+    });
+    state
+        .ipc
+        .call("getIsolatedBalance", args, Duration::from_secs(5))
+        .await
+        .map(Json)
+        .map_err(map_ipc_error)
+}
+
+async fn get_server_public_key(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<Value>, ApiError> {
+    log_request("/server/public-key", &uri, None);
+    let args = json!({});
+    state
+        .ipc
+        .call("getServerPublicKey", args, Duration::from_secs(5))
+        .await
+        .map(Json)
+        .map_err(map_ipc_error)
+}
+
+async fn list_wallets(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+) -> Json<Vec<WalletSummary>> {
+    log_request("/wallets", &uri, None);
+    Json(
+        state
+            .executor
+            .wallets()
+            .iter()
+            .map(|wallet| wallet.summary())
+            .collect(),
+    )
+}
+
+async fn stream_positions(
+    State(state): State<AppState>,
+    Query(query): Query<WalletQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    validate_wallet(&query.wallet)?;
+    log_request("/stream/positions", &uri, serialize_payload(&query));
+    let feed = state.streams.subscribe(&query.wallet);
+    let last: Option<Arc<WalletSnapshot>> = None;
+
+    // First item is the full snapshot, every later one only carries what changed.
+    let events = stream::unfold(
+        (feed, last),
+        |(mut feed, last): (SnapshotReceiver, _)| async move {
+            loop {
+                let Some(previous) = last.as_ref() else {
+                    let current = feed.wait_for(Option::is_some).await.ok()?.clone()?;
+                    let event = Event::default()
+                        .event("snapshot")
+                        .json_data(&*current)
+                        .ok()?;
+                    return Some((Ok(event), (feed, Some(current))));
+                };
+                feed.changed().await.ok()?;
+                let current = feed.borrow_and_update().clone()?;
+                if let Some(diff) = current.diff(previous) {
+                    let event = Event::default().event("diff").json_data(diff).ok()?;
+                    return Some((Ok(event), (feed, Some(current))));
+                }
+            }
+        },
+    );
+
+    Ok(Sse::new(events).keep_alive(
+        KeepAlive::new()
+            .interval(STREAM_HEARTBEAT)
+            .text("heartbeat"),
+    ))
+}
+
+async fn call_worker(
+    state: &AppState,
+    function: &str,
+    args: Value,
+    timeout: Duration,
+) -> Result<Value, ApiError> {
+    state
+        .ipc
+        .call(function, args, timeout)
+        .await
+        .map_err(map_ipc_error)
+}
+
+/// Who a build or execute request acts for once `walletLabel` is resolved.
+struct ActingWallet {
+    /// Registry label, `None` for wallets the server cannot sign for.
+    label: Option<String>,
+    sub_account_id: u16,
+}
+
+impl ActingWallet {
+    fn signing_label(&self) -> Result<&str, ApiError> {
+        self.label.as_deref().ok_or_else(|| {
+            ApiError::new(
+                StatusCode::FORBIDDEN,
+                "wallet is not a configured server wallet",
+            )
+        })
+    }
+}
+
+/// Resolves `walletLabel`, or a `wallet` that belongs to the registry, and fills in
+/// `wallet`. Registered wallets are held to their sub-accounts and, for perp `market`s,
+/// their markets. Other wallets can still build unsigned transactions.
+fn resolve_wallet(
+    state: &AppState,
+    wallet: &mut String,
+    label: Option<&str>,
+    sub_account_id: Option<u16>,
+    market: Option<&str>,
+) -> Result<ActingWallet, ApiError> {
+    let registry = state.executor.wallets();
+    let entry = match label.map(str::trim) {
+        Some(label) => {
+            let entry = registry.get(label).ok_or_else(|| {
+                ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("unknown walletLabel '{label}'"),
+                )
+            })?;
+            let pubkey = entry.pubkey().to_string();
+            if !wallet.trim().is_empty() && wallet.trim() != pubkey {
+                return Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("wallet does not belong to walletLabel '{label}'"),
+                ));
+            }
+            *wallet = pubkey;
+            entry
+        }
+        None => match registry.by_pubkey(wallet) {
+            Some(entry) => entry,
+            None => {
+                return Ok(ActingWallet {
+                    label: None,
+                    sub_account_id: sub_account_id.unwrap_or(0),
+                })
+            }
+        },
+    };
+
+    let sub_account_id = sub_account_id.unwrap_or_else(|| entry.default_sub_account());
+    if !entry.allows_sub_account(sub_account_id) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!(
+                "sub-account {sub_account_id} is not allowed for wallet '{}'",
+                entry.label
+            ),
+        )
+        .with_details(json!({
+            "walletLabel": entry.label,
+            "allowedSubAccounts": entry.sub_accounts,
+        })));
+    }
+    if let Some(market) = market.filter(|market| !entry.allows_market(market)) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!(
+                "market {market} is not allowed for wallet '{}'",
+                entry.label
+            ),
+        )
+        .with_details(json!({ "walletLabel": entry.label })));
+    }
+    Ok(ActingWallet {
+        label: Some(entry.label.clone()),
+        sub_account_id,
+    })
+}
+
+fn transfer_action(delta: f64) -> TradeAction {
+    if delta > 0.0 {
+        TradeAction::Deposit
+    } else {
+        TradeAction::Withdraw
+    }
+}
+
+/// Applies the trading mode to a build or execute request before anything is built.
+/// Rejects `/execute` routes the paper book cannot fill while paper trading is on.
+fn ensure_live(state: &AppState) -> Result<(), ApiError> {
+    if state.paper.is_none() {
+        return Ok(());
+    }
+    Err(ApiError::new(
+        StatusCode::NOT_IMPLEMENTED,
+        "not available in paper trading mode",
+    )
+    .with_details(json!({ "code": "PAPER_TRADING" })))
+}
+
+/// Fills an `/execute` request against the paper book instead of sending a transaction.
+/// Risk limits are left out, since they are measured against the on-chain account.
+async fn execute_paper(
+    paper: &PaperBook,
+    wallet: &str,
+    acting: &ActingWallet,
+    market: &str,
+    order: PaperOrder,
+    dry_run: bool,
+) -> Result<Json<Value>, ApiError> {
+    acting.signing_label()?;
+    let account = PaperAccount {
+        wallet,
+        sub_account_id: acting.sub_account_id,
+        market,
+    };
+    let execution = paper
+        .execute(&account, order, dry_run)
+        .await
+        .map_err(map_paper_error)?;
+    let mut value = json!({
+        "paper": true,
+        "outcome": if dry_run { "simulated" } else { "confirmed" },
+        "fill": execution.fill,
+        "position": execution.position,
+    });
+    if !dry_run {
+        value["txSignature"] = json!(execution.signature);
+    }
+    Ok(Json(value))
+}
+
+fn map_paper_error(err: PaperError) -> ApiError {
+    match err {
+        PaperError::Rejected(message) => ApiError::new(StatusCode::BAD_REQUEST, message)
+            .with_details(json!({ "code": "PAPER_REJECTED" })),
+        PaperError::Worker(err) => map_ipc_error(err),
+        PaperError::Market(message) => ApiError::new(StatusCode::BAD_GATEWAY, message),
+        PaperError::Database(err) => {
+            error!(?err, "paper trading database error");
+            ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        }
+        PaperError::Config(message) => ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, message),
+    }
+}
+
+async fn get_paper_positions(
+    State(state): State<AppState>,
+    Query(query): Query<PaperPositionsQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<Vec<PaperPosition>>, ApiError> {
+    log_request("/paper/positions", &uri, serialize_payload(&query));
+    let paper = state
+        .paper
+        .as_ref()
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "paper trading is not enabled"))?;
+    validate_wallet(&query.wallet)?;
+    paper
+        .positions(&query.wallet, query.sub_account_id)
+        .await
+        .map(Json)
+        .map_err(map_paper_error)
+}
+
+fn ensure_trading_allowed(state: &AppState, action: TradeAction) -> Result<(), ApiError> {
+    state.trading.check(action).map_err(|status| {
+        let (code, message, http_status) = match status.mode {
+            TradingMode::Halted => (
+                "TRADING_HALTED",
+                "trading is halted",
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            _ => (
+                "TRADING_REDUCE_ONLY",
+                "trading is reduce-only: only closes and margin deposits are allowed",
+                StatusCode::FORBIDDEN,
+            ),
+        };
+        ApiError::new(http_status, message).with_details(json!({
+            "code": code,
+            "tradingMode": status,
+        }))
+    })
+}
+
+async fn health(State(state): State<AppState>) -> Json<Value> {
+    Json(json!({
+        "status": "ok",
+        "tradingMode": state.trading.status(),
+    }))
+}
+
+async fn get_trading_mode(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+) -> Json<TradingModeStatus> {
+    log_request("/admin/trading-mode", &uri, None);
+    Json(state.trading.status())
+}
+
+async fn set_trading_mode(
+    State(state): State<AppState>,
+    OriginalUri(uri): OriginalUri,
+    Json(body): Json<TradingModeRequest>,
+) -> Result<Json<TradingModeStatus>, ApiError> {
+    log_request("/admin/trading-mode", &uri, serialize_payload(&body));
+    let reason = body
+        .reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+    state
+        .trading
+        .set(body.mode, reason)
+        .await
+        .map(Json)
+        .map_err(|err| {
+            error!(?err, "failed to store trading mode");
+            ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        })
+}
+
+async fn enforce_risk(
+    state: &AppState,
+    wallet: &str,
+    acting: &ActingWallet,
+    route: &str,
+    dry_run: bool,
+    order: RiskOrder<'_>,
+) -> Result<(), ApiError> {
+    let ctx = RiskContext {
+        wallet,
+        wallet_label: acting.label.as_deref(),
+        sub_account_id: acting.sub_account_id,
+        route,
+        dry_run,
+    };
+    state.risk.check(&ctx, &order).await.map_err(map_risk_error)
+}
+
+fn map_risk_error(err: RiskError) -> ApiError {
+    match err {
+        RiskError::Breach(breach) => {
+            let mut details = json!({ "code": breach.code });
+            if let (Some(obj), Value::Object(extra)) = (details.as_object_mut(), breach.details) {
+                obj.extend(extra);
+            }
+            ApiError::new(StatusCode::FORBIDDEN, breach.message).with_details(details)
+        }
+        RiskError::Worker(err) => map_ipc_error(err),
+        RiskError::Snapshot(msg) => {
+            ApiError::new(StatusCode::BAD_GATEWAY, format!("risk check failed: {msg}"))
+        }
+        RiskError::Database(err) => {
+            error!(?err, "database error during risk check");
+            ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        }
+        RiskError::Config(msg) => ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, msg),
+    }
+}
+
+async fn get_risk_events(
+    State(state): State<AppState>,
+    Query(query): Query<RiskEventsQuery>,
+    OriginalUri(uri): OriginalUri,
+) -> Result<Json<Vec<RiskEvent>>, ApiError> {
+    log_request("/risk/events", &uri, serialize_payload(&query));
+    if let Some(wallet) = &query.wallet {
+        validate_wallet(wallet)?;
+    }
+    let limit = query.limit.unwrap_or(100).clamp(1, MAX_RISK_EVENTS_LIMIT);
+    db::fetch_risk_events(state.db.as_ref(), query.wallet.as_deref(), limit)
+        .await
+        .map(Json)
+        .map_err(|err| {
+            error!(?err, "failed to fetch risk events");
+            ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        })
+}
+
+async fn execute_transaction(
+    state: &AppState,
+    mut value: Value,
+    route: &str,
+    acting: &ActingWallet,
+    ordering_keys: Vec<String>,
+) -> Result<Value, ApiError> {
+    let wallet = acting.signing_label()?;
+    let tx_base64 = value
+        .get("txBase64")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "worker response missing txBase64",
+            )
+        })?;
+    let last_valid_block_height = value.get("lastValidBlockHeight").and_then(Value::as_u64);
+    let result = state
+        .executor
+        .execute(ExecutionRequest {
+            tx_base64,
+            wallet,
+            route,
+            ordering_keys,
+            last_valid_block_height,
+            progress: None,
+        })
+        .await;
+    let context = json!({
+        "route": route,
+        "walletLabel": wallet,
+        "subAccountId": acting.sub_account_id,
+    });
+    let receipt = match result {
+        Ok(receipt) => {
+            notify_execution(state, context, Ok(&receipt)).await;
+            receipt
+        }
+        Err(err) => {
+            let event = failure_event(&err);
+            let api_error = map_execution_failure(state, err);
+            notify_execution(state, context, Err((event, &api_error))).await;
+            return Err(api_error);
+        }
+    };
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("txSignature".into(), json!(receipt.signature.to_string()));
+        obj.insert("outcome".into(), json!("confirmed"));
+        obj.insert("slot".into(), json!(receipt.slot));
+        if !receipt.expired_signatures.is_empty() {
+            let expired: Vec<String> = receipt
+                .expired_signatures
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            obj.insert("expiredSignatures".into(), json!(expired));
+        }
+    }
+    Ok(value)
+}
+
+/// Records a submission and executes it in the background; progress is read back through
+/// `GET /transactions/:id`.
+async fn submit_transaction(
+    state: &AppState,
+    value: Value,
+    route: &'static str,
+    acting: &ActingWallet,
+    wallet: &str,
+    market: Option<&str>,
+) -> Result<Value, ApiError> {
+    submit_transaction_as(state, Uuid::new_v4(), value, route, acting, wallet, market).await
+}
+
+/// [`submit_transaction`] under a submission id chosen by the caller.
+async fn submit_transaction_as(
+    state: &AppState,
+    id: Uuid,
+    mut value: Value,
+    route: &'static str,
+    acting: &ActingWallet,
+    wallet: &str,
+    market: Option<&str>,
+) -> Result<Value, ApiError> {
+    let wallet_label = acting.signing_label()?.to_string();
+    let tx_base64 = value
+        .get("txBase64")
+        .and_then(Value::as_str)
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "worker response missing txBase64",
+            )
+        })?;
+    let last_valid_block_height = value.get("lastValidBlockHeight").and_then(Value::as_u64);
+    db::insert_submission(state.db.as_ref(), id, route, wallet, market)
+        .await
+        .map_err(|err| {
+            error!(?err, route, "failed to record submission");
+            ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        })?;
 
-    validate_wallet(&body.wallet)?;
+    let ordering_key = ordering_key(
+        wallet,
+        acting.sub_account_id,
+        market.unwrap_or(COLLATERAL_ORDERING_MARKET),
+    );
+    let state_for_task = state.clone();
+    tokio::spawn(async move {
+        let (progress, events) = mpsc::unbounded_channel();
+        let recorder = tokio::spawn(record_progress(state_for_task.clone(), id, events));
+        let result = state_for_task
+            .executor
+            .execute(ExecutionRequest {
+                tx_base64: &tx_base64,
+                wallet: &wallet_label,
+                route,
+                ordering_keys: vec![ordering_key],
+                last_valid_block_height,
+                progress: Some(progress),
+            })
+            .await;
+        finish_submission(&state_for_task, id, result, recorder).await;
+    });
 
-    if !body.amount.is_finite() || body.amount <= 0.0 {
-        warn!(
-            "[DEPOSIT_NATIVE_BUILD] Invalid amount provided: {}",
-            body.amount
-        );
-        return Err(ApiError::new(
-            StatusCode::BAD_REQUEST,
-            "amount must be positive",
-        ));
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("submissionId".into(), json!(id.to_string()));
+        obj.insert("state".into(), json!(SubmissionState::Built));
     }
+    Ok(value)
+}
 
-    let args = json!({
-        "wallet": body.wallet,
-        "amount": body.amount,
-        "market": body.market,
-    });
+/// Resumes tracking of submissions a previous run left unfinished. Ones that never got as
+/// far as signing are marked failed, since their worker-built transaction was not kept.
+pub async fn resume_submissions(state: &AppState) -> anyhow::Result<usize> {
+    let submissions = db::fetch_resumable_submissions(state.db.as_ref()).await?;
+    let count = submissions.len();
+    for submission in submissions {
+        let id = submission.id;
+        let (Some(signed_tx), Some(last_valid)) =
+            (submission.signed_tx, submission.last_valid_block_height)
+        else {
+            let update = db::SubmissionUpdate {
+                error: Some("interrupted by a restart before signing".into()),
+                ..Default::default()
+            };
+            if let Err(err) = db::record_submission_transition(
+                state.db.as_ref(),
+                id,
+                SubmissionState::Failed,
+                &update,
+            )
+            .await
+            {
+                warn!(?err, %id, "failed to close interrupted submission");
+            }
+            continue;
+        };
 
-    debug!(
-        "[DEPOSIT_NATIVE_BUILD] Calling worker with args: {:?}",
-        args
-    );
+        info!(%id, state = submission.state.as_str(), "resuming submission");
+        let state = state.clone();
+        tokio::spawn(async move {
+            let (progress, events) = mpsc::unbounded_channel();
+            let recorder = tokio::spawn(record_progress(state.clone(), id, events));
+            let result = state
+                .executor
+                .resume(&signed_tx, last_valid, Some(progress))
+                .await;
+            finish_submission(&state, id, result, recorder).await;
+        });
+    }
+    Ok(count)
+}
 
-    match call_worker(state, "depositNativeSol", args, WORKER_TIMEOUT).await {
-        Ok(result) => {
-            debug!("[DEPOSIT_NATIVE_BUILD] Worker returned successfully");
-            Ok(result)
-        }
-        Err(e) => {
-            error!("[DEPOSIT_NATIVE_BUILD] Worker call failed: {:?}", e);
-            Err(e)
+async fn record_progress(
+    state: AppState,
+    id: Uuid,
+    mut events: mpsc::UnboundedReceiver<ExecutionEvent>,
+) {
+    while let Some(event) = events.recv().await {
+        let (next, update) = match event {
+            // Written before the executor may send, so a restart can resume tracking.
+            ExecutionEvent::Signed {
+                signature,
+                tx_base64,
+                last_valid_block_height,
+                recorded,
+            } => {
+                let update = db::SubmissionUpdate {
+                    signature: Some(signature.to_string()),
+                    signed_tx: Some(tx_base64),
+                    last_valid_block_height: Some(last_valid_block_height),
+                    ..Default::default()
+                };
+                let result = db::record_submission_transition(
+                    state.db.as_ref(),
+                    id,
+                    SubmissionState::Signed,
+                    &update,
+                )
+                .await;
+                if let Err(err) = &result {
+                    error!(?err, %id, "failed to record signed transaction");
+                }
+                let _ = recorded.send(result.map_err(|_| "database error".to_string()));
+                continue;
+            }
+            ExecutionEvent::Sent { signature } => (
+                SubmissionState::Sent,
+                db::SubmissionUpdate {
+                    signature: Some(signature.to_string()),
+                    ..Default::default()
+                },
+            ),
+            ExecutionEvent::Processed { signature, slot } => (
+                SubmissionState::Processed,
+                db::SubmissionUpdate {
+                    signature: Some(signature.to_string()),
+                    slot: Some(slot),
+                    ..Default::default()
+                },
+            ),
+            ExecutionEvent::Confirmed { signature, slot } => (
+                SubmissionState::Confirmed,
+                db::SubmissionUpdate {
+                    signature: Some(signature.to_string()),
+                    slot: Some(slot),
+                    ..Default::default()
+                },
+            ),
+            ExecutionEvent::Finalized { signature, slot } => (
+                SubmissionState::Finalized,
+                db::SubmissionUpdate {
+                    signature: Some(signature.to_string()),
+                    slot: Some(slot),
+                    ..Default::default()
+                },
+            ),
+            ExecutionEvent::Resigning { signature } => (
+                SubmissionState::Expired,
+                db::SubmissionUpdate {
+                    signature: Some(signature.to_string()),
+                    detail: Some("blockhash expired, re-signing".into()),
+                    ..Default::default()
+                },
+            ),
+        };
+        if let Err(err) =
+            db::record_submission_transition(state.db.as_ref(), id, next, &update).await
+        {
+            warn!(?err, %id, state = next.as_str(), "failed to record submission progress");
         }
     }
 }
 
-async fn deposit_token_build(
+/// Stores the actions of a confirmed submission, or its terminal failure once every progress
+/// event before it has been recorded.
+async fn finish_submission(
     state: &AppState,
-    body: &DepositTokenRequest,
-) -> Result<Value, ApiError> {
-    debug!(
-        "[DEPOSIT_TOKEN_BUILD] Starting build for wallet: {}, amount: {}, market: {:?}",
-        body.wallet, body.amount, body.market
-    );
-
-    validate_wallet(&body.wallet)?;
+    id: Uuid,
+    result: Result<ExecutionReceipt, ExecutorError>,
+    recorder: tokio::task::JoinHandle<()>,
+) {
+    let err = match result {
+        Ok(receipt) => {
+            notify_execution(state, submission_context(state, id).await, Ok(&receipt)).await;
+            let signature = receipt.signature.to_string();
+            if let Err(err) = decode_and_store_signature(state, &signature).await {
+                warn!(%id, %signature, error = %err, "failed to persist decoded actions");
+            }
+            return;
+        }
+        Err(err) => err,
+    };
 
-    if !body.amount.is_finite() || body.amount <= 0.0 {
-        warn!(
-            "[DEPOSIT_TOKEN_BUILD] Invalid amount provided: {}",
-            body.amount
-        );
-        return Err(ApiError::new(
-            StatusCode::BAD_REQUEST,
-            "amount must be positive",
-        ));
+    let _ = recorder.await;
+    let (next, signature) = match &err {
+        ExecutorError::Expired { signature } => (SubmissionState::Expired, Some(*signature)),
+        ExecutorError::FailedOnChain { signature, .. }
+        | ExecutorError::Unconfirmed { signature, .. } => {
+            (SubmissionState::Failed, Some(*signature))
+        }
+        _ => (SubmissionState::Failed, None),
+    };
+    let event = failure_event(&err);
+    let api_error = map_execution_failure(state, err);
+    let update = db::SubmissionUpdate {
+        signature: signature.map(|signature| signature.to_string()),
+        error: Some(api_error.message.clone()),
+        error_details: api_error.details.clone().map(Value::Object),
+        ..Default::default()
+    };
+    if let Err(err) = db::record_submission_transition(state.db.as_ref(), id, next, &update).await {
+        warn!(?err, %id, "failed to record submission outcome");
     }
+    notify_execution(
+        state,
+        submission_context(state, id).await,
+        Err((event, &api_error)),
+    )
+    .await;
+}
 
-    let args = json!({
-        "wallet": body.wallet,
-        "amount": body.amount,
-        "market": body.market,
-    });
+/// What a webhook says about an asynchronous submission besides its outcome.
+async fn submission_context(state: &AppState, id: Uuid) -> Value {
+    let mut context = json!({ "submissionId": id.to_string() });
+    if !state.webhooks.enabled() {
+        return context;
+    }
+    match db::fetch_submission(state.db.as_ref(), id).await {
+        Ok(Some(submission)) => {
+            context["route"] = json!(submission.route);
+            context["wallet"] = json!(submission.wallet);
+            context["market"] = json!(submission.market);
+        }
+        Ok(None) => {}
+        Err(err) => warn!(?err, %id, "failed to load submission for webhook"),
+    }
+    context
+}
 
-    debug!("[DEPOSIT_TOKEN_BUILD] Calling worker with args: {:?}", args);
+fn failure_event(err: &ExecutorError) -> WebhookEvent {
+    match err {
+        ExecutorError::Expired { .. } => WebhookEvent::TransactionExpired,
+        _ => WebhookEvent::TransactionFailed,
+    }
+}
 
-    match call_worker(state, "depositToken", args, WORKER_TIMEOUT).await {
-        Ok(result) => {
-            debug!("[DEPOSIT_TOKEN_BUILD] Worker returned successfully");
-            Ok(result)
+/// Sends `transaction.confirmed`, `transaction.failed` or `transaction.expired`, adding the
+/// outcome to `context`.
+async fn notify_execution(
+    state: &AppState,
+    mut context: Value,
+    outcome: Result<&ExecutionReceipt, (WebhookEvent, &ApiError)>,
+) {
+    let event = match outcome {
+        Ok(receipt) => {
+            context["txSignature"] = json!(receipt.signature.to_string());
+            context["slot"] = json!(receipt.slot);
+            WebhookEvent::TransactionConfirmed
         }
-        Err(e) => {
-            error!("[DEPOSIT_TOKEN_BUILD] Worker call failed: {:?}", e);
-            Err(e)
+        Err((event, api_error)) => {
+            if let Some(details) = &api_error.details {
+                for (key, value) in details {
+                    context[key] = value.clone();
+                }
+            }
+            context["error"] = json!(api_error.message);
+            event
         }
-    }
+    };
+    state.webhooks.emit(event, context).await;
 }
 
-async fn get_positions(
+async fn get_transaction(
     State(state): State<AppState>,
-    Query(query): Query<WalletQuery>,
     OriginalUri(uri): OriginalUri,
-) -> Result<Json<Value>, ApiError> {
-    validate_wallet(&query.wallet)?;
-    log_request("/positions", &uri, serialize_payload(&query));
-    let args = json!({ "wallet": query.wallet });
-    state
-        .ipc
-        .call("getPositions", args, Duration::from_secs(5))
+    Path(id): Path<String>,
+) -> Result<Json<TransactionSubmission>, ApiError> {
+    log_request("/transactions", &uri, None);
+    let id = Uuid::parse_str(id.trim())
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "invalid submission id"))?;
+    db::fetch_submission(state.db.as_ref(), id)
         .await
+        .map_err(|err| {
+            error!(?err, %id, "failed to fetch submission");
+            ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        })?
         .map(Json)
-        .map_err(map_ipc_error)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "submission not found"))
 }
 
-async fn get_position_details(
+async fn get_webhook_deliveries(
     State(state): State<AppState>,
-    Query(query): Query<WalletQuery>,
+    Query(query): Query<WebhookDeliveriesQuery>,
     OriginalUri(uri): OriginalUri,
-) -> Result<Json<Value>, ApiError> {
-    validate_wallet(&query.wallet)?;
-    log_request("/positions/details", &uri, serialize_payload(&query));
-    let args = json!({ "wallet": query.wallet });
-    state
-        .ipc
-        .call("getPositionDetails", args, Duration::from_secs(5))
-        .await
-        .map(Json)
-        .map_err(map_ipc_error)
+) -> Result<Json<Vec<WebhookDelivery>>, ApiError> {
+    log_request(
+        "/admin/webhooks/deliveries",
+        &uri,
+        serialize_payload(&query),
+    );
+    let limit = query
+        .limit
+        .unwrap_or(100)
+        .clamp(1, MAX_WEBHOOK_DELIVERIES_LIMIT);
+    db::fetch_webhook_deliveries(
+        state.db.as_ref(),
+        query.state,
+        query.event_type.as_deref(),
+        limit,
+    )
+    .await
+    .map(Json)
+    .map_err(|err| {
+        error!(?err, "failed to fetch webhook deliveries");
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+    })
 }
 
-async fn get_balances(
+/// Sends a delivery again, whether it was delivered, gave up or is still retrying.
+async fn replay_webhook_delivery(
     State(state): State<AppState>,
-    Query(query): Query<WalletQuery>,
     OriginalUri(uri): OriginalUri,
-) -> Result<Json<Value>, ApiError> {
-    validate_wallet(&query.wallet)?;
-    log_request("/balances", &uri, serialize_payload(&query));
-    let args = json!({ "wallet": query.wallet });
-    state
-        .ipc
-        .call("getBalances", args, Duration::from_secs(5))
+    Path(id): Path<i64>,
+) -> Result<Json<WebhookDelivery>, ApiError> {
+    log_request("/admin/webhooks/deliveries/replay", &uri, None);
+    if !state.webhooks.enabled() {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "webhooks are not configured; set WEBHOOK_URLS and WEBHOOK_SECRET",
+        ));
+    }
+    let delivery = db::replay_webhook_delivery(state.db.as_ref(), id)
         .await
-        .map(Json)
-        .map_err(map_ipc_error)
+        .map_err(|err| {
+            error!(?err, id, "failed to replay webhook delivery");
+            ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        })?
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "webhook delivery not found"))?;
+    state.webhooks.wake();
+    info!(id, url = %delivery.url, event = %delivery.event_type, "replaying webhook delivery");
+    Ok(Json(delivery))
 }
 
-async fn get_trades(
+async fn list_api_keys(
     State(state): State<AppState>,
-    Query(query): Query<WalletQuery>,
     OriginalUri(uri): OriginalUri,
-) -> Result<Json<Value>, ApiError> {
-    validate_wallet(&query.wallet)?;
-    log_request("/trade-history", &uri, serialize_payload(&query));
-    let args = json!({ "wallet": query.wallet });
-    state
-        .ipc
-        .call("getTrades", args, Duration::from_secs(5))
-        .await
-        .map(Json)
-        .map_err(map_ipc_error)
+) -> Result<Json<Vec<ApiKey>>, ApiError> {
+    log_request("/admin/api-keys", &uri, None);
+    state.api_keys.list().await.map(Json).map_err(|err| {
+        error!(?err, "failed to fetch api keys");
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+    })
 }
 
-async fn get_market(
+/// Returns the new key once; only its hash is stored.
+async fn create_api_key(
     State(state): State<AppState>,
-    Path(symbol): Path<String>,
     OriginalUri(uri): OriginalUri,
-) -> Result<Json<Value>, ApiError> {
-    let args = json!({ "symbol": symbol });
-    state
-        .ipc
-        .call("getMarket", args, Duration::from_secs(5))
+    Json(body): Json<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<CreatedApiKey>), ApiError> {
+    log_request("/admin/api-keys", &uri, serialize_payload(&body));
+    auth::check_new_key(&body.name, &body.scopes)
+        .map_err(|err| ApiError::new(StatusCode::BAD_REQUEST, err))?;
+    let name = body.name.trim();
+    let created = state
+        .api_keys
+        .create(name, &body.scopes)
         .await
-        .map(Json)
-        .map_err(map_ipc_error)
+        .map_err(|err| {
+            error!(?err, "failed to create api key");
+            ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        })?
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::CONFLICT,
+                format!("an active API key is already named '{name}'"),
+            )
+        })?;
+    info!(name, id = %created.info.id, scopes = ?created.info.scopes, "created api key");
+    Ok((StatusCode::CREATED, Json(created)))
 }
 
-async fn get_isolated_balance(
+async fn revoke_api_key(
     State(state): State<AppState>,
-    Query(query): Query<IsolatedBalanceQuery>,
     OriginalUri(uri): OriginalUri,
-) -> Result<Json<Value>, ApiError> {
-    validate_wallet(&query.wallet)?;
-    log_request(
-        "/positions/isolated-balance",
-        &uri,
-        serialize_payload(&query),
-    );
-    let args = json!({
-        "wallet": query.wallet,
-        "market": query.market,
-    });
-    state
-        .ipc
-        .call("getIsolatedBalance", args, Duration::from_secs(5))
+    Path(id): Path<String>,
+) -> Result<Json<ApiKey>, ApiError> {
+    log_request("/admin/api-keys/revoke", &uri, None);
+    let id = Uuid::parse_str(id.trim())
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "invalid api key id"))?;
+    let key = state
+        .api_keys
+        .revoke(id)
         .await
-        .map(Json)
-        .map_err(map_ipc_error)
+        .map_err(|err| {
+            error!(?err, %id, "failed to revoke api key");
+            ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        })?
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "api key not found"))?;
+    info!(%id, name = %key.name, "revoked api key");
+    Ok(Json(key))
 }
 
-async fn get_server_public_key(
+/// Like `map_executor_error`, but names the Drift error when a Drift instruction failed on-chain.
+fn map_execution_failure(state: &AppState, err: ExecutorError) -> ApiError {
+    let drift_error = match &err {
+        ExecutorError::FailedOnChain {
+            err: tx_err,
+            failed_program_id: Some(program),
+            ..
+        } if *program == state.decoder.program_id().to_string() => {
+            drift_error_from_tx_error(tx_err).map(|(_, error)| error)
+        }
+        _ => None,
+    };
+    let mut api_error = map_executor_error(err);
+    if let (Some(drift_error), Some(details)) = (drift_error, api_error.details.as_mut()) {
+        details.insert("driftError".into(), json!(drift_error));
+    }
+    api_error
+}
+
+async fn simulate_route(
     State(state): State<AppState>,
     OriginalUri(uri): OriginalUri,
+    Json(body): Json<SimulateRequest>,
 ) -> Result<Json<Value>, ApiError> {
-    log_request("/server/public-key", &uri, None);
-    let args = json!({});
-    state
-        .ipc
-        .call("getServerPublicKey", args, Duration::from_secs(5))
-        .await
-        .map(Json)
-        .map_err(map_ipc_error)
+    log_request("/simulate", &uri, serialize_payload(&body));
+    if let Some(wallet) = &body.wallet {
+        validate_wallet(wallet)?;
+    }
+    let simulation = simulate_transaction(
+        &state,
+        &body.tx_base64,
+        body.wallet.as_deref(),
+        body.market.as_deref(),
+    )
+    .await?;
+    Ok(Json(simulation))
 }
 
-async fn call_worker(
+async fn attach_simulation(
     state: &AppState,
-    function: &str,
-    args: Value,
-    timeout: Duration,
+    mut value: Value,
+    wallet: &str,
+    market: Option<&str>,
 ) -> Result<Value, ApiError> {
-    state
-        .ipc
-        .call(function, args, timeout)
-        .await
-        .map_err(map_ipc_error)
-}
-
-async fn execute_transaction(state: &AppState, mut value: Value) -> Result<Value, ApiError> {
     let tx_base64 = value
         .get("txBase64")
         .and_then(|v| v.as_str())
@@ -1038,43 +4481,172 @@ async fn execute_transaction(state: &AppState, mut value: Value) -> Result<Value
                 "worker response missing txBase64",
             )
         })?;
-    let signature = state
+    let simulation = simulate_transaction(state, tx_base64, Some(wallet), market).await?;
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("simulation".into(), simulation);
+    }
+    Ok(value)
+}
+
+async fn simulate_transaction(
+    state: &AppState,
+    tx_base64: &str,
+    wallet: Option<&str>,
+    market: Option<&str>,
+) -> Result<Value, ApiError> {
+    let user_account = wallet
+        .map(Pubkey::from_str)
+        .transpose()
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, "wallet must be a valid public key"))?
+        .map(|authority| state.decoder.user_account_pubkey(&authority, 0));
+    let accounts: Vec<Pubkey> = user_account.into_iter().collect();
+
+    let report = state
         .executor
-        .execute(tx_base64)
+        .simulate(tx_base64, &accounts)
         .await
         .map_err(map_executor_error)?;
+
+    let drift_program = state.decoder.program_id().to_string();
+    let drift_error = report
+        .err
+        .as_ref()
+        .filter(|_| report.failed_program_id.as_deref() == Some(drift_program.as_str()))
+        .and_then(drift_error_from_tx_error)
+        .map(|(instruction_index, error)| {
+            json!({
+                "instructionIndex": instruction_index,
+                "code": error.code,
+                "name": error.name,
+                "msg": error.msg,
+            })
+        });
+
+    // Decoding the post-simulation user account needs the Drift account coder,
+    // which lives in the worker.
+    let position_change = match (wallet, market, report.accounts.first()) {
+        (Some(wallet), Some(market), Some(Some(data))) => {
+            let args = json!({
+                "wallet": wallet,
+                "market": market,
+                "userAccountBase64": STANDARD.encode(data),
+            });
+            match call_worker(state, "decodePositionChange", args, WORKER_TIMEOUT).await {
+                Ok(change) => Some(change),
+                Err(err) => {
+                    warn!(?err, "failed to decode simulated position change");
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let mut value = serde_json::to_value(&report).map_err(|err| {
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to serialize simulation: {err}"),
+        )
+    })?;
     if let Some(obj) = value.as_object_mut() {
-        obj.insert("txSignature".into(), json!(signature.to_string()));
+        obj.insert("driftError".into(), json!(drift_error));
+        obj.insert("positionChange".into(), json!(position_change));
     }
     Ok(value)
 }
 
 async fn decode_and_store_signature(state: &AppState, signature: &str) -> Result<u64, String> {
-    let (_, actions) = state
+    let (dump, actions) = state
         .decoder
         .decode_signature(signature)
         .map_err(|err| format!("decode failed: {err:?}"))?;
 
-    db::insert_actions(state.db.as_ref(), &actions)
+    let rows = store_actions(state, signature, &actions)
         .await
-        .map_err(|err| format!("database insert failed: {err:?}"))
+        .map_err(|err| format!("database insert failed: {err:?}"))?;
+    let events = db::insert_pnl_events(state.db.as_ref(), &dump.pnl_events)
+        .await
+        .map_err(|err| format!("database insert failed: {err:?}"))?;
+    Ok(rows + events)
+}
+
+/// Stores decoded actions, announcing them through `actions.decoded` the first time a
+/// signature's actions are stored.
+async fn store_actions(
+    state: &AppState,
+    signature: &str,
+    actions: &[ActionRecord],
+) -> anyhow::Result<u64> {
+    let known = actions.is_empty()
+        || !state.webhooks.enabled()
+        || db::signature_has_actions(state.db.as_ref(), signature).await?;
+    let rows = db::insert_actions(state.db.as_ref(), actions).await?;
+    if !known {
+        state
+            .webhooks
+            .emit(
+                WebhookEvent::ActionsDecoded,
+                json!({ "signature": signature, "actions": actions }),
+            )
+            .await;
+    }
+    Ok(rows)
 }
 
 fn map_executor_error(err: ExecutorError) -> ApiError {
     match err {
-        ExecutorError::MissingKey => ApiError::new(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "server missing signing key",
-        ),
-        ExecutorError::InvalidKey(msg) => ApiError::new(
+        ExecutorError::Signer(err @ SignerError::Remote(_)) => {
+            ApiError::new(StatusCode::BAD_GATEWAY, err.to_string())
+        }
+        ExecutorError::Signer(err) => ApiError::new(
             StatusCode::INTERNAL_SERVER_ERROR,
-            format!("invalid signing key: {msg}"),
+            format!("signing failed: {err}"),
         ),
         ExecutorError::Decode(msg) => ApiError::new(
             StatusCode::BAD_REQUEST,
             format!("invalid transaction: {msg}"),
         ),
         ExecutorError::Rpc(msg) => ApiError::new(StatusCode::BAD_GATEWAY, msg),
+        ExecutorError::Config(msg) => ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, msg),
+        ExecutorError::UnknownWallet(label) => ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("unknown server wallet '{label}'"),
+        ),
+        ExecutorError::PolicyViolation(msg) => ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("transaction rejected by policy: {msg}"),
+        ),
+        ExecutorError::Record(msg) => ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to record signed transaction: {msg}"),
+        ),
+        ExecutorError::Expired { signature } => ApiError::new(
+            StatusCode::GATEWAY_TIMEOUT,
+            "transaction expired before confirmation",
+        )
+        .with_details(json!({
+            "outcome": "expired",
+            "txSignature": signature.to_string(),
+        }))
+        .after_send(),
+        ExecutorError::FailedOnChain { signature, err, .. } => ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("transaction failed on-chain: {err}"),
+        )
+        .with_details(json!({
+            "outcome": "failed",
+            "txSignature": signature.to_string(),
+        }))
+        .after_send(),
+        ExecutorError::Unconfirmed { signature, reason } => ApiError::new(
+            StatusCode::BAD_GATEWAY,
+            format!("transaction was sent but its outcome is unknown: {reason}"),
+        )
+        .with_details(json!({
+            "outcome": "unknown",
+            "txSignature": signature.to_string(),
+        }))
+        .after_send(),
     }
 }
 
@@ -1086,3 +4658,74 @@ fn log_request(label: &str, uri: &Uri, payload: Option<String>) {
 fn serialize_payload<T: serde::Serialize>(value: &T) -> Option<String> {
     serde_json::to_string(value).ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(value: Value) -> Result<Vec<ScheduleSlice>, ApiError> {
+        let mut body = json!({ "wallet": "wallet", "market": "SOL-PERP" });
+        body.as_object_mut()
+            .unwrap()
+            .extend(value.as_object().unwrap().clone());
+        schedule_slices(&serde_json::from_value(body).unwrap())
+    }
+
+    #[test]
+    fn slices_split_evenly_and_add_up() {
+        let start = now_ms() + 60_000;
+        let slices = schedule(json!({
+            "action": "open",
+            "size": -1.0,
+            "leverage": 2.0,
+            "margin": 10.0,
+            "slices": 3,
+            "durationSecs": 90,
+            "startAt": start,
+        }))
+        .unwrap();
+        assert_eq!(slices.len(), 3);
+        let due: Vec<i64> = slices.iter().map(|slice| slice.due_at).collect();
+        assert_eq!(due, [start, start + 30_000, start + 60_000]);
+        let size: f64 = slices.iter().map(|slice| slice.size.unwrap()).sum();
+        let margin: f64 = slices.iter().map(|slice| slice.margin.unwrap()).sum();
+        assert_eq!(size, -1.0);
+        assert_eq!(margin, 10.0);
+        assert!(slices
+            .iter()
+            .all(|slice| slice.state == SliceState::Pending));
+    }
+
+    #[test]
+    fn single_slice_needs_no_duration() {
+        let slices = schedule(json!({ "action": "close" })).unwrap();
+        assert_eq!(slices.len(), 1);
+        assert_eq!(slices[0].size, None);
+        assert_eq!(slices[0].margin, None);
+    }
+
+    #[test]
+    fn rejects_invalid_schedules() {
+        let open = |extra: Value| {
+            let mut body =
+                json!({ "action": "open", "size": 1.0, "leverage": 2.0, "margin": 10.0 });
+            body.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            schedule(body)
+        };
+        assert!(open(json!({ "slices": 0 })).is_err());
+        assert!(open(json!({ "slices": MAX_SCHEDULE_SLICES + 1, "durationSecs": 60 })).is_err());
+        assert!(open(json!({ "slices": 2 })).is_err());
+        assert!(
+            open(json!({ "slices": 2, "durationSecs": MAX_SCHEDULE_DURATION_SECS + 1 })).is_err()
+        );
+        assert!(open(json!({ "startAt": now_ms() - 2 * SCHEDULE_START_TOLERANCE_MS })).is_err());
+        assert!(open(json!({ "startAt": now_ms() + MAX_SCHEDULE_DELAY_MS + 60_000 })).is_err());
+        assert!(open(json!({ "margin": null })).is_err());
+
+        assert!(schedule(json!({ "action": "close", "slices": 2, "durationSecs": 60 })).is_err());
+        assert!(schedule(json!({ "action": "close", "margin": 1.0 })).is_err());
+        assert!(schedule(json!({ "action": "close", "size": -1.0 })).is_err());
+    }
+}
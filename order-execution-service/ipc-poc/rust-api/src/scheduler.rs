@@ -0,0 +1,238 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::time::MissedTickBehavior;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    db,
+    routes::{self, AppState},
+    trading_mode::TradeAction,
+    types::{
+        now_ms, Schedule, ScheduleAction, ScheduleSlice, ScheduleState, SliceState, SubmissionState,
+    },
+};
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Why a slice could not be handed to the executor.
+pub enum DispatchError {
+    /// The worker or database was unavailable; the slice is retried on the next tick.
+    Unavailable(String),
+    /// The order was refused, which fails the schedule.
+    Rejected(String),
+}
+
+/// Sends the slices of active schedules as they fall due. Each slice goes out as an
+/// asynchronous submission, so one interrupted by a restart is picked up by
+/// `resume_submissions` and settled here afterwards.
+#[derive(Clone)]
+pub struct Scheduler {
+    state: AppState,
+    interval: Duration,
+}
+
+impl Scheduler {
+    pub fn new(state: AppState, interval: Duration) -> Self {
+        Self { state, interval }
+    }
+
+    pub fn from_env(state: AppState) -> Self {
+        let interval = std::env::var("SCHEDULE_POLL_INTERVAL_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_POLL_INTERVAL);
+        Self::new(state, interval)
+    }
+
+    /// Runs the scheduler for the life of the process.
+    pub fn spawn(self) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            info!(interval = ?self.interval, "order scheduler started");
+            loop {
+                ticker.tick().await;
+                let schedules = match db::fetch_runnable_schedules(self.state.db.as_ref()).await {
+                    Ok(schedules) => schedules,
+                    Err(err) => {
+                        warn!(?err, "failed to load schedules");
+                        continue;
+                    }
+                };
+                for schedule in schedules {
+                    let id = schedule.id.clone();
+                    if let Err(err) = self.advance(schedule).await {
+                        warn!(%id, error = %err, "schedule check failed");
+                    }
+                }
+            }
+        })
+    }
+
+    /// Settles the slice in flight, then sends the next one once it is due. Slices go out
+    /// one at a time, in order.
+    async fn advance(&self, schedule: Schedule) -> Result<()> {
+        let id = Uuid::parse_str(&schedule.id).context("invalid schedule id")?;
+        let mut slices = db::fetch_schedule_slices(self.state.db.as_ref(), id).await?;
+
+        if let Some(slice) = slices
+            .iter_mut()
+            .find(|slice| slice.state == SliceState::Submitted)
+        {
+            if !self.settle(id, slice).await? {
+                return Ok(());
+            }
+            if slice.state == SliceState::Failed {
+                let detail = format!(
+                    "slice {} failed: {}",
+                    slice.index,
+                    slice.detail.as_deref().unwrap_or("unknown error")
+                );
+                return self.finish(id, ScheduleState::Failed, &detail).await;
+            }
+        }
+        if schedule.state != ScheduleState::Active {
+            return Ok(());
+        }
+
+        match slices
+            .iter_mut()
+            .find(|slice| slice.state == SliceState::Pending)
+        {
+            Some(slice) if slice.due_at <= now_ms() => self.dispatch(id, &schedule, slice).await,
+            Some(_) => Ok(()),
+            None => {
+                self.finish(id, ScheduleState::Completed, "all slices confirmed")
+                    .await
+            }
+        }
+    }
+
+    /// Reads the outcome of a submitted slice, returning false while it is still in flight.
+    async fn settle(&self, schedule_id: Uuid, slice: &mut ScheduleSlice) -> Result<bool> {
+        let submission_id = slice
+            .submission_id
+            .as_deref()
+            .map(Uuid::parse_str)
+            .transpose()
+            .context("invalid submission id")?;
+        let submission = match submission_id {
+            Some(id) => db::fetch_submission(self.state.db.as_ref(), id).await?,
+            None => None,
+        };
+        match submission {
+            // A restart between marking the slice and recording its submission; nothing
+            // was sent, so it goes out again.
+            None => {
+                slice.state = SliceState::Pending;
+                slice.submission_id = None;
+            }
+            Some(submission) => match submission.state {
+                SubmissionState::Confirmed | SubmissionState::Finalized => {
+                    slice.state = SliceState::Confirmed;
+                    slice.signature = submission.signature;
+                    slice.detail = None;
+                }
+                SubmissionState::Failed => {
+                    slice.state = SliceState::Failed;
+                    slice.signature = submission.signature;
+                    slice.detail = submission.error;
+                }
+                // Without an error the transaction is still being re-signed.
+                SubmissionState::Expired if submission.error.is_some() => {
+                    slice.state = SliceState::Failed;
+                    slice.detail = submission.error;
+                }
+                _ => return Ok(false),
+            },
+        }
+        info!(
+            schedule = %schedule_id,
+            slice = slice.index,
+            state = slice.state.as_str(),
+            "schedule slice settled"
+        );
+        db::update_schedule_slice(self.state.db.as_ref(), schedule_id, slice).await?;
+        Ok(true)
+    }
+
+    /// Sends a due slice unless the trading mode or paper trading holds it back, in which
+    /// case it waits.
+    async fn dispatch(
+        &self,
+        schedule_id: Uuid,
+        schedule: &Schedule,
+        slice: &mut ScheduleSlice,
+    ) -> Result<()> {
+        // Paper trading cannot fill slices, so they wait for live trading.
+        if self.state.paper.is_some()
+            || self
+                .state
+                .trading
+                .check(trade_action(schedule.action))
+                .is_err()
+        {
+            return Ok(());
+        }
+
+        // Marked before sending, so a restart can tell the slice may have gone out.
+        let submission_id = Uuid::new_v4();
+        slice.state = SliceState::Submitted;
+        slice.submission_id = Some(submission_id.to_string());
+        db::update_schedule_slice(self.state.db.as_ref(), schedule_id, slice).await?;
+
+        match routes::submit_schedule_slice(&self.state, schedule, slice, submission_id).await {
+            Ok(()) => {
+                info!(
+                    schedule = %schedule_id,
+                    slice = slice.index,
+                    submission = %submission_id,
+                    "schedule slice submitted"
+                );
+                Ok(())
+            }
+            Err(DispatchError::Unavailable(message)) => {
+                warn!(schedule = %schedule_id, slice = slice.index, %message, "slice deferred");
+                slice.state = SliceState::Pending;
+                slice.submission_id = None;
+                slice.detail = Some(message);
+                db::update_schedule_slice(self.state.db.as_ref(), schedule_id, slice).await?;
+                Ok(())
+            }
+            Err(DispatchError::Rejected(message)) => {
+                slice.state = SliceState::Failed;
+                slice.submission_id = None;
+                slice.detail = Some(message.clone());
+                db::update_schedule_slice(self.state.db.as_ref(), schedule_id, slice).await?;
+                let detail = format!("slice {} failed: {message}", slice.index);
+                self.finish(schedule_id, ScheduleState::Failed, &detail)
+                    .await
+            }
+        }
+    }
+
+    async fn finish(&self, id: Uuid, state: ScheduleState, detail: &str) -> Result<()> {
+        let moved = db::transition_schedule(
+            self.state.db.as_ref(),
+            id,
+            &[ScheduleState::Active, ScheduleState::Paused],
+            state,
+            Some(detail),
+        )
+        .await?;
+        if moved {
+            info!(%id, state = state.as_str(), detail, "schedule finished");
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn trade_action(action: ScheduleAction) -> TradeAction {
+    match action {
+        ScheduleAction::Open => TradeAction::Open,
+        ScheduleAction::Close => TradeAction::Close,
+    }
+}
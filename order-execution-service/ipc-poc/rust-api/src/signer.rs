@@ -0,0 +1,479 @@
+use std::{num::NonZeroU32, path::Path, str::FromStr, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use reqwest::Client as ReqwestClient;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::{keypair::Keypair, Signer},
+};
+use thiserror::Error;
+use tracing::info;
+
+const KEYSTORE_VERSION: u32 = 1;
+const KEYSTORE_ITERATIONS: u32 = 600_000;
+const KEYSTORE_SALT_LEN: usize = 16;
+const DEFAULT_REMOTE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum SignerError {
+    #[error("missing {0} env var")]
+    Missing(&'static str),
+    #[error("invalid signer configuration: {0}")]
+    Config(String),
+    #[error("invalid private key: {0}")]
+    InvalidKey(String),
+    #[error("keystore error: {0}")]
+    Keystore(String),
+    #[error("remote signer error: {0}")]
+    Remote(String),
+}
+
+/// Where the server wallet's signatures come from, selected by `SIGNER_BACKEND`.
+pub enum ServerSigner {
+    /// Key held in memory, loaded from the env, a keypair file or a keystore.
+    Local(Keypair),
+    Remote(RemoteSigner),
+}
+
+impl ServerSigner {
+    pub fn from_env() -> Result<Self, SignerError> {
+        let backend = match std::env::var("SIGNER_BACKEND") {
+            Ok(backend) => backend.trim().to_lowercase(),
+            // Same precedence as the worker's wallet configuration.
+            Err(_) if std::env::var("SERVER_PRIVATE_KEY").is_ok() => "env".to_string(),
+            Err(_) if std::env::var("SERVER_KEYPAIR_PATH").is_ok() => "file".to_string(),
+            Err(_) => return Err(SignerError::Missing("SERVER_PRIVATE_KEY")),
+        };
+        let signer = match backend.as_str() {
+            "env" => {
+                let key = env_var("SERVER_PRIVATE_KEY")?;
+                Self::Local(parse_keypair(&key).map_err(SignerError::InvalidKey)?)
+            }
+            "file" => Self::Local(read_keypair_file(env_var("SERVER_KEYPAIR_PATH")?)?),
+            "keystore" => {
+                let path = env_var("SIGNER_KEYSTORE_PATH")?;
+                Self::Local(read_keystore(&path, &keystore_passphrase(&path)?)?)
+            }
+            "remote" => Self::Remote(RemoteSigner::from_env()?),
+            other => {
+                return Err(SignerError::Config(format!(
+                    "unknown SIGNER_BACKEND '{other}', expected env, file, keystore or remote"
+                )))
+            }
+        };
+        info!(backend, pubkey = %signer.pubkey(), "loaded server signer");
+        Ok(signer)
+    }
+
+    pub fn from_config(config: &SignerConfig) -> Result<Self, SignerError> {
+        let signer = match config {
+            SignerConfig::Env { var } => {
+                let key = std::env::var(var)
+                    .map_err(|_| SignerError::Config(format!("missing {var} env var")))?;
+                Self::Local(parse_keypair(&key).map_err(SignerError::InvalidKey)?)
+            }
+            SignerConfig::File { path } => Self::Local(read_keypair_file(path)?),
+            SignerConfig::Keystore {
+                path,
+                passphrase_file,
+            } => {
+                let passphrase = match passphrase_file {
+                    Some(file) => read_passphrase_file(file)?,
+                    None => keystore_passphrase(path)?,
+                };
+                Self::Local(read_keystore(path, &passphrase)?)
+            }
+            SignerConfig::Remote {
+                url,
+                pubkey,
+                token,
+                timeout_ms,
+            } => {
+                let pubkey = Pubkey::from_str(pubkey.trim()).map_err(|err| {
+                    SignerError::InvalidKey(format!("invalid remote signer pubkey: {err}"))
+                })?;
+                let timeout = timeout_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or(DEFAULT_REMOTE_TIMEOUT);
+                Self::Remote(RemoteSigner::new(
+                    url.clone(),
+                    pubkey,
+                    token.clone(),
+                    timeout,
+                )?)
+            }
+        };
+        Ok(signer)
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        match self {
+            Self::Local(keypair) => keypair.pubkey(),
+            Self::Remote(remote) => remote.pubkey,
+        }
+    }
+
+    pub async fn sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        match self {
+            Self::Local(keypair) => keypair
+                .try_sign_message(message)
+                .map_err(|err| SignerError::InvalidKey(err.to_string())),
+            Self::Remote(remote) => remote.sign_message(message).await,
+        }
+    }
+}
+
+/// Signer backend of one wallet in the `WALLETS_CONFIG` file, mirroring `SIGNER_BACKEND`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(
+    tag = "backend",
+    rename_all = "lowercase",
+    rename_all_fields = "camelCase"
+)]
+pub enum SignerConfig {
+    /// Secret key in the named env var.
+    Env {
+        var: String,
+    },
+    File {
+        path: String,
+    },
+    Keystore {
+        path: String,
+        /// Prompted for at startup when absent.
+        passphrase_file: Option<String>,
+    },
+    Remote {
+        url: String,
+        pubkey: String,
+        token: Option<String>,
+        timeout_ms: Option<u64>,
+    },
+}
+
+fn env_var(name: &'static str) -> Result<String, SignerError> {
+    std::env::var(name).map_err(|_| SignerError::Missing(name))
+}
+
+/// Parses a secret key given as a JSON byte array, comma-separated bytes or base58.
+pub fn parse_keypair(key_str: &str) -> Result<Keypair, String> {
+    let trimmed = key_str.trim();
+    if trimmed.is_empty() {
+        return Err("empty private key".into());
+    }
+    if trimmed.starts_with('[') {
+        let bytes: Vec<u8> =
+            serde_json::from_str(trimmed).map_err(|err| format!("invalid json array: {err}"))?;
+        return Keypair::from_bytes(&bytes).map_err(|err| err.to_string());
+    }
+    if trimmed.contains(',') {
+        let mut bytes = Vec::new();
+        for part in trimmed.split(',') {
+            let value: u8 = part
+                .trim()
+                .parse()
+                .map_err(|err| format!("invalid byte '{part}': {err}"))?;
+            bytes.push(value);
+        }
+        return Keypair::from_bytes(&bytes).map_err(|err| err.to_string());
+    }
+    let decoded = bs58::decode(trimmed)
+        .into_vec()
+        .map_err(|err| format!("invalid base58: {err}"))?;
+    Keypair::from_bytes(&decoded).map_err(|err| err.to_string())
+}
+
+/// Reads a Solana CLI keypair file (`solana-keygen new -o <path>`).
+pub fn read_keypair_file(path: impl AsRef<Path>) -> Result<Keypair, SignerError> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path).map_err(|err| {
+        SignerError::InvalidKey(format!("failed to read {}: {err}", path.display()))
+    })?;
+    parse_keypair(&contents).map_err(SignerError::InvalidKey)
+}
+
+/// `SIGNER_KEYSTORE_PASSPHRASE_FILE`, then `SIGNER_KEYSTORE_PASSPHRASE`, then a terminal
+/// prompt naming `keystore_path`.
+pub fn keystore_passphrase(keystore_path: &str) -> Result<String, SignerError> {
+    if let Ok(path) = std::env::var("SIGNER_KEYSTORE_PASSPHRASE_FILE") {
+        return read_passphrase_file(&path);
+    }
+    if let Ok(passphrase) = std::env::var("SIGNER_KEYSTORE_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+    rpassword::prompt_password(format!("Passphrase for {keystore_path}: "))
+        .map_err(|err| SignerError::Keystore(format!("failed to read passphrase: {err}")))
+}
+
+fn read_passphrase_file(path: &str) -> Result<String, SignerError> {
+    let passphrase = std::fs::read_to_string(path)
+        .map_err(|err| SignerError::Keystore(format!("failed to read {path}: {err}")))?;
+    Ok(passphrase.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn read_keystore(path: &str, passphrase: &str) -> Result<Keypair, SignerError> {
+    let keystore = std::fs::read_to_string(path)
+        .map_err(|err| SignerError::Keystore(format!("failed to read {path}: {err}")))?;
+    decrypt_keystore(&keystore, passphrase)
+}
+
+/// Encrypted keypair on disk: AES-256-GCM under a PBKDF2-HMAC-SHA256 key derived from the
+/// passphrase, with the public key as associated data.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Keystore {
+    version: u32,
+    pubkey: String,
+    iterations: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+pub fn encrypt_keystore(keypair: &Keypair, passphrase: &str) -> Result<String, SignerError> {
+    let rng = SystemRandom::new();
+    let mut salt = [0u8; KEYSTORE_SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill(&mut salt)
+        .and_then(|_| rng.fill(&mut nonce))
+        .map_err(|_| SignerError::Keystore("failed to generate randomness".into()))?;
+
+    let pubkey = keypair.pubkey().to_string();
+    let key = keystore_key(passphrase, &salt, KEYSTORE_ITERATIONS)?;
+    let mut ciphertext = keypair.to_bytes().to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(pubkey.as_bytes()),
+        &mut ciphertext,
+    )
+    .map_err(|_| SignerError::Keystore("encryption failed".into()))?;
+
+    let keystore = Keystore {
+        version: KEYSTORE_VERSION,
+        pubkey,
+        iterations: KEYSTORE_ITERATIONS,
+        salt: STANDARD.encode(salt),
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    };
+    serde_json::to_string_pretty(&keystore).map_err(|err| SignerError::Keystore(err.to_string()))
+}
+
+pub fn decrypt_keystore(contents: &str, passphrase: &str) -> Result<Keypair, SignerError> {
+    let keystore: Keystore = serde_json::from_str(contents)
+        .map_err(|err| SignerError::Keystore(format!("invalid keystore: {err}")))?;
+    if keystore.version != KEYSTORE_VERSION {
+        return Err(SignerError::Keystore(format!(
+            "unsupported keystore version {}",
+            keystore.version
+        )));
+    }
+    let decode = |field: &str, value: &str| {
+        STANDARD
+            .decode(value)
+            .map_err(|err| SignerError::Keystore(format!("invalid {field}: {err}")))
+    };
+    let salt = decode("salt", &keystore.salt)?;
+    let nonce = Nonce::try_assume_unique_for_key(&decode("nonce", &keystore.nonce)?)
+        .map_err(|_| SignerError::Keystore("invalid nonce length".into()))?;
+    let mut ciphertext = decode("ciphertext", &keystore.ciphertext)?;
+
+    let key = keystore_key(passphrase, &salt, keystore.iterations)?;
+    let secret = key
+        .open_in_place(
+            nonce,
+            Aad::from(keystore.pubkey.as_bytes()),
+            &mut ciphertext,
+        )
+        .map_err(|_| SignerError::Keystore("wrong passphrase or corrupted keystore".into()))?;
+    let keypair =
+        Keypair::from_bytes(secret).map_err(|err| SignerError::Keystore(err.to_string()))?;
+    if keypair.pubkey().to_string() != keystore.pubkey {
+        return Err(SignerError::Keystore(
+            "decrypted key does not match the keystore public key".into(),
+        ));
+    }
+    Ok(keypair)
+}
+
+fn keystore_key(
+    passphrase: &str,
+    salt: &[u8],
+    iterations: u32,
+) -> Result<LessSafeKey, SignerError> {
+    let iterations = NonZeroU32::new(iterations)
+        .ok_or_else(|| SignerError::Keystore("iterations must be positive".into()))?;
+    let mut key = [0u8; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    let key = UnboundKey::new(&AES_256_GCM, &key)
+        .map_err(|_| SignerError::Keystore("invalid key length".into()))?;
+    Ok(LessSafeKey::new(key))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignRequest {
+    pub pubkey: String,
+    /// Base64 serialized transaction message.
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignResponse {
+    /// Base58 signature.
+    pub signature: String,
+}
+
+/// Signing service reached over HTTP: `POST <url>/sign` with a [`SignRequest`], answered
+/// with a [`SignResponse`]. Signatures are verified against the pinned public key.
+pub struct RemoteSigner {
+    http: ReqwestClient,
+    url: String,
+    pubkey: Pubkey,
+    token: Option<String>,
+}
+
+impl RemoteSigner {
+    pub fn new(
+        url: String,
+        pubkey: Pubkey,
+        token: Option<String>,
+        timeout: Duration,
+    ) -> Result<Self, SignerError> {
+        let http = ReqwestClient::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|err| SignerError::Remote(err.to_string()))?;
+        Ok(Self {
+            http,
+            url: url.trim_end_matches('/').to_string(),
+            pubkey,
+            token,
+        })
+    }
+
+    pub fn from_env() -> Result<Self, SignerError> {
+        let url = env_var("SIGNER_REMOTE_URL")?;
+        let pubkey = env_var("SIGNER_REMOTE_PUBKEY")?;
+        let pubkey = Pubkey::from_str(pubkey.trim()).map_err(|err| {
+            SignerError::InvalidKey(format!("invalid SIGNER_REMOTE_PUBKEY: {err}"))
+        })?;
+        let token = std::env::var("SIGNER_REMOTE_TOKEN").ok();
+        let timeout = std::env::var("SIGNER_REMOTE_TIMEOUT_MS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_REMOTE_TIMEOUT);
+        Self::new(url, pubkey, token, timeout)
+    }
+
+    async fn sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let request = SignRequest {
+            pubkey: self.pubkey.to_string(),
+            message: STANDARD.encode(message),
+        };
+        let mut builder = self.http.post(format!("{}/sign", self.url)).json(&request);
+        if let Some(token) = &self.token {
+            builder = builder.bearer_auth(token);
+        }
+        let response = builder
+            .send()
+            .await
+            .map_err(|err| SignerError::Remote(err.to_string()))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(SignerError::Remote(format!("{status}: {body}")));
+        }
+        let response: SignResponse = response
+            .json()
+            .await
+            .map_err(|err| SignerError::Remote(format!("invalid response: {err}")))?;
+        let signature = Signature::from_str(&response.signature)
+            .map_err(|err| SignerError::Remote(format!("invalid signature: {err}")))?;
+        if !signature.verify(self.pubkey.as_ref(), message) {
+            return Err(SignerError::Remote(
+                "signature does not verify against the configured public key".into(),
+            ));
+        }
+        Ok(signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{routing::post, Json, Router};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn keystore_round_trips() {
+        let keypair = Keypair::new();
+        let contents = encrypt_keystore(&keypair, "correct horse").unwrap();
+        let decrypted = decrypt_keystore(&contents, "correct horse").unwrap();
+        assert_eq!(decrypted.to_bytes(), keypair.to_bytes());
+        assert!(matches!(
+            decrypt_keystore(&contents, "wrong horse"),
+            Err(SignerError::Keystore(_))
+        ));
+    }
+
+    /// Serves `/sign` on a local port, answering with signatures from `signer`.
+    async fn remote_signer(signer: Keypair, pubkey: Pubkey) -> RemoteSigner {
+        let signer = Arc::new(signer);
+        let app = Router::new().route(
+            "/sign",
+            post(move |Json(request): Json<SignRequest>| async move {
+                let message = STANDARD.decode(request.message).unwrap();
+                Json(SignResponse {
+                    signature: signer.sign_message(&message).to_string(),
+                })
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        RemoteSigner::new(
+            format!("http://{addr}"),
+            pubkey,
+            None,
+            DEFAULT_REMOTE_TIMEOUT,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn remote_signature_is_verified() {
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let remote = remote_signer(keypair, pubkey).await;
+        let signature = remote.sign_message(b"message").await.unwrap();
+        assert!(signature.verify(pubkey.as_ref(), b"message"));
+    }
+
+    #[tokio::test]
+    async fn remote_signature_from_another_key_is_rejected() {
+        let remote = remote_signer(Keypair::new(), Keypair::new().pubkey()).await;
+        assert!(matches!(
+            remote.sign_message(b"message").await,
+            Err(SignerError::Remote(message)) if message.contains("does not verify")
+        ));
+    }
+}
@@ -0,0 +1,296 @@
+use std::{sync::Arc, time::Duration};
+
+use dashmap::DashMap;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use tokio::{
+    sync::{broadcast::error::RecvError, watch},
+    time::MissedTickBehavior,
+};
+use tracing::{debug, info, warn};
+
+use crate::ipc::{IpcError, TsIpc, WorkerEvent};
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(15);
+const EVENT_DEBOUNCE: Duration = Duration::from_millis(250);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type SnapshotReceiver = watch::Receiver<Option<Arc<WalletSnapshot>>>;
+type SnapshotSender = Arc<watch::Sender<Option<Arc<WalletSnapshot>>>>;
+
+/// Latest known view of a wallet, as returned by the worker's read functions.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletSnapshot {
+    pub positions: Value,
+    pub position_details: Value,
+    pub balances: Value,
+}
+
+impl WalletSnapshot {
+    /// Sections that changed since `previous`, keyed by market for position lists.
+    /// Returns `None` when nothing changed.
+    pub fn diff(&self, previous: &WalletSnapshot) -> Option<Value> {
+        let mut changes = Map::new();
+        if let Some(diff) = diff_by_market(&previous.positions, &self.positions) {
+            changes.insert("positions".into(), diff);
+        }
+        if let Some(diff) = diff_by_market(&previous.position_details, &self.position_details) {
+            changes.insert("positionDetails".into(), diff);
+        }
+        if previous.balances != self.balances {
+            changes.insert("balances".into(), self.balances.clone());
+        }
+        (!changes.is_empty()).then_some(Value::Object(changes))
+    }
+}
+
+fn diff_by_market(previous: &Value, current: &Value) -> Option<Value> {
+    let empty = Vec::new();
+    let before = previous.as_array().unwrap_or(&empty);
+    let after = current.as_array().unwrap_or(&empty);
+    let market_of = |entry: &Value| {
+        entry
+            .get("market")
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+    };
+
+    let upserted: Vec<&Value> = after
+        .iter()
+        .filter(|entry| !before.contains(entry))
+        .collect();
+    let removed: Vec<String> = before
+        .iter()
+        .filter_map(market_of)
+        .filter(|market| {
+            !after
+                .iter()
+                .any(|entry| market_of(entry).as_ref() == Some(market))
+        })
+        .collect();
+
+    if upserted.is_empty() && removed.is_empty() {
+        return None;
+    }
+    Some(json!({ "upserted": upserted, "removed": removed }))
+}
+
+/// Shares one refresh loop per wallet between all connected stream clients.
+///
+/// Each feed pushes into a `watch` channel, so a slow client only ever sees the newest
+/// snapshot instead of queueing every intermediate one.
+#[derive(Clone)]
+pub struct PositionStreams {
+    ipc: TsIpc,
+    feeds: Arc<DashMap<String, SnapshotSender>>,
+    poll_interval: Duration,
+}
+
+impl PositionStreams {
+    pub fn new(ipc: TsIpc, poll_interval: Duration) -> Self {
+        Self {
+            ipc,
+            feeds: Arc::new(DashMap::new()),
+            poll_interval,
+        }
+    }
+
+    pub fn from_env(ipc: TsIpc) -> Self {
+        let poll_interval = std::env::var("STREAM_POLL_INTERVAL_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_POLL_INTERVAL);
+        Self::new(ipc, poll_interval)
+    }
+
+    pub fn subscribe(&self, wallet: &str) -> SnapshotReceiver {
+        let entry = self.feeds.entry(wallet.to_string()).or_insert_with(|| {
+            let (sender, _) = watch::channel(None);
+            let sender = Arc::new(sender);
+            tokio::spawn(run_feed(
+                self.clone(),
+                wallet.to_string(),
+                Arc::clone(&sender),
+            ));
+            sender
+        });
+        entry.subscribe()
+    }
+
+    fn release(&self, wallet: &str) -> bool {
+        self.feeds
+            .remove_if(wallet, |_, sender| sender.receiver_count() == 0)
+            .is_some()
+    }
+}
+
+async fn run_feed(streams: PositionStreams, wallet: String, sender: SnapshotSender) {
+    let ipc = streams.ipc.clone();
+    let mut events = ipc.subscribe();
+    let mut watched = watch_wallet(&ipc, &wallet).await;
+    let mut ticker = tokio::time::interval(streams.poll_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    info!(%wallet, "position stream feed started");
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            event = events.recv() => match event {
+                Ok(event) if !concerns_wallet(&event, &wallet) => continue,
+                Ok(WorkerEvent::SubscriptionLost { reason, .. }) => {
+                    warn!(%wallet, %reason, "worker subscription lost, re-subscribing");
+                    if watched {
+                        // Keeps the worker's watcher count balanced when it survived the error.
+                        let _ = ipc.unwatch_wallet(&wallet).await;
+                    }
+                    watched = watch_wallet(&ipc, &wallet).await;
+                }
+                Ok(_) => tokio::time::sleep(EVENT_DEBOUNCE).await,
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!(%wallet, skipped, "position stream feed lagged behind worker events");
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = sender.closed() => {
+                if streams.release(&wallet) {
+                    break;
+                }
+                continue;
+            }
+        }
+
+        match fetch_snapshot(&ipc, &wallet).await {
+            Ok(snapshot) => {
+                sender.send_if_modified(|current| {
+                    if current.as_deref() == Some(&snapshot) {
+                        return false;
+                    }
+                    *current = Some(Arc::new(snapshot));
+                    true
+                });
+            }
+            Err(err) => warn!(%wallet, error = %err, "position stream refresh failed"),
+        }
+    }
+
+    if watched {
+        if let Err(err) = ipc.unwatch_wallet(&wallet).await {
+            warn!(%wallet, error = %err, "failed to release worker subscription");
+        }
+    }
+    info!(%wallet, "position stream feed stopped");
+}
+
+fn concerns_wallet(event: &WorkerEvent, wallet: &str) -> bool {
+    match event {
+        WorkerEvent::SubscriptionLost { wallet: None, .. } => true,
+        other => other.wallet() == Some(wallet),
+    }
+}
+
+async fn watch_wallet(ipc: &TsIpc, wallet: &str) -> bool {
+    match ipc.watch_wallet(wallet).await {
+        Ok(()) => true,
+        Err(err) => {
+            // Polling still keeps the stream fresh, just with more latency.
+            warn!(%wallet, error = %err, "worker push unavailable, falling back to polling");
+            false
+        }
+    }
+}
+
+async fn fetch_snapshot(ipc: &TsIpc, wallet: &str) -> Result<WalletSnapshot, IpcError> {
+    let args = json!({ "wallet": wallet });
+    let (positions, position_details, balances) = tokio::try_join!(
+        ipc.call("getPositions", args.clone(), FETCH_TIMEOUT),
+        ipc.call("getPositionDetails", args.clone(), FETCH_TIMEOUT),
+        ipc.call("getBalances", args, FETCH_TIMEOUT),
+    )?;
+    Ok(WalletSnapshot {
+        positions,
+        position_details,
+        balances,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(positions: Value, balances: Value) -> WalletSnapshot {
+        WalletSnapshot {
+            position_details: positions.clone(),
+            positions,
+            balances,
+        }
+    }
+
+    fn sol(size: f64) -> Value {
+        json!({ "market": "SOL-PERP", "size": size })
+    }
+
+    fn btc(size: f64) -> Value {
+        json!({ "market": "BTC-PERP", "size": size })
+    }
+
+    #[test]
+    fn unchanged_snapshot_has_no_diff() {
+        let current = snapshot(json!([sol(1.0)]), json!({ "usdc": 100.0 }));
+        assert_eq!(current.diff(&current.clone()), None);
+    }
+
+    #[test]
+    fn added_position_is_upserted() {
+        let previous = snapshot(json!([sol(1.0)]), json!({ "usdc": 100.0 }));
+        let current = snapshot(json!([sol(1.0), btc(0.5)]), json!({ "usdc": 100.0 }));
+        let changes = json!({ "upserted": [btc(0.5)], "removed": [] });
+        assert_eq!(
+            current.diff(&previous),
+            Some(json!({ "positions": changes, "positionDetails": changes }))
+        );
+    }
+
+    #[test]
+    fn removed_position_is_listed_by_market() {
+        let previous = snapshot(json!([sol(1.0), btc(0.5)]), json!({ "usdc": 100.0 }));
+        let current = snapshot(json!([sol(1.0)]), json!({ "usdc": 100.0 }));
+        let changes = json!({ "upserted": [], "removed": ["BTC-PERP"] });
+        assert_eq!(
+            current.diff(&previous),
+            Some(json!({ "positions": changes, "positionDetails": changes }))
+        );
+    }
+
+    #[test]
+    fn changed_position_is_upserted_not_removed() {
+        let previous = snapshot(json!([sol(1.0), btc(0.5)]), json!({ "usdc": 100.0 }));
+        let current = snapshot(json!([sol(2.0), btc(0.5)]), json!({ "usdc": 100.0 }));
+        let changes = json!({ "upserted": [sol(2.0)], "removed": [] });
+        assert_eq!(
+            current.diff(&previous),
+            Some(json!({ "positions": changes, "positionDetails": changes }))
+        );
+    }
+
+    #[test]
+    fn changed_balances_are_sent_whole() {
+        let previous = snapshot(json!([sol(1.0)]), json!({ "usdc": 100.0, "sol": 2.0 }));
+        let current = snapshot(json!([sol(1.0)]), json!({ "usdc": 90.0, "sol": 2.0 }));
+        assert_eq!(
+            current.diff(&previous),
+            Some(json!({ "balances": { "usdc": 90.0, "sol": 2.0 } }))
+        );
+    }
+
+    #[test]
+    fn added_and_removed_balances_are_sent_whole() {
+        let previous = snapshot(json!([]), json!({ "usdc": 100.0 }));
+        let current = snapshot(json!([]), json!({ "sol": 2.0 }));
+        assert_eq!(
+            current.diff(&previous),
+            Some(json!({ "balances": { "sol": 2.0 } }))
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::sync::{Arc, RwLock};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Client;
+use tracing::{info, warn};
+
+use crate::db;
+
+/// How much trading the service allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TradingMode {
+    Normal,
+    /// Only closes and margin deposits.
+    ReduceOnly,
+    /// Nothing that builds or sends a transaction.
+    Halted,
+}
+
+impl TradingMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::ReduceOnly => "reduce-only",
+            Self::Halted => "halted",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "normal" => Self::Normal,
+            "reduce-only" => Self::ReduceOnly,
+            "halted" => Self::Halted,
+            _ => return None,
+        })
+    }
+
+    pub fn allows(&self, action: TradeAction) -> bool {
+        match self {
+            Self::Normal => true,
+            Self::ReduceOnly => matches!(action, TradeAction::Close | TradeAction::Deposit),
+            Self::Halted => false,
+        }
+    }
+}
+
+/// What a build or execute request would do to the account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TradeAction {
+    Open,
+    Close,
+    /// Moving margin into the account or an isolated position.
+    Deposit,
+    /// Moving margin out of an isolated position.
+    Withdraw,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TradingModeStatus {
+    pub mode: TradingMode,
+    pub reason: Option<String>,
+    /// Unix milliseconds, `None` until the mode is first changed.
+    pub updated_at: Option<i64>,
+}
+
+/// The current [`TradingMode`], persisted in Postgres (`trading_mode`) and cached in
+/// memory, so a change made through one process is only seen by others after a restart.
+#[derive(Clone)]
+pub struct TradingSwitch {
+    db: Arc<Client>,
+    current: Arc<RwLock<TradingModeStatus>>,
+}
+
+impl TradingSwitch {
+    /// Loads the persisted mode, `normal` when none was ever set.
+    pub async fn load(db: Arc<Client>) -> Result<Self> {
+        let status = db::fetch_trading_mode(db.as_ref())
+            .await?
+            .unwrap_or(TradingModeStatus {
+                mode: TradingMode::Normal,
+                reason: None,
+                updated_at: None,
+            });
+        if status.mode != TradingMode::Normal {
+            warn!(
+                mode = status.mode.as_str(),
+                reason = status.reason.as_deref(),
+                "starting with trading restricted"
+            );
+        }
+        Ok(Self {
+            db,
+            current: Arc::new(RwLock::new(status)),
+        })
+    }
+
+    pub fn status(&self) -> TradingModeStatus {
+        self.current
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// `Err` carries the status that refused `action`.
+    pub fn check(&self, action: TradeAction) -> Result<(), TradingModeStatus> {
+        let status = self.status();
+        if status.mode.allows(action) {
+            Ok(())
+        } else {
+            Err(status)
+        }
+    }
+
+    pub async fn set(
+        &self,
+        mode: TradingMode,
+        reason: Option<String>,
+    ) -> Result<TradingModeStatus> {
+        let updated_at = db::store_trading_mode(self.db.as_ref(), mode, reason.as_deref()).await?;
+        let status = TradingModeStatus {
+            mode,
+            reason,
+            updated_at: Some(updated_at),
+        };
+        *self
+            .current
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = status.clone();
+        info!(
+            mode = mode.as_str(),
+            reason = status.reason.as_deref(),
+            "trading mode changed"
+        );
+        Ok(status)
+    }
+}
@@ -1,26 +1,303 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use serde::{Deserialize, Serialize};
 
+use crate::{auth::Scope, trading_mode::TradingMode};
+
+/// Current Unix time in milliseconds, the unit timestamps are stored and returned in.
+pub fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as i64)
+        .unwrap_or_default()
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct OpenIsolatedRequest {
+    /// Filled in from `walletLabel` when omitted.
+    #[serde(default)]
     pub wallet: String,
+    /// Server wallet to act as, from the wallet registry.
+    #[serde(default, rename = "walletLabel")]
+    pub wallet_label: Option<String>,
+    /// Drift sub-account; defaults to the wallet's first allowed one, or 0.
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
     pub market: String,
     pub size: f64,
     pub leverage: f64,
     pub margin: f64,
+    /// Order type and its prices; a plain market order when left out.
+    #[serde(flatten)]
+    pub order: OrderOptions,
+    #[serde(default)]
+    pub simulate: bool,
+    /// Return a `submissionId` immediately instead of waiting for confirmation.
+    #[serde(default, rename = "async")]
+    pub asynchronous: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OrderKind {
+    #[default]
+    Market,
+    Limit,
+    TriggerMarket,
+    TriggerLimit,
+    /// Priced at an offset from the oracle.
+    Oracle,
+}
+
+impl OrderKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Market => "market",
+            Self::Limit => "limit",
+            Self::TriggerMarket => "triggerMarket",
+            Self::TriggerLimit => "triggerLimit",
+            Self::Oracle => "oracle",
+        }
+    }
+
+    pub fn is_trigger(&self) -> bool {
+        matches!(self, Self::TriggerMarket | Self::TriggerLimit)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PostOnly {
+    #[default]
+    None,
+    MustPostOnly,
+    TryPostOnly,
+    Slide,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TriggerCondition {
+    Above,
+    Below,
+}
+
+/// Drift order parameters beyond size and direction. Prices are in quote units; oracle
+/// order auction prices are offsets from the oracle.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderOptions {
+    #[serde(default)]
+    pub order_type: OrderKind,
+    /// Limit price, for `limit` and `triggerLimit` orders.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    #[serde(default)]
+    pub post_only: PostOnly,
+    #[serde(default)]
+    pub immediate_or_cancel: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_price: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_condition: Option<TriggerCondition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oracle_price_offset: Option<f64>,
+    /// Auction length in slots.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auction_duration: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auction_start_price: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auction_end_price: Option<f64>,
+    /// Unix seconds after which the order expires.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_ts: Option<i64>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ClosePositionRequest {
+    /// Filled in from `walletLabel` when omitted.
+    #[serde(default)]
     pub wallet: String,
+    /// Server wallet to act as, from the wallet registry.
+    #[serde(default, rename = "walletLabel")]
+    pub wallet_label: Option<String>,
+    /// Drift sub-account; defaults to the wallet's first allowed one, or 0.
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
     pub market: String,
     pub size: Option<f64>,
+    #[serde(default)]
+    pub simulate: bool,
+    /// Return a `submissionId` immediately instead of waiting for confirmation.
+    #[serde(default, rename = "async")]
+    pub asynchronous: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OpenOrdersQuery {
+    pub wallet: String,
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
+    pub market: Option<String>,
+}
+
+/// Cancels by exactly one of `orderIds`, `userOrderIds`, `market` or `all`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CancelOrdersRequest {
+    /// Filled in from `walletLabel` when omitted.
+    #[serde(default)]
+    pub wallet: String,
+    /// Server wallet to act as, from the wallet registry.
+    #[serde(default, rename = "walletLabel")]
+    pub wallet_label: Option<String>,
+    /// Drift sub-account; defaults to the wallet's first allowed one, or 0.
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
+    #[serde(default, rename = "orderIds", skip_serializing_if = "Option::is_none")]
+    pub order_ids: Option<Vec<u32>>,
+    #[serde(
+        default,
+        rename = "userOrderIds",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub user_order_ids: Option<Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub market: Option<String>,
+    #[serde(default)]
+    pub all: bool,
+    #[serde(default)]
+    pub simulate: bool,
+    /// Return a `submissionId` immediately instead of waiting for confirmation.
+    #[serde(default, rename = "async")]
+    pub asynchronous: bool,
+}
+
+/// Fields of a resting order to replace; unset ones keep their current value.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderChanges {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_price: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_condition: Option<TriggerCondition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oracle_price_offset: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reduce_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_only: Option<PostOnly>,
+    /// Unix seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_ts: Option<i64>,
+}
+
+/// Modifies the open order named by `orderId` or `userOrderId`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ModifyOrderRequest {
+    /// Filled in from `walletLabel` when omitted.
+    #[serde(default)]
+    pub wallet: String,
+    /// Server wallet to act as, from the wallet registry.
+    #[serde(default, rename = "walletLabel")]
+    pub wallet_label: Option<String>,
+    /// Drift sub-account; defaults to the wallet's first allowed one, or 0.
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
+    #[serde(rename = "orderId", skip_serializing_if = "Option::is_none")]
+    pub order_id: Option<u32>,
+    #[serde(rename = "userOrderId", skip_serializing_if = "Option::is_none")]
+    pub user_order_id: Option<u8>,
+    #[serde(flatten)]
+    pub changes: OrderChanges,
+    #[serde(default)]
+    pub simulate: bool,
+    /// Return a `submissionId` immediately instead of waiting for confirmation.
+    #[serde(default, rename = "async")]
+    pub asynchronous: bool,
+}
+
+/// Opens, closes and margin transfers for one wallet and sub-account, built into as few
+/// transactions as fit.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BatchRequest {
+    /// Filled in from `walletLabel` when omitted.
+    #[serde(default)]
+    pub wallet: String,
+    /// Server wallet to act as, from the wallet registry.
+    #[serde(default, rename = "walletLabel")]
+    pub wallet_label: Option<String>,
+    /// Drift sub-account; defaults to the wallet's first allowed one, or 0.
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
+    pub legs: Vec<BatchLeg>,
+    #[serde(default)]
+    pub simulate: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum BatchLeg {
+    Open(BatchOpenLeg),
+    Close(BatchCloseLeg),
+    Transfer(BatchTransferLeg),
+}
+
+impl BatchLeg {
+    pub fn market(&self) -> &str {
+        match self {
+            Self::Open(leg) => &leg.market,
+            Self::Close(leg) => &leg.market,
+            Self::Transfer(leg) => &leg.market,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BatchOpenLeg {
+    pub market: String,
+    pub size: f64,
+    pub leverage: f64,
+    pub margin: f64,
+    #[serde(flatten)]
+    pub order: OrderOptions,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BatchCloseLeg {
+    pub market: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BatchTransferLeg {
+    pub market: String,
+    pub delta: f64,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TransferMarginRequest {
+    /// Filled in from `walletLabel` when omitted.
+    #[serde(default)]
     pub wallet: String,
+    /// Server wallet to act as, from the wallet registry.
+    #[serde(default, rename = "walletLabel")]
+    pub wallet_label: Option<String>,
+    /// Drift sub-account; defaults to the wallet's first allowed one, or 0.
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
     pub market: String,
     pub delta: f64,
+    #[serde(default)]
+    pub simulate: bool,
+    /// Return a `submissionId` immediately instead of waiting for confirmation.
+    #[serde(default, rename = "async")]
+    pub asynchronous: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -31,16 +308,56 @@ pub struct IsolatedBalanceQuery {
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct DepositNativeRequest {
+    /// Filled in from `walletLabel` when omitted.
+    #[serde(default)]
     pub wallet: String,
+    /// Server wallet to act as, from the wallet registry.
+    #[serde(default, rename = "walletLabel")]
+    pub wallet_label: Option<String>,
+    /// Drift sub-account; defaults to the wallet's first allowed one, or 0.
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
     pub amount: f64,
     pub market: Option<String>,
+    #[serde(default)]
+    pub simulate: bool,
+    /// Return a `submissionId` immediately instead of waiting for confirmation.
+    #[serde(default, rename = "async")]
+    pub asynchronous: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct DepositTokenRequest {
+    /// Filled in from `walletLabel` when omitted.
+    #[serde(default)]
     pub wallet: String,
+    /// Server wallet to act as, from the wallet registry.
+    #[serde(default, rename = "walletLabel")]
+    pub wallet_label: Option<String>,
+    /// Drift sub-account; defaults to the wallet's first allowed one, or 0.
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
     pub amount: f64,
     pub market: Option<String>,
+    #[serde(default)]
+    pub simulate: bool,
+    /// Return a `submissionId` immediately instead of waiting for confirmation.
+    #[serde(default, rename = "async")]
+    pub asynchronous: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SimulateRequest {
+    #[serde(rename = "txBase64")]
+    pub tx_base64: String,
+    pub wallet: Option<String>,
+    pub market: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TradingModeRequest {
+    pub mode: TradingMode,
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -56,4 +373,725 @@ pub struct MarketQuery {
 #[derive(Debug, Serialize)]
 pub struct ApiErrorBody<'a> {
     pub error: &'a str,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub details: Option<&'a serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Lifecycle of an asynchronous submission. `expired` is followed by `signed` again when
+/// the executor re-signs over a fresh blockhash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubmissionState {
+    Built,
+    Signed,
+    Sent,
+    Processed,
+    Confirmed,
+    Finalized,
+    Failed,
+    Expired,
+}
+
+impl SubmissionState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Built => "built",
+            Self::Signed => "signed",
+            Self::Sent => "sent",
+            Self::Processed => "processed",
+            Self::Confirmed => "confirmed",
+            Self::Finalized => "finalized",
+            Self::Failed => "failed",
+            Self::Expired => "expired",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "built" => Self::Built,
+            "signed" => Self::Signed,
+            "sent" => Self::Sent,
+            "processed" => Self::Processed,
+            "confirmed" => Self::Confirmed,
+            "finalized" => Self::Finalized,
+            "failed" => Self::Failed,
+            "expired" => Self::Expired,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionSubmission {
+    pub submission_id: String,
+    pub route: String,
+    pub wallet: String,
+    pub market: Option<String>,
+    pub state: SubmissionState,
+    pub signature: Option<String>,
+    pub slot: Option<u64>,
+    pub error: Option<String>,
+    pub error_details: Option<serde_json::Value>,
+    /// Unix milliseconds.
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub events: Vec<SubmissionEvent>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionEvent {
+    pub state: SubmissionState,
+    pub signature: Option<String>,
+    pub slot: Option<u64>,
+    pub detail: Option<String>,
+    /// Unix milliseconds.
+    pub at: i64,
+}
+
+/// A request the risk engine rejected.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskEvent {
+    pub wallet: String,
+    pub wallet_label: Option<String>,
+    pub sub_account_id: u16,
+    pub route: String,
+    pub code: String,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+    /// Unix milliseconds.
+    pub created_at: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RiskEventsQuery {
+    pub wallet: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// One reduce-only trigger order of a bracket; a trigger-limit order when `limitPrice` is set.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BracketLeg {
+    pub trigger_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_price: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BracketLegKind {
+    TakeProfit,
+    StopLoss,
+}
+
+impl BracketLegKind {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::TakeProfit => "take profit",
+            Self::StopLoss => "stop loss",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BracketRequest {
+    #[serde(flatten)]
+    pub open: OpenIsolatedRequest,
+    #[serde(rename = "takeProfit")]
+    pub take_profit: Option<BracketLeg>,
+    #[serde(rename = "stopLoss")]
+    pub stop_loss: Option<BracketLeg>,
+}
+
+/// Replaces the given legs of the position's bracket, or attaches a new bracket.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AmendBracketRequest {
+    /// Filled in from `walletLabel` when omitted.
+    #[serde(default)]
+    pub wallet: String,
+    #[serde(default, rename = "walletLabel")]
+    pub wallet_label: Option<String>,
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
+    pub market: String,
+    #[serde(rename = "takeProfit")]
+    pub take_profit: Option<BracketLeg>,
+    #[serde(rename = "stopLoss")]
+    pub stop_loss: Option<BracketLeg>,
+    #[serde(default)]
+    pub simulate: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RemoveBracketRequest {
+    /// Filled in from `walletLabel` when omitted.
+    #[serde(default)]
+    pub wallet: String,
+    #[serde(default, rename = "walletLabel")]
+    pub wallet_label: Option<String>,
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
+    pub market: String,
+    /// Removes only this leg; both when omitted.
+    pub leg: Option<BracketLegKind>,
+    #[serde(default)]
+    pub simulate: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BracketQuery {
+    pub wallet: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BracketState {
+    /// Sent but not yet confirmed.
+    Pending,
+    Active,
+    /// A leg filled, or the position closed, and the remaining legs were cancelled.
+    Completed,
+    /// Removed through the API.
+    Cancelled,
+    Failed,
+    /// A leg stopped being open without the position moving, e.g. it was cancelled outside
+    /// the API. The remaining leg is left in place.
+    Broken,
+}
+
+impl BracketState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Active => "active",
+            Self::Completed => "completed",
+            Self::Cancelled => "cancelled",
+            Self::Failed => "failed",
+            Self::Broken => "broken",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "pending" => Self::Pending,
+            "active" => Self::Active,
+            "completed" => Self::Completed,
+            "cancelled" => Self::Cancelled,
+            "failed" => Self::Failed,
+            "broken" => Self::Broken,
+            _ => return None,
+        })
+    }
+}
+
+/// A leg as placed on-chain.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlacedLeg {
+    pub user_order_id: u8,
+    pub trigger_price: f64,
+    pub limit_price: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bracket {
+    pub id: String,
+    pub wallet: String,
+    pub wallet_label: String,
+    pub sub_account_id: u16,
+    pub market: String,
+    pub state: BracketState,
+    pub take_profit: Option<PlacedLeg>,
+    pub stop_loss: Option<PlacedLeg>,
+    pub position_seen: bool,
+    /// Signed position size at the monitor's last check.
+    pub position_size: Option<f64>,
+    pub submission_id: Option<String>,
+    pub signature: Option<String>,
+    pub detail: Option<String>,
+    /// Unix milliseconds.
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Bracket {
+    pub fn legs(&self) -> impl Iterator<Item = (BracketLegKind, PlacedLeg)> {
+        [
+            (BracketLegKind::TakeProfit, self.take_profit),
+            (BracketLegKind::StopLoss, self.stop_loss),
+        ]
+        .into_iter()
+        .filter_map(|(kind, leg)| leg.map(|leg| (kind, leg)))
+    }
+}
+
+/// A parent order split into child orders sent over time.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ScheduleRequest {
+    /// Filled in from `walletLabel` when omitted.
+    #[serde(default)]
+    pub wallet: String,
+    /// Server wallet to act as, from the wallet registry.
+    #[serde(default, rename = "walletLabel")]
+    pub wallet_label: Option<String>,
+    /// Drift sub-account; defaults to the wallet's first allowed one, or 0.
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
+    pub market: String,
+    pub action: ScheduleAction,
+    /// Signed total for opens; for closes, the total to close, or the whole position when
+    /// left out.
+    pub size: Option<f64>,
+    pub leverage: Option<f64>,
+    /// Total margin for opens, split evenly across the slices.
+    pub margin: Option<f64>,
+    /// Number of child orders; defaults to 1.
+    pub slices: Option<u32>,
+    /// Time the slices are spread over; required with more than one slice.
+    #[serde(rename = "durationSecs")]
+    pub duration_secs: Option<u64>,
+    /// Unix milliseconds of the first slice; defaults to now.
+    #[serde(rename = "startAt")]
+    pub start_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleAction {
+    Open,
+    Close,
+}
+
+impl ScheduleAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Close => "close",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "open" => Self::Open,
+            "close" => Self::Close,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleState {
+    Active,
+    Paused,
+    /// Every slice confirmed.
+    Completed,
+    /// Cancelled through the API.
+    Cancelled,
+    /// A slice failed; the remaining ones were cancelled.
+    Failed,
+}
+
+impl ScheduleState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Paused => "paused",
+            Self::Completed => "completed",
+            Self::Cancelled => "cancelled",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "active" => Self::Active,
+            "paused" => Self::Paused,
+            "completed" => Self::Completed,
+            "cancelled" => Self::Cancelled,
+            "failed" => Self::Failed,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SliceState {
+    Pending,
+    /// Handed to the executor; its outcome is read from the submission.
+    Submitted,
+    Confirmed,
+    Failed,
+    Cancelled,
+}
+
+impl SliceState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Submitted => "submitted",
+            Self::Confirmed => "confirmed",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "pending" => Self::Pending,
+            "submitted" => Self::Submitted,
+            "confirmed" => Self::Confirmed,
+            "failed" => Self::Failed,
+            "cancelled" => Self::Cancelled,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    pub id: String,
+    pub wallet: String,
+    pub wallet_label: String,
+    pub sub_account_id: u16,
+    pub market: String,
+    pub action: ScheduleAction,
+    pub size: Option<f64>,
+    pub leverage: Option<f64>,
+    pub margin: Option<f64>,
+    pub state: ScheduleState,
+    pub detail: Option<String>,
+    pub slice_count: u32,
+    pub confirmed_slices: u32,
+    /// Unix milliseconds.
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// Only filled in when a single schedule is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slices: Option<Vec<ScheduleSlice>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleSlice {
+    pub index: u32,
+    /// Unix milliseconds.
+    pub due_at: i64,
+    /// Signed for opens; `None` closes whatever is left of the position.
+    pub size: Option<f64>,
+    pub margin: Option<f64>,
+    pub state: SliceState,
+    pub submission_id: Option<String>,
+    pub signature: Option<String>,
+    pub detail: Option<String>,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ScheduleQuery {
+    pub wallet: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// A virtual isolated position held by paper-trading mode.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaperPosition {
+    pub wallet: String,
+    pub sub_account_id: u16,
+    pub market: String,
+    /// Signed base amount, negative for shorts.
+    pub size: f64,
+    pub entry_price: f64,
+    /// Isolated margin, after realized PnL and fees.
+    pub margin: f64,
+    pub realized_pnl: f64,
+    pub fees: f64,
+    /// At the oracle price when read; `None` when the price could not be fetched.
+    pub unrealized_pnl: Option<f64>,
+    /// Unix milliseconds.
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PaperPositionsQuery {
+    pub wallet: String,
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
+}
+
+/// Sizes an isolated open from any two of `size`, `leverage` and `margin`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct QuoteRequest {
+    /// Only needed for the risk check; filled in from `walletLabel` when omitted.
+    #[serde(default)]
+    pub wallet: String,
+    #[serde(default, rename = "walletLabel")]
+    pub wallet_label: Option<String>,
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
+    pub market: String,
+    /// Signed base amount, negative for shorts.
+    pub size: Option<f64>,
+    pub leverage: Option<f64>,
+    pub margin: Option<f64>,
+    /// Side to quote when `size` is left out.
+    pub direction: Option<PositionSide>,
+    #[serde(flatten)]
+    pub order: OrderOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PositionSide {
+    Long,
+    Short,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quote {
+    pub market: String,
+    pub direction: PositionSide,
+    pub size: f64,
+    pub leverage: f64,
+    pub margin: f64,
+    /// Size at the estimated entry price.
+    pub notional: f64,
+    pub oracle_price: f64,
+    pub entry_price: f64,
+    pub fee_rate: f64,
+    pub fee: f64,
+    /// Margin after fees over notional.
+    pub margin_ratio: f64,
+    pub initial_margin_ratio: f64,
+    pub maintenance_margin_ratio: f64,
+    pub max_leverage: f64,
+    /// `None` when the position cannot be liquidated at any positive price.
+    pub liquidation_price: Option<f64>,
+    pub min_order_size: f64,
+    /// `None` without a wallet to check against.
+    pub risk: Option<QuoteRisk>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuoteRisk {
+    pub passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PnlQuery {
+    pub wallet: String,
+    /// Drift sub-account; defaults to 0.
+    #[serde(default, rename = "subAccountId")]
+    pub sub_account_id: Option<u16>,
+    /// Unix milliseconds; defaults to 30 days before `to`.
+    pub from: Option<i64>,
+    /// Unix milliseconds; defaults to now.
+    pub to: Option<i64>,
+    pub market: Option<String>,
+}
+
+/// Settlements, fees and funding for one market on one UTC day.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PnlDay {
+    #[serde(skip)]
+    pub market_index: u16,
+    /// `YYYY-MM-DD`.
+    pub date: String,
+    pub settled_pnl: f64,
+    pub fees: f64,
+    pub funding: f64,
+    pub fills: i64,
+}
+
+/// Sums over a report or one of its markets. Drift nets fees and funding into the PnL
+/// it settles and reports as unrealized, so `netPnl` is settled plus unrealized and the
+/// other two are its breakdown.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PnlTotals {
+    pub settled_pnl: f64,
+    pub fees: f64,
+    pub funding: f64,
+    pub unrealized_pnl: f64,
+    pub net_pnl: f64,
+}
+
+impl PnlTotals {
+    pub fn add_day(&mut self, day: &PnlDay) {
+        self.settled_pnl += day.settled_pnl;
+        self.fees += day.fees;
+        self.funding += day.funding;
+        self.net_pnl += day.settled_pnl;
+    }
+
+    pub fn add_unrealized(&mut self, pnl: f64) {
+        self.unrealized_pnl += pnl;
+        self.net_pnl += pnl;
+    }
+
+    pub fn add(&mut self, other: &PnlTotals) {
+        self.settled_pnl += other.settled_pnl;
+        self.fees += other.fees;
+        self.funding += other.funding;
+        self.unrealized_pnl += other.unrealized_pnl;
+        self.net_pnl += other.net_pnl;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketPnl {
+    pub market: String,
+    pub market_index: u16,
+    #[serde(flatten)]
+    pub totals: PnlTotals,
+    pub days: Vec<PnlDay>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PnlReport {
+    pub wallet: String,
+    pub sub_account_id: u16,
+    pub from: i64,
+    pub to: i64,
+    /// Whether `unrealizedPnl` holds current open positions; only when `to` is not past.
+    pub includes_unrealized: bool,
+    #[serde(flatten)]
+    pub totals: PnlTotals,
+    pub markets: Vec<MarketPnl>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FundingQuery {
+    pub wallet: String,
+    pub market: String,
+    pub limit: Option<i64>,
+}
+
+/// Funding settled into a position, as stored by the indexer.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FundingPayment {
+    pub signature: String,
+    pub user_account: String,
+    pub market_index: u16,
+    /// USDC; positive when the position received funding.
+    pub funding_payment: f64,
+    /// Position size at settlement, negative for shorts.
+    pub base_asset_amount: f64,
+    pub slot: i64,
+    pub block_time_ms: Option<i64>,
+}
+
+/// A market's funding rates from the worker, in quote per unit of base per period;
+/// positive rates are paid by longs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FundingRate {
+    pub symbol: String,
+    pub market_index: u16,
+    pub funding_period_secs: i64,
+    pub last_funding_ts: i64,
+    pub next_funding_ts: i64,
+    pub mark_twap: f64,
+    pub oracle_twap: f64,
+    pub last_rate: f64,
+    pub last_rate_pct: Option<f64>,
+    pub projected_rate: f64,
+    pub projected_rate_pct: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FundingReport {
+    pub wallet: String,
+    pub market: String,
+    pub rate: FundingRate,
+    /// Open size in the market on sub-account 0, if any.
+    pub position_size: Option<f64>,
+    /// What that position would receive (positive) or pay at the projected rate.
+    pub projected_payment: Option<f64>,
+    /// Sum of every recorded payment for the market, not just those returned.
+    pub total_payments: f64,
+    pub payments: Vec<FundingPayment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookDeliveryState {
+    /// Waiting for its first or next attempt.
+    Pending,
+    Delivered,
+    /// Gave up after `WEBHOOK_MAX_ATTEMPTS`; only a replay sends it again.
+    Failed,
+}
+
+impl WebhookDeliveryState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Delivered => "delivered",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "pending" => Self::Pending,
+            "delivered" => Self::Delivered,
+            "failed" => Self::Failed,
+            _ => return None,
+        })
+    }
+}
+
+/// One webhook event bound for one endpoint.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub event_id: String,
+    pub event_type: String,
+    pub url: String,
+    pub state: WebhookDeliveryState,
+    pub attempts: u32,
+    /// HTTP status of the last attempt, `None` when it got no response.
+    pub last_status: Option<u16>,
+    pub last_error: Option<String>,
+    /// Unix milliseconds.
+    pub next_attempt_at: i64,
+    pub delivered_at: Option<i64>,
+    pub created_at: i64,
+    /// The body that is signed and posted.
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDeliveriesQuery {
+    pub state: Option<WebhookDeliveryState>,
+    pub event_type: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
 }
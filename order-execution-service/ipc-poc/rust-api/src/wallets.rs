@@ -0,0 +1,203 @@
+use std::{collections::HashSet, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use tracing::info;
+
+use crate::signer::{ServerSigner, SignerConfig, SignerError};
+
+const DEFAULT_LABEL: &str = "default";
+
+/// One server wallet: its signer and the Drift sub-accounts and perp markets it may trade.
+pub struct WalletEntry {
+    pub label: String,
+    pub signer: Arc<ServerSigner>,
+    /// Allowed sub-account ids; the first is used when a request names none.
+    pub sub_accounts: Vec<u16>,
+    /// Allowed perp markets as [`market_key`]s, `None` for any.
+    markets: Option<HashSet<String>>,
+}
+
+impl WalletEntry {
+    pub fn pubkey(&self) -> Pubkey {
+        self.signer.pubkey()
+    }
+
+    pub fn default_sub_account(&self) -> u16 {
+        self.sub_accounts[0]
+    }
+
+    pub fn allows_sub_account(&self, sub_account_id: u16) -> bool {
+        self.sub_accounts.contains(&sub_account_id)
+    }
+
+    pub fn allows_market(&self, market: &str) -> bool {
+        self.markets
+            .as_ref()
+            .is_none_or(|markets| markets.contains(&market_key(market)))
+    }
+
+    pub fn summary(&self) -> WalletSummary {
+        let mut markets: Option<Vec<String>> = self
+            .markets
+            .as_ref()
+            .map(|set| set.iter().cloned().collect());
+        if let Some(markets) = markets.as_mut() {
+            markets.sort();
+        }
+        WalletSummary {
+            label: self.label.clone(),
+            pubkey: self.pubkey().to_string(),
+            sub_accounts: self.sub_accounts.clone(),
+            markets,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletSummary {
+    pub label: String,
+    pub pubkey: String,
+    pub sub_accounts: Vec<u16>,
+    /// `null` when every market is allowed.
+    pub markets: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WalletsFile {
+    wallets: Vec<WalletConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WalletConfig {
+    label: String,
+    signer: SignerConfig,
+    #[serde(default)]
+    sub_accounts: Vec<u16>,
+    markets: Option<Vec<String>>,
+}
+
+/// Server wallets the executor may sign for, keyed by label.
+pub struct WalletRegistry {
+    wallets: Vec<Arc<WalletEntry>>,
+}
+
+impl WalletRegistry {
+    /// A registry holding only `signer`, labelled `default`, on sub-account 0.
+    pub fn single(signer: ServerSigner) -> Self {
+        Self {
+            wallets: vec![Arc::new(WalletEntry {
+                label: DEFAULT_LABEL.to_string(),
+                signer: Arc::new(signer),
+                sub_accounts: vec![0],
+                markets: None,
+            })],
+        }
+    }
+
+    /// Loads the wallets listed in the `WALLETS_CONFIG` file, or the single signer
+    /// configured through `SIGNER_BACKEND` when it is unset.
+    pub fn from_env() -> Result<Self, SignerError> {
+        let Ok(path) = std::env::var("WALLETS_CONFIG") else {
+            return Ok(Self::single(ServerSigner::from_env()?));
+        };
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|err| SignerError::Config(format!("failed to read {path}: {err}")))?;
+        let file: WalletsFile = serde_json::from_str(&contents)
+            .map_err(|err| SignerError::Config(format!("invalid {path}: {err}")))?;
+        let registry = Self::from_configs(file.wallets)?;
+        for wallet in &registry.wallets {
+            info!(
+                label = %wallet.label,
+                pubkey = %wallet.pubkey(),
+                sub_accounts = ?wallet.sub_accounts,
+                "loaded server wallet"
+            );
+        }
+        Ok(registry)
+    }
+
+    fn from_configs(configs: Vec<WalletConfig>) -> Result<Self, SignerError> {
+        if configs.is_empty() {
+            return Err(SignerError::Config(
+                "WALLETS_CONFIG lists no wallets".into(),
+            ));
+        }
+        let mut wallets: Vec<Arc<WalletEntry>> = Vec::with_capacity(configs.len());
+        for config in configs {
+            let label = config.label.trim().to_string();
+            if label.is_empty() {
+                return Err(SignerError::Config("wallet label must not be empty".into()));
+            }
+            if wallets.iter().any(|wallet| wallet.label == label) {
+                return Err(SignerError::Config(format!(
+                    "duplicate wallet label '{label}'"
+                )));
+            }
+            let mut sub_accounts = config.sub_accounts;
+            if sub_accounts.is_empty() {
+                sub_accounts.push(0);
+            }
+            let signer = ServerSigner::from_config(&config.signer)?;
+            let entry = WalletEntry {
+                label,
+                signer: Arc::new(signer),
+                sub_accounts,
+                markets: config
+                    .markets
+                    .map(|markets| markets.iter().map(|market| market_key(market)).collect()),
+            };
+            // Two labels on one key could trade the same sub-account under different rules.
+            if let Some(other) = wallets
+                .iter()
+                .find(|wallet| wallet.pubkey() == entry.pubkey())
+            {
+                return Err(SignerError::Config(format!(
+                    "wallets '{}' and '{}' use the same key",
+                    other.label, entry.label
+                )));
+            }
+            wallets.push(Arc::new(entry));
+        }
+        Ok(Self { wallets })
+    }
+
+    pub fn get(&self, label: &str) -> Option<&Arc<WalletEntry>> {
+        self.wallets.iter().find(|wallet| wallet.label == label)
+    }
+
+    pub fn by_pubkey(&self, pubkey: &str) -> Option<&Arc<WalletEntry>> {
+        let pubkey = pubkey.trim();
+        self.wallets
+            .iter()
+            .find(|wallet| wallet.pubkey().to_string() == pubkey)
+    }
+
+    /// The first configured wallet.
+    pub fn primary(&self) -> &Arc<WalletEntry> {
+        &self.wallets[0]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<WalletEntry>> {
+        self.wallets.iter()
+    }
+}
+
+/// Perp market name reduced to its base asset, so `SOL`, `SOL-PERP` and `sol_perp` compare
+/// equal as they do in the worker.
+pub fn market_key(market: &str) -> String {
+    let normalised: String = market
+        .trim()
+        .to_uppercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    normalised
+        .strip_suffix("_PERP")
+        .or_else(|| normalised.strip_prefix("PERP_"))
+        .unwrap_or(&normalised)
+        .to_string()
+}
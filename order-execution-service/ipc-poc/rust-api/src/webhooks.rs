@@ -0,0 +1,349 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::future::join_all;
+use reqwest::Client as ReqwestClient;
+use ring::hmac;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::{sync::Notify, time::MissedTickBehavior};
+use tokio_postgres::Client;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    db,
+    types::{now_ms, WebhookDelivery, WebhookDeliveryState},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a claimed delivery is held before another sender may pick it up.
+const CLAIM_LEASE: Duration = Duration::from_secs(60);
+const CLAIM_BATCH: i64 = 20;
+const DEFAULT_MAX_ATTEMPTS: u32 = 10;
+const DEFAULT_RETRY_BASE: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    #[error("invalid webhook configuration: {0}")]
+    Config(String),
+}
+
+/// What happened; sent as the payload's `type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEvent {
+    TransactionConfirmed,
+    /// Failed on-chain, or could not be signed or sent.
+    TransactionFailed,
+    /// The blockhash expired before the transaction confirmed.
+    TransactionExpired,
+    /// The decoder stored actions for a signature it had not seen before.
+    ActionsDecoded,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TransactionConfirmed => "transaction.confirmed",
+            Self::TransactionFailed => "transaction.failed",
+            Self::TransactionExpired => "transaction.expired",
+            Self::ActionsDecoded => "actions.decoded",
+        }
+    }
+}
+
+struct Dispatch {
+    urls: Vec<String>,
+    key: hmac::Key,
+    client: ReqwestClient,
+    max_attempts: u32,
+    retry_base: Duration,
+    wake: Notify,
+}
+
+/// Queues events in the `webhook_deliveries` outbox and posts them, HMAC-signed, to every
+/// URL in `WEBHOOK_URLS`, retrying failures with exponential backoff. Does nothing when no
+/// URLs are configured.
+#[derive(Clone)]
+pub struct Webhooks {
+    db: Arc<Client>,
+    dispatch: Option<Arc<Dispatch>>,
+}
+
+impl Webhooks {
+    pub fn disabled(db: Arc<Client>) -> Self {
+        Self { db, dispatch: None }
+    }
+
+    /// Reads `WEBHOOK_URLS` (comma-separated), the signing key `WEBHOOK_SECRET`, and the
+    /// retry policy `WEBHOOK_MAX_ATTEMPTS` and `WEBHOOK_RETRY_BASE_SECS`.
+    pub fn from_env(db: Arc<Client>) -> Result<Self, WebhookError> {
+        let urls: Vec<String> = std::env::var("WEBHOOK_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(str::to_owned)
+            .collect();
+        if urls.is_empty() {
+            return Ok(Self::disabled(db));
+        }
+        if let Some(url) = urls
+            .iter()
+            .find(|url| !url.starts_with("http://") && !url.starts_with("https://"))
+        {
+            return Err(WebhookError::Config(format!(
+                "'{url}' in WEBHOOK_URLS is not an http(s) URL"
+            )));
+        }
+        let secret = std::env::var("WEBHOOK_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .ok_or_else(|| {
+                WebhookError::Config("WEBHOOK_SECRET is required with WEBHOOK_URLS".into())
+            })?;
+        let max_attempts = std::env::var("WEBHOOK_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|attempts| *attempts > 0)
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS);
+        let retry_base = std::env::var("WEBHOOK_RETRY_BASE_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_RETRY_BASE);
+        let client = ReqwestClient::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|err| WebhookError::Config(format!("failed to build http client: {err}")))?;
+        Ok(Self {
+            db,
+            dispatch: Some(Arc::new(Dispatch {
+                urls,
+                key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()),
+                client,
+                max_attempts,
+                retry_base,
+                wake: Notify::new(),
+            })),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.dispatch.is_some()
+    }
+
+    /// Queues `event` for every endpoint and wakes the sender. Failures are logged, never
+    /// returned, so a notification cannot fail the request that caused it.
+    pub async fn emit(&self, event: WebhookEvent, data: Value) {
+        let Some(dispatch) = &self.dispatch else {
+            return;
+        };
+        let event_id = Uuid::new_v4();
+        let payload = json!({
+            "id": event_id.to_string(),
+            "type": event.as_str(),
+            "createdAt": now_ms(),
+            "data": data,
+        });
+        match db::insert_webhook_deliveries(
+            self.db.as_ref(),
+            event_id,
+            event.as_str(),
+            &dispatch.urls,
+            &payload,
+        )
+        .await
+        {
+            Ok(_) => dispatch.wake.notify_one(),
+            Err(err) => warn!(?err, event = event.as_str(), "failed to queue webhook"),
+        }
+    }
+
+    /// Wakes the sender, e.g. after a replay.
+    pub fn wake(&self) {
+        if let Some(dispatch) = &self.dispatch {
+            dispatch.wake.notify_one();
+        }
+    }
+
+    /// Sends due deliveries for the life of the process, including any left pending by a
+    /// previous run. Returns `None` when webhooks are disabled.
+    pub fn spawn(self) -> Option<tokio::task::JoinHandle<()>> {
+        let dispatch = self.dispatch.clone()?;
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(POLL_INTERVAL);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            info!(
+                endpoints = dispatch.urls.len(),
+                max_attempts = dispatch.max_attempts,
+                "webhook sender started"
+            );
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = dispatch.wake.notified() => {}
+                }
+                loop {
+                    let claimed = match db::claim_webhook_deliveries(
+                        self.db.as_ref(),
+                        CLAIM_BATCH,
+                        CLAIM_LEASE.as_secs_f64(),
+                    )
+                    .await
+                    {
+                        Ok(claimed) => claimed,
+                        Err(err) => {
+                            warn!(?err, "failed to claim webhook deliveries");
+                            break;
+                        }
+                    };
+                    if claimed.is_empty() {
+                        break;
+                    }
+                    join_all(
+                        claimed
+                            .iter()
+                            .map(|delivery| self.deliver(&dispatch, delivery)),
+                    )
+                    .await;
+                }
+            }
+        }))
+    }
+
+    async fn deliver(&self, dispatch: &Dispatch, delivery: &WebhookDelivery) {
+        let (status, error) = match send(dispatch, delivery).await {
+            Ok(status) => (Some(status), None),
+            Err((status, error)) => (status, Some(error)),
+        };
+        let attempts = delivery.attempts + 1;
+        let (state, retry) = match &error {
+            None => (WebhookDeliveryState::Delivered, Duration::ZERO),
+            Some(_) if attempts >= dispatch.max_attempts => {
+                (WebhookDeliveryState::Failed, Duration::ZERO)
+            }
+            Some(_) => (
+                WebhookDeliveryState::Pending,
+                retry_delay(dispatch.retry_base, attempts),
+            ),
+        };
+        if let Some(error) = &error {
+            warn!(
+                id = delivery.id,
+                url = %delivery.url,
+                event = %delivery.event_type,
+                attempts,
+                state = state.as_str(),
+                %error,
+                "webhook delivery failed"
+            );
+        }
+        if let Err(err) = db::record_webhook_attempt(
+            self.db.as_ref(),
+            delivery.id,
+            state,
+            status,
+            error.as_deref(),
+            retry.as_secs_f64(),
+        )
+        .await
+        {
+            warn!(?err, id = delivery.id, "failed to record webhook attempt");
+        }
+    }
+}
+
+/// Posts the payload with `X-Webhook-Signature: sha256=<hex>`, an HMAC-SHA256 over
+/// `<X-Webhook-Timestamp>.<body>` keyed with `WEBHOOK_SECRET`. Any 2xx response counts as
+/// delivered; the error carries the status when there was a response.
+async fn send(
+    dispatch: &Dispatch,
+    delivery: &WebhookDelivery,
+) -> Result<u16, (Option<u16>, String)> {
+    let body = serde_json::to_vec(&delivery.payload)
+        .map_err(|err| (None, format!("failed to serialize payload: {err}")))?;
+    let timestamp = (now_ms() / 1000).to_string();
+    let signature = signature_header(&dispatch.key, &timestamp, &body);
+
+    let response = dispatch
+        .client
+        .post(&delivery.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Webhook-Id", &delivery.event_id)
+        .header("X-Webhook-Event", &delivery.event_type)
+        .header("X-Webhook-Delivery", delivery.id.to_string())
+        .header("X-Webhook-Timestamp", &timestamp)
+        .header("X-Webhook-Signature", signature)
+        .body(body)
+        .send()
+        .await
+        .map_err(|err| (None, err.to_string()))?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(status.as_u16())
+    } else {
+        Err((Some(status.as_u16()), format!("responded {status}")))
+    }
+}
+
+/// `sha256=<hex>` of the HMAC-SHA256 over `<timestamp>.<body>`.
+fn signature_header(key: &hmac::Key, timestamp: &str, body: &[u8]) -> String {
+    let mut signed = Vec::with_capacity(timestamp.len() + 1 + body.len());
+    signed.extend_from_slice(timestamp.as_bytes());
+    signed.push(b'.');
+    signed.extend_from_slice(body);
+    format!("sha256={}", hex(hmac::sign(key, &signed).as_ref()))
+}
+
+/// `base` doubled for every attempt after the first, capped at an hour.
+fn retry_delay(base: Duration, attempts: u32) -> Duration {
+    base.saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(MAX_RETRY_DELAY)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles_per_attempt() {
+        let base = Duration::from_secs(5);
+        assert_eq!(retry_delay(base, 0), base);
+        assert_eq!(retry_delay(base, 1), base);
+        assert_eq!(retry_delay(base, 2), Duration::from_secs(10));
+        assert_eq!(retry_delay(base, 4), Duration::from_secs(40));
+    }
+
+    #[test]
+    fn retry_delay_is_capped() {
+        let base = Duration::from_secs(5);
+        assert_eq!(retry_delay(base, 11), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(base, u32::MAX), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(Duration::MAX, 1), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn signature_covers_timestamp_and_body() {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"whsec_test");
+        let body = br#"{"event":"order.filled"}"#;
+        assert_eq!(
+            signature_header(&key, "1700000000", body),
+            "sha256=9b22f9ce8b97dcb5a78dd387ebf32c083b495b3cfc5cf31ef616c9f4e64f825a"
+        );
+        assert_ne!(
+            signature_header(&key, "1700000001", body),
+            signature_header(&key, "1700000000", body)
+        );
+    }
+
+    #[test]
+    fn hex_is_lowercase_and_padded() {
+        assert_eq!(hex(&[0x00, 0x0f, 0xab]), "000fab");
+    }
+}
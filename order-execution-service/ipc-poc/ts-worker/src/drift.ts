@@ -4,6 +4,7 @@ import {
 	PublicKey,
 	Transaction,
 	TransactionInstruction,
+	PACKET_DATA_SIZE,
 	VersionedTransaction,
 	type TransactionVersion,
 	ParsedAccountData,
@@ -25,6 +26,14 @@ import {
 	getUserAccountPublicKeySync,
 	getUserStatsAccountPublicKey,
 	getMarketOrderParams,
+	getOrderParams,
+	OrderType,
+	MarketType,
+	PostOnlyParams,
+	OrderTriggerCondition,
+	OrderParamsBitFlag,
+	type OptionalOrderParams,
+	type OrderParams,
 	PositionDirection,
 	findDirectionToClose,
 	calculateEntryPrice,
@@ -33,10 +42,14 @@ import {
 	BASE_PRECISION,
 	PRICE_PRECISION,
 	FUNDING_RATE_PRECISION,
+	MARGIN_PRECISION,
 	ZERO,
 	convertToNumber,
+	getTokenAmount,
+	SpotBalanceType,
 	WRAPPED_SOL_MINT,
 	isVariant,
+	UserStatus,
 	type UserAccount,
 	type PerpMarketAccount,
 	type SpotMarketAccount,
@@ -44,7 +57,12 @@ import {
 	type IWallet,
 } from '@drift-labs/sdk';
 
-import { RPC_URL, NETWORK, getServerKeypair } from './env.js';
+import {
+	RPC_URL,
+	NETWORK,
+	ORACLE_EVENT_INTERVAL_MS,
+	getServerKeypair,
+} from './env.js';
 import bs58 from 'bs58';
 import { LAMPORTS_PER_SOL } from '@solana/web3.js';
 import {
@@ -52,13 +70,26 @@ import {
 	IsolatedBalanceReq,
 	MarketQueryReq,
 	OpenIsolatedReq,
+	OrderOptions,
 	TransferMarginReq,
 	WalletOnlyReq,
+	PositionDetailsReq,
 	DepositNativeReq,
 	DepositTokenReq,
 	DepositIsolatedReq,
+	PositionChangeReq,
+	RiskSnapshotReq,
+	OpenBracketReq,
+	BracketLegsReq,
+	BracketStatusReq,
+	OpenOrdersReq,
+	CancelOrdersReq,
+	ModifyOrderReq,
+	BatchLeg,
+	BatchReq,
 } from './types.js';
 import { debugLog, printPayload } from './logger.js';
+import { emitEvent } from './events.js';
 
 
 type DriftTx = Transaction | VersionedTransaction;
@@ -138,6 +169,33 @@ type SpotMarketMaps = {
 	byMint: Map<string, SpotMarketConfig>;
 };
 
+type PositionSnapshot = {
+	baseAssetAmount: BN;
+	isolatedPositionScaledBalance: BN;
+};
+
+type OrderSnapshot = {
+	marketIndex: number;
+	direction: PositionDirection;
+	baseAssetAmount: BN;
+	baseAssetAmountFilled: BN;
+	quoteAssetAmountFilled: BN;
+};
+
+type WalletWatch = {
+	user: User;
+	watchers: number;
+	// Settles once the first watcher's subscribe has succeeded or failed.
+	ready: Promise<void>;
+	// Set once the subscribe succeeds while someone is still watching.
+	subscribed: boolean;
+	positions: Map<number, PositionSnapshot>;
+	orders: Map<number, OrderSnapshot>;
+};
+
+const walletWatches = new Map<string, WalletWatch>();
+const lastOracleEventAt = new Map<number, number>();
+
 let marketMaps: MarketMaps | null = null;
 let spotMarketMaps: SpotMarketMaps | null = null;
 
@@ -174,29 +232,30 @@ function logInstruction(label: string, wallet: PublicKey, mint: PublicKey) {
 
 async function ensureDriftUserCached(
 	wallet: PublicKey,
-	userAccount?: UserAccount | null
+	userAccount?: UserAccount | null,
+	subAccountId = 0
 ) {
 	await withAuthority(wallet, async () => {
-		if (!driftClient.hasUser(0, wallet)) {
+		if (!driftClient.hasUser(subAccountId, wallet)) {
 			// If user account doesn't exist, we still need to add it (even if null)
 			// so the client knows about the user. If userAccount is null, addUser
 			// will handle it appropriately.
 			if (userAccount) {
-				await driftClient.addUser(0, wallet, userAccount);
+				await driftClient.addUser(subAccountId, wallet, userAccount);
 			} else {
 				// User account doesn't exist yet, but we still need to register the user
 				// so methods like getIsolatedPerpPositionTokenAmount work
 				// We'll fetch it fresh or let it be initialized
-				const fetched = await fetchUserAccount(wallet);
+				const fetched = await fetchUserAccount(wallet, subAccountId);
 				if (fetched) {
-					await driftClient.addUser(0, wallet, fetched);
+					await driftClient.addUser(subAccountId, wallet, fetched);
 				}
 			}
 		} else if (userAccount) {
 			// User already exists, update it with fresh data
-			await driftClient.addUser(0, wallet, userAccount);
+			await driftClient.addUser(subAccountId, wallet, userAccount);
 		}
-	});
+	}, subAccountId);
 }
 
 function buildMarketMaps(): MarketMaps {
@@ -317,13 +376,15 @@ function resolveSpotMarketConfig(requested?: string): SpotMarketConfig {
 
 async function withAuthority<T>(
 	authority: PublicKey,
-	fn: () => Promise<T>
+	fn: () => Promise<T>,
+	subAccountId = 0
 ): Promise<T> {
 	ensureInitialized();
 	return walletLock.runExclusive(async () => {
 		const originalAuthority = (driftClient as unknown as { authority: PublicKey })
 			.authority;
 		const originalWallet = driftClient.wallet;
+		const originalSubAccountId = driftClient.activeSubAccountId;
 		const originalUserStatsPk = (driftClient as {
 			userStatsAccountPublicKey?: PublicKey;
 		}).userStatsAccountPublicKey;
@@ -334,6 +395,8 @@ async function withAuthority<T>(
 		).wallet = new ReadonlyWallet(authority);
 		(driftClient as { userStatsAccountPublicKey?: PublicKey }).userStatsAccountPublicKey =
 			undefined;
+		// SDK helpers default to the active sub-account.
+		driftClient.activeSubAccountId = subAccountId;
 
 		try {
 			return await fn();
@@ -343,6 +406,7 @@ async function withAuthority<T>(
 			(driftClient as unknown as { wallet: IWallet }).wallet = originalWallet;
 			(driftClient as { userStatsAccountPublicKey?: PublicKey }).userStatsAccountPublicKey =
 				originalUserStatsPk;
+			driftClient.activeSubAccountId = originalSubAccountId;
 		}
 	});
 }
@@ -351,6 +415,71 @@ function toQuotePrecision(amount: number): BN {
 	return new BN(Math.round(amount * 1e6));
 }
 
+function toPricePrecision(price: number): BN {
+	return new BN(Math.round(price * PRICE_PRECISION.toNumber()));
+}
+
+const ORDER_TYPES = {
+	market: OrderType.MARKET,
+	limit: OrderType.LIMIT,
+	triggerMarket: OrderType.TRIGGER_MARKET,
+	triggerLimit: OrderType.TRIGGER_LIMIT,
+	oracle: OrderType.ORACLE,
+} as const;
+
+const POST_ONLY = {
+	none: PostOnlyParams.NONE,
+	mustPostOnly: PostOnlyParams.MUST_POST_ONLY,
+	tryPostOnly: PostOnlyParams.TRY_POST_ONLY,
+	slide: PostOnlyParams.SLIDE,
+} as const;
+
+// getOrderParams copies undefined fields over its defaults, so only set what was given.
+function buildPerpOrderParams(
+	options: OrderOptions,
+	marketIndex: number,
+	direction: PositionDirection,
+	baseAssetAmount: BN,
+	reduceOnly: boolean
+) {
+	const params: OptionalOrderParams = {
+		orderType: ORDER_TYPES[options.orderType],
+		marketType: MarketType.PERP,
+		marketIndex,
+		direction,
+		baseAssetAmount,
+		reduceOnly,
+		postOnly: POST_ONLY[options.postOnly],
+		bitFlags: options.immediateOrCancel ? OrderParamsBitFlag.ImmediateOrCancel : 0,
+	};
+	if (options.price !== undefined) {
+		params.price = toPricePrecision(options.price);
+	}
+	if (options.triggerPrice !== undefined) {
+		params.triggerPrice = toPricePrecision(options.triggerPrice);
+		params.triggerCondition =
+			options.triggerCondition === 'below'
+				? OrderTriggerCondition.BELOW
+				: OrderTriggerCondition.ABOVE;
+	}
+	if (options.oraclePriceOffset !== undefined) {
+		params.oraclePriceOffset = toPricePrecision(options.oraclePriceOffset).toNumber();
+	}
+	if (options.auctionDuration !== undefined) {
+		params.auctionDuration = options.auctionDuration;
+	}
+	if (options.auctionStartPrice !== undefined) {
+		params.auctionStartPrice = toPricePrecision(options.auctionStartPrice);
+	}
+	if (options.auctionEndPrice !== undefined) {
+		params.auctionEndPrice = toPricePrecision(options.auctionEndPrice);
+	}
+	if (options.maxTs !== undefined) {
+		params.maxTs = new BN(options.maxTs);
+	}
+	return getOrderParams(params);
+}
+
 function toBasePrecision(amount: number, precision: BN): BN {
 	const scale = precision.toNumber();
 	return new BN(Math.round(amount * scale));
@@ -436,28 +565,32 @@ async function buildAccountSummary(owner: PublicKey): Promise<AccountBalanceSumm
 }
 
 async function ensureUserInitIxs(
-	wallet: PublicKey
+	wallet: PublicKey,
+	subAccountId = 0
 ): Promise<TransactionInstruction[]> {
 	return withAuthority(wallet, async () => {
 		const userPk = getUserAccountPublicKeySync(
 			driftClient.program.programId,
 			wallet,
-			0
+			subAccountId
 		);
 		const accountInfo = await connection.getAccountInfo(userPk);
 		if (accountInfo) {
 			return [];
 		}
-		const [ixs] = await driftClient.getInitializeUserAccountIxs(0);
+		const [ixs] = await driftClient.getInitializeUserAccountIxs(subAccountId);
 		return ixs;
-	});
+	}, subAccountId);
 }
 
-async function fetchUserAccount(wallet: PublicKey): Promise<UserAccount | null> {
+async function fetchUserAccount(
+	wallet: PublicKey,
+	subAccountId = 0
+): Promise<UserAccount | null> {
 	const userPk = getUserAccountPublicKeySync(
 		driftClient.program.programId,
 		wallet,
-		0
+		subAccountId
 	);
 	const account = await driftClient.program.account.user.fetchNullable(userPk);
 	return (account as UserAccount | null) ?? null;
@@ -466,6 +599,7 @@ async function fetchUserAccount(wallet: PublicKey): Promise<UserAccount | null>
 type BuiltTx = {
 	txBase64: string;
 	signatures: string[];
+	lastValidBlockHeight: number;
 };
 
 async function buildTransaction(
@@ -495,7 +629,11 @@ async function buildTransaction(
 		return bs58.encode(bytes);
 	});
 
-	return { txBase64: Buffer.from(serialized).toString('base64'), signatures };
+	return {
+		txBase64: Buffer.from(serialized).toString('base64'),
+		signatures,
+		lastValidBlockHeight,
+	};
 }
 
 function calcEntryPrice(position: PerpPosition): number | null {
@@ -510,17 +648,296 @@ function bnAbs(value: BN): BN {
 	return value.isNeg() ? value.neg() : value;
 }
 
+function marketSymbol(marketIndex: number): string {
+	return marketMaps?.byIndex.get(marketIndex)?.symbol ?? `MARKET_${marketIndex}`;
+}
+
+// Isolated margin in quote tokens. The scaled balance accrues the quote spot market's deposit
+// interest, so it goes through getTokenAmount the way getIsolatedPerpPositionTokenAmount does;
+// this version works for any decoded account, not only the client's active user.
+function isolatedMarginTokenAmount(marketIndex: number, scaledBalance: BN): BN {
+	const perpMarket = driftClient.getPerpMarketAccount(marketIndex) as PerpMarketAccount;
+	const spotMarket = driftClient.getSpotMarketAccount(
+		perpMarket.quoteSpotMarketIndex
+	) as SpotMarketAccount;
+	return getTokenAmount(scaledBalance, spotMarket, SpotBalanceType.DEPOSIT);
+}
+
+function snapshotPositions(userAccount: UserAccount): Map<number, PositionSnapshot> {
+	const snapshot = new Map<number, PositionSnapshot>();
+	for (const pos of userAccount.perpPositions) {
+		const isolated = pos.isolatedPositionScaledBalance ?? ZERO;
+		if (pos.baseAssetAmount.eq(ZERO) && isolated.eq(ZERO)) continue;
+		snapshot.set(pos.marketIndex, {
+			baseAssetAmount: pos.baseAssetAmount,
+			isolatedPositionScaledBalance: isolated,
+		});
+	}
+	return snapshot;
+}
+
+function snapshotOrders(userAccount: UserAccount): Map<number, OrderSnapshot> {
+	const snapshot = new Map<number, OrderSnapshot>();
+	for (const order of userAccount.orders) {
+		if (!isVariant(order.status, 'open') || !isVariant(order.marketType, 'perp')) continue;
+		snapshot.set(order.orderId, {
+			marketIndex: order.marketIndex,
+			direction: order.direction,
+			baseAssetAmount: order.baseAssetAmount,
+			baseAssetAmountFilled: order.baseAssetAmountFilled,
+			quoteAssetAmountFilled: order.quoteAssetAmountFilled,
+		});
+	}
+	return snapshot;
+}
+
+function emitFill(
+	wallet: string,
+	orderId: number,
+	order: OrderSnapshot,
+	baseFilled: BN,
+	quoteFilled: BN | null
+) {
+	const fillPrice =
+		quoteFilled && baseFilled.gt(ZERO)
+			? convertToNumber(quoteFilled.mul(BASE_PRECISION).div(baseFilled), QUOTE_PRECISION)
+			: null;
+	emitEvent({
+		event: 'orderFilled',
+		data: {
+			wallet,
+			market: marketSymbol(order.marketIndex),
+			orderId,
+			direction: isVariant(order.direction, 'long') ? 'long' : 'short',
+			filledSize: convertToNumber(baseFilled, BASE_PRECISION),
+			fillPrice,
+		},
+	});
+}
+
+function diffUserAccount(wallet: string, watch: WalletWatch, userAccount: UserAccount) {
+	const positions = snapshotPositions(userAccount);
+	const orders = snapshotOrders(userAccount);
+
+	const marketIndexes = new Set([...watch.positions.keys(), ...positions.keys()]);
+	for (const marketIndex of marketIndexes) {
+		const before = watch.positions.get(marketIndex);
+		const after = positions.get(marketIndex);
+		const unchanged =
+			before &&
+			after &&
+			before.baseAssetAmount.eq(after.baseAssetAmount) &&
+			before.isolatedPositionScaledBalance.eq(after.isolatedPositionScaledBalance);
+		if (unchanged) continue;
+		emitEvent({
+			event: 'positionChanged',
+			data: {
+				wallet,
+				market: marketSymbol(marketIndex),
+				size: after ? convertToNumber(after.baseAssetAmount, BASE_PRECISION) : 0,
+				isolatedMargin: after
+					? convertToNumber(
+							isolatedMarginTokenAmount(marketIndex, after.isolatedPositionScaledBalance),
+							QUOTE_PRECISION
+					  )
+					: null,
+			},
+		});
+	}
+
+	for (const [orderId, before] of watch.orders) {
+		const after = orders.get(orderId);
+		if (after) {
+			const baseDelta = after.baseAssetAmountFilled.sub(before.baseAssetAmountFilled);
+			if (baseDelta.gt(ZERO)) {
+				const quoteDelta = after.quoteAssetAmountFilled.sub(before.quoteAssetAmountFilled);
+				emitFill(wallet, orderId, after, baseDelta, quoteDelta);
+			}
+			continue;
+		}
+		// Drift recycles the order slot once an order completes, so a vanished order
+		// only counts as filled if its market's position moved in the same update.
+		const positionBefore = watch.positions.get(before.marketIndex)?.baseAssetAmount ?? ZERO;
+		const positionAfter = positions.get(before.marketIndex)?.baseAssetAmount ?? ZERO;
+		if (!positionBefore.eq(positionAfter)) {
+			const remaining = before.baseAssetAmount.sub(before.baseAssetAmountFilled);
+			emitFill(wallet, orderId, before, remaining, null);
+		}
+	}
+
+	// Market orders placed and filled between two updates never show up as open.
+	for (const [marketIndex, after] of positions) {
+		const before = watch.positions.get(marketIndex);
+		const pendingOrder = [...watch.orders.values()].some(
+			(order) => order.marketIndex === marketIndex
+		);
+		if (pendingOrder) continue;
+		const baseBefore = before?.baseAssetAmount ?? ZERO;
+		const delta = after.baseAssetAmount.sub(baseBefore);
+		if (delta.isZero()) continue;
+		emitEvent({
+			event: 'orderFilled',
+			data: {
+				wallet,
+				market: marketSymbol(marketIndex),
+				orderId: 0,
+				direction: delta.isNeg() ? 'short' : 'long',
+				filledSize: convertToNumber(bnAbs(delta), BASE_PRECISION),
+				fillPrice: null,
+			},
+		});
+	}
+
+	watch.positions = positions;
+	watch.orders = orders;
+}
+
+function subscribeOracleEvents() {
+	const byOracle = new Map<string, PerpMarketConfig>();
+	for (const cfg of marketMaps?.byIndex.values() ?? []) {
+		byOracle.set(cfg.oracle.toBase58(), cfg);
+	}
+
+	driftClient.eventEmitter.on('oraclePriceUpdate', (publicKey, _source, data) => {
+		const cfg = byOracle.get(publicKey.toBase58());
+		if (!cfg) return;
+		const now = Date.now();
+		const last = lastOracleEventAt.get(cfg.marketIndex) ?? 0;
+		if (now - last < ORACLE_EVENT_INTERVAL_MS) return;
+		lastOracleEventAt.set(cfg.marketIndex, now);
+		emitEvent({
+			event: 'oraclePriceUpdate',
+			data: {
+				market: cfg.symbol,
+				price: convertToNumber(data.price, PRICE_PRECISION),
+				slot: data.slot.toNumber(),
+			},
+		});
+	});
+
+	driftClient.eventEmitter.on('error', (err) => {
+		emitEvent({
+			event: 'subscriptionLost',
+			data: {
+				wallet: null,
+				reason: err instanceof Error ? err.message : String(err),
+			},
+		});
+	});
+}
+
 export async function initDrift(): Promise<void> {
 	if (initialized) {
 		return;
 	}
 	await driftClient.subscribe();
 	marketMaps = buildMarketMaps();
+	subscribeOracleEvents();
 	initialized = true;
 }
 
-export async function buildOpenIsolatedTx(req: OpenIsolatedReq) {
+export async function watchWallet(req: WalletOnlyReq) {
+	ensureInitialized();
+	const existing = walletWatches.get(req.wallet);
+	if (existing) {
+		existing.watchers += 1;
+		// Only acknowledge once the shared subscription is live; a failed subscribe
+		// rejects every caller that was waiting on it.
+		await existing.ready;
+		return { wallet: req.wallet, watchers: existing.watchers };
+	}
+
+	const walletPk = new PublicKey(req.wallet);
+	const userAccountPublicKey = getUserAccountPublicKeySync(
+		driftClient.program.programId,
+		walletPk,
+		0
+	);
+	const user = new User({
+		driftClient,
+		userAccountPublicKey,
+		accountSubscription: { type: 'websocket' },
+	});
+	const watch: WalletWatch = {
+		user,
+		watchers: 1,
+		ready: Promise.resolve(),
+		subscribed: false,
+		positions: new Map(),
+		orders: new Map(),
+	};
+
+	// Register before awaiting so concurrent watchers share one subscription.
+	walletWatches.set(req.wallet, watch);
+	watch.ready = subscribeWatch(req.wallet, watch);
+	await watch.ready;
+	return { wallet: req.wallet, watchers: watch.watchers };
+}
+
+// Drops the wallet's entry only while it is still this watch, not a newer one that replaced it.
+function forgetWatch(wallet: string, watch: WalletWatch) {
+	if (walletWatches.get(wallet) === watch) {
+		walletWatches.delete(wallet);
+	}
+}
+
+async function subscribeWatch(wallet: string, watch: WalletWatch): Promise<void> {
+	const { user } = watch;
+	const subscribed = await user.subscribe().catch(() => false);
+	if (!subscribed) {
+		forgetWatch(wallet, watch);
+		throw new Error(`Drift user account not found for ${wallet}`);
+	}
+	// Every watcher may have left while the subscribe was in flight.
+	if (watch.watchers <= 0) {
+		forgetWatch(wallet, watch);
+		await user.unsubscribe();
+		debugLog('stopped watching wallet', wallet);
+		return;
+	}
+	watch.subscribed = true;
+	const initial = user.getUserAccount();
+	watch.positions = snapshotPositions(initial);
+	watch.orders = snapshotOrders(initial);
+
+	user.eventEmitter.on('userAccountUpdate', (account: UserAccount) => {
+		diffUserAccount(wallet, watch, account);
+	});
+	user.eventEmitter.on('error', (err: unknown) => {
+		forgetWatch(wallet, watch);
+		void user.unsubscribe().catch(() => undefined);
+		emitEvent({
+			event: 'subscriptionLost',
+			data: {
+				wallet,
+				reason: err instanceof Error ? err.message : String(err),
+			},
+		});
+	});
+
+	debugLog('watching wallet', wallet);
+}
+
+export async function unwatchWallet(req: WalletOnlyReq) {
+	const watch = walletWatches.get(req.wallet);
+	if (!watch) {
+		return { wallet: req.wallet, watchers: 0 };
+	}
+	watch.watchers -= 1;
+	if (watch.watchers <= 0) {
+		walletWatches.delete(req.wallet);
+		// A subscribe still in flight tears itself down once it sees no watchers left.
+		if (watch.subscribed) {
+			await watch.user.unsubscribe();
+			debugLog('stopped watching wallet', req.wallet);
+		}
+	}
+	return { wallet: req.wallet, watchers: Math.max(watch.watchers, 0) };
+}
+
+async function openIsolatedInstructions(req: OpenIsolatedReq) {
 	const walletPk = new PublicKey(req.wallet);
+	const { subAccountId } = req;
 	const marketConfig = resolveMarketConfig(req.market);
 	const perpMarket = driftClient.getPerpMarketAccount(
 		marketConfig.marketIndex
@@ -539,12 +956,12 @@ export async function buildOpenIsolatedTx(req: OpenIsolatedReq) {
 	const userPk = getUserAccountPublicKeySync(
 		driftClient.program.programId,
 		walletPk,
-		0
+		subAccountId
 	);
 
-	const initIxs = await ensureUserInitIxs(walletPk) as TransactionInstruction[];
-	const userAccount = await fetchUserAccount(walletPk);
-	await ensureDriftUserCached(walletPk, userAccount);
+	const initIxs = await ensureUserInitIxs(walletPk, subAccountId) as TransactionInstruction[];
+	const userAccount = await fetchUserAccount(walletPk, subAccountId);
+	await ensureDriftUserCached(walletPk, userAccount, subAccountId);
 
 	debugLog('open isolated userAccount', userAccount);
 	debugLog('open isolated userPk', userPk.toBase58());
@@ -597,39 +1014,734 @@ export async function buildOpenIsolatedTx(req: OpenIsolatedReq) {
 			marketConfig.marketIndex,
 			userTokenAccount
 		);
-	});
+	}, subAccountId);
 
 	const orderIx = await withAuthority(walletPk, async () => {
-		const orderParams = getMarketOrderParams({
-			marketIndex: marketConfig.marketIndex,
+		const orderParams = buildPerpOrderParams(
+			req,
+			marketConfig.marketIndex,
 			direction,
-			baseAssetAmount: baseAmount,
-			reduceOnly: false,
-		});
+			baseAmount,
+			false
+		);
 		return driftClient.getPlacePerpOrderIx(orderParams);
-	});
+	}, subAccountId);
 
 	const oraclePrice = driftClient.getOracleDataForPerpMarket(
 		marketConfig.marketIndex
 	).price;
 
 	const meta = {
-		entryPrice: convertToNumber(oraclePrice, PRICE_PRECISION),
+		orderType: req.orderType,
+		entryPrice: req.price ?? convertToNumber(oraclePrice, PRICE_PRECISION),
 		estLiquidationPrice: null as number | null,
 	};
 
-	const { txBase64, signatures } = await buildTransaction(walletPk, [
+	const instructions = [
 		...initIxs,
 		...(ensureTokenAccountIx ? [ensureTokenAccountIx] : []),
 		depositIx,
 		orderIx,
+	];
+	return { walletPk, userAccount, marketConfig, direction, baseAmount, instructions, meta };
+}
+
+export async function buildOpenIsolatedTx(req: OpenIsolatedReq) {
+	const { walletPk, instructions, meta } = await openIsolatedInstructions(req);
+	const { txBase64, signatures, lastValidBlockHeight } = await buildTransaction(
+		walletPk,
+		instructions
+	);
+	return { txBase64, signatures, lastValidBlockHeight, meta };
+}
+
+type BracketLeg = { triggerPrice: number; limitPrice?: number };
+type PlacedLeg = { userOrderId: number; triggerPrice: number; limitPrice: number | null };
+
+function openUserOrderIds(userAccount: UserAccount | null, marketIndex?: number): number[] {
+	return (userAccount?.orders ?? [])
+		.filter(
+			(order) =>
+				isVariant(order.status, 'open') &&
+				order.userOrderId !== 0 &&
+				(marketIndex === undefined ||
+					(order.marketIndex === marketIndex && isVariant(order.marketType, 'perp')))
+		)
+		.map((order) => order.userOrderId);
+}
+
+function freeUserOrderIds(
+	userAccount: UserAccount | null,
+	count: number,
+	reserved: number[]
+): number[] {
+	const used = new Set([...openUserOrderIds(userAccount), ...reserved]);
+	const free: number[] = [];
+	for (let id = 1; id <= 255 && free.length < count; id++) {
+		if (!used.has(id)) free.push(id);
+	}
+	if (free.length < count) {
+		throw new Error('No free user order ids for bracket legs');
+	}
+	return free;
+}
+
+// Reduce-only trigger orders closing a position held in `positionDirection`. The
+// take-profit has to trigger on the profitable side of the oracle, the stop-loss on the
+// other, or it would fire straight away.
+function bracketLegOrders(
+	legs: { takeProfit?: BracketLeg; stopLoss?: BracketLeg },
+	marketIndex: number,
+	positionDirection: PositionDirection,
+	baseAssetAmount: BN,
+	userAccount: UserAccount | null,
+	reservedUserOrderIds: number[]
+) {
+	const isLong = isVariant(positionDirection, 'long');
+	const oraclePrice = convertToNumber(
+		driftClient.getOracleDataForPerpMarket(marketIndex).price,
+		PRICE_PRECISION
+	);
+	const entries = (
+		[
+			['takeProfit', legs.takeProfit],
+			['stopLoss', legs.stopLoss],
+		] as const
+	).filter((entry): entry is readonly ['takeProfit' | 'stopLoss', BracketLeg] => !!entry[1]);
+
+	const ids = freeUserOrderIds(userAccount, entries.length, reservedUserOrderIds);
+	const closeDirection = isLong ? PositionDirection.SHORT : PositionDirection.LONG;
+	const placed: { takeProfit?: PlacedLeg; stopLoss?: PlacedLeg } = {};
+	const params = entries.map(([name, leg], index) => {
+		const above = (name === 'takeProfit') === isLong;
+		if (above ? leg.triggerPrice <= oraclePrice : leg.triggerPrice >= oraclePrice) {
+			throw new Error(
+				`${name} trigger ${leg.triggerPrice} must be ${above ? 'above' : 'below'} the oracle price ${oraclePrice}`
+			);
+		}
+		const orderParams = buildPerpOrderParams(
+			{
+				orderType: leg.limitPrice !== undefined ? 'triggerLimit' : 'triggerMarket',
+				price: leg.limitPrice,
+				postOnly: 'none',
+				immediateOrCancel: false,
+				triggerPrice: leg.triggerPrice,
+				triggerCondition: above ? 'above' : 'below',
+			},
+			marketIndex,
+			closeDirection,
+			baseAssetAmount,
+			true
+		);
+		orderParams.userOrderId = ids[index];
+		placed[name] = {
+			userOrderId: ids[index],
+			triggerPrice: leg.triggerPrice,
+			limitPrice: leg.limitPrice ?? null,
+		};
+		return orderParams;
+	});
+	return { params, placed };
+}
+
+// Opens the isolated position and places its reduce-only legs, sized to the entry, in the
+// same transaction.
+export async function buildOpenBracketTx(req: OpenBracketReq) {
+	const { walletPk, userAccount, marketConfig, direction, baseAmount, instructions, meta } =
+		await openIsolatedInstructions(req);
+	const { params, placed } = bracketLegOrders(
+		req,
+		marketConfig.marketIndex,
+		direction,
+		baseAmount,
+		userAccount,
+		[]
+	);
+	if (params.length === 0) {
+		throw new Error('A bracket needs a takeProfit or a stopLoss');
+	}
+	const legsIx = await withAuthority(
+		walletPk,
+		async () => driftClient.getPlaceOrdersIx(params),
+		req.subAccountId
+	);
+	const { txBase64, signatures, lastValidBlockHeight } = await buildTransaction(walletPk, [
+		...instructions,
+		legsIx,
 	]);
+	return { txBase64, signatures, lastValidBlockHeight, meta, legs: placed };
+}
+
+// Cancels bracket legs by user order id and places replacements sized to the current
+// position. Legs that are no longer open are skipped.
+export async function buildBracketLegsTx(req: BracketLegsReq) {
+	const walletPk = new PublicKey(req.wallet);
+	const { subAccountId } = req;
+	const marketConfig = resolveMarketConfig(req.market);
+	const userAccount = await fetchUserAccount(walletPk, subAccountId);
+	if (!userAccount) {
+		throw new Error('User account not found');
+	}
+	await ensureDriftUserCached(walletPk, userAccount, subAccountId);
+
+	const open = new Set(openUserOrderIds(userAccount, marketConfig.marketIndex));
+	const cancelled = req.cancelUserOrderIds.filter((id) => open.has(id));
+	const instructions: TransactionInstruction[] = [];
+	for (const userOrderId of cancelled) {
+		instructions.push(
+			await withAuthority(
+				walletPk,
+				async () => driftClient.getCancelOrderByUserIdIx(userOrderId),
+				subAccountId
+			)
+		);
+	}
+
+	let placed = {};
+	if (req.takeProfit || req.stopLoss) {
+		const position = userAccount.perpPositions.find(
+			(pos) => pos.marketIndex === marketConfig.marketIndex && !pos.baseAssetAmount.eq(ZERO)
+		);
+		if (!position) {
+			throw new Error(`No open ${marketConfig.symbol} position to bracket`);
+		}
+		const direction = position.baseAssetAmount.isNeg()
+			? PositionDirection.SHORT
+			: PositionDirection.LONG;
+		const legs = bracketLegOrders(
+			req,
+			marketConfig.marketIndex,
+			direction,
+			bnAbs(position.baseAssetAmount),
+			userAccount,
+			req.cancelUserOrderIds
+		);
+		instructions.push(
+			await withAuthority(
+				walletPk,
+				async () => driftClient.getPlaceOrdersIx(legs.params),
+				subAccountId
+			)
+		);
+		placed = legs.placed;
+	}
+
+	if (instructions.length === 0) {
+		throw new Error('No bracket legs to place or cancel');
+	}
+	const { txBase64, signatures, lastValidBlockHeight } = await buildTransaction(
+		walletPk,
+		instructions
+	);
+	return {
+		txBase64,
+		signatures,
+		lastValidBlockHeight,
+		legs: placed,
+		cancelledUserOrderIds: cancelled,
+	};
+}
+
+export async function getBracketStatus(req: BracketStatusReq) {
+	const walletPk = new PublicKey(req.wallet);
+	const marketConfig = resolveMarketConfig(req.market);
+	const userAccount = await fetchUserAccount(walletPk, req.subAccountId);
+	const position = userAccount?.perpPositions.find(
+		(pos) => pos.marketIndex === marketConfig.marketIndex
+	);
+	const open = openUserOrderIds(userAccount, marketConfig.marketIndex);
+	return {
+		market: marketConfig.symbol,
+		positionSize: position ? convertToNumber(position.baseAssetAmount, BASE_PRECISION) : 0,
+		openUserOrderIds: req.userOrderIds.filter((id) => open.includes(id)),
+	};
+}
+
+function openPerpOrders(userAccount: UserAccount | null) {
+	return (userAccount?.orders ?? []).filter(
+		(order) => isVariant(order.status, 'open') && isVariant(order.marketType, 'perp')
+	);
+}
+
+export async function getOpenOrders(req: OpenOrdersReq) {
+	const walletPk = new PublicKey(req.wallet);
+	const userAccount = await fetchUserAccount(walletPk, req.subAccountId);
+	const marketIndex = req.market ? resolveMarketConfig(req.market).marketIndex : undefined;
+	return openPerpOrders(userAccount)
+		.filter((order) => marketIndex === undefined || order.marketIndex === marketIndex)
+		.map((order) => {
+			const hasTrigger = !order.triggerPrice.eq(ZERO);
+			return {
+				orderId: order.orderId,
+				userOrderId: order.userOrderId,
+				market: marketSymbol(order.marketIndex),
+				marketIndex: order.marketIndex,
+				// Anchor enums are single-key objects named like ORDER_TYPES.
+				orderType: Object.keys(order.orderType)[0],
+				direction: isVariant(order.direction, 'long') ? 'long' : 'short',
+				size: convertToNumber(order.baseAssetAmount, BASE_PRECISION),
+				filled: convertToNumber(order.baseAssetAmountFilled, BASE_PRECISION),
+				price: order.price.eq(ZERO) ? null : convertToNumber(order.price, PRICE_PRECISION),
+				triggerPrice: hasTrigger ? convertToNumber(order.triggerPrice, PRICE_PRECISION) : null,
+				triggerCondition: hasTrigger
+					? isVariant(order.triggerCondition, 'below') ||
+						isVariant(order.triggerCondition, 'triggeredBelow')
+						? 'below'
+						: 'above'
+					: null,
+				oraclePriceOffset:
+					order.oraclePriceOffset === 0
+						? null
+						: convertToNumber(new BN(order.oraclePriceOffset), PRICE_PRECISION),
+				reduceOnly: order.reduceOnly,
+				postOnly: order.postOnly,
+				maxTs: order.maxTs.eq(ZERO) ? null : order.maxTs.toNumber(),
+				slot: order.slot.toNumber(),
+			};
+		});
+}
+
+// Cancels by order ids, user order ids, every perp order in a market, or every order.
+// Ids that are not open are skipped.
+export async function buildCancelOrdersTx(req: CancelOrdersReq) {
+	const walletPk = new PublicKey(req.wallet);
+	const { subAccountId } = req;
+	const userAccount = await fetchUserAccount(walletPk, subAccountId);
+	if (!userAccount) {
+		throw new Error('User account not found');
+	}
+	await ensureDriftUserCached(walletPk, userAccount, subAccountId);
+
+	const open = openPerpOrders(userAccount);
+	let cancelled = open;
+	let instructions: TransactionInstruction[];
+	if (req.orderIds) {
+		const ids = new Set(req.orderIds);
+		cancelled = open.filter((order) => ids.has(order.orderId));
+		instructions = cancelled.length
+			? [
+					await withAuthority(
+						walletPk,
+						async () =>
+							driftClient.getCancelOrdersByIdsIx(
+								cancelled.map((order) => order.orderId),
+								subAccountId
+							),
+						subAccountId
+					),
+				]
+			: [];
+	} else if (req.userOrderIds) {
+		const ids = new Set(req.userOrderIds);
+		cancelled = open.filter((order) => ids.has(order.userOrderId));
+		instructions = [];
+		for (const order of cancelled) {
+			instructions.push(
+				await withAuthority(
+					walletPk,
+					async () => driftClient.getCancelOrderByUserIdIx(order.userOrderId),
+					subAccountId
+				)
+			);
+		}
+	} else if (req.market) {
+		const marketConfig = resolveMarketConfig(req.market);
+		cancelled = open.filter((order) => order.marketIndex === marketConfig.marketIndex);
+		instructions = cancelled.length
+			? [
+					await withAuthority(
+						walletPk,
+						async () =>
+							driftClient.getCancelOrdersIx(
+								MarketType.PERP,
+								marketConfig.marketIndex,
+								null,
+								subAccountId
+							),
+						subAccountId
+					),
+				]
+			: [];
+	} else {
+		instructions = cancelled.length
+			? [
+					await withAuthority(
+						walletPk,
+						async () => driftClient.getCancelOrdersIx(null, null, null, subAccountId),
+						subAccountId
+					),
+				]
+			: [];
+	}
+
+	if (instructions.length === 0) {
+		throw new Error('No matching open orders to cancel');
+	}
+	const { txBase64, signatures, lastValidBlockHeight } = await buildTransaction(
+		walletPk,
+		instructions
+	);
+	return {
+		txBase64,
+		signatures,
+		lastValidBlockHeight,
+		cancelled: cancelled.map((order) => ({
+			orderId: order.orderId,
+			userOrderId: order.userOrderId,
+			market: marketSymbol(order.marketIndex),
+		})),
+	};
+}
+
+// Drift keeps the order id and replaces the fields that are given.
+export async function buildModifyOrderTx(req: ModifyOrderReq) {
+	const walletPk = new PublicKey(req.wallet);
+	const { subAccountId } = req;
+	const userAccount = await fetchUserAccount(walletPk, subAccountId);
+	if (!userAccount) {
+		throw new Error('User account not found');
+	}
+	await ensureDriftUserCached(walletPk, userAccount, subAccountId);
+
+	const order = openPerpOrders(userAccount).find((candidate) =>
+		req.orderId !== undefined
+			? candidate.orderId === req.orderId
+			: candidate.userOrderId === req.userOrderId
+	);
+	if (!order) {
+		throw new Error('Order not found or no longer open');
+	}
+	const changes: Record<string, unknown> = {};
+	if (req.size !== undefined) {
+		changes.newBaseAmount = toBasePrecision(req.size, BASE_PRECISION);
+	}
+	if (req.price !== undefined) {
+		changes.newLimitPrice = toPricePrecision(req.price);
+	}
+	if (req.triggerPrice !== undefined) {
+		changes.newTriggerPrice = toPricePrecision(req.triggerPrice);
+	}
+	if (req.triggerCondition !== undefined) {
+		changes.newTriggerCondition =
+			req.triggerCondition === 'below'
+				? OrderTriggerCondition.BELOW
+				: OrderTriggerCondition.ABOVE;
+	}
+	if (req.oraclePriceOffset !== undefined) {
+		changes.newOraclePriceOffset = toPricePrecision(req.oraclePriceOffset).toNumber();
+	}
+	if (req.reduceOnly !== undefined) {
+		changes.reduceOnly = req.reduceOnly;
+	}
+	if (req.postOnly !== undefined) {
+		changes.postOnly = POST_ONLY[req.postOnly];
+	}
+	if (req.maxTs !== undefined) {
+		changes.maxTs = new BN(req.maxTs);
+	}
+	if (Object.keys(changes).length === 0) {
+		throw new Error('Nothing to modify');
+	}
+
+	const ix = await withAuthority(
+		walletPk,
+		async () =>
+			req.orderId !== undefined
+				? driftClient.getModifyOrderIx(
+						{ orderId: order.orderId, ...changes },
+						subAccountId
+					)
+				: driftClient.getModifyOrderByUserIdIx(
+						{ userOrderId: order.userOrderId, ...changes },
+						subAccountId
+					),
+		subAccountId
+	);
+	const { txBase64, signatures, lastValidBlockHeight } = await buildTransaction(walletPk, [ix]);
+	// The order as it stands once modified, for the rust-api risk check.
+	const baseAmount = (changes.newBaseAmount as BN | undefined) ?? order.baseAssetAmount;
+	const price = (changes.newLimitPrice as BN | undefined) ?? order.price;
+	return {
+		txBase64,
+		signatures,
+		lastValidBlockHeight,
+		order: {
+			orderId: order.orderId,
+			userOrderId: order.userOrderId,
+			market: marketSymbol(order.marketIndex),
+			direction: isVariant(order.direction, 'long') ? 'long' : 'short',
+			unfilledSize: convertToNumber(
+				BN.max(baseAmount.sub(order.baseAssetAmountFilled), ZERO),
+				BASE_PRECISION
+			),
+			price: price.eq(ZERO) ? null : convertToNumber(price, PRICE_PRECISION),
+			reduceOnly: (changes.reduceOnly as boolean | undefined) ?? order.reduceOnly,
+		},
+	};
+}
+
+// Room left for the compute budget instructions rust-api may add before signing.
+const COMPUTE_BUDGET_RESERVE_BYTES = 100;
+
+function fitsInTransaction(feePayer: PublicKey, instructions: TransactionInstruction[]): boolean {
+	const tx = new Transaction({ feePayer });
+	tx.recentBlockhash = PublicKey.default.toBase58();
+	tx.add(...instructions);
+	try {
+		const size = tx.serialize({ requireAllSignatures: false, verifySignatures: false }).length;
+		return size <= PACKET_DATA_SIZE - COMPUTE_BUDGET_RESERVE_BYTES;
+	} catch {
+		return false;
+	}
+}
+
+type BatchPart = {
+	index: number;
+	type: BatchLeg['type'];
+	market: string;
+	// Margin movements, run before the orders.
+	setup: TransactionInstruction[];
+	order: OrderParams | null;
+	meta: Record<string, unknown>;
+};
+
+async function batchPart(
+	walletPk: PublicKey,
+	subAccountId: number,
+	userAccount: UserAccount | null,
+	leg: BatchLeg,
+	index: number
+): Promise<BatchPart> {
+	const marketConfig = resolveMarketConfig(leg.market);
+	const marketIndex = marketConfig.marketIndex;
+	const perpMarket = driftClient.getPerpMarketAccount(marketIndex) as PerpMarketAccount;
+	const amm = perpMarket.amm as unknown as { basePrecision?: BN };
+	const basePrecision = amm.basePrecision ?? BASE_PRECISION;
+	const part = { index, type: leg.type, market: marketConfig.symbol };
+	const position = userAccount?.perpPositions.find(
+		(pos) => pos.marketIndex === marketIndex && !pos.baseAssetAmount.eq(ZERO)
+	);
+
+	switch (leg.type) {
+		case 'open': {
+			const spotMarket = driftClient.getSpotMarketAccount(
+				perpMarket.quoteSpotMarketIndex
+			) as SpotMarketAccount;
+			const tokenProgram = driftClient.getTokenProgramForSpotMarket(spotMarket);
+			const userTokenAccount = await driftClient.getAssociatedTokenAccount(
+				perpMarket.quoteSpotMarketIndex,
+				false,
+				tokenProgram,
+				walletPk
+			);
+			const depositIx = await withAuthority(
+				walletPk,
+				async () =>
+					driftClient.getDepositIntoIsolatedPerpPositionIx(
+						toQuotePrecision(leg.margin),
+						marketIndex,
+						userTokenAccount
+					),
+				subAccountId
+			);
+			const direction = leg.size >= 0 ? PositionDirection.LONG : PositionDirection.SHORT;
+			const order = buildPerpOrderParams(
+				leg,
+				marketIndex,
+				direction,
+				toBasePrecision(Math.abs(leg.size), basePrecision),
+				false
+			);
+			const oraclePrice = driftClient.getOracleDataForPerpMarket(marketIndex).price;
+			return {
+				...part,
+				setup: [depositIx],
+				order,
+				meta: {
+					orderType: leg.orderType,
+					entryPrice: leg.price ?? convertToNumber(oraclePrice, PRICE_PRECISION),
+				},
+			};
+		}
+		case 'close': {
+			if (!position) {
+				throw new Error(`Leg ${index}: no open ${marketConfig.symbol} position to close`);
+			}
+			const held = bnAbs(position.baseAssetAmount);
+			const size =
+				leg.size !== undefined
+					? BN.min(toBasePrecision(leg.size, basePrecision), held)
+					: held;
+			const order = buildPerpOrderParams(
+				{ orderType: 'market', postOnly: 'none', immediateOrCancel: false },
+				marketIndex,
+				findDirectionToClose(position),
+				size,
+				true
+			);
+			return {
+				...part,
+				setup: [],
+				order,
+				meta: { size: convertToNumber(size, basePrecision) },
+			};
+		}
+		case 'transfer': {
+			const amount = toQuotePrecision(Math.abs(leg.delta));
+			if (amount.isZero()) {
+				throw new Error(`Leg ${index}: delta resolves to zero`);
+			}
+			const spotMarket = driftClient.getSpotMarketAccount(
+				perpMarket.quoteSpotMarketIndex
+			) as SpotMarketAccount;
+			const setup =
+				leg.delta > 0
+					? [
+							await withAuthority(
+								walletPk,
+								async () =>
+									driftClient.getTransferIsolatedPerpPositionDepositIx(
+										amount,
+										marketIndex
+									),
+								subAccountId
+							),
+						]
+					: await withAuthority(
+							walletPk,
+							async () =>
+								driftClient.getWithdrawFromIsolatedPerpPositionIxsBundle(
+									amount,
+									marketIndex,
+									subAccountId,
+									findAssociatedTokenAddress(walletPk, spotMarket.mint)
+								),
+							subAccountId
+						);
+			return { ...part, setup, order: null, meta: { delta: leg.delta } };
+		}
+	}
+}
+
+async function batchInstructions(
+	walletPk: PublicKey,
+	subAccountId: number,
+	shared: TransactionInstruction[],
+	parts: BatchPart[]
+): Promise<TransactionInstruction[]> {
+	const orders = parts.flatMap((part) => (part.order ? [part.order] : []));
+	const instructions = [...shared, ...parts.flatMap((part) => part.setup)];
+	if (orders.length > 0) {
+		instructions.push(
+			await withAuthority(
+				walletPk,
+				async () => driftClient.getPlaceOrdersIx(orders),
+				subAccountId
+			)
+		);
+	}
+	return instructions;
+}
+
+// Builds the legs into as few transactions as fit, in leg order. Every order in a
+// transaction goes through one placeOrders instruction after that transaction's margin
+// movements. A single transaction succeeds or fails as a whole.
+export async function buildBatchTx(req: BatchReq) {
+	const walletPk = new PublicKey(req.wallet);
+	const { subAccountId } = req;
+	const initIxs = await ensureUserInitIxs(walletPk, subAccountId);
+	const userAccount = await fetchUserAccount(walletPk, subAccountId);
+	await ensureDriftUserCached(walletPk, userAccount, subAccountId);
+
+	const parts: BatchPart[] = [];
+	for (const [index, leg] of req.legs.entries()) {
+		parts.push(await batchPart(walletPk, subAccountId, userAccount, leg, index));
+	}
+
+	// Isolated deposits pull from the wallet's token accounts, so create any that are missing.
+	const shared: TransactionInstruction[] = [...initIxs];
+	const spotIndexes = new Set(
+		req.legs
+			.filter((leg) => leg.type === 'open')
+			.map(
+				(leg) =>
+					(
+						driftClient.getPerpMarketAccount(
+							resolveMarketConfig(leg.market).marketIndex
+						) as PerpMarketAccount
+					).quoteSpotMarketIndex
+			)
+	);
+	for (const spotIndex of spotIndexes) {
+		const spotMarket = driftClient.getSpotMarketAccount(spotIndex) as SpotMarketAccount;
+		const tokenProgram = driftClient.getTokenProgramForSpotMarket(spotMarket);
+		const tokenAccount = await driftClient.getAssociatedTokenAccount(
+			spotIndex,
+			false,
+			tokenProgram,
+			walletPk
+		);
+		if ((await connection.getAccountInfo(tokenAccount)) === null) {
+			shared.push(
+				driftClient.createAssociatedTokenAccountIdempotentInstruction(
+					tokenAccount,
+					walletPk,
+					walletPk,
+					spotMarket.mint,
+					tokenProgram
+				)
+			);
+		}
+	}
+
+	const groups: BatchPart[][] = [];
+	let current: BatchPart[] = [];
+	for (const part of parts) {
+		const candidate = [...current, part];
+		const instructions = await batchInstructions(
+			walletPk,
+			subAccountId,
+			groups.length === 0 ? shared : [],
+			candidate
+		);
+		if (current.length > 0 && !fitsInTransaction(walletPk, instructions)) {
+			groups.push(current);
+			current = [part];
+		} else {
+			current = candidate;
+		}
+	}
+	groups.push(current);
 
-	return { txBase64, signatures, meta };
+	const transactions = [];
+	for (const [txIndex, group] of groups.entries()) {
+		const instructions = await batchInstructions(
+			walletPk,
+			subAccountId,
+			txIndex === 0 ? shared : [],
+			group
+		);
+		const built = await buildTransaction(walletPk, instructions);
+		transactions.push({ ...built, legs: group.map((part) => part.index) });
+	}
+	return {
+		atomic: transactions.length === 1,
+		transactions,
+		legs: groups.flatMap((group, txIndex) =>
+			group.map((part) => ({
+				index: part.index,
+				type: part.type,
+				market: part.market,
+				transaction: txIndex,
+				meta: part.meta,
+			}))
+		),
+	};
 }
 
 export async function buildInitializeAndDepositIsolatedTx(req: DepositIsolatedReq) {
 	const walletPk = new PublicKey(req.wallet);
+	const { subAccountId } = req;
 	const marketConfig = resolveMarketConfig(req.market);
 	const perpMarket = driftClient.getPerpMarketAccount(
 		marketConfig.marketIndex
@@ -641,9 +1753,9 @@ export async function buildInitializeAndDepositIsolatedTx(req: DepositIsolatedRe
 
 	const depositAmount = toQuotePrecision(req.amount);
 
-	const initIxs = (await ensureUserInitIxs(walletPk)) as TransactionInstruction[];
-	const userAccount = await fetchUserAccount(walletPk);
-	await ensureDriftUserCached(walletPk, userAccount);
+	const initIxs = (await ensureUserInitIxs(walletPk, subAccountId)) as TransactionInstruction[];
+	const userAccount = await fetchUserAccount(walletPk, subAccountId);
+	await ensureDriftUserCached(walletPk, userAccount, subAccountId);
 
 	const userStatsPk = getUserStatsAccountPublicKey(
 		driftClient.program.programId,
@@ -677,21 +1789,22 @@ export async function buildInitializeAndDepositIsolatedTx(req: DepositIsolatedRe
 			marketConfig.marketIndex,
 			userTokenAccount
 		);
-	});
+	}, subAccountId);
 
-	const { txBase64, signatures } = await buildTransaction(walletPk, [
+	const { txBase64, signatures, lastValidBlockHeight } = await buildTransaction(walletPk, [
 		...initIxs,
 		...(ensureTokenAccountIx ? [ensureTokenAccountIx] : []),
 		depositIx,
 	]);
 
-	return { txBase64, signatures };
+	return { txBase64, signatures, lastValidBlockHeight };
 }
 
 export async function buildClosePositionTx(req: ClosePositionReq) {
 	const walletPk = new PublicKey(req.wallet);
+	const { subAccountId } = req;
 	const marketConfig = resolveMarketConfig(req.market);
-	const userAccount = await fetchUserAccount(walletPk);
+	const userAccount = await fetchUserAccount(walletPk, subAccountId);
 	if (!userAccount) {
 		throw new Error('User account not found');
 	}
@@ -705,14 +1818,14 @@ export async function buildClosePositionTx(req: ClosePositionReq) {
 		(pos) => pos.marketIndex === marketConfig.marketIndex
 	);
 
-	await ensureDriftUserCached(walletPk, userAccount);
+	await ensureDriftUserCached(walletPk, userAccount, subAccountId);
 
 	// Check if there's isolated margin to withdraw
 	const isolatedMarginAmount = await withAuthority(walletPk, async () => {
 		return driftClient.getIsolatedPerpPositionTokenAmount(
 			marketConfig.marketIndex
 		) ?? ZERO;
-	});
+	}, subAccountId);
 
 	const hasIsolatedMargin = isolatedMarginAmount.gt(ZERO);
 	const hasOpenPosition = position && !position.baseAssetAmount.eq(new BN(0));
@@ -727,13 +1840,13 @@ export async function buildClosePositionTx(req: ClosePositionReq) {
 			return driftClient.getWithdrawFromIsolatedPerpPositionIxsBundle(
 				isolatedMarginAmount,
 				marketConfig.marketIndex,
-				0,
+				subAccountId,
 				findAssociatedTokenAddress(walletPk, spotMarket.mint)
 			);
-		});
+		}, subAccountId);
 		
-		const { txBase64, signatures } = await buildTransaction(walletPk, withdrawIxs);
-		return { txBase64, signatures };
+		const { txBase64, signatures, lastValidBlockHeight } = await buildTransaction(walletPk, withdrawIxs);
+		return { txBase64, signatures, lastValidBlockHeight };
 	}
 
 	// If there's an open position, we need to close it with a reduce-only order
@@ -759,7 +1872,7 @@ export async function buildClosePositionTx(req: ClosePositionReq) {
 	const userPk = getUserAccountPublicKeySync(
 		driftClient.program.programId,
 		walletPk,
-		0
+		subAccountId
 	);
 
 	// 1) Close the perp position with a reduce-only order using placeAndTake
@@ -781,9 +1894,9 @@ export async function buildClosePositionTx(req: ClosePositionReq) {
 			undefined, // referrerInfo
 			undefined, // successCondition
 			undefined, // auctionDurationPercentage
-			0 // subAccountId
+			subAccountId
 		);
-	});
+	}, subAccountId);
 
 	const instructions: TransactionInstruction[] = [orderIx];
 
@@ -796,7 +1909,7 @@ export async function buildClosePositionTx(req: ClosePositionReq) {
 				userAccount,
 				marketConfig.marketIndex
 			);
-		});
+		}, subAccountId);
 		instructions.push(settlePnlIx);
 	} catch (err) {
 		// If settle PnL fails (e.g., no PnL to settle), continue without it
@@ -830,10 +1943,10 @@ export async function buildClosePositionTx(req: ClosePositionReq) {
 				return driftClient.getWithdrawFromIsolatedPerpPositionIxsBundle(
 					availableMargin,
 					marketConfig.marketIndex,
-					0,
+					subAccountId,
 					findAssociatedTokenAddress(walletPk, spotMarket.mint)
 				);
-			});
+			}, subAccountId);
 			instructions.push(...withdrawIxs);
 		} catch (err) {
 			// If withdraw fails (e.g., order didn't fill completely), continue without it
@@ -842,12 +1955,13 @@ export async function buildClosePositionTx(req: ClosePositionReq) {
 		}
 	}
 
-	const { txBase64, signatures } = await buildTransaction(walletPk, instructions);
-	return { txBase64, signatures };
+	const { txBase64, signatures, lastValidBlockHeight } = await buildTransaction(walletPk, instructions);
+	return { txBase64, signatures, lastValidBlockHeight };
 }
 
 export async function buildTransferIsolatedMarginTx(req: TransferMarginReq) {
 	const walletPk = new PublicKey(req.wallet);
+	const { subAccountId } = req;
 	const marketConfig = resolveMarketConfig(req.market);
 	const perpMarket = driftClient.getPerpMarketAccount(
 		marketConfig.marketIndex
@@ -858,20 +1972,20 @@ export async function buildTransferIsolatedMarginTx(req: TransferMarginReq) {
 	const userPk = getUserAccountPublicKeySync(
 		driftClient.program.programId,
 		walletPk,
-		0
+		subAccountId
 	);
 	const userStatsPk = getUserStatsAccountPublicKey(
 		driftClient.program.programId,
 		walletPk
 	);
 
-	const userAccount = await fetchUserAccount(walletPk);
+	const userAccount = await fetchUserAccount(walletPk, subAccountId);
 	if (!userAccount) {
 		throw new Error('User account not found');
 	}
 
 	// Ensure user is cached so methods like getTransferIsolatedPerpPositionDepositIx work
-	await ensureDriftUserCached(walletPk, userAccount);
+	await ensureDriftUserCached(walletPk, userAccount, subAccountId);
 
 	const amount = toQuotePrecision(Math.abs(req.delta));
 	if (amount.isZero()) {
@@ -884,7 +1998,7 @@ export async function buildTransferIsolatedMarginTx(req: TransferMarginReq) {
 			return driftClient.getIsolatedPerpPositionTokenAmount(
 				marketConfig.marketIndex
 			) ?? ZERO;
-		});
+		}, subAccountId);
 
 		// Check if there's an open position that requires margin to remain
 		const position = userAccount.perpPositions.find(
@@ -926,7 +2040,7 @@ export async function buildTransferIsolatedMarginTx(req: TransferMarginReq) {
 		}
 	}
 
-	const initIxs = await ensureUserInitIxs(walletPk);
+	const initIxs = await ensureUserInitIxs(walletPk, subAccountId);
 	const instructions: TransactionInstruction[] = [...initIxs];
 
 	if (req.delta >= 0) {
@@ -935,22 +2049,22 @@ export async function buildTransferIsolatedMarginTx(req: TransferMarginReq) {
 				amount,
 				marketConfig.marketIndex
 			);
-		});
+		}, subAccountId);
 		instructions.push(transferIx);
 	} else {
 		const withdrawIxs = await withAuthority(walletPk, async () => {
 			return driftClient.getWithdrawFromIsolatedPerpPositionIxsBundle(
 				amount,
 				marketConfig.marketIndex,
-				0,
+				subAccountId,
 				findAssociatedTokenAddress(walletPk, spotMarket.mint)
 			);
-		});
+		}, subAccountId);
 		instructions.push(...withdrawIxs);
 	}
 
-	const { txBase64, signatures } = await buildTransaction(walletPk, instructions);
-	return { txBase64, signatures };
+	const { txBase64, signatures, lastValidBlockHeight } = await buildTransaction(walletPk, instructions);
+	return { txBase64, signatures, lastValidBlockHeight };
 }
 
 export async function getPositions(req: WalletOnlyReq) {
@@ -1013,15 +2127,70 @@ export async function getMarket(req: MarketQueryReq) {
 	const price = convertToNumber(oracle.price, PRICE_PRECISION);
 	const mark = convertToNumber(perpMarket.amm.lastMarkPriceTwap, PRICE_PRECISION);
 	const funding = convertToNumber(perpMarket.amm.lastFundingRate, FUNDING_RATE_PRECISION);
+	// Base fee tier, scaled by the market's fee adjustment.
+	const feeTier = driftClient.getStateAccount().perpFeeStructure.feeTiers[0];
+	const feeAdjustment = 1 + perpMarket.feeAdjustment / 100;
+	const marginPrecision = MARGIN_PRECISION.toNumber();
 
 	return {
 		symbol: marketCfg.symbol,
+		marketIndex: marketCfg.marketIndex,
+		slot: oracle.slot.toNumber(),
 		price,
 		mark,
 		funding,
+		takerFee: (feeTier.feeNumerator / feeTier.feeDenominator) * feeAdjustment,
+		makerFee:
+			-(feeTier.makerRebateNumerator / feeTier.makerRebateDenominator) * feeAdjustment,
+		initialMarginRatio: perpMarket.marginRatioInitial / marginPrecision,
+		maintenanceMarginRatio: perpMarket.marginRatioMaintenance / marginPrecision,
+		minOrderSize: convertToNumber(perpMarket.amm.minOrderSize, BASE_PRECISION),
+	};
+}
+
+export async function getFundingRate(req: MarketQueryReq) {
+	const marketCfg = resolveMarketConfig(req.symbol);
+	const perpMarket = driftClient.getPerpMarketAccount(
+		marketCfg.marketIndex
+	) as PerpMarketAccount;
+	const amm = perpMarket.amm;
+
+	const fundingPeriodSecs = amm.fundingPeriod.toNumber();
+	const lastFundingTs = amm.lastFundingRateTs.toNumber();
+	const markTwap = convertToNumber(amm.lastMarkPriceTwap, PRICE_PRECISION);
+	const oracleTwap = convertToNumber(
+		amm.historicalOracleData.lastOraclePriceTwap,
+		PRICE_PRECISION
+	);
+	// Quote per unit of base for one funding period; positive means longs pay shorts.
+	const lastRate = convertToNumber(amm.lastFundingRate, FUNDING_RATE_PRECISION);
+	// The mark/oracle TWAP spread, prorated from a day to one period, before rate caps.
+	const projectedRate = ((markTwap - oracleTwap) * fundingPeriodSecs) / 86400;
+
+	return {
+		symbol: marketCfg.symbol,
+		marketIndex: marketCfg.marketIndex,
+		fundingPeriodSecs,
+		lastFundingTs: lastFundingTs * 1000,
+		nextFundingTs: (lastFundingTs + fundingPeriodSecs) * 1000,
+		markTwap,
+		oracleTwap,
+		lastRate,
+		lastRatePct: oracleTwap > 0 ? (lastRate / oracleTwap) * 100 : null,
+		projectedRate,
+		projectedRatePct: oracleTwap > 0 ? (projectedRate / oracleTwap) * 100 : null,
 	};
 }
 
+export async function listMarkets() {
+	if (!marketMaps) {
+		marketMaps = buildMarketMaps();
+	}
+	return Array.from(marketMaps.byIndex.values())
+		.sort((a, b) => a.marketIndex - b.marketIndex)
+		.map((cfg) => ({ symbol: cfg.symbol, marketIndex: cfg.marketIndex }));
+}
+
 export async function getIsolatedBalance(req: IsolatedBalanceReq) {
 	const walletPk = new PublicKey(req.wallet);
 	const marketCfg = resolveMarketConfig(req.market);
@@ -1051,15 +2220,16 @@ export async function buildDepositNativeSolTx(req: DepositNativeReq) {
 		throw new Error('amount must be positive');
 	}
 	const walletPk = new PublicKey(req.wallet);
+	const { subAccountId } = req;
 	const spotConfig = resolveSpotMarketConfig(req.market ?? 'SOL');
 	const lamports = new BN(Math.round(req.amount * LAMPORTS_PER_SOL));
 	if (lamports.lte(ZERO)) {
 		throw new Error('amount too small');
 	}
 
-	const initIxs = await ensureUserInitIxs(walletPk);
-	const userAccount = await fetchUserAccount(walletPk);
-	await ensureDriftUserCached(walletPk, userAccount);
+	const initIxs = await ensureUserInitIxs(walletPk, subAccountId);
+	const userAccount = await fetchUserAccount(walletPk, subAccountId);
+	await ensureDriftUserCached(walletPk, userAccount, subAccountId);
 	const userInitialized = !!userAccount;
 
 	const spotMarket = driftClient.getSpotMarketAccount(
@@ -1073,7 +2243,7 @@ export async function buildDepositNativeSolTx(req: DepositNativeReq) {
 	if (spotMarket.mint.equals(WRAPPED_SOL_MINT)) {
 		const wrap = await withAuthority(walletPk, async () => {
 			return driftClient.getWrappedSolAccountCreationIxs(lamports, true);
-		});
+		}, subAccountId);
 		wrappedAccount = wrap.pubkey;
 		depositAccount = wrap.pubkey;
 		instructions.push(...wrap.ixs);
@@ -1087,30 +2257,31 @@ export async function buildDepositNativeSolTx(req: DepositNativeReq) {
 			lamports,
 			spotConfig.marketIndex,
 			depositAccount,
-			undefined,
+			subAccountId,
 			false,
 			userInitialized
 		);
-	});
+	}, subAccountId);
 	instructions.push(depositIx);
 
 	if (wrappedAccount) {
 		instructions.push(createCloseAccountIx(wrappedAccount, walletPk, walletPk));
 	}
 
-	const { txBase64, signatures } = await buildTransaction(walletPk, instructions);
-	return { txBase64, signatures };
+	const { txBase64, signatures, lastValidBlockHeight } = await buildTransaction(walletPk, instructions);
+	return { txBase64, signatures, lastValidBlockHeight };
 }
 
-export async function getPositionDetails(req: WalletOnlyReq) {
+export async function getPositionDetails(req: PositionDetailsReq) {
 	const walletPk = new PublicKey(req.wallet);
-	const userAccount = await fetchUserAccount(walletPk);
+	const { subAccountId } = req;
+	const userAccount = await fetchUserAccount(walletPk, subAccountId);
 	if (!userAccount) {
 		return [];
 	}
 
 	// Ensure user is cached so getIsolatedPerpPositionTokenAmount works
-	await ensureDriftUserCached(walletPk, userAccount);
+	await ensureDriftUserCached(walletPk, userAccount, subAccountId);
 
 	if (!marketMaps) {
 		marketMaps = buildMarketMaps();
@@ -1137,7 +2308,7 @@ export async function getPositionDetails(req: WalletOnlyReq) {
 			// Check if there's isolated margin even without an open position
 			const isolatedMargin = await withAuthority(walletPk, async () => {
 				return driftClient.getIsolatedPerpPositionTokenAmount(pos.marketIndex) ?? ZERO;
-			});
+			}, subAccountId);
 			const hasIsolatedMargin = isolatedMargin.gt(ZERO);
 			
 			return { pos, hasIsolatedMargin };
@@ -1309,6 +2480,7 @@ export async function getPositionDetails(req: WalletOnlyReq) {
 
 			return {
 				market: marketCfg?.symbol ?? `MARKET_${pos.marketIndex}`,
+				marketIndex: pos.marketIndex,
 				positionSize: size,
 				entryPrice,
 				currentPrice,
@@ -1323,6 +2495,7 @@ export async function getPositionDetails(req: WalletOnlyReq) {
 
 export async function buildDepositTokenTx(req: DepositTokenReq) {
 	const walletPk = new PublicKey(req.wallet);
+	const { subAccountId } = req;
 	const spotConfig = resolveSpotMarketConfig(req.market ?? 'USDC');
 	const spotMarket = driftClient.getSpotMarketAccount(spotConfig.marketIndex) as SpotMarketAccount;
 	const decimals = Number(spotMarket.decimals ?? 6);
@@ -1331,9 +2504,9 @@ export async function buildDepositTokenTx(req: DepositTokenReq) {
 		throw new Error('amount too small');
 	}
 
-	const initIxs = await ensureUserInitIxs(walletPk);
-	const userAccount = await fetchUserAccount(walletPk);
-	await ensureDriftUserCached(walletPk, userAccount);
+	const initIxs = await ensureUserInitIxs(walletPk, subAccountId);
+	const userAccount = await fetchUserAccount(walletPk, subAccountId);
+	await ensureDriftUserCached(walletPk, userAccount, subAccountId);
 	const userInitialized = !!userAccount;
 
 	const tokenProgram = driftClient.getTokenProgramForSpotMarket(spotMarket);
@@ -1364,15 +2537,15 @@ export async function buildDepositTokenTx(req: DepositTokenReq) {
 			amount,
 			spotConfig.marketIndex,
 			associatedAccount,
-			undefined,
+			subAccountId,
 			false,
 			userInitialized
 		);
-	});
+	}, subAccountId);
 	instructions.push(depositIx);
 
-	const { txBase64, signatures } = await buildTransaction(walletPk, instructions);
-	return { txBase64, signatures };
+	const { txBase64, signatures, lastValidBlockHeight } = await buildTransaction(walletPk, instructions);
+	return { txBase64, signatures, lastValidBlockHeight };
 }
 
 export async function getBalances(req: WalletOnlyReq) {
@@ -1416,6 +2589,107 @@ export async function getBalances(req: WalletOnlyReq) {
 		drift_account: driftAccountSummary,
 	};
 }
+function summarisePerpPosition(userAccount: UserAccount | null, marketIndex: number) {
+	const pos = userAccount?.perpPositions.find(
+		(p) =>
+			p.marketIndex === marketIndex &&
+			(!p.baseAssetAmount.eq(ZERO) ||
+				!(p.isolatedPositionScaledBalance ?? ZERO).eq(ZERO) ||
+				p.openOrders > 0)
+	);
+	if (!pos) {
+		return null;
+	}
+	return {
+		size: convertToNumber(pos.baseAssetAmount, BASE_PRECISION),
+		quoteAssetAmount: convertToNumber(pos.quoteAssetAmount, QUOTE_PRECISION),
+		entryPrice: calcEntryPrice(pos),
+		isolatedMargin: convertToNumber(
+			isolatedMarginTokenAmount(pos.marketIndex, pos.isolatedPositionScaledBalance ?? ZERO),
+			QUOTE_PRECISION
+		),
+		openOrders: pos.openOrders,
+	};
+}
+
+export async function decodePositionChange(req: PositionChangeReq) {
+	const walletPk = new PublicKey(req.wallet);
+	const marketConfig = resolveMarketConfig(req.market);
+	const before = await fetchUserAccount(walletPk);
+	const after = driftClient.program.coder.accounts.decodeUnchecked(
+		'User',
+		Buffer.from(req.userAccountBase64, 'base64')
+	) as UserAccount;
+
+	return {
+		market: marketConfig.symbol,
+		before: summarisePerpPosition(before, marketConfig.marketIndex),
+		after: summarisePerpPosition(after, marketConfig.marketIndex),
+	};
+}
+
+// Inputs for the rust-api risk engine; prices are oracle prices.
+export async function getRiskSnapshot(req: RiskSnapshotReq) {
+	const walletPk = new PublicKey(req.wallet);
+	// Keyed by the names as requested.
+	const marketPrices: Record<string, number> = {};
+	for (const market of req.markets) {
+		marketPrices[market] = convertToNumber(
+			driftClient.getOracleDataForPerpMarket(resolveMarketConfig(market).marketIndex).price,
+			PRICE_PRECISION
+		);
+	}
+	const userAccount = await fetchUserAccount(walletPk, req.subAccountId);
+	if (!userAccount) {
+		return {
+			positions: [],
+			settledPerpPnl: 0,
+			nextLiquidationId: 0,
+			beingLiquidated: false,
+			marketPrices,
+		};
+	}
+
+	if (!marketMaps) {
+		marketMaps = buildMarketMaps();
+	}
+	const positions = userAccount.perpPositions
+		.filter((pos) => !pos.baseAssetAmount.eq(ZERO))
+		.map((pos) => {
+			const oracle = driftClient.getOracleDataForPerpMarket(pos.marketIndex);
+			const perpMarket = driftClient.getPerpMarketAccount(
+				pos.marketIndex
+			) as PerpMarketAccount;
+			const size = convertToNumber(pos.baseAssetAmount, BASE_PRECISION);
+			return {
+				market: marketSymbol(pos.marketIndex),
+				size,
+				notional: Math.abs(size) * convertToNumber(oracle.price, PRICE_PRECISION),
+				isolatedMargin: convertToNumber(
+					isolatedMarginTokenAmount(
+						pos.marketIndex,
+						pos.isolatedPositionScaledBalance ?? ZERO
+					),
+					QUOTE_PRECISION
+				),
+				unrealizedPnl: convertToNumber(
+					calculatePositionPNL(perpMarket, pos, true, oracle),
+					QUOTE_PRECISION
+				),
+				maintenanceMarginRatio:
+					perpMarket.marginRatioMaintenance / MARGIN_PRECISION.toNumber(),
+			};
+		});
+
+	return {
+		positions,
+		settledPerpPnl: convertToNumber(userAccount.settledPerpPnl, QUOTE_PRECISION),
+		nextLiquidationId: userAccount.nextLiquidationId,
+		beingLiquidated: (userAccount.status & UserStatus.BEING_LIQUIDATED) !== 0,
+		marketPrices,
+	};
+}
+
 function instantiateUserHelper(walletPk: PublicKey, userAccount: UserAccount): User {
 	const subscriber = new OneShotUserAccountSubscriber(
 		driftClient.program,
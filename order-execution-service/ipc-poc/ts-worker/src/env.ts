@@ -19,6 +19,10 @@ export const RPC_URL =
 
 export const NETWORK = 'devnet' as const;
 
+export const ORACLE_EVENT_INTERVAL_MS = Number(
+	process.env.ORACLE_EVENT_INTERVAL_MS?.trim() || 1000
+);
+
 export const SERVER_KEYPAIR_PATH = process.env.SERVER_KEYPAIR_PATH?.trim();
 const SERVER_PRIVATE_KEY = process.env.SERVER_PRIVATE_KEY?.trim();
 
@@ -0,0 +1,9 @@
+import { stdout as output } from 'node:process';
+
+import type { WorkerEvent } from './types.js';
+
+// Events share stdout with IPC responses; the Rust side tells them apart by the
+// absence of an `id` field.
+export function emitEvent(payload: WorkerEvent) {
+	output.write(`${JSON.stringify(payload)}\n`);
+}
@@ -5,10 +5,20 @@ import {
 	ClosePositionReqSchema,
 	TransferMarginReqSchema,
 	WalletOnlySchema,
+	PositionDetailsReqSchema,
 	MarketQuerySchema,
 	IsolatedBalanceSchema,
 	DepositNativeReqSchema,
 	DepositTokenReqSchema,
+	PositionChangeReqSchema,
+	RiskSnapshotReqSchema,
+	OpenBracketReqSchema,
+	BracketLegsReqSchema,
+	BracketStatusReqSchema,
+	OpenOrdersReqSchema,
+	CancelOrdersReqSchema,
+	ModifyOrderReqSchema,
+	BatchReqSchema,
 	EmptyArgsSchema,
 	RequestValidators,
 	IpcRequestSchema,
@@ -23,12 +33,25 @@ import {
 	getPositions,
 	getTrades,
 	getMarket,
+	getFundingRate,
+	listMarkets,
 	getIsolatedBalance,
 	getServerPublicKey,
 	getPositionDetails,
 	buildDepositNativeSolTx,
 	buildDepositTokenTx,
 	getBalances,
+	watchWallet,
+	unwatchWallet,
+	decodePositionChange,
+	getRiskSnapshot,
+	buildOpenBracketTx,
+	buildBracketLegsTx,
+	getBracketStatus,
+	getOpenOrders,
+	buildCancelOrdersTx,
+	buildModifyOrderTx,
+	buildBatchTx,
 } from './drift.js';
 
 type HandlerMap = {
@@ -60,6 +83,14 @@ const handlers: HandlerMap = {
 		const parsed = MarketQuerySchema.parse(args);
 		return getMarket(parsed);
 	},
+	getFundingRate: async (args) => {
+		const parsed = MarketQuerySchema.parse(args);
+		return getFundingRate(parsed);
+	},
+	listMarkets: async (args) => {
+		EmptyArgsSchema.parse(args);
+		return listMarkets();
+	},
 	getIsolatedBalance: async (args) => {
 		const parsed = IsolatedBalanceSchema.parse(args);
 		return getIsolatedBalance(parsed);
@@ -69,7 +100,7 @@ const handlers: HandlerMap = {
 		return { publicKey: getServerPublicKey() };
 	},
 	getPositionDetails: async (args) => {
-		const parsed = WalletOnlySchema.parse(args);
+		const parsed = PositionDetailsReqSchema.parse(args);
 		return getPositionDetails(parsed);
 	},
 	depositNativeSol: async (args) => {
@@ -84,6 +115,50 @@ const handlers: HandlerMap = {
 		const parsed = WalletOnlySchema.parse(args);
 		return getBalances(parsed);
 	},
+	watchWallet: async (args) => {
+		const parsed = WalletOnlySchema.parse(args);
+		return watchWallet(parsed);
+	},
+	unwatchWallet: async (args) => {
+		const parsed = WalletOnlySchema.parse(args);
+		return unwatchWallet(parsed);
+	},
+	decodePositionChange: async (args) => {
+		const parsed = PositionChangeReqSchema.parse(args);
+		return decodePositionChange(parsed);
+	},
+	getRiskSnapshot: async (args) => {
+		const parsed = RiskSnapshotReqSchema.parse(args);
+		return getRiskSnapshot(parsed);
+	},
+	openBracket: async (args) => {
+		const parsed = OpenBracketReqSchema.parse(args);
+		return buildOpenBracketTx(parsed);
+	},
+	buildBracketLegs: async (args) => {
+		const parsed = BracketLegsReqSchema.parse(args);
+		return buildBracketLegsTx(parsed);
+	},
+	getBracketStatus: async (args) => {
+		const parsed = BracketStatusReqSchema.parse(args);
+		return getBracketStatus(parsed);
+	},
+	getOpenOrders: async (args) => {
+		const parsed = OpenOrdersReqSchema.parse(args);
+		return getOpenOrders(parsed);
+	},
+	cancelOrders: async (args) => {
+		const parsed = CancelOrdersReqSchema.parse(args);
+		return buildCancelOrdersTx(parsed);
+	},
+	modifyOrder: async (args) => {
+		const parsed = ModifyOrderReqSchema.parse(args);
+		return buildModifyOrderTx(parsed);
+	},
+	buildBatch: async (args) => {
+		const parsed = BatchReqSchema.parse(args);
+		return buildBatchTx(parsed);
+	},
 };
 
 function writeResponse(payload: IpcSuccess<unknown> | IpcFailure) {
@@ -1,17 +1,144 @@
 import { z } from 'zod';
 
-export const OpenIsolatedReqSchema = z.object({
+// Order type and prices, validated in rust-api; prices are in quote units.
+export const OrderOptionsSchema = z.object({
+	orderType: z
+		.enum(['market', 'limit', 'triggerMarket', 'triggerLimit', 'oracle'])
+		.default('market'),
+	price: z.number().positive().optional(),
+	postOnly: z.enum(['none', 'mustPostOnly', 'tryPostOnly', 'slide']).default('none'),
+	immediateOrCancel: z.boolean().default(false),
+	triggerPrice: z.number().positive().optional(),
+	triggerCondition: z.enum(['above', 'below']).optional(),
+	oraclePriceOffset: z.number().finite().optional(),
+	auctionDuration: z.number().int().min(0).max(255).optional(),
+	auctionStartPrice: z.number().finite().optional(),
+	auctionEndPrice: z.number().finite().optional(),
+	maxTs: z.number().int().positive().optional(),
+});
+
+export type OrderOptions = z.infer<typeof OrderOptionsSchema>;
+
+export const OpenIsolatedReqSchema = z
+	.object({
+		wallet: z.string().min(32),
+		subAccountId: z.number().int().min(0).max(65535).default(0),
+		market: z.string().min(1),
+		size: z.number().finite(),
+		leverage: z.number().positive().max(100),
+		margin: z.number().positive(),
+	})
+	.merge(OrderOptionsSchema);
+
+export type OpenIsolatedReq = z.infer<typeof OpenIsolatedReqSchema>;
+
+// One reduce-only trigger order of a bracket; a trigger-limit order when limitPrice is set.
+export const BracketLegSchema = z.object({
+	triggerPrice: z.number().positive(),
+	limitPrice: z.number().positive().optional(),
+});
+
+export const OpenBracketReqSchema = OpenIsolatedReqSchema.extend({
+	takeProfit: BracketLegSchema.optional(),
+	stopLoss: BracketLegSchema.optional(),
+});
+
+export type OpenBracketReq = z.infer<typeof OpenBracketReqSchema>;
+
+export const BracketLegsReqSchema = z.object({
 	wallet: z.string().min(32),
+	subAccountId: z.number().int().min(0).max(65535).default(0),
 	market: z.string().min(1),
-	size: z.number().finite(),
-	leverage: z.number().positive().max(100),
-	margin: z.number().positive(),
+	takeProfit: BracketLegSchema.optional(),
+	stopLoss: BracketLegSchema.optional(),
+	cancelUserOrderIds: z.array(z.number().int().min(1).max(255)).default([]),
 });
 
-export type OpenIsolatedReq = z.infer<typeof OpenIsolatedReqSchema>;
+export type BracketLegsReq = z.infer<typeof BracketLegsReqSchema>;
+
+export const BracketStatusReqSchema = z.object({
+	wallet: z.string().min(32),
+	subAccountId: z.number().int().min(0).max(65535).default(0),
+	market: z.string().min(1),
+	userOrderIds: z.array(z.number().int().min(1).max(255)),
+});
+
+export type BracketStatusReq = z.infer<typeof BracketStatusReqSchema>;
+
+export const OpenOrdersReqSchema = z.object({
+	wallet: z.string().min(32),
+	subAccountId: z.number().int().min(0).max(65535).default(0),
+	market: z.string().min(1).optional(),
+});
+
+export type OpenOrdersReq = z.infer<typeof OpenOrdersReqSchema>;
+
+// Exactly one selector is set; rust-api checks this before calling.
+export const CancelOrdersReqSchema = z.object({
+	wallet: z.string().min(32),
+	subAccountId: z.number().int().min(0).max(65535).default(0),
+	orderIds: z.array(z.number().int().positive()).optional(),
+	userOrderIds: z.array(z.number().int().min(1).max(255)).optional(),
+	market: z.string().min(1).optional(),
+	all: z.boolean().default(false),
+});
+
+export type CancelOrdersReq = z.infer<typeof CancelOrdersReqSchema>;
+
+// Identifies the order by orderId or userOrderId; unset fields keep their current value.
+export const ModifyOrderReqSchema = z.object({
+	wallet: z.string().min(32),
+	subAccountId: z.number().int().min(0).max(65535).default(0),
+	orderId: z.number().int().positive().optional(),
+	userOrderId: z.number().int().min(1).max(255).optional(),
+	size: z.number().positive().optional(),
+	price: z.number().positive().optional(),
+	triggerPrice: z.number().positive().optional(),
+	triggerCondition: z.enum(['above', 'below']).optional(),
+	oraclePriceOffset: z.number().finite().optional(),
+	reduceOnly: z.boolean().optional(),
+	postOnly: z.enum(['none', 'mustPostOnly', 'tryPostOnly', 'slide']).optional(),
+	maxTs: z.number().int().positive().optional(),
+});
+
+export type ModifyOrderReq = z.infer<typeof ModifyOrderReqSchema>;
+
+// One leg of a batch; legs share the request's wallet and sub-account.
+export const BatchLegSchema = z.discriminatedUnion('type', [
+	z
+		.object({
+			type: z.literal('open'),
+			market: z.string().min(1),
+			size: z.number().finite(),
+			leverage: z.number().positive().max(100),
+			margin: z.number().positive(),
+		})
+		.merge(OrderOptionsSchema),
+	z.object({
+		type: z.literal('close'),
+		market: z.string().min(1),
+		size: z.number().positive().optional(),
+	}),
+	z.object({
+		type: z.literal('transfer'),
+		market: z.string().min(1),
+		delta: z.number().finite(),
+	}),
+]);
+
+export type BatchLeg = z.infer<typeof BatchLegSchema>;
+
+export const BatchReqSchema = z.object({
+	wallet: z.string().min(32),
+	subAccountId: z.number().int().min(0).max(65535).default(0),
+	legs: z.array(BatchLegSchema).min(1),
+});
+
+export type BatchReq = z.infer<typeof BatchReqSchema>;
 
 export const ClosePositionReqSchema = z.object({
 	wallet: z.string().min(32),
+	subAccountId: z.number().int().min(0).max(65535).default(0),
 	market: z.string().min(1),
 	size: z.number().finite().optional(),
 });
@@ -20,6 +147,7 @@ export type ClosePositionReq = z.infer<typeof ClosePositionReqSchema>;
 
 export const TransferMarginReqSchema = z.object({
 	wallet: z.string().min(32),
+	subAccountId: z.number().int().min(0).max(65535).default(0),
 	market: z.string().min(1),
 	delta: z.number().finite(),
 });
@@ -30,6 +158,11 @@ export const WalletOnlySchema = z.object({
 	wallet: z.string().min(32),
 });
 
+export const PositionDetailsReqSchema = z.object({
+	wallet: z.string().min(32),
+	subAccountId: z.number().int().min(0).max(65535).default(0),
+});
+
 export const MarketQuerySchema = z.object({
 	symbol: z.string().min(1),
 });
@@ -43,28 +176,47 @@ export const EmptyArgsSchema = z.object({}).optional();
 
 export const DepositNativeReqSchema = z.object({
 	wallet: z.string().min(32),
+	subAccountId: z.number().int().min(0).max(65535).default(0),
 	amount: z.number().positive(),
 	market: z.string().min(1).optional(),
 });
 
 export const DepositTokenReqSchema = z.object({
 	wallet: z.string().min(32),
+	subAccountId: z.number().int().min(0).max(65535).default(0),
 	amount: z.number().positive(),
 	market: z.string().min(1).optional(),
 });
 
 export const DepositIsolatedReqSchema = z.object({
 	wallet: z.string().min(32),
+	subAccountId: z.number().int().min(0).max(65535).default(0),
 	market: z.string().min(1),
 	amount: z.number().positive(),
 });
 
+export const PositionChangeReqSchema = z.object({
+	wallet: z.string().min(32),
+	market: z.string().min(1),
+	userAccountBase64: z.string().min(1),
+});
+
+export const RiskSnapshotReqSchema = z.object({
+	wallet: z.string().min(32),
+	subAccountId: z.number().int().min(0).max(65535).default(0),
+	// Markets to include oracle prices for, e.g. those an order would open in.
+	markets: z.array(z.string().min(1)).default([]),
+});
+
 export type WalletOnlyReq = z.infer<typeof WalletOnlySchema>;
+export type PositionDetailsReq = z.infer<typeof PositionDetailsReqSchema>;
 export type MarketQueryReq = z.infer<typeof MarketQuerySchema>;
 export type IsolatedBalanceReq = z.infer<typeof IsolatedBalanceSchema>;
 export type DepositNativeReq = z.infer<typeof DepositNativeReqSchema>;
 export type DepositTokenReq = z.infer<typeof DepositTokenReqSchema>;
 export type DepositIsolatedReq = z.infer<typeof DepositIsolatedReqSchema>;
+export type PositionChangeReq = z.infer<typeof PositionChangeReqSchema>;
+export type RiskSnapshotReq = z.infer<typeof RiskSnapshotReqSchema>;
 
 export const FnNames = [
 	'openIsolated',
@@ -73,12 +225,25 @@ export const FnNames = [
 	'getPositions',
 	'getTrades',
 	'getMarket',
+	'getFundingRate',
+	'listMarkets',
 	'getIsolatedBalance',
 	'getServerPublicKey',
 	'getPositionDetails',
 	'depositNativeSol',
 	'depositToken',
 	'getBalances',
+	'watchWallet',
+	'unwatchWallet',
+	'decodePositionChange',
+	'getRiskSnapshot',
+	'openBracket',
+	'buildBracketLegs',
+	'getBracketStatus',
+	'getOpenOrders',
+	'cancelOrders',
+	'modifyOrder',
+	'buildBatch',
 ] as const;
 
 export type FnName = (typeof FnNames)[number];
@@ -90,12 +255,25 @@ export const RequestValidators: Record<FnName, z.ZodTypeAny> = {
 	getPositions: WalletOnlySchema,
 	getTrades: WalletOnlySchema,
 	getMarket: MarketQuerySchema,
+	getFundingRate: MarketQuerySchema,
+	listMarkets: EmptyArgsSchema,
 	getIsolatedBalance: IsolatedBalanceSchema,
 	getServerPublicKey: EmptyArgsSchema,
-	getPositionDetails: WalletOnlySchema,
+	getPositionDetails: PositionDetailsReqSchema,
 	depositNativeSol: DepositNativeReqSchema,
 	depositToken: DepositTokenReqSchema,
 	getBalances: WalletOnlySchema,
+	watchWallet: WalletOnlySchema,
+	unwatchWallet: WalletOnlySchema,
+	decodePositionChange: PositionChangeReqSchema,
+	getRiskSnapshot: RiskSnapshotReqSchema,
+	openBracket: OpenBracketReqSchema,
+	buildBracketLegs: BracketLegsReqSchema,
+	getBracketStatus: BracketStatusReqSchema,
+	getOpenOrders: OpenOrdersReqSchema,
+	cancelOrders: CancelOrdersReqSchema,
+	modifyOrder: ModifyOrderReqSchema,
+	buildBatch: BatchReqSchema,
 };
 
 export const FnEnum = z.enum(FnNames);
@@ -125,3 +303,49 @@ export type IpcFailure = {
 	ok: false;
 	error: IpcErrorPayload;
 };
+
+export type PositionChangedEvent = {
+	event: 'positionChanged';
+	data: {
+		wallet: string;
+		market: string;
+		size: number;
+		isolatedMargin: number | null;
+	};
+};
+
+export type OrderFilledEvent = {
+	event: 'orderFilled';
+	data: {
+		wallet: string;
+		market: string;
+		orderId: number;
+		direction: 'long' | 'short';
+		filledSize: number;
+		fillPrice: number | null;
+	};
+};
+
+export type OraclePriceUpdateEvent = {
+	event: 'oraclePriceUpdate';
+	data: {
+		market: string;
+		price: number;
+		slot: number;
+	};
+};
+
+export type SubscriptionLostEvent = {
+	event: 'subscriptionLost';
+	data: {
+		wallet: string | null;
+		reason: string;
+	};
+};
+
+// Unsolicited messages written to stdout without a request id.
+export type WorkerEvent =
+	| PositionChangedEvent
+	| OrderFilledEvent
+	| OraclePriceUpdateEvent
+	| SubscriptionLostEvent;
@@ -1,7 +1,10 @@
 use std::{collections::HashSet, env, str::FromStr, sync::Arc};
 
 use anyhow::{anyhow, Context, Result};
-use indexer_common::{connect_pool, insert_trade, parse_pubkey, parse_trade_from_tx, ui_encoding};
+use indexer_common::{
+    connect_pool, insert_funding_payment, insert_trade, parse_funding_payments_from_tx,
+    parse_pubkey, parse_trade_from_tx, ui_encoding,
+};
 use solana_client::{
     nonblocking::rpc_client::RpcClient,
     rpc_config::{CommitmentConfig, GetConfirmedSignaturesForAddress2Config, RpcTransactionConfig},
@@ -53,6 +56,7 @@ async fn main() -> Result<()> {
     collect_signatures(&rpc, &drift_account, fetch_limit, &mut signatures).await?;
 
     let mut inserted = 0usize;
+    let mut funding_inserted = 0usize;
     for sig_str in signatures {
         let signature = Signature::from_str(&sig_str)
             .map_err(|err| anyhow!("invalid signature {sig_str}: {err}"))?;
@@ -65,12 +69,19 @@ async fn main() -> Result<()> {
                         inserted += 1;
                     }
                 }
+                for payment in parse_funding_payments_from_tx(&tx, &wallet_key, &drift_program) {
+                    if let Err(err) = insert_funding_payment(&pool, &payment).await {
+                        error!(%payment.signature, ?err, "failed to insert funding payment");
+                    } else {
+                        funding_inserted += 1;
+                    }
+                }
             }
             Err(err) => warn!(?err, %sig_str, "failed to fetch transaction"),
         }
     }
 
-    info!(?inserted, "backfill completed");
+    info!(?inserted, ?funding_inserted, "backfill completed");
     Ok(())
 }
 
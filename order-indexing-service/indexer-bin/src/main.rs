@@ -10,7 +10,10 @@ use std::{
 
 use anyhow::{Context, Result};
 use axum::{routing::get, Json, Router};
-use indexer_common::{connect_pool, insert_trade, parse_pubkey, parse_trade_from_tx, ui_encoding};
+use indexer_common::{
+    connect_pool, insert_funding_payment, insert_trade, parse_funding_payments_from_tx,
+    parse_pubkey, parse_trade_from_tx, ui_encoding,
+};
 use serde::Serialize;
 use solana_client::{
     nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
@@ -180,6 +183,9 @@ async fn handle_signature(
     if let Some(record) = parse_trade_from_tx(&tx, &wallet, &drift_program, &drift_account) {
         insert_trade(pool, &record).await?;
     }
+    for payment in parse_funding_payments_from_tx(&tx, &wallet, &drift_program) {
+        insert_funding_payment(pool, &payment).await?;
+    }
 
     Ok(())
 }
@@ -1,6 +1,7 @@
 use std::{collections::HashMap, str::FromStr};
 
 use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
 use chrono::{DateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 use solana_program::pubkey::Pubkey;
@@ -12,6 +13,12 @@ use sqlx::{postgres::PgPoolOptions, PgPool};
 use tracing::warn;
 
 const LAMPORTS_PER_SOL: f64 = 1_000_000_000.0;
+const QUOTE_PRECISION: f64 = 1_000_000.0;
+const BASE_PRECISION: f64 = 1_000_000_000.0;
+const FUNDING_RATE_PRECISION: f64 = 1_000_000_000.0;
+
+/// Anchor event discriminator: the first 8 bytes of `sha256("event:FundingPaymentRecord")`.
+const FUNDING_PAYMENT_DISCRIMINATOR: [u8; 8] = [8, 59, 96, 20, 137, 201, 56, 95];
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TradeRecord {
@@ -25,6 +32,24 @@ pub struct TradeRecord {
     pub block_time: Option<DateTime<Utc>>,
 }
 
+/// Funding settled into one perp position, from a Drift `FundingPaymentRecord` event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FundingPaymentRecord {
+    pub wallet: String,
+    pub user_account: String,
+    pub signature: String,
+    pub market_index: u16,
+    /// USDC; positive when the position received funding.
+    pub funding_payment: f64,
+    /// Position size when funding was settled, negative for shorts.
+    pub base_asset_amount: f64,
+    pub user_last_cumulative_funding: f64,
+    pub amm_cumulative_funding_long: f64,
+    pub amm_cumulative_funding_short: f64,
+    pub slot: u64,
+    pub block_time: Option<DateTime<Utc>>,
+}
+
 pub async fn connect_pool(database_url: &str) -> Result<PgPool> {
     PgPoolOptions::new()
         .max_connections(5)
@@ -56,6 +81,122 @@ pub async fn insert_trade(pool: &PgPool, trade: &TradeRecord) -> Result<()> {
     Ok(())
 }
 
+pub async fn insert_funding_payment(pool: &PgPool, payment: &FundingPaymentRecord) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO funding_payments (
+            wallet, user_account, signature, market_index, funding_payment, base_asset_amount,
+            user_last_cumulative_funding, amm_cumulative_funding_long, amm_cumulative_funding_short,
+            slot, block_time
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        ON CONFLICT (signature, user_account, market_index) DO NOTHING
+        "#,
+    )
+    .bind(&payment.wallet)
+    .bind(&payment.user_account)
+    .bind(&payment.signature)
+    .bind(payment.market_index as i16)
+    .bind(payment.funding_payment)
+    .bind(payment.base_asset_amount)
+    .bind(payment.user_last_cumulative_funding)
+    .bind(payment.amm_cumulative_funding_long)
+    .bind(payment.amm_cumulative_funding_short)
+    .bind(payment.slot as i64)
+    .bind(payment.block_time)
+    .execute(pool)
+    .await
+    .context("failed to insert funding payment")?;
+
+    Ok(())
+}
+
+/// Funding payments the transaction settled for `wallet`'s positions, read from the
+/// `Program data:` events in its logs.
+pub fn parse_funding_payments_from_tx(
+    tx: &EncodedTransactionWithStatusMeta,
+    wallet: &Pubkey,
+    drift_program: &Pubkey,
+) -> Vec<FundingPaymentRecord> {
+    let Some(meta) = tx.transaction.meta.as_ref() else {
+        return Vec::new();
+    };
+    let Some(logs) = meta.log_messages.as_ref() else {
+        return Vec::new();
+    };
+    let signature = match &tx.transaction.transaction {
+        EncodedTransaction::Json(parsed) => match parsed.signatures.get(0) {
+            Some(sig) => sig.clone(),
+            None => return Vec::new(),
+        },
+        _ => return Vec::new(),
+    };
+    if !logs.iter().any(|log| log.contains(&drift_program.to_string())) {
+        return Vec::new();
+    }
+
+    logs.iter()
+        .filter_map(|log| log.strip_prefix("Program data: "))
+        .filter_map(|data| STANDARD.decode(data).ok())
+        .filter_map(|data| decode_funding_payment(&data))
+        .filter(|event| event.user_authority == *wallet)
+        .map(|event| FundingPaymentRecord {
+            wallet: event.user_authority.to_string(),
+            user_account: event.user.to_string(),
+            signature: signature.clone(),
+            market_index: event.market_index,
+            funding_payment: event.funding_payment as f64 / QUOTE_PRECISION,
+            base_asset_amount: event.base_asset_amount as f64 / BASE_PRECISION,
+            user_last_cumulative_funding: event.user_last_cumulative_funding as f64
+                / FUNDING_RATE_PRECISION,
+            amm_cumulative_funding_long: event.amm_cumulative_funding_long as f64
+                / FUNDING_RATE_PRECISION,
+            amm_cumulative_funding_short: event.amm_cumulative_funding_short as f64
+                / FUNDING_RATE_PRECISION,
+            slot: tx.slot,
+            block_time: Utc.timestamp_opt(event.ts, 0).single(),
+        })
+        .collect()
+}
+
+struct FundingPaymentEvent {
+    ts: i64,
+    user_authority: Pubkey,
+    user: Pubkey,
+    market_index: u16,
+    funding_payment: i64,
+    base_asset_amount: i64,
+    user_last_cumulative_funding: i64,
+    amm_cumulative_funding_long: i128,
+    amm_cumulative_funding_short: i128,
+}
+
+/// Reads the fixed-size Borsh layout of `FundingPaymentRecord` after its discriminator.
+fn decode_funding_payment(data: &[u8]) -> Option<FundingPaymentEvent> {
+    let mut cursor = data.strip_prefix(FUNDING_PAYMENT_DISCRIMINATOR.as_slice())?;
+    let cursor = &mut cursor;
+    Some(FundingPaymentEvent {
+        ts: i64::from_le_bytes(take::<8>(cursor)?),
+        user_authority: Pubkey::new_from_array(take::<32>(cursor)?),
+        user: Pubkey::new_from_array(take::<32>(cursor)?),
+        market_index: u16::from_le_bytes(take::<2>(cursor)?),
+        funding_payment: i64::from_le_bytes(take::<8>(cursor)?),
+        base_asset_amount: i64::from_le_bytes(take::<8>(cursor)?),
+        user_last_cumulative_funding: i64::from_le_bytes(take::<8>(cursor)?),
+        amm_cumulative_funding_long: i128::from_le_bytes(take::<16>(cursor)?),
+        amm_cumulative_funding_short: i128::from_le_bytes(take::<16>(cursor)?),
+    })
+}
+
+fn take<'a, const N: usize>(cursor: &mut &'a [u8]) -> Option<[u8; N]> {
+    if cursor.len() < N {
+        return None;
+    }
+    let (head, tail) = cursor.split_at(N);
+    *cursor = tail;
+    head.try_into().ok()
+}
+
 pub fn parse_trade_from_tx(
     tx: &EncodedTransactionWithStatusMeta,
     wallet: &Pubkey,